@@ -1,5 +1,7 @@
 use crate::error::MocksError;
+use crate::schema::validator::Violation;
 use colored::*;
+use serde_json::Value;
 
 fn print_separator() {
     println!("{}", "======================================".cyan());
@@ -55,6 +57,175 @@ pub fn print_startup_info(url: &str, file: &str, overwrite: bool) {
     print_blank();
 }
 
+/// Print the OpenAPI spec the server enforces in contract mode.
+///
+/// # Arguments
+/// * `spec`: Path of the OpenAPI spec.
+/// * `mode`: Contract mode name (e.g., "strict").
+pub fn print_contract_info(spec: &str, mode: &str) {
+    print_heading("Contract:");
+    print_kv_with_indent("Spec", spec.bright_cyan());
+    print_kv_with_indent("Mode", mode.bright_cyan());
+    print_blank();
+}
+
+/// Print the latency added to requests.
+///
+/// # Arguments
+/// * `delay`: The global delay (e.g., "100..800ms").
+pub fn print_delay_info(delay: &str) {
+    print_heading("Delay:");
+    print_kv_with_indent("Global", delay.bright_cyan());
+    print_blank();
+}
+
+/// Print the faults injected into requests.
+///
+/// # Arguments
+/// * `faults`: The global faults (e.g., "error=0.1,status=503").
+/// * `seed`: Seed of the fault injection, if fixed.
+pub fn print_chaos_info(faults: &str, seed: Option<u64>) {
+    print_heading("Chaos:");
+    print_kv_with_indent("Faults", faults.bright_cyan());
+    if let Some(seed) = seed {
+        print_kv_with_indent("Seed", seed.to_string().bright_cyan());
+    }
+    print_blank();
+}
+
+/// Print the authentication required from clients.
+///
+/// # Arguments
+/// * `schemes`: The accepted schemes (e.g., "Basic, Bearer (HS256)").
+/// * `token`: Whether tokens can be minted at `POST /_mocks/token`.
+/// * `oidc`: Whether the OpenID Connect provider is enabled.
+pub fn print_auth_info(schemes: &str, token: bool, oidc: bool) {
+    print_heading("Auth:");
+    print_kv_with_indent("Schemes", schemes.bright_cyan());
+    if token {
+        print_kv_with_indent("Token", "POST /_mocks/token".bright_cyan());
+    }
+    if oidc {
+        print_kv_with_indent(
+            "OIDC",
+            "GET /_mocks/oidc/.well-known/openid-configuration".bright_cyan(),
+        );
+    }
+    print_blank();
+}
+
+/// Print the certificate HTTPS is served with.
+///
+/// # Arguments
+/// * `certificate`: Description of the certificate (e.g., "self-signed (localhost.pem)").
+pub fn print_tls_info(certificate: &str) {
+    print_heading("TLS:");
+    print_kv_with_indent("Certificate", certificate.bright_cyan());
+    print_blank();
+}
+
+/// Print the cross-origin policy.
+///
+/// # Arguments
+/// * `policy`: Description of the policy (e.g., "http://localhost:5173 (credentials)").
+pub fn print_cors_info(policy: &str) {
+    print_heading("CORS:");
+    print_kv_with_indent("Origins", policy.bright_cyan());
+    print_blank();
+}
+
+/// Print the upstream requests fall back to.
+///
+/// # Arguments
+/// * `fallback`: Description of the fallback (e.g., "http://localhost:8080 (cached 30s)").
+pub fn print_proxy_info(fallback: &str) {
+    print_heading("Proxy:");
+    print_kv_with_indent("Fallback", fallback.bright_cyan());
+    print_blank();
+}
+
+/// Print the rate limit of requests.
+///
+/// # Arguments
+/// * `limit`: Description of the limit (e.g., "10/s (burst 10, per ip)").
+pub fn print_rate_limit_info(limit: &str) {
+    print_heading("Rate Limit:");
+    print_kv_with_indent("Limit", limit.bright_cyan());
+    print_blank();
+}
+
+/// Print a banner and the proxy information of `mocks record` to stdout.
+///
+/// # Arguments
+/// * `url`: The URL the proxy is listening on (e.g., "http://localhost:3000").
+/// * `target`: The upstream requests are forwarded to.
+/// * `file`: Path of the storage file recorded to.
+pub fn print_record_info(url: &str, target: &str, file: &str) {
+    print_banner("mocks recorder started!".green().bold());
+    print_info("Press CTRL-C to stop");
+    print_blank();
+
+    print_heading("Recorder Information:");
+    print_kv_with_indent("URL", url.bright_cyan());
+    print_kv_with_indent("Target", target.bright_cyan());
+    print_kv_with_indent("Storage", file.bright_cyan());
+    print_blank();
+}
+
+/// Print an exchange recorded by `mocks record`.
+///
+/// # Arguments
+/// * `method`: Method of the request.
+/// * `uri`: Path and query of the request.
+/// * `status`: Status of the upstream response.
+/// * `recorded`: Where the exchange was recorded (e.g., "users (3 items)").
+pub fn print_recorded(method: &str, uri: &str, status: u16, recorded: &str) {
+    let status = if status < 400 {
+        status.to_string().green()
+    } else {
+        status.to_string().red()
+    };
+    println!(
+        "{} {} {} {} {}",
+        method.bright_white(),
+        uri.bright_cyan(),
+        status,
+        "->".bright_black(),
+        recorded
+    );
+}
+
+/// Print contract violations found in warn mode as an expected/actual diff.
+///
+/// # Arguments
+/// * `label`: The exchange the violations belong to (e.g., "GET /posts response").
+/// * `violations`: The violations to display.
+/// * `body`: Parsed JSON body of the exchange, used to show actual values.
+pub fn print_contract_violations(label: &str, violations: &[Violation], body: Option<&Value>) {
+    eprintln!(
+        "{}: {}",
+        "Contract violation".yellow().bold(),
+        label.bright_white()
+    );
+    for violation in violations {
+        let path = if violation.path.is_empty() {
+            "/"
+        } else {
+            violation.path.as_str()
+        };
+        eprintln!("   {} ({})", path.bright_cyan(), violation.keyword);
+        eprintln!("   {}", format!("- expected: {}", violation.message).red());
+
+        let actual = violation
+            .path
+            .strip_prefix("/body")
+            .and_then(|pointer| body.and_then(|body| body.pointer(pointer)));
+        if let Some(actual) = actual {
+            eprintln!("   {}", format!("+ actual:   {actual}").green());
+        }
+    }
+}
+
 /// Print a success message after initializing a storage file.
 ///
 /// # Arguments
@@ -72,6 +243,42 @@ pub fn print_init_aborted() {
     print_blank();
 }
 
+/// Print a success message after writing inferred schema files.
+///
+/// # Arguments
+/// * `files`: Paths of the written schema files.
+pub fn print_schema_infer_success(files: &[String]) {
+    print_banner("mocks schemas inferred!".green().bold());
+    for file in files {
+        print_kv("Created", file.bright_cyan());
+    }
+    print_blank();
+}
+
+/// Print a success message after writing an OpenAPI document.
+///
+/// # Arguments
+/// * `file_path`: The written document path.
+pub fn print_openapi_success(file_path: &str) {
+    print_banner("mocks openapi generated!".green().bold());
+    print_kv("Created", file_path.bright_cyan());
+    print_blank();
+}
+
+/// Print a success message after generating fake records.
+///
+/// # Arguments
+/// * `file_path`: The updated storage file path.
+/// * `resources`: Generated resources and their number of records.
+pub fn print_generate_success(file_path: &str, resources: &[(String, usize)]) {
+    print_banner("mocks data generated!".green().bold());
+    print_kv("Updated", file_path.bright_cyan());
+    for (resource, count) in resources {
+        print_kv_with_indent(resource, format!("{count} records"));
+    }
+    print_blank();
+}
+
 /// Print a formatted error to stderr and optional hints for certain error types.
 ///
 /// # Arguments
@@ -150,6 +357,72 @@ mod tests {
         print_startup_info(url, file, overwrite);
     }
 
+    #[test]
+    fn test_print_contract_info() {
+        print_contract_info("openapi.yaml", "strict");
+    }
+
+    #[test]
+    fn test_print_delay_info() {
+        print_delay_info("100..800ms");
+    }
+
+    #[test]
+    fn test_print_auth_info() {
+        print_auth_info("Basic, Bearer (HS256)", true, true);
+    }
+
+    #[test]
+    fn test_print_tls_info() {
+        print_tls_info("self-signed (localhost.pem), HTTP/2");
+    }
+
+    #[test]
+    fn test_print_cors_info() {
+        print_cors_info("http://localhost:5173 (credentials)");
+    }
+
+    #[test]
+    fn test_print_proxy_info() {
+        print_proxy_info("http://localhost:8080 (cached 30s)");
+    }
+
+    #[test]
+    fn test_print_rate_limit_info() {
+        print_rate_limit_info("10/s (burst 10, per ip)");
+    }
+
+    #[test]
+    fn test_print_record_info() {
+        print_record_info(
+            "http://localhost:3000",
+            "https://api.example.com",
+            "storage.json",
+        );
+    }
+
+    #[test]
+    fn test_print_recorded() {
+        print_recorded("GET", "/users?page=2", 200, "users (3 items)");
+        print_recorded("GET", "/missing", 404, "route GET /missing");
+    }
+
+    #[test]
+    fn test_print_chaos_info() {
+        print_chaos_info("error=0.1,status=503", Some(42));
+        print_chaos_info("drop=0.05", None);
+    }
+
+    #[test]
+    fn test_print_contract_violations() {
+        let violations = vec![
+            Violation::new("/body/title", "type", "Expected string."),
+            Violation::new("", "path", "GET /x is not defined in the spec."),
+        ];
+        let body = serde_json::json!({"title": 1});
+        print_contract_violations("POST /posts request", &violations, Some(&body));
+    }
+
     #[test]
     fn test_print_init_success() {
         let file_path = "storage.json";
@@ -161,6 +434,23 @@ mod tests {
         print_init_aborted();
     }
 
+    #[test]
+    fn test_print_schema_infer_success() {
+        let files = vec!["schemas/posts.json".to_string()];
+        print_schema_infer_success(&files);
+    }
+
+    #[test]
+    fn test_print_openapi_success() {
+        print_openapi_success("openapi.json");
+    }
+
+    #[test]
+    fn test_print_generate_success() {
+        let resources = vec![("users".to_string(), 500)];
+        print_generate_success("storage.json", &resources);
+    }
+
     #[test]
     fn test_print_error() {
         let error = MocksError::InvalidArgs("Invalid argument".to_string());
@@ -16,12 +16,19 @@ pub enum MocksError {
     ObjectNotFound,
     MethodNotAllowed,
     InvalidRequest,
+    /// Invalid request with the reason given to the client
+    BadRequest(String),
     DuplicateId,
     QueryParamsNotAllowed,
     InvalidSearchValue,
     InvalidMatchType,
     InvalidQueryParam,
     MatchTypeRequired,
+    PreconditionFailed,
+    Unauthorized,
+    Forbidden,
+    TooManyRequests,
+    BadGateway(String),
     Aborted,
 }
 
@@ -42,6 +49,7 @@ impl fmt::Display for MocksError {
             Self::ObjectNotFound => write!(fmt, "Object not found."),
             Self::MethodNotAllowed => write!(fmt, "Method not allowed."),
             Self::InvalidRequest => write!(fmt, "Invalid request."),
+            Self::BadRequest(err) => write!(fmt, "{err}"),
             Self::DuplicateId => write!(fmt, "Duplicate ID."),
             Self::QueryParamsNotAllowed => write!(fmt, "Query parameters not allowed."),
             Self::InvalidSearchValue => {
@@ -53,32 +61,49 @@ impl fmt::Display for MocksError {
             ),
             Self::InvalidQueryParam => write!(fmt, "Invalid query parameter format."),
             Self::MatchTypeRequired => write!(fmt, "Match type is required. Use: field.exact, field.startswith, field.endswith, or field.contains."),
+            Self::PreconditionFailed => {
+                write!(fmt, "Precondition failed. The resource has been modified.")
+            }
+            Self::Unauthorized => write!(fmt, "Authentication required."),
+            Self::Forbidden => write!(fmt, "Access denied."),
+            Self::TooManyRequests => write!(fmt, "Too many requests. Retry later."),
+            Self::BadGateway(err) => write!(fmt, "Upstream request failed: {err}"),
             Self::Aborted => write!(fmt, "Operation aborted by user."),
         }
     }
 }
 
+impl MocksError {
+    /// HTTP status code returned for this error
+    pub fn status(&self) -> StatusCode {
+        match self {
+            MocksError::FailedReadFile(_)
+            | MocksError::FailedWriteFile(_)
+            | MocksError::InvalidArgs(_)
+            | MocksError::Exception(_) => StatusCode::INTERNAL_SERVER_ERROR,
+            MocksError::ResourceNotFound | MocksError::ObjectNotFound => StatusCode::NOT_FOUND,
+            MocksError::MethodNotAllowed => StatusCode::METHOD_NOT_ALLOWED,
+            MocksError::InvalidRequest | MocksError::BadRequest(_) => StatusCode::BAD_REQUEST,
+            MocksError::DuplicateId => StatusCode::CONFLICT,
+            MocksError::QueryParamsNotAllowed => StatusCode::BAD_REQUEST,
+            MocksError::InvalidSearchValue => StatusCode::BAD_REQUEST,
+            MocksError::InvalidMatchType => StatusCode::BAD_REQUEST,
+            MocksError::InvalidQueryParam => StatusCode::BAD_REQUEST,
+            MocksError::MatchTypeRequired => StatusCode::BAD_REQUEST,
+            MocksError::PreconditionFailed => StatusCode::PRECONDITION_FAILED,
+            MocksError::Unauthorized => StatusCode::UNAUTHORIZED,
+            MocksError::Forbidden => StatusCode::FORBIDDEN,
+            MocksError::TooManyRequests => StatusCode::TOO_MANY_REQUESTS,
+            MocksError::BadGateway(_) => StatusCode::BAD_GATEWAY,
+            MocksError::Aborted => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+}
+
 impl IntoResponse for MocksError {
     fn into_response(self) -> Response {
-        let (status, message) = match self {
-            MocksError::FailedReadFile(err)
-            | MocksError::FailedWriteFile(err)
-            | MocksError::InvalidArgs(err)
-            | MocksError::Exception(err) => (StatusCode::INTERNAL_SERVER_ERROR, err),
-            MocksError::ResourceNotFound | MocksError::ObjectNotFound => {
-                (StatusCode::NOT_FOUND, self.to_string())
-            }
-            MocksError::MethodNotAllowed => (StatusCode::METHOD_NOT_ALLOWED, self.to_string()),
-            MocksError::InvalidRequest => (StatusCode::BAD_REQUEST, self.to_string()),
-            MocksError::DuplicateId => (StatusCode::CONFLICT, self.to_string()),
-            MocksError::QueryParamsNotAllowed => (StatusCode::BAD_REQUEST, self.to_string()),
-            MocksError::InvalidSearchValue => (StatusCode::BAD_REQUEST, self.to_string()),
-            MocksError::InvalidMatchType => (StatusCode::BAD_REQUEST, self.to_string()),
-            MocksError::InvalidQueryParam => (StatusCode::BAD_REQUEST, self.to_string()),
-            MocksError::MatchTypeRequired => (StatusCode::BAD_REQUEST, self.to_string()),
-            MocksError::Aborted => (StatusCode::INTERNAL_SERVER_ERROR, self.to_string()),
-        };
-
+        let status = self.status();
+        let message = self.to_string();
         (status, Json(json!({ "error": message }))).into_response()
     }
 }
@@ -262,6 +287,48 @@ mod tests {
         let response = error.into_response();
         assert_eq!(response.status(), StatusCode::BAD_REQUEST);
 
+        // PreconditionFailed
+        let error = MocksError::PreconditionFailed;
+        assert_eq!(
+            error.to_string(),
+            "Precondition failed. The resource has been modified."
+        );
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::PRECONDITION_FAILED);
+
+        // Unauthorized
+        let error = MocksError::Unauthorized;
+        assert_eq!(error.to_string(), "Authentication required.");
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+
+        // Forbidden
+        let error = MocksError::Forbidden;
+        assert_eq!(error.to_string(), "Access denied.");
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::FORBIDDEN);
+
+        // TooManyRequests
+        let error = MocksError::TooManyRequests;
+        assert_eq!(error.to_string(), "Too many requests. Retry later.");
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::TOO_MANY_REQUESTS);
+
+        // BadRequest
+        let error = MocksError::BadRequest("state is required.".to_string());
+        assert_eq!(error.to_string(), "state is required.");
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+
+        // BadGateway
+        let error = MocksError::BadGateway("connection refused".to_string());
+        assert_eq!(
+            error.to_string(),
+            "Upstream request failed: connection refused"
+        );
+        let response = error.into_response();
+        assert_eq!(response.status(), StatusCode::BAD_GATEWAY);
+
         // Aborted
         let error = MocksError::Aborted;
         assert_eq!(error.to_string(), "Operation aborted by user.");
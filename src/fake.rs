@@ -0,0 +1,374 @@
+use chrono::{DateTime, SecondsFormat};
+
+pub mod template;
+mod words;
+
+/// Crockford's Base32 alphabet used by ULIDs
+const CROCKFORD: &[u8; 32] = b"0123456789ABCDEFGHJKMNPQRSTVWXYZ";
+
+/// Start of the range of generated dates (2020-01-01T00:00:00Z)
+const EPOCH_FROM: i64 = 1_577_836_800;
+/// End of the range of generated dates (2026-01-01T00:00:00Z)
+const EPOCH_TO: i64 = 1_767_225_600;
+
+/// Deterministic pseudo random number generator (SplitMix64)
+///
+/// A hand-rolled generator keeps the output for a given seed stable across releases,
+/// so fixtures generated with a seed are reproducible.
+#[derive(Debug, Clone)]
+pub struct Rng {
+    state: u64,
+}
+
+impl Rng {
+    pub fn new(seed: u64) -> Rng {
+        Rng { state: seed }
+    }
+
+    pub fn next_u64(&mut self) -> u64 {
+        self.state = self.state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.state;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    /// Uniform float in `[0, 1)`
+    pub fn next_f64(&mut self) -> f64 {
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    /// Uniform integer in `[min, max]`
+    pub fn range(&mut self, min: i64, max: i64) -> i64 {
+        if max <= min {
+            return min;
+        }
+        // The span of the full `i64` range does not fit in a `u64`
+        let offset = match max.abs_diff(min).checked_add(1) {
+            Some(span) => self.next_u64() % span,
+            None => self.next_u64(),
+        };
+        min.wrapping_add_unsigned(offset)
+    }
+
+    /// Uniform float in `[min, max)`
+    pub fn range_f64(&mut self, min: f64, max: f64) -> f64 {
+        min + self.next_f64() * (max - min)
+    }
+
+    /// `true` with probability `p`
+    pub fn chance(&mut self, p: f64) -> bool {
+        self.next_f64() < p
+    }
+
+    pub fn pick<'a, T>(&mut self, items: &'a [T]) -> &'a T {
+        &items[self.range(0, items.len() as i64 - 1) as usize]
+    }
+}
+
+/// Generator of plausible fake values
+#[derive(Debug, Clone)]
+pub struct Faker {
+    pub rng: Rng,
+}
+
+impl Faker {
+    pub fn new(seed: u64) -> Faker {
+        Faker {
+            rng: Rng::new(seed),
+        }
+    }
+
+    pub fn first_name(&mut self) -> String {
+        self.rng.pick(&words::FIRST_NAMES).to_string()
+    }
+
+    pub fn last_name(&mut self) -> String {
+        self.rng.pick(&words::LAST_NAMES).to_string()
+    }
+
+    pub fn name(&mut self) -> String {
+        format!("{} {}", self.first_name(), self.last_name())
+    }
+
+    pub fn username(&mut self) -> String {
+        format!(
+            "{}{}",
+            self.first_name().to_lowercase(),
+            self.rng.range(1, 999)
+        )
+    }
+
+    pub fn email(&mut self) -> String {
+        let domain = self.rng.pick(&words::DOMAINS);
+        format!(
+            "{}.{}@{domain}",
+            self.first_name().to_lowercase(),
+            self.last_name().to_lowercase()
+        )
+    }
+
+    pub fn phone(&mut self) -> String {
+        format!(
+            "+1-555-{:03}-{:04}",
+            self.rng.range(0, 999),
+            self.rng.range(0, 9999)
+        )
+    }
+
+    pub fn street(&mut self) -> String {
+        format!(
+            "{} {}",
+            self.rng.range(1, 9999),
+            self.rng.pick(&words::STREETS)
+        )
+    }
+
+    pub fn city(&mut self) -> String {
+        self.rng.pick(&words::CITIES).to_string()
+    }
+
+    pub fn country(&mut self) -> String {
+        self.rng.pick(&words::COUNTRIES).to_string()
+    }
+
+    pub fn zip_code(&mut self) -> String {
+        format!("{:05}", self.rng.range(10000, 99999))
+    }
+
+    pub fn url(&mut self) -> String {
+        let domain = self.rng.pick(&words::DOMAINS);
+        format!("https://{domain}/{}", self.word())
+    }
+
+    pub fn word(&mut self) -> String {
+        self.rng.pick(&words::LOREM).to_string()
+    }
+
+    /// Lorem ipsum sentence of `count` words
+    pub fn words(&mut self, count: usize) -> String {
+        let words: Vec<String> = (0..count.max(1)).map(|_| self.word()).collect();
+        words.join(" ")
+    }
+
+    pub fn sentence(&mut self) -> String {
+        let count = self.rng.range(4, 10) as usize;
+        let mut sentence = self.words(count);
+        if let Some(first) = sentence.get_mut(0..1) {
+            first.make_ascii_uppercase();
+        }
+        sentence.push('.');
+        sentence
+    }
+
+    pub fn paragraph(&mut self) -> String {
+        let count = self.rng.range(3, 6);
+        let sentences: Vec<String> = (0..count).map(|_| self.sentence()).collect();
+        sentences.join(" ")
+    }
+
+    /// Random UUID (version 4)
+    pub fn uuid(&mut self) -> String {
+        let mut bytes = [0u8; 16];
+        bytes[..8].copy_from_slice(&self.rng.next_u64().to_be_bytes());
+        bytes[8..].copy_from_slice(&self.rng.next_u64().to_be_bytes());
+        bytes[6] = (bytes[6] & 0x0f) | 0x40;
+        bytes[8] = (bytes[8] & 0x3f) | 0x80;
+
+        let hex: String = bytes.iter().map(|b| format!("{b:02x}")).collect();
+        format!(
+            "{}-{}-{}-{}-{}",
+            &hex[0..8],
+            &hex[8..12],
+            &hex[12..16],
+            &hex[16..20],
+            &hex[20..32]
+        )
+    }
+
+    /// ULID with a timestamp in the generated date range
+    pub fn ulid(&mut self) -> String {
+        let millis = self.rng.range(EPOCH_FROM, EPOCH_TO) as u128 * 1000;
+        let random = ((self.rng.next_u64() as u128) << 16) | (self.rng.next_u64() as u128 >> 48);
+        encode_ulid(millis, random)
+    }
+
+    /// Epoch seconds in the generated date range
+    pub fn timestamp(&mut self) -> i64 {
+        self.rng.range(EPOCH_FROM, EPOCH_TO)
+    }
+
+    /// RFC 3339 date-time in the generated date range
+    pub fn date_time(&mut self) -> String {
+        let timestamp = self.timestamp();
+        format_date_time(timestamp)
+    }
+
+    /// ISO 8601 date in the generated date range
+    pub fn date(&mut self) -> String {
+        self.date_time()[..10].to_string()
+    }
+
+    pub fn boolean(&mut self) -> bool {
+        self.rng.chance(0.5)
+    }
+
+    /// Plausible string for a field, guessed from its name
+    ///
+    /// # Arguments
+    /// * `field` - Field name such as `email`, `first_name` or `createdAt`
+    pub fn string_for(&mut self, field: &str) -> String {
+        let field = field.to_lowercase().replace(['-', '_'], "");
+        let has = |parts: &[&str]| parts.iter().any(|part| field.contains(part));
+
+        if has(&["email"]) {
+            self.email()
+        } else if has(&["firstname", "givenname"]) {
+            self.first_name()
+        } else if has(&["lastname", "surname", "familyname"]) {
+            self.last_name()
+        } else if has(&["username", "login", "handle", "nickname"]) {
+            self.username()
+        } else if has(&["name", "author"]) {
+            self.name()
+        } else if has(&["phone", "tel", "mobile"]) {
+            self.phone()
+        } else if has(&["street", "address"]) {
+            self.street()
+        } else if has(&["city", "town"]) {
+            self.city()
+        } else if has(&["country"]) {
+            self.country()
+        } else if has(&["zip", "postal"]) {
+            self.zip_code()
+        } else if has(&["url", "link", "website", "avatar", "image", "href"]) {
+            self.url()
+        } else if has(&["title", "subject", "summary", "headline"]) {
+            self.sentence()
+        } else if has(&[
+            "description",
+            "body",
+            "content",
+            "text",
+            "bio",
+            "comment",
+            "message",
+        ]) {
+            self.paragraph()
+        } else if field.ends_with("at") || has(&["date", "time"]) {
+            self.date_time()
+        } else {
+            self.words(2)
+        }
+    }
+}
+
+/// Encode a ULID from its 48-bit millisecond timestamp and 80-bit randomness
+pub fn encode_ulid(millis: u128, random: u128) -> String {
+    let value = (millis << 80) | (random & ((1u128 << 80) - 1));
+    (0..26)
+        .rev()
+        .map(|i| CROCKFORD[((value >> (i * 5)) & 0x1f) as usize] as char)
+        .collect()
+}
+
+/// Format epoch seconds as an RFC 3339 date-time in UTC
+pub fn format_date_time(timestamp: i64) -> String {
+    DateTime::from_timestamp(timestamp, 0)
+        .unwrap_or_default()
+        .to_rfc3339_opts(SecondsFormat::Secs, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::format;
+
+    #[test]
+    fn test_rng_is_deterministic() {
+        let mut a = Rng::new(42);
+        let mut b = Rng::new(42);
+        let mut c = Rng::new(43);
+        let first: Vec<u64> = (0..5).map(|_| a.next_u64()).collect();
+        let second: Vec<u64> = (0..5).map(|_| b.next_u64()).collect();
+        let third: Vec<u64> = (0..5).map(|_| c.next_u64()).collect();
+        assert_eq!(first, second);
+        assert_ne!(first, third);
+    }
+
+    #[test]
+    fn test_rng_ranges() {
+        let mut rng = Rng::new(1);
+        for _ in 0..1000 {
+            let n = rng.range(-3, 3);
+            assert!((-3..=3).contains(&n));
+            let f = rng.range_f64(1.5, 2.5);
+            assert!((1.5..2.5).contains(&f));
+        }
+        assert_eq!(rng.range(5, 5), 5);
+        assert_eq!(rng.range(5, 1), 5);
+
+        for _ in 0..1000 {
+            rng.range(i64::MIN, i64::MAX);
+            assert!(rng.range(i64::MAX - 1, i64::MAX) >= i64::MAX - 1);
+            assert!(rng.range(i64::MIN, i64::MIN + 1) <= i64::MIN + 1);
+            assert!(rng.range(-1, i64::MAX) >= -1);
+        }
+    }
+
+    #[test]
+    fn test_faker_formats() {
+        let mut faker = Faker::new(7);
+        for _ in 0..50 {
+            assert!(format::matches("uuid", &faker.uuid()));
+            assert!(format::matches("ulid", &faker.ulid()));
+            assert!(format::matches("date-time", &faker.date_time()));
+            assert!(format::matches("date", &faker.date()));
+            assert!(format::matches("email", &faker.email()));
+            assert!(format::matches("uri", &faker.url()));
+        }
+    }
+
+    #[test]
+    fn test_faker_text() {
+        let mut faker = Faker::new(7);
+        assert_eq!(faker.name().split(' ').count(), 2);
+        assert_eq!(faker.words(3).split(' ').count(), 3);
+
+        let sentence = faker.sentence();
+        assert!(sentence.ends_with('.'));
+        assert!(sentence.chars().next().unwrap().is_ascii_uppercase());
+        assert_eq!(faker.zip_code().len(), 5);
+    }
+
+    #[test]
+    fn test_faker_string_for() {
+        let mut faker = Faker::new(7);
+        assert!(format::matches("email", &faker.string_for("contact_email")));
+        assert!(format::matches("date-time", &faker.string_for("createdAt")));
+        assert!(format::matches(
+            "date-time",
+            &faker.string_for("updated_at")
+        ));
+        assert!(format::matches("uri", &faker.string_for("avatarUrl")));
+        assert_eq!(faker.string_for("name").split(' ').count(), 2);
+        assert!(faker.string_for("title").ends_with('.'));
+        assert_eq!(faker.string_for("zip").len(), 5);
+        assert_eq!(faker.string_for("misc").split(' ').count(), 2);
+    }
+
+    #[test]
+    fn test_encode_ulid() {
+        assert_eq!(encode_ulid(0, 0), "00000000000000000000000000");
+        assert_eq!(
+            encode_ulid(1_469_918_176_385, 0),
+            "01ARYZ6S410000000000000000"
+        );
+    }
+
+    #[test]
+    fn test_format_date_time() {
+        assert_eq!(format_date_time(0), "1970-01-01T00:00:00Z");
+        assert_eq!(format_date_time(1_577_836_800), "2020-01-01T00:00:00Z");
+    }
+}
@@ -0,0 +1,458 @@
+use crate::error::MocksError;
+use crate::fake::Faker;
+use crate::schema::resource_name;
+use serde_json::{Map, Value};
+use std::collections::HashMap;
+use std::fs;
+
+/// Template declaring how each field of a generated record is produced
+///
+/// A template is a JSON document whose strings may contain `{{generator args}}` placeholders.
+/// A string made of a single placeholder yields the typed value of the generator
+/// (e.g. `"{{int 18 80}}"` becomes a number); otherwise placeholders are interpolated as text.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Template {
+    root: Node,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Node {
+    Literal(Value),
+    Text(Vec<Part>),
+    Array(Vec<Node>),
+    Object(Vec<(String, Node)>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Part {
+    Raw(String),
+    Placeholder(Generator),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Generator {
+    Seq,
+    Uuid,
+    Ulid,
+    FirstName,
+    LastName,
+    Name,
+    Username,
+    Email,
+    Phone,
+    Street,
+    City,
+    Country,
+    ZipCode,
+    Url,
+    Word,
+    Words(usize),
+    Sentence,
+    Paragraph,
+    Date,
+    DateTime,
+    Timestamp,
+    Bool,
+    Int(i64, i64),
+    Float(f64, f64, u32),
+    Pick(Vec<String>),
+    Ref(String),
+}
+
+/// IDs of existing records, by resource name, available to `{{ref resource}}`
+pub type References = HashMap<String, Vec<Value>>;
+
+impl Template {
+    /// Load a template from a JSON file
+    ///
+    /// # Arguments
+    /// * `path` - Path of the template file
+    pub fn load(path: &str) -> Result<Template, MocksError> {
+        let text =
+            fs::read_to_string(path).map_err(|e| MocksError::FailedReadFile(e.to_string()))?;
+        let value: Value = serde_json::from_str(&text)
+            .map_err(|e| MocksError::FailedReadFile(format!("{path}: {e}")))?;
+        Template::parse(&value)
+    }
+
+    pub fn parse(value: &Value) -> Result<Template, MocksError> {
+        if !value.is_object() {
+            return Err(MocksError::InvalidArgs(
+                "Template must be a JSON object.".to_string(),
+            ));
+        }
+        Ok(Template {
+            root: parse_node(value)?,
+        })
+    }
+
+    /// Resources referenced with `{{ref resource}}`
+    pub fn references(&self) -> Vec<&str> {
+        let mut names = vec![];
+        collect_references(&self.root, &mut names);
+        names.sort_unstable();
+        names.dedup();
+        names
+    }
+
+    /// Render the record at `index` (zero-based)
+    ///
+    /// An integer `id` of `index + 1` is added when the template does not declare one.
+    pub fn render(&self, faker: &mut Faker, index: usize, refs: &References) -> Value {
+        let mut value = render_node(&self.root, faker, index, refs);
+        if let Some(obj) = value.as_object_mut() {
+            if !obj.contains_key("id") {
+                obj.insert("id".to_string(), Value::from(index + 1));
+            }
+        }
+        value
+    }
+}
+
+/// Collect the IDs of the records of every array resource
+pub fn references(data: &Map<String, Value>) -> References {
+    data.iter()
+        .filter_map(|(key, value)| {
+            let ids = value
+                .as_array()?
+                .iter()
+                .filter_map(|item| item.get("id").cloned())
+                .collect();
+            Some((resource_name(key).to_string(), ids))
+        })
+        .collect()
+}
+
+/// Generate records for each resource and store them in `data`
+///
+/// Resources are generated in order, so a template may reference the IDs of a resource
+/// generated before it as well as those already in `data`.
+///
+/// # Arguments
+/// * `data` - Storage data, updated in place
+/// * `jobs` - Resource key, number of records and template of each resource
+/// * `seed` - Seed for reproducible records
+pub fn generate_resources(
+    data: &mut Map<String, Value>,
+    jobs: &[(String, usize, Template)],
+    seed: u64,
+) -> Result<(), MocksError> {
+    let mut faker = Faker::new(seed);
+    for (key, count, template) in jobs {
+        let refs = references(data);
+        if let Some(missing) = template
+            .references()
+            .into_iter()
+            .find(|name| !matches!(refs.get(*name), Some(ids) if !ids.is_empty()))
+        {
+            return Err(MocksError::InvalidArgs(format!(
+                "No records of {missing} to reference from {key}."
+            )));
+        }
+
+        let records: Vec<Value> = (0..*count)
+            .map(|index| template.render(&mut faker, index, &refs))
+            .collect();
+        data.insert(key.to_string(), Value::Array(records));
+    }
+    Ok(())
+}
+
+fn parse_node(value: &Value) -> Result<Node, MocksError> {
+    Ok(match value {
+        Value::String(text) if text.contains("{{") => Node::Text(parse_text(text)?),
+        Value::Array(items) => Node::Array(items.iter().map(parse_node).collect::<Result<_, _>>()?),
+        Value::Object(obj) => Node::Object(
+            obj.iter()
+                .map(|(key, value)| Ok((key.to_string(), parse_node(value)?)))
+                .collect::<Result<_, MocksError>>()?,
+        ),
+        _ => Node::Literal(value.clone()),
+    })
+}
+
+fn parse_text(text: &str) -> Result<Vec<Part>, MocksError> {
+    let mut parts = vec![];
+    let mut rest = text;
+    while let Some(start) = rest.find("{{") {
+        let Some(end) = rest[start..].find("}}") else {
+            break;
+        };
+        if start > 0 {
+            parts.push(Part::Raw(rest[..start].to_string()));
+        }
+        let expression = &rest[start + 2..start + end];
+        parts.push(Part::Placeholder(parse_generator(expression)?));
+        rest = &rest[start + end + 2..];
+    }
+    if !rest.is_empty() {
+        parts.push(Part::Raw(rest.to_string()));
+    }
+    Ok(parts)
+}
+
+fn parse_generator(expression: &str) -> Result<Generator, MocksError> {
+    let mut tokens = expression.split_whitespace();
+    let name = tokens.next().unwrap_or_default();
+    let args: Vec<&str> = tokens.collect();
+    let invalid = || {
+        MocksError::InvalidArgs(format!(
+            "Invalid generator in template: {{{{{}}}}}",
+            expression.trim()
+        ))
+    };
+    let int = |i: usize| args.get(i).and_then(|a| a.parse::<i64>().ok());
+    let float = |i: usize| args.get(i).and_then(|a| a.parse::<f64>().ok());
+
+    let generator = match name {
+        "seq" => Generator::Seq,
+        "uuid" => Generator::Uuid,
+        "ulid" => Generator::Ulid,
+        "first_name" => Generator::FirstName,
+        "last_name" => Generator::LastName,
+        "name" => Generator::Name,
+        "username" => Generator::Username,
+        "email" => Generator::Email,
+        "phone" => Generator::Phone,
+        "street" => Generator::Street,
+        "city" => Generator::City,
+        "country" => Generator::Country,
+        "zip_code" => Generator::ZipCode,
+        "url" => Generator::Url,
+        "word" => Generator::Word,
+        "words" => Generator::Words(int(0).unwrap_or(3).max(1) as usize),
+        "sentence" | "lorem" => Generator::Sentence,
+        "paragraph" => Generator::Paragraph,
+        "date" => Generator::Date,
+        "date_time" => Generator::DateTime,
+        "timestamp" => Generator::Timestamp,
+        "bool" => Generator::Bool,
+        "int" => Generator::Int(int(0).ok_or_else(invalid)?, int(1).ok_or_else(invalid)?),
+        "float" => Generator::Float(
+            float(0).ok_or_else(invalid)?,
+            float(1).ok_or_else(invalid)?,
+            int(2).unwrap_or(2).clamp(0, 10) as u32,
+        ),
+        "pick" if !args.is_empty() => Generator::Pick(args.iter().map(|a| a.to_string()).collect()),
+        "ref" if args.len() == 1 => Generator::Ref(args[0].to_string()),
+        _ => return Err(invalid()),
+    };
+    Ok(generator)
+}
+
+fn collect_references<'a>(node: &'a Node, names: &mut Vec<&'a str>) {
+    match node {
+        Node::Text(parts) => {
+            for part in parts {
+                if let Part::Placeholder(Generator::Ref(name)) = part {
+                    names.push(name);
+                }
+            }
+        }
+        Node::Array(items) => items.iter().for_each(|n| collect_references(n, names)),
+        Node::Object(fields) => fields
+            .iter()
+            .for_each(|(_, n)| collect_references(n, names)),
+        Node::Literal(_) => {}
+    }
+}
+
+fn render_node(node: &Node, faker: &mut Faker, index: usize, refs: &References) -> Value {
+    match node {
+        Node::Literal(value) => value.clone(),
+        Node::Text(parts) => match parts.as_slice() {
+            [Part::Placeholder(generator)] => generate(generator, faker, index, refs),
+            _ => {
+                let text: String = parts
+                    .iter()
+                    .map(|part| match part {
+                        Part::Raw(raw) => raw.to_string(),
+                        Part::Placeholder(generator) => {
+                            match generate(generator, faker, index, refs) {
+                                Value::String(s) => s,
+                                value => value.to_string(),
+                            }
+                        }
+                    })
+                    .collect();
+                Value::String(text)
+            }
+        },
+        Node::Array(items) => items
+            .iter()
+            .map(|n| render_node(n, faker, index, refs))
+            .collect(),
+        Node::Object(fields) => Value::Object(
+            fields
+                .iter()
+                .map(|(key, n)| (key.to_string(), render_node(n, faker, index, refs)))
+                .collect(),
+        ),
+    }
+}
+
+fn generate(generator: &Generator, faker: &mut Faker, index: usize, refs: &References) -> Value {
+    match generator {
+        Generator::Seq => Value::from(index + 1),
+        Generator::Uuid => Value::from(faker.uuid()),
+        Generator::Ulid => Value::from(faker.ulid()),
+        Generator::FirstName => Value::from(faker.first_name()),
+        Generator::LastName => Value::from(faker.last_name()),
+        Generator::Name => Value::from(faker.name()),
+        Generator::Username => Value::from(faker.username()),
+        Generator::Email => Value::from(faker.email()),
+        Generator::Phone => Value::from(faker.phone()),
+        Generator::Street => Value::from(faker.street()),
+        Generator::City => Value::from(faker.city()),
+        Generator::Country => Value::from(faker.country()),
+        Generator::ZipCode => Value::from(faker.zip_code()),
+        Generator::Url => Value::from(faker.url()),
+        Generator::Word => Value::from(faker.word()),
+        Generator::Words(count) => Value::from(faker.words(*count)),
+        Generator::Sentence => Value::from(faker.sentence()),
+        Generator::Paragraph => Value::from(faker.paragraph()),
+        Generator::Date => Value::from(faker.date()),
+        Generator::DateTime => Value::from(faker.date_time()),
+        Generator::Timestamp => Value::from(faker.timestamp()),
+        Generator::Bool => Value::from(faker.boolean()),
+        Generator::Int(min, max) => Value::from(faker.rng.range(*min, *max)),
+        Generator::Float(min, max, precision) => {
+            let factor = 10f64.powi(*precision as i32);
+            let value = (faker.rng.range_f64(*min, *max) * factor).round() / factor;
+            Value::from(value)
+        }
+        Generator::Pick(options) => Value::from(faker.rng.pick(options).to_string()),
+        Generator::Ref(resource) => match refs.get(resource) {
+            Some(ids) if !ids.is_empty() => faker.rng.pick(ids).clone(),
+            _ => Value::Null,
+        },
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::format;
+    use serde_json::json;
+
+    fn user_template() -> Template {
+        Template::parse(&json!({
+            "id": "{{ulid}}",
+            "name": "{{name}}",
+            "email": "{{email}}",
+            "age": "{{int 18 80}}",
+            "score": "{{float 0 5 1}}",
+            "role": "{{pick admin member}}",
+            "handle": "user-{{seq}}",
+            "address": {"street": "{{street}}", "city": "{{city}}"},
+            "tags": ["{{word}}", "fixed"],
+            "active": true,
+            "teamId": "{{ref teams}}"
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn test_render() {
+        let refs = References::from([("teams".to_string(), vec![json!(10), json!(20)])]);
+        let mut faker = Faker::new(1);
+        let user = user_template().render(&mut faker, 2, &refs);
+
+        assert!(format::matches("ulid", user["id"].as_str().unwrap()));
+        assert!(format::matches("email", user["email"].as_str().unwrap()));
+        assert!((18..=80).contains(&user["age"].as_i64().unwrap()));
+        let score = user["score"].as_f64().unwrap();
+        assert!((0.0..5.0).contains(&score));
+        assert_eq!((score * 10.0).round() / 10.0, score);
+        assert!(["admin", "member"].contains(&user["role"].as_str().unwrap()));
+        assert_eq!(user["handle"], "user-3");
+        assert!(user["address"]["city"].is_string());
+        assert_eq!(user["tags"][1], "fixed");
+        assert_eq!(user["active"], true);
+        assert!([json!(10), json!(20)].contains(&user["teamId"]));
+    }
+
+    #[test]
+    fn test_render_is_reproducible() {
+        let template = user_template();
+        let refs = References::new();
+        let first = template.render(&mut Faker::new(42), 0, &refs);
+        let second = template.render(&mut Faker::new(42), 0, &refs);
+        let third = template.render(&mut Faker::new(43), 0, &refs);
+        assert_eq!(first, second);
+        assert_ne!(first, third);
+    }
+
+    #[test]
+    fn test_render_adds_sequential_id() {
+        let template = Template::parse(&json!({"title": "{{sentence}}"})).unwrap();
+        let post = template.render(&mut Faker::new(0), 4, &References::new());
+        assert_eq!(post["id"], 5);
+    }
+
+    #[test]
+    fn test_parse_invalid_generator() {
+        for text in [
+            "{{unknown}}",
+            "{{int 1}}",
+            "{{float a b}}",
+            "{{pick}}",
+            "{{ref}}",
+        ] {
+            let result = Template::parse(&json!({ "field": text }));
+            assert!(
+                matches!(result, Err(MocksError::InvalidArgs(_))),
+                "{text} should be invalid"
+            );
+        }
+        assert!(Template::parse(&json!([])).is_err());
+    }
+
+    #[test]
+    fn test_generate_resources() {
+        let mut data = json!({"teams": [{"id": 1}]}).as_object().unwrap().clone();
+        let jobs = vec![
+            ("users".to_string(), 3, user_template()),
+            (
+                "posts".to_string(),
+                5,
+                Template::parse(&json!({"userId": "{{ref users}}"})).unwrap(),
+            ),
+        ];
+        generate_resources(&mut data, &jobs, 7).unwrap();
+
+        let users = data["users"].as_array().unwrap();
+        assert_eq!(users.len(), 3);
+        let user_ids: Vec<&Value> = users.iter().map(|u| &u["id"]).collect();
+        for post in data["posts"].as_array().unwrap() {
+            assert!(user_ids.contains(&&post["userId"]));
+        }
+        assert_eq!(data["posts"][4]["id"], 5);
+    }
+
+    #[test]
+    fn test_generate_resources_with_missing_reference() {
+        let mut data = Map::new();
+        let jobs = vec![("users".to_string(), 3, user_template())];
+        let result = generate_resources(&mut data, &jobs, 7);
+        assert!(matches!(result, Err(MocksError::InvalidArgs(_))));
+    }
+
+    #[test]
+    fn test_references() {
+        assert_eq!(user_template().references(), vec!["teams"]);
+        let template = Template::parse(&json!({
+            "ids": ["{{ref teams}}", "{{ref users}}", "{{ref teams}}"]
+        }))
+        .unwrap();
+        assert_eq!(template.references(), vec!["teams", "users"]);
+
+        let data = json!({
+            "api/v1/users": [{"id": 1}, {"id": "a"}, {"name": "no id"}],
+            "profile": {"id": 1}
+        });
+        let refs = references(data.as_object().unwrap());
+        assert_eq!(refs["users"], vec![json!(1), json!("a")]);
+        assert!(!refs.contains_key("profile"));
+    }
+}
@@ -0,0 +1,127 @@
+pub const FIRST_NAMES: [&str; 24] = [
+    "James",
+    "Mary",
+    "John",
+    "Patricia",
+    "Robert",
+    "Jennifer",
+    "Michael",
+    "Linda",
+    "David",
+    "Elizabeth",
+    "William",
+    "Barbara",
+    "Richard",
+    "Susan",
+    "Joseph",
+    "Jessica",
+    "Thomas",
+    "Sarah",
+    "Haruto",
+    "Yui",
+    "Lucas",
+    "Emma",
+    "Mateo",
+    "Sofia",
+];
+
+pub const LAST_NAMES: [&str; 20] = [
+    "Smith",
+    "Johnson",
+    "Williams",
+    "Brown",
+    "Jones",
+    "Garcia",
+    "Miller",
+    "Davis",
+    "Rodriguez",
+    "Martinez",
+    "Hernandez",
+    "Lopez",
+    "Wilson",
+    "Anderson",
+    "Taylor",
+    "Thomas",
+    "Moore",
+    "Sato",
+    "Suzuki",
+    "Tanaka",
+];
+
+pub const DOMAINS: [&str; 4] = ["example.com", "example.net", "example.org", "mail.example"];
+
+pub const STREETS: [&str; 10] = [
+    "Main St",
+    "Oak Ave",
+    "Pine St",
+    "Maple Ave",
+    "Cedar Ln",
+    "Elm St",
+    "Park Rd",
+    "Lake Dr",
+    "Hill St",
+    "River Rd",
+];
+
+pub const CITIES: [&str; 12] = [
+    "Springfield",
+    "Riverside",
+    "Franklin",
+    "Greenville",
+    "Bristol",
+    "Clinton",
+    "Fairview",
+    "Salem",
+    "Madison",
+    "Georgetown",
+    "Tokyo",
+    "Osaka",
+];
+
+pub const COUNTRIES: [&str; 10] = [
+    "United States",
+    "Japan",
+    "Canada",
+    "United Kingdom",
+    "Germany",
+    "France",
+    "Australia",
+    "Brazil",
+    "India",
+    "Spain",
+];
+
+pub const LOREM: [&str; 32] = [
+    "lorem",
+    "ipsum",
+    "dolor",
+    "sit",
+    "amet",
+    "consectetur",
+    "adipiscing",
+    "elit",
+    "sed",
+    "do",
+    "eiusmod",
+    "tempor",
+    "incididunt",
+    "ut",
+    "labore",
+    "et",
+    "dolore",
+    "magna",
+    "aliqua",
+    "enim",
+    "ad",
+    "minim",
+    "veniam",
+    "quis",
+    "nostrud",
+    "exercitation",
+    "ullamco",
+    "laboris",
+    "nisi",
+    "aliquip",
+    "ex",
+    "commodo",
+];
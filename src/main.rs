@@ -1,14 +1,31 @@
 mod console;
 mod error;
+mod fake;
+mod openapi;
+mod schema;
 mod server;
 mod storage;
 
-use crate::console::{print_error, print_init_aborted, print_init_success, print_startup_info};
+use crate::console::{
+    print_auth_info, print_chaos_info, print_contract_info, print_cors_info, print_delay_info,
+    print_error, print_generate_success, print_init_aborted, print_init_success,
+    print_openapi_success, print_proxy_info, print_rate_limit_info, print_record_info,
+    print_schema_infer_success, print_startup_info, print_tls_info,
+};
 use crate::error::MocksError;
-use crate::server::Server;
+use crate::fake::template::{generate_resources, Template};
+use crate::openapi::contract::{Contract, ContractMode};
+use crate::schema::Schemas;
+use crate::server::{
+    Auth, Chaos, Cors, Delay, Delays, Fallback, Faults, Headers, LimitKey, Methods, Origins,
+    ProxyHeader, Rate, RateLimiter, Server, ServerOptions, Tls, Upstream,
+};
+use crate::storage::recorder::Recorder;
 use crate::storage::Storage;
 use clap::Parser;
+use serde_json::{Map, Value};
 use std::net::{IpAddr, SocketAddr};
+use std::path::{Path, PathBuf};
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None, styles = get_styles())]
@@ -20,9 +37,17 @@ struct Cli {
 #[derive(clap::Subcommand, Debug)]
 enum Commands {
     /// Start the mock api server
-    Run(RunArgs),
+    Run(Box<RunArgs>),
     /// Initialize a new storage file
     Init(InitArgs),
+    /// Work with JSON Schemas of resources
+    Schema(SchemaArgs),
+    /// Print the OpenAPI document of the mock api
+    Openapi(OpenapiArgs),
+    /// Generate fake records for resources from templates
+    Generate(GenerateArgs),
+    /// Proxy an upstream api and record its responses into a storage file
+    Record(RecordArgs),
 }
 
 #[derive(clap::Args, Debug)]
@@ -41,6 +66,119 @@ struct RunArgs {
     /// No overwrite save to json file
     #[arg(long, default_value_t = false)]
     no_overwrite: bool,
+
+    /// Validate requests and responses against an OpenAPI spec (YAML or JSON)
+    #[arg(long, value_name = "SPEC")]
+    spec: Option<String>,
+
+    /// How contract violations are handled (with --spec)
+    #[arg(long, value_enum, default_value = "strict", requires = "spec")]
+    contract_mode: ContractMode,
+
+    /// Latency added to every request (e.g. 200ms, 100..800ms, 1..2s:normal)
+    #[arg(long, value_name = "DELAY")]
+    delay: Option<Delay>,
+
+    /// Faults injected into requests (e.g. error=0.1,status=503,drop=0.05)
+    #[arg(long, value_name = "FAULTS")]
+    chaos: Option<Faults>,
+
+    /// Seed for reproducible fault injection
+    #[arg(long, value_name = "SEED")]
+    chaos_seed: Option<u64>,
+
+    /// Requests allowed per client (e.g. 10/s, 100/min, 1000/h)
+    #[arg(long, value_name = "RATE")]
+    rate_limit: Option<Rate>,
+
+    /// Requests allowed at once before the rate applies (default: the rate count)
+    #[arg(long, value_name = "COUNT", requires = "rate_limit")]
+    rate_limit_burst: Option<u32>,
+
+    /// What requests are counted by: global, ip or header:NAME (with --rate-limit)
+    #[arg(
+        long,
+        value_name = "KEY",
+        default_value = "ip",
+        requires = "rate_limit"
+    )]
+    rate_limit_key: LimitKey,
+
+    /// Allow cross-origin requests from browsers, from any origin or --cors=ORIGIN,...
+    #[arg(
+        long,
+        value_name = "ORIGINS",
+        num_args = 0..=1,
+        require_equals = true,
+        default_missing_value = "*"
+    )]
+    cors: Option<Origins>,
+
+    /// Methods allowed cross-origin (default: all, with --cors)
+    #[arg(long, value_name = "METHODS", requires = "cors")]
+    cors_methods: Option<Methods>,
+
+    /// Request headers allowed cross-origin (default: those requested, with --cors)
+    #[arg(long, value_name = "HEADERS", requires = "cors")]
+    cors_headers: Option<Headers>,
+
+    /// Allow cookies and credentials in cross-origin requests (with --cors)
+    #[arg(long, default_value_t = false, requires = "cors")]
+    cors_credentials: bool,
+
+    /// Seconds browsers may cache preflight responses (with --cors)
+    #[arg(long, value_name = "SECONDS", requires = "cors")]
+    cors_max_age: Option<u64>,
+
+    /// Serve HTTPS with a PEM certificate chain (with --tls-key)
+    #[arg(long, value_name = "FILE", requires = "tls_key", group = "tls")]
+    tls_cert: Option<PathBuf>,
+
+    /// PEM private key of the certificate (with --tls-cert)
+    #[arg(long, value_name = "FILE", requires = "tls_cert")]
+    tls_key: Option<PathBuf>,
+
+    /// Serve HTTPS with a cached self-signed certificate for localhost
+    #[arg(long, default_value_t = false, group = "tls")]
+    tls_self_signed: bool,
+
+    /// Offer HTTP/2 to clients over TLS
+    #[arg(long, default_value_t = false, requires = "tls")]
+    http2: bool,
+
+    /// Forward requests no resource or route serves to an upstream api
+    #[arg(long, value_name = "URL")]
+    proxy_fallback: Option<String>,
+
+    /// Header set on forwarded requests, e.g. "X-Api-Key: abc" or "Cookie:" to remove it
+    #[arg(
+        long = "proxy-header",
+        value_name = "HEADER",
+        requires = "proxy_fallback"
+    )]
+    proxy_headers: Vec<ProxyHeader>,
+
+    /// Seconds successful GET responses of the upstream are cached (with --proxy-fallback)
+    #[arg(long, value_name = "SECONDS", requires = "proxy_fallback")]
+    proxy_cache: Option<u64>,
+}
+
+#[derive(clap::Args, Debug)]
+struct RecordArgs {
+    /// Path of json file to record to (created if missing)
+    file: String,
+
+    /// Base URL of the upstream api (e.g. https://api.example.com)
+    #[arg(short, long, value_name = "URL")]
+    target: String,
+
+    /// Host
+    #[arg(short = 'H', long, default_value = "localhost")]
+    host: String,
+
+    /// Port
+    #[arg(short, long, default_value_t = 3000)]
+    port: u16,
 }
 
 #[derive(clap::Args, Debug)]
@@ -50,8 +188,91 @@ struct InitArgs {
     file: String,
 
     /// Create empty structure instead of sample data
-    #[arg(short = 'E', long)]
+    #[arg(short = 'E', long, conflicts_with = "from_openapi")]
     empty: bool,
+
+    /// Generate resources and example records from an OpenAPI spec (YAML or JSON)
+    #[arg(long, value_name = "SPEC")]
+    from_openapi: Option<String>,
+
+    /// Number of records generated per array resource (with --from-openapi)
+    #[arg(short = 'n', long, default_value_t = 5, requires = "from_openapi")]
+    count: usize,
+
+    /// Generate fake records instead of sample data (e.g. users:500)
+    #[arg(
+        short,
+        long = "resource",
+        value_name = "NAME:COUNT",
+        conflicts_with_all = ["empty", "from_openapi"],
+        requires = "templates",
+        value_parser = parse_resource_count
+    )]
+    resources: Vec<(String, usize)>,
+
+    /// Template file declaring the fields of each --resource, in the same order
+    #[arg(short, long = "template", value_name = "FILE", requires = "resources")]
+    templates: Vec<String>,
+
+    /// Seed for reproducible records (with --from-openapi or --resource)
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
+}
+
+#[derive(clap::Args, Debug)]
+struct SchemaArgs {
+    #[command(subcommand)]
+    command: SchemaCommands,
+}
+
+#[derive(clap::Subcommand, Debug)]
+enum SchemaCommands {
+    /// Infer JSON Schemas from the data in a storage file
+    Infer(SchemaInferArgs),
+}
+
+#[derive(clap::Args, Debug)]
+struct SchemaInferArgs {
+    /// Path of json file for data storage
+    file: String,
+
+    /// Directory to write one schema file per resource to (default: print to stdout)
+    #[arg(short, long)]
+    out_dir: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct OpenapiArgs {
+    /// Path of json file for data storage
+    file: String,
+
+    /// Path of file to write the document to (default: print to stdout)
+    #[arg(short, long)]
+    output: Option<String>,
+}
+
+#[derive(clap::Args, Debug)]
+struct GenerateArgs {
+    /// Path of json file for data storage (created if missing)
+    file: String,
+
+    /// Resource and number of records to generate (e.g. users:500)
+    #[arg(
+        short,
+        long = "resource",
+        value_name = "NAME:COUNT",
+        required = true,
+        value_parser = parse_resource_count
+    )]
+    resources: Vec<(String, usize)>,
+
+    /// Template file declaring the fields of each --resource, in the same order
+    #[arg(short, long = "template", value_name = "FILE", required = true)]
+    templates: Vec<String>,
+
+    /// Seed for reproducible records
+    #[arg(long, default_value_t = 0)]
+    seed: u64,
 }
 
 #[tokio::main]
@@ -73,7 +294,21 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             };
 
-            let url = format!("http://{}:{}", &args.host, args.port);
+            let tls = match (&args.tls_cert, &args.tls_key) {
+                (Some(cert), Some(key)) => Some(Tls::from_files(cert, key, args.http2)),
+                _ if args.tls_self_signed => Some(Tls::self_signed(&args.host, args.http2)),
+                _ => None,
+            };
+            let tls = match tls.transpose() {
+                Ok(tls) => tls,
+                Err(e) => {
+                    print_error(&e);
+                    std::process::exit(1);
+                }
+            };
+
+            let scheme = if tls.is_some() { "https" } else { "http" };
+            let url = format!("{scheme}://{}:{}", &args.host, args.port);
             let overwrite = !args.no_overwrite;
 
             let storage = match Storage::new(&args.file, overwrite) {
@@ -84,12 +319,116 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
                 }
             };
 
+            let contract = match &args.spec {
+                Some(spec) => match openapi::load_spec(spec)
+                    .and_then(|value| Contract::new(value, args.contract_mode))
+                {
+                    Ok(contract) => Some(contract),
+                    Err(e) => {
+                        print_error(&e);
+                        std::process::exit(1);
+                    }
+                },
+                None => None,
+            };
+
+            let delays = match Delays::load(&storage, args.delay.clone()) {
+                Ok(delays) => delays,
+                Err(e) => {
+                    print_error(&e);
+                    std::process::exit(1);
+                }
+            };
+
+            let chaos = match Chaos::load(&storage, args.chaos.clone(), args.chaos_seed) {
+                Ok(chaos) => chaos,
+                Err(e) => {
+                    print_error(&e);
+                    std::process::exit(1);
+                }
+            };
+
+            let auth = match Auth::load(&storage) {
+                Ok(auth) => auth,
+                Err(e) => {
+                    print_error(&e);
+                    std::process::exit(1);
+                }
+            };
+
+            let fallback = match args.proxy_fallback.as_deref().map(Upstream::new) {
+                Some(Ok(upstream)) => Some(Fallback::new(
+                    upstream,
+                    args.proxy_headers.clone(),
+                    args.proxy_cache,
+                )),
+                Some(Err(e)) => {
+                    print_error(&e);
+                    std::process::exit(1);
+                }
+                None => None,
+            };
+
             print_startup_info(&url, &args.file, overwrite);
+            if let Some(tls) = &tls {
+                print_tls_info(&tls.describe());
+            }
+            if let Some(spec) = &args.spec {
+                print_contract_info(spec, args.contract_mode.as_str());
+            }
+            if let Some(auth) = &auth {
+                print_auth_info(
+                    &auth.describe(),
+                    auth.jwt().is_some_and(|j| j.can_sign()),
+                    auth.oidc().is_some(),
+                );
+            }
+            if let Some(delay) = &args.delay {
+                print_delay_info(&delay.to_string());
+            }
+            if let Some(faults) = &args.chaos {
+                print_chaos_info(&faults.to_string(), args.chaos_seed);
+            }
+            let rate_limit = args.rate_limit.map(|rate| {
+                RateLimiter::new(rate, args.rate_limit_burst, args.rate_limit_key.clone())
+            });
+            if let Some(limiter) = &rate_limit {
+                print_rate_limit_info(&limiter.describe());
+            }
+            let cors = args.cors.clone().map(|origins| {
+                Cors::new(
+                    origins,
+                    args.cors_methods.clone(),
+                    args.cors_headers.clone(),
+                    args.cors_credentials,
+                    args.cors_max_age,
+                )
+            });
+            if let Some(cors) = &cors {
+                print_cors_info(&cors.describe());
+            }
+            if let Some(fallback) = &fallback {
+                print_proxy_info(&fallback.describe());
+            }
 
-            Server::startup(socket_addr, storage).await
+            let options = ServerOptions {
+                contract,
+                auth,
+                delays,
+                chaos: Some(chaos),
+                rate_limit,
+                cors,
+                tls,
+                fallback,
+            };
+            Server::startup(socket_addr, storage, options).await
         }
         Commands::Init(args) => {
-            let result = Storage::init_file(&args.file, args.empty);
+            let result = match &args.from_openapi {
+                Some(spec) => init_from_openapi(&args, spec),
+                None if !args.resources.is_empty() => init_from_templates(&args),
+                None => Storage::init_file(&args.file, args.empty),
+            };
             match &result {
                 Ok(()) => print_init_success(&args.file),
                 Err(MocksError::Aborted) => {
@@ -102,6 +441,12 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
             }
             result
         }
+        Commands::Schema(args) => match args.command {
+            SchemaCommands::Infer(args) => infer_schemas(&args),
+        },
+        Commands::Openapi(args) => print_openapi(&args),
+        Commands::Generate(args) => generate(&args),
+        Commands::Record(args) => record(&args).await,
     };
 
     if let Err(e) = result {
@@ -112,6 +457,132 @@ async fn main() -> Result<(), Box<dyn std::error::Error>> {
     Ok(())
 }
 
+async fn record(args: &RecordArgs) -> Result<(), MocksError> {
+    let socket_addr = parse_socket_addr(&args.host, args.port)?;
+    let upstream = Upstream::new(&args.target)?;
+    let recorder = Recorder::new(&args.file)?;
+
+    let url = format!("http://{}:{}", &args.host, args.port);
+    print_record_info(&url, &upstream.describe(), &args.file);
+    Server::record(socket_addr, upstream, recorder).await
+}
+
+fn init_from_openapi(args: &InitArgs, spec: &str) -> Result<(), MocksError> {
+    let spec = openapi::load_spec(spec)?;
+    let data = openapi::bootstrap::bootstrap(&spec, args.count, args.seed)?;
+    Storage::init_file_with_data(&args.file, &data, false)
+}
+
+fn init_from_templates(args: &InitArgs) -> Result<(), MocksError> {
+    let mut data = Map::new();
+    let jobs = template_jobs(&args.resources, &args.templates, &data)?;
+    generate_resources(&mut data, &jobs, args.seed)?;
+    Storage::init_file_with_data(&args.file, &Value::Object(data), false)
+}
+
+fn infer_schemas(args: &SchemaInferArgs) -> Result<(), MocksError> {
+    let storage = Storage::new(&args.file, false)?;
+    match &args.out_dir {
+        Some(dir) => {
+            let written = schema::write_inferred(&storage, dir)?;
+            print_schema_infer_success(&written);
+        }
+        None => {
+            let schemas = schema::infer_all(&storage);
+            let text = serde_json::to_string_pretty(&schemas)
+                .map_err(|e| MocksError::Exception(e.to_string()))?;
+            println!("{text}");
+        }
+    }
+    Ok(())
+}
+
+fn print_openapi(args: &OpenapiArgs) -> Result<(), MocksError> {
+    let storage = Storage::new(&args.file, false)?;
+    let schemas = Schemas::load(&storage)?;
+    let document = openapi::generate(&storage, &schemas);
+    let text = serde_json::to_string_pretty(&document)
+        .map_err(|e| MocksError::Exception(e.to_string()))?;
+
+    match &args.output {
+        Some(path) => {
+            std::fs::write(path, text).map_err(|e| MocksError::FailedWriteFile(e.to_string()))?;
+            print_openapi_success(path);
+        }
+        None => println!("{text}"),
+    }
+    Ok(())
+}
+
+fn generate(args: &GenerateArgs) -> Result<(), MocksError> {
+    let mut storage = if Path::new(&args.file).exists() {
+        Some(Storage::new(&args.file, true)?)
+    } else {
+        None
+    };
+    let mut data = storage
+        .as_ref()
+        .and_then(|s| s.data.as_object().cloned())
+        .unwrap_or_default();
+
+    let jobs = template_jobs(&args.resources, &args.templates, &data)?;
+    generate_resources(&mut data, &jobs, args.seed)?;
+
+    match storage.as_mut() {
+        Some(storage) => {
+            for (key, _, _) in &jobs {
+                storage.set_resource(key, data[key].clone())?;
+            }
+        }
+        None => Storage::init_file_with_data(&args.file, &Value::Object(data), true)?,
+    }
+
+    let generated: Vec<(String, usize)> = jobs
+        .iter()
+        .map(|(key, count, _)| (key.to_string(), *count))
+        .collect();
+    print_generate_success(&args.file, &generated);
+    Ok(())
+}
+
+/// Pair each `--resource` with its `--template`, resolving the storage key of the resource
+fn template_jobs(
+    resources: &[(String, usize)],
+    templates: &[String],
+    data: &Map<String, Value>,
+) -> Result<Vec<(String, usize, Template)>, MocksError> {
+    if resources.len() != templates.len() {
+        return Err(MocksError::InvalidArgs(
+            "Each --resource needs a --template.".to_string(),
+        ));
+    }
+
+    let mut jobs = vec![];
+    for ((name, count), template) in resources.iter().zip(templates) {
+        // Keep nested keys such as `api/v1/users` when regenerating an existing resource
+        let key = data
+            .keys()
+            .find(|key| schema::resource_name(key) == schema::resource_name(name))
+            .cloned()
+            .unwrap_or_else(|| name.to_string());
+        jobs.push((key, *count, Template::load(template)?));
+    }
+    Ok(jobs)
+}
+
+fn parse_resource_count(value: &str) -> Result<(String, usize), String> {
+    let (name, count) = value
+        .rsplit_once(':')
+        .ok_or_else(|| format!("expected NAME:COUNT, got {value}"))?;
+    let count = count
+        .parse::<usize>()
+        .map_err(|e| format!("invalid count {count}: {e}"))?;
+    if name.is_empty() {
+        return Err("resource name is empty".to_string());
+    }
+    Ok((name.to_string(), count))
+}
+
 fn parse_socket_addr(host: &str, port: u16) -> Result<SocketAddr, MocksError> {
     let ip_addr = if host == "localhost" {
         "127.0.0.1"
@@ -160,6 +631,21 @@ mod tests {
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_parse_resource_count() {
+        assert_eq!(
+            parse_resource_count("users:500").unwrap(),
+            ("users".to_string(), 500)
+        );
+        assert_eq!(
+            parse_resource_count("api/v1/users:3").unwrap(),
+            ("api/v1/users".to_string(), 3)
+        );
+        assert!(parse_resource_count("users").is_err());
+        assert!(parse_resource_count("users:many").is_err());
+        assert!(parse_resource_count(":5").is_err());
+    }
+
     #[test]
     fn test_get_styles() {
         let _ = get_styles();
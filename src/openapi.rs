@@ -0,0 +1,562 @@
+use crate::error::MocksError;
+use crate::schema::infer::infer_items;
+use crate::schema::{resource_name, Schemas};
+use crate::storage::Storage;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fs;
+
+pub mod bootstrap;
+pub mod contract;
+
+const OPENAPI_VERSION: &str = "3.1.0";
+const MATCH_TYPES: [&str; 4] = ["exact", "startswith", "endswith", "contains"];
+
+/// Load an OpenAPI spec written in YAML or JSON
+///
+/// # Arguments
+/// * `path` - Path of the spec file
+pub fn load_spec(path: &str) -> Result<Value, MocksError> {
+    let text = fs::read_to_string(path).map_err(|e| MocksError::FailedReadFile(e.to_string()))?;
+    let yaml: serde_yaml::Value = serde_yaml::from_str(&text)
+        .map_err(|e| MocksError::FailedReadFile(format!("{path}: {e}")))?;
+
+    let spec = yaml_to_json(yaml);
+    if !matches!(spec.get("paths"), Some(Value::Object(_))) {
+        return Err(MocksError::FailedReadFile(format!(
+            "{path}: OpenAPI spec has no paths."
+        )));
+    }
+    Ok(spec)
+}
+
+/// Convert YAML to JSON, stringifying non-string keys such as status codes
+fn yaml_to_json(yaml: serde_yaml::Value) -> Value {
+    match yaml {
+        serde_yaml::Value::Null => Value::Null,
+        serde_yaml::Value::Bool(b) => Value::Bool(b),
+        serde_yaml::Value::Number(n) => {
+            if let Some(i) = n.as_i64() {
+                json!(i)
+            } else if let Some(u) = n.as_u64() {
+                json!(u)
+            } else {
+                n.as_f64().map_or(Value::Null, |f| json!(f))
+            }
+        }
+        serde_yaml::Value::String(s) => Value::String(s),
+        serde_yaml::Value::Sequence(items) => {
+            Value::Array(items.into_iter().map(yaml_to_json).collect())
+        }
+        serde_yaml::Value::Mapping(mapping) => {
+            let mut obj = Map::new();
+            for (key, value) in mapping {
+                let key = match key {
+                    serde_yaml::Value::String(s) => s,
+                    other => match yaml_to_json(other) {
+                        Value::String(s) => s,
+                        other => other.to_string(),
+                    },
+                };
+                obj.insert(key, yaml_to_json(value));
+            }
+            Value::Object(obj)
+        }
+        serde_yaml::Value::Tagged(tagged) => yaml_to_json(tagged.value),
+    }
+}
+
+/// Generate an OpenAPI document describing the API served for a storage
+///
+/// Component schemas come from the declared resource schemas, falling back to
+/// schemas inferred from the current data.
+///
+/// # Arguments
+/// * `storage` - The storage whose resources are described
+/// * `schemas` - Declared resource schemas
+pub fn generate(storage: &Storage, schemas: &Schemas) -> Value {
+    let mut paths = Map::new();
+    let mut components = Map::new();
+    components.insert(
+        "Error".to_string(),
+        json!({
+            "type": "object",
+            "properties": {"error": {"type": "string"}},
+            "required": ["error"]
+        }),
+    );
+    components.insert(
+        "ValidationError".to_string(),
+        json!({
+            "type": "object",
+            "properties": {
+                "error": {"type": "string"},
+                "violations": {
+                    "type": "array",
+                    "items": {
+                        "type": "object",
+                        "properties": {
+                            "path": {"type": "string"},
+                            "keyword": {"type": "string"},
+                            "message": {"type": "string"}
+                        },
+                        "required": ["path", "keyword", "message"]
+                    }
+                }
+            },
+            "required": ["error", "violations"]
+        }),
+    );
+
+    paths.insert(
+        "/_hc".to_string(),
+        json!({
+            "get": {
+                "operationId": "health_check",
+                "summary": "Health check",
+                "responses": {"204": {"description": "Server is running."}}
+            }
+        }),
+    );
+
+    if let Value::Object(obj) = &storage.data {
+        for (key, value) in obj {
+            let name = resource_name(key);
+            let declared = schemas.get(name).cloned();
+            let validated = declared.is_some();
+            let schema = match (declared, value) {
+                (Some(schema), _) => schema,
+                (None, Value::Array(items)) => infer_items(items),
+                (None, Value::Object(_)) => infer_items(std::slice::from_ref(value)),
+                _ => continue,
+            };
+            let resource = Resource {
+                name,
+                schema: &schema,
+                validated,
+            };
+
+            match value {
+                Value::Array(_) => {
+                    paths.insert(format!("/{key}"), resource.collection());
+                    paths.insert(format!("/{key}/{{id}}"), resource.item());
+                }
+                Value::Object(_) => {
+                    paths.insert(format!("/{key}"), resource.singleton());
+                }
+                _ => continue,
+            }
+            components.insert(name.to_string(), schema.clone());
+        }
+    }
+
+    json!({
+        "openapi": OPENAPI_VERSION,
+        "info": {
+            "title": "mocks",
+            "description": format!("Mock REST API generated from {}", storage.file),
+            "version": env!("CARGO_PKG_VERSION")
+        },
+        "paths": paths,
+        "components": {"schemas": components}
+    })
+}
+
+/// A resource being described
+struct Resource<'a> {
+    name: &'a str,
+    schema: &'a Value,
+    /// Whether request bodies are validated against a declared schema
+    validated: bool,
+}
+
+impl Resource<'_> {
+    fn schema_ref(&self) -> Value {
+        json!({"$ref": format!("#/components/schemas/{}", self.name)})
+    }
+
+    /// Schema of PATCH bodies, which may omit required properties
+    fn partial_schema(&self) -> Value {
+        let mut schema = self.schema.clone();
+        if let Some(obj) = schema.as_object_mut() {
+            obj.remove("required");
+        }
+        schema
+    }
+
+    fn collection(&self) -> Value {
+        let name = self.name;
+        json!({
+            "get": {
+                "operationId": format!("list_{name}"),
+                "summary": format!("List {name}"),
+                "tags": [name],
+                "parameters": self.filter_parameters(),
+                "responses": responses(
+                    json!({"200": json_response(
+                        "Items of the resource.",
+                        json!({
+                            "type": "object",
+                            "properties": {name: {"type": "array", "items": self.schema_ref()}},
+                            "required": [name]
+                        })
+                    )}),
+                    &[
+                        MocksError::InvalidQueryParam,
+                        MocksError::InvalidMatchType,
+                        MocksError::MatchTypeRequired,
+                        MocksError::InvalidSearchValue,
+                        MocksError::ResourceNotFound,
+                    ],
+                ),
+            },
+            "post": self.write_operation(
+                format!("create_{name}"),
+                format!("Create an item in {name}"),
+                self.schema_ref(),
+                json!({"201": json_response("Created item.", self.schema_ref())}),
+                &[MocksError::InvalidRequest, MocksError::DuplicateId, MocksError::ObjectNotFound],
+            )
+        })
+    }
+
+    fn item(&self) -> Value {
+        let name = self.name;
+        let found = json!({"200": json_response("The item.", self.schema_ref())});
+        json!({
+            "parameters": [{
+                "name": "id",
+                "in": "path",
+                "required": true,
+                "schema": {"type": "string"}
+            }],
+            "get": {
+                "operationId": format!("get_{name}"),
+                "summary": format!("Get an item of {name}"),
+                "tags": [name],
+                "responses": responses(
+                    found.clone(),
+                    &[MocksError::QueryParamsNotAllowed, MocksError::ObjectNotFound],
+                ),
+            },
+            "put": self.write_operation(
+                format!("replace_{name}"),
+                format!("Replace an item of {name}"),
+                self.schema_ref(),
+                found.clone(),
+                &[
+                    MocksError::InvalidRequest,
+                    MocksError::ObjectNotFound,
+                    MocksError::PreconditionFailed,
+                ],
+            ),
+            "patch": self.write_operation(
+                format!("update_{name}"),
+                format!("Update an item of {name} partially"),
+                self.partial_schema(),
+                found.clone(),
+                &[
+                    MocksError::InvalidRequest,
+                    MocksError::ObjectNotFound,
+                    MocksError::PreconditionFailed,
+                ],
+            ),
+            "delete": {
+                "operationId": format!("delete_{name}"),
+                "summary": format!("Delete an item of {name}"),
+                "tags": [name],
+                "responses": responses(
+                    found,
+                    &[MocksError::ObjectNotFound, MocksError::PreconditionFailed],
+                ),
+            }
+        })
+    }
+
+    fn singleton(&self) -> Value {
+        let name = self.name;
+        let found = json!({"200": json_response("The object.", self.schema_ref())});
+        json!({
+            "get": {
+                "operationId": format!("get_{name}"),
+                "summary": format!("Get {name}"),
+                "tags": [name],
+                "responses": responses(
+                    json!({"200": json_response(
+                        "The object.",
+                        json!({
+                            "type": "object",
+                            "properties": {name: self.schema_ref()},
+                            "required": [name]
+                        })
+                    )}),
+                    &[MocksError::QueryParamsNotAllowed, MocksError::ResourceNotFound],
+                ),
+            },
+            "put": self.write_operation(
+                format!("replace_{name}"),
+                format!("Replace {name}"),
+                self.schema_ref(),
+                found.clone(),
+                &[
+                    MocksError::InvalidRequest,
+                    MocksError::ObjectNotFound,
+                    MocksError::PreconditionFailed,
+                ],
+            ),
+            "patch": self.write_operation(
+                format!("update_{name}"),
+                format!("Update {name} partially"),
+                self.partial_schema(),
+                found,
+                &[
+                    MocksError::InvalidRequest,
+                    MocksError::ObjectNotFound,
+                    MocksError::PreconditionFailed,
+                ],
+            )
+        })
+    }
+
+    fn write_operation(
+        &self,
+        operation_id: String,
+        summary: String,
+        body: Value,
+        success: Value,
+        errors: &[MocksError],
+    ) -> Value {
+        let mut responses = responses(success, errors);
+        if self.validated {
+            responses["422"] = json!({
+                "description": "Request body does not match the resource schema.",
+                "content": {"application/json": {"schema": {"$ref": "#/components/schemas/ValidationError"}}}
+            });
+        }
+        json!({
+            "operationId": operation_id,
+            "summary": summary,
+            "tags": [self.name],
+            "requestBody": {
+                "required": true,
+                "content": {"application/json": {"schema": body}}
+            },
+            "responses": responses
+        })
+    }
+
+    /// Search parameters (`{field}.{matchtype}`) for every scalar property
+    fn filter_parameters(&self) -> Vec<Value> {
+        let Some(properties) = self.schema.get("properties").and_then(Value::as_object) else {
+            return vec![];
+        };
+
+        let mut parameters = vec![];
+        for (field, schema) in properties {
+            if !is_scalar(schema) {
+                continue;
+            }
+            for match_type in MATCH_TYPES {
+                parameters.push(json!({
+                    "name": format!("{field}.{match_type}"),
+                    "in": "query",
+                    "required": false,
+                    "description": format!("Filter by {field} ({match_type}, case-insensitive)."),
+                    "schema": {"type": "string"}
+                }));
+            }
+        }
+        parameters
+    }
+}
+
+fn is_scalar(schema: &Value) -> bool {
+    let scalar = |t: &Value| !matches!(t.as_str(), Some("object") | Some("array"));
+    match schema.get("type") {
+        Some(Value::Array(types)) => types.iter().all(scalar),
+        Some(t) => scalar(t),
+        None => false,
+    }
+}
+
+fn json_response(description: &str, schema: Value) -> Value {
+    json!({
+        "description": description,
+        "content": {"application/json": {"schema": schema}}
+    })
+}
+
+/// Merge success responses with error responses, grouped by status code
+fn responses(success: Value, errors: &[MocksError]) -> Value {
+    let mut grouped: BTreeMap<u16, Vec<String>> = BTreeMap::new();
+    for error in errors {
+        let messages = grouped.entry(error.status().as_u16()).or_default();
+        let message = error.to_string();
+        if !messages.contains(&message) {
+            messages.push(message);
+        }
+    }
+    grouped
+        .entry(MocksError::Exception(String::new()).status().as_u16())
+        .or_default()
+        .push("Unexpected server error.".to_string());
+
+    let mut responses = success;
+    for (status, messages) in grouped {
+        responses[status.to_string()] = json_response(
+            &messages.join(" / "),
+            json!({"$ref": "#/components/schemas/Error"}),
+        );
+    }
+    responses
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::validator::Validator;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_load_spec_yaml() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("spec.yaml");
+        fs::write(
+            &path,
+            "openapi: 3.0.3\npaths:\n  /users:\n    get:\n      responses:\n        200:\n          description: ok\n",
+        )
+        .unwrap();
+
+        let spec = load_spec(path.to_str().unwrap()).unwrap();
+        assert_eq!(spec["openapi"], "3.0.3");
+        assert_eq!(
+            spec["paths"]["/users"]["get"]["responses"]["200"]["description"],
+            "ok"
+        );
+    }
+
+    #[test]
+    fn test_load_spec_json_and_errors() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("spec.json");
+        fs::write(&path, r#"{"openapi": "3.1.0", "paths": {}}"#).unwrap();
+        assert!(load_spec(path.to_str().unwrap()).is_ok());
+
+        fs::write(&path, r#"{"openapi": "3.1.0"}"#).unwrap();
+        assert!(matches!(
+            load_spec(path.to_str().unwrap()),
+            Err(MocksError::FailedReadFile(_))
+        ));
+        assert!(load_spec("missing.yaml").is_err());
+    }
+
+    fn create_storage(dir: &TempDir) -> Storage {
+        let file_path = dir.path().join("storage.json");
+        let data = json!({
+            "api/v1/posts": [{"id": 1, "title": "first post", "tags": ["a"]}],
+            "profile": {"id": 1, "name": "mocks"},
+            "_mocks": {"schemas": {"profile": {"type": "object", "required": ["id"]}}}
+        });
+        fs::write(&file_path, data.to_string()).unwrap();
+        Storage::new(file_path.to_str().unwrap(), false).unwrap()
+    }
+
+    #[test]
+    fn test_generate_paths() {
+        let dir = TempDir::new().unwrap();
+        let storage = create_storage(&dir);
+        let schemas = Schemas::load(&storage).unwrap();
+        let doc = generate(&storage, &schemas);
+
+        assert_eq!(doc["openapi"], "3.1.0");
+        let paths = doc["paths"].as_object().unwrap();
+        assert!(paths.contains_key("/_hc"));
+        assert!(paths.contains_key("/api/v1/posts"));
+        assert!(paths.contains_key("/api/v1/posts/{id}"));
+        assert!(paths.contains_key("/profile"));
+        assert!(!paths.contains_key("/profile/{id}"));
+
+        let collection = &paths["/api/v1/posts"];
+        assert_eq!(collection["get"]["operationId"], "list_posts");
+        assert_eq!(collection["post"]["operationId"], "create_posts");
+        let item = &paths["/api/v1/posts/{id}"];
+        for method in ["get", "put", "patch", "delete"] {
+            assert!(item.get(method).is_some());
+        }
+    }
+
+    #[test]
+    fn test_generate_filter_parameters() {
+        let dir = TempDir::new().unwrap();
+        let storage = create_storage(&dir);
+        let doc = generate(&storage, &Schemas::default());
+
+        let names: Vec<&str> = doc["paths"]["/api/v1/posts"]["get"]["parameters"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|p| p["name"].as_str().unwrap())
+            .collect();
+        assert!(names.contains(&"title.contains"));
+        assert!(names.contains(&"id.exact"));
+        assert!(!names.iter().any(|n| n.starts_with("tags.")));
+    }
+
+    #[test]
+    fn test_generate_error_responses() {
+        let dir = TempDir::new().unwrap();
+        let storage = create_storage(&dir);
+        let schemas = Schemas::load(&storage).unwrap();
+        let doc = generate(&storage, &schemas);
+
+        let create = &doc["paths"]["/api/v1/posts"]["post"]["responses"];
+        assert!(create.get("201").is_some());
+        assert_eq!(create["409"]["description"], "Duplicate ID.");
+        assert!(create.get("500").is_some());
+        // No declared schema for posts, so no validation errors
+        assert!(create.get("422").is_none());
+
+        let delete = &doc["paths"]["/api/v1/posts/{id}"]["delete"]["responses"];
+        assert!(delete.get("412").is_some());
+
+        let replace = &doc["paths"]["/profile"]["put"]["responses"];
+        assert!(replace.get("412").is_some());
+        assert_eq!(
+            replace["422"]["content"]["application/json"]["schema"]["$ref"],
+            "#/components/schemas/ValidationError"
+        );
+    }
+
+    #[test]
+    fn test_generate_component_schemas() {
+        let dir = TempDir::new().unwrap();
+        let storage = create_storage(&dir);
+        let schemas = Schemas::load(&storage).unwrap();
+        let doc = generate(&storage, &schemas);
+
+        let components = &doc["components"]["schemas"];
+        assert_eq!(
+            components["profile"],
+            json!({"type": "object", "required": ["id"]})
+        );
+        assert_eq!(
+            components["posts"]["properties"]["title"],
+            json!({"type": "string"})
+        );
+
+        // PATCH bodies do not require properties
+        let patch_body = &doc["paths"]["/profile"]["patch"]["requestBody"]["content"]
+            ["application/json"]["schema"];
+        assert_eq!(*patch_body, json!({"type": "object"}));
+
+        // Stored items conform to the documented schemas
+        let validator = Validator::new(&doc);
+        let item_schema = json!({"$ref": "#/components/schemas/posts"});
+        assert!(validator
+            .validate_with(
+                &item_schema,
+                &json!({"id": 1, "title": "first post", "tags": []})
+            )
+            .is_empty());
+    }
+}
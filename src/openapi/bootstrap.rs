@@ -0,0 +1,511 @@
+use crate::error::MocksError;
+use crate::fake::Faker;
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+
+/// Maximum nesting of generated objects and arrays
+const MAX_DEPTH: usize = 4;
+/// Maximum number of generated array items, whatever `minItems` asks for
+const MAX_ITEMS: i64 = 20;
+/// Maximum number of `$ref` hops followed while resolving a schema
+const MAX_REF_HOPS: usize = 16;
+/// Probability of generating a property that is not required
+const OPTIONAL_PROBABILITY: f64 = 0.8;
+
+const NO_RESOURCES_ERROR: &str = "No resources could be derived from the OpenAPI spec.";
+
+/// How a path of the spec is served by mocks
+#[derive(Debug, Default)]
+struct Candidate<'a> {
+    /// Operations on the path itself (e.g. `/users`)
+    collection: Option<&'a Map<String, Value>>,
+    /// Operations on the item path (e.g. `/users/{id}`)
+    item: Option<&'a Map<String, Value>>,
+}
+
+/// Build storage data from an OpenAPI spec
+///
+/// Paths with an item path (`/users/{id}`), or returning an array, become array
+/// resources holding `count` generated records; other paths become object resources.
+/// Paths that mocks cannot serve, such as paths with parameters in the middle, are skipped.
+///
+/// # Arguments
+/// * `spec` - The OpenAPI document
+/// * `count` - Number of records generated per array resource
+/// * `seed` - Seed of the value generator
+pub fn bootstrap(spec: &Value, count: usize, seed: u64) -> Result<Value, MocksError> {
+    let paths = spec
+        .get("paths")
+        .and_then(Value::as_object)
+        .ok_or_else(|| MocksError::InvalidArgs(NO_RESOURCES_ERROR.to_string()))?;
+
+    let mut candidates: BTreeMap<String, Candidate> = BTreeMap::new();
+    for (path, operations) in paths {
+        let Some(operations) = operations.as_object() else {
+            continue;
+        };
+        let segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        let is_param = |s: &&str| s.starts_with('{') && s.ends_with('}');
+
+        match segments.split_last() {
+            Some((last, base))
+                if is_param(last) && !base.is_empty() && !base.iter().any(is_param) =>
+            {
+                candidates.entry(base.join("/")).or_default().item = Some(operations);
+            }
+            Some((last, _)) if !last.is_empty() && !segments.iter().any(is_param) => {
+                candidates.entry(segments.join("/")).or_default().collection = Some(operations);
+            }
+            _ => {}
+        }
+    }
+
+    let mut generator = Generator {
+        spec,
+        faker: Faker::new(seed),
+    };
+    let mut data = Map::new();
+    for (key, candidate) in candidates {
+        let list = candidate
+            .collection
+            .and_then(|ops| response_schema(spec, ops.get("get")?));
+        let list_items = list.and_then(|schema| array_items(spec, schema));
+        let is_collection = candidate.item.is_some()
+            || list_items.is_some()
+            || (list.is_none()
+                && candidate
+                    .collection
+                    .is_some_and(|ops| ops.contains_key("post")));
+
+        let item_schema = candidate
+            .item
+            .and_then(|ops| response_schema(spec, ops.get("get")?))
+            .or(list_items)
+            .or_else(|| {
+                let ops = candidate.item.or(candidate.collection)?;
+                ["post", "put", "patch"]
+                    .iter()
+                    .find_map(|method| request_schema(spec, ops.get(*method)?))
+            });
+
+        let value = if is_collection {
+            let records = (0..count)
+                .map(|i| generator.record(item_schema, i))
+                .collect();
+            Value::Array(records)
+        } else {
+            let schema = list.or(item_schema).cloned().unwrap_or(json!({}));
+            match generator.generate(&schema, &key, 0) {
+                object @ Value::Object(_) => object,
+                _ => json!({}),
+            }
+        };
+        data.insert(key, value);
+    }
+
+    if data.is_empty() {
+        return Err(MocksError::InvalidArgs(NO_RESOURCES_ERROR.to_string()));
+    }
+
+    Ok(Value::Object(data))
+}
+
+/// Schema of the successful JSON response of an operation
+fn response_schema<'a>(spec: &'a Value, operation: &'a Value) -> Option<&'a Value> {
+    let responses = operation.get("responses")?.as_object()?;
+    let response = ["200", "201", "2XX", "default"]
+        .iter()
+        .find_map(|status| responses.get(*status))?;
+    json_schema(resolve(spec, response))
+}
+
+/// Schema of the JSON request body of an operation
+fn request_schema<'a>(spec: &'a Value, operation: &'a Value) -> Option<&'a Value> {
+    json_schema(resolve(spec, operation.get("requestBody")?))
+}
+
+fn json_schema(object: &Value) -> Option<&Value> {
+    let content = object.get("content")?.as_object()?;
+    content
+        .iter()
+        .find(|(media_type, _)| media_type.contains("json"))
+        .and_then(|(_, media)| media.get("schema"))
+}
+
+/// Item schema of a list response, either a bare array or an object wrapping one
+fn array_items<'a>(spec: &'a Value, schema: &'a Value) -> Option<&'a Value> {
+    let schema = resolve(spec, schema);
+    if let Some(items) = schema.get("items") {
+        return Some(items);
+    }
+    schema
+        .get("properties")?
+        .as_object()?
+        .values()
+        .find_map(|property| resolve(spec, property).get("items"))
+}
+
+/// Follow `$ref`s until a concrete schema is reached
+pub fn resolve<'a>(spec: &'a Value, mut schema: &'a Value) -> &'a Value {
+    for _ in 0..MAX_REF_HOPS {
+        let Some(target) = schema
+            .get("$ref")
+            .and_then(Value::as_str)
+            .and_then(|reference| reference.strip_prefix('#'))
+            .and_then(|pointer| spec.pointer(pointer))
+        else {
+            break;
+        };
+        schema = target;
+    }
+    schema
+}
+
+/// Example value generator for spec schemas
+struct Generator<'a> {
+    spec: &'a Value,
+    faker: Faker,
+}
+
+impl Generator<'_> {
+    /// Generate the `index`-th record of an array resource, with a unique `id`
+    fn record(&mut self, schema: Option<&Value>, index: usize) -> Value {
+        let schema = schema.cloned().unwrap_or(json!({"type": "object"}));
+        let mut record = self.generate(&schema, "", 0);
+        let Some(obj) = record.as_object_mut() else {
+            return json!({"id": index + 1});
+        };
+
+        let id_schema = resolve(self.spec, &schema)
+            .get("properties")
+            .and_then(|p| p.get("id"))
+            .map(|s| resolve(self.spec, s));
+        let id = match id_schema {
+            Some(s) if schema_type(s) == Some("string") => {
+                match s.get("format").and_then(Value::as_str) {
+                    Some("uuid") => json!(self.faker.uuid()),
+                    _ => json!(self.faker.ulid()),
+                }
+            }
+            _ => json!(index + 1),
+        };
+        obj.insert("id".to_string(), id);
+        record
+    }
+
+    fn generate(&mut self, schema: &Value, field: &str, depth: usize) -> Value {
+        let schema = resolve(self.spec, schema);
+
+        if let Some(example) = schema.get("example") {
+            return example.clone();
+        }
+        if let Some(Value::Array(examples)) = schema.get("examples") {
+            if !examples.is_empty() {
+                return self.faker.rng.pick(examples).clone();
+            }
+        }
+        if let Some(constant) = schema.get("const") {
+            return constant.clone();
+        }
+        if let Some(Value::Array(options)) = schema.get("enum") {
+            if !options.is_empty() {
+                return self.faker.rng.pick(options).clone();
+            }
+        }
+        if depth < MAX_DEPTH {
+            if let Some(Value::Array(all)) = schema.get("allOf") {
+                return self.generate(&merge_all_of(self.spec, all), field, depth + 1);
+            }
+            for key in ["oneOf", "anyOf"] {
+                if let Some(first) = schema.get(key).and_then(|s| s.get(0)) {
+                    return self.generate(first, field, depth + 1);
+                }
+            }
+        }
+
+        match schema_type(schema) {
+            Some("object") => self.object(schema, depth),
+            Some("array") => self.array(schema, field, depth),
+            Some("string") => json!(self.string(schema, field)),
+            Some("integer") => json!(self.integer(schema)),
+            Some("number") => json!(self.number(schema)),
+            Some("boolean") => json!(self.faker.boolean()),
+            Some("null") => Value::Null,
+            _ => json!(self.faker.string_for(field)),
+        }
+    }
+
+    fn object(&mut self, schema: &Value, depth: usize) -> Value {
+        let mut obj = Map::new();
+        if depth >= MAX_DEPTH {
+            return Value::Object(obj);
+        }
+
+        let required: Vec<&str> = schema
+            .get("required")
+            .and_then(Value::as_array)
+            .map(|r| r.iter().filter_map(Value::as_str).collect())
+            .unwrap_or_default();
+        if let Some(properties) = schema.get("properties").and_then(Value::as_object) {
+            for (key, property) in properties {
+                if !required.contains(&key.as_str()) && !self.faker.rng.chance(OPTIONAL_PROBABILITY)
+                {
+                    continue;
+                }
+                obj.insert(key.to_string(), self.generate(property, key, depth + 1));
+            }
+        }
+        Value::Object(obj)
+    }
+
+    fn array(&mut self, schema: &Value, field: &str, depth: usize) -> Value {
+        if depth >= MAX_DEPTH {
+            return json!([]);
+        }
+        let max_items = schema.get("maxItems").and_then(Value::as_i64);
+        let min = schema
+            .get("minItems")
+            .and_then(Value::as_i64)
+            .unwrap_or(max_items.map_or(1, |max| max.min(1)));
+        let max = max_items.unwrap_or(min.max(3));
+        let len = self.faker.rng.range(min, max.max(min)).clamp(0, MAX_ITEMS);
+        let items = schema.get("items").cloned().unwrap_or(json!({}));
+        (0..len)
+            .map(|_| self.generate(&items, field, depth + 1))
+            .collect()
+    }
+
+    fn string(&mut self, schema: &Value, field: &str) -> String {
+        let value = match schema.get("format").and_then(Value::as_str) {
+            Some("uuid") => self.faker.uuid(),
+            Some("ulid") => self.faker.ulid(),
+            Some("date-time") => self.faker.date_time(),
+            Some("date") => self.faker.date(),
+            Some("email") => self.faker.email(),
+            Some("uri") | Some("url") => self.faker.url(),
+            _ => self.faker.string_for(field),
+        };
+
+        let min = schema.get("minLength").and_then(Value::as_u64).unwrap_or(0) as usize;
+        let max = schema.get("maxLength").and_then(Value::as_u64);
+        let mut value: String = match max {
+            Some(max) => value.chars().take(max as usize).collect(),
+            None => value,
+        };
+        while value.chars().count() < min {
+            value.push('x');
+        }
+        value
+    }
+
+    fn integer(&mut self, schema: &Value) -> i64 {
+        let (min, max) = bounds(schema, 1.0, 1000.0);
+        self.faker.rng.range(min.ceil() as i64, max.floor() as i64)
+    }
+
+    fn number(&mut self, schema: &Value) -> f64 {
+        let (min, max) = bounds(schema, 0.0, 1000.0);
+        (self.faker.rng.range_f64(min, max) * 100.0).round() / 100.0
+    }
+}
+
+/// Inclusive numeric bounds of a schema, with defaults for open ends
+fn bounds(schema: &Value, default_min: f64, default_max: f64) -> (f64, f64) {
+    let get = |key: &str| schema.get(key).and_then(Value::as_f64);
+    let min = get("minimum")
+        .or_else(|| get("exclusiveMinimum").map(|n| n + 1.0))
+        .unwrap_or(default_min);
+    let max = get("maximum")
+        .or_else(|| get("exclusiveMaximum").map(|n| n - 1.0))
+        .unwrap_or(default_max.max(min));
+    (min, max.max(min))
+}
+
+/// Primary type of a schema, ignoring `null` in type unions
+fn schema_type(schema: &Value) -> Option<&str> {
+    match schema.get("type") {
+        Some(Value::String(t)) => Some(t.as_str()),
+        Some(Value::Array(types)) => types
+            .iter()
+            .filter_map(Value::as_str)
+            .find(|t| *t != "null"),
+        _ if schema.get("properties").is_some() => Some("object"),
+        _ if schema.get("items").is_some() => Some("array"),
+        _ => None,
+    }
+}
+
+/// Merge the members of an `allOf` into a single object schema
+fn merge_all_of(spec: &Value, all: &[Value]) -> Value {
+    let mut properties = Map::new();
+    let mut required = vec![];
+    for member in all {
+        let member = resolve(spec, member);
+        if let Some(p) = member.get("properties").and_then(Value::as_object) {
+            properties.extend(p.clone());
+        }
+        if let Some(r) = member.get("required").and_then(Value::as_array) {
+            required.extend(r.iter().cloned());
+        }
+    }
+    json!({"type": "object", "properties": properties, "required": required})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::format;
+
+    fn spec() -> Value {
+        json!({
+            "openapi": "3.0.3",
+            "paths": {
+                "/users": {
+                    "get": {"responses": {"200": {"content": {"application/json": {
+                        "schema": {"type": "array", "items": {"$ref": "#/components/schemas/User"}}
+                    }}}}},
+                    "post": {}
+                },
+                "/users/{userId}": {"get": {}},
+                "/users/{userId}/posts": {"get": {}},
+                "/api/v1/orders": {
+                    "post": {"requestBody": {"content": {"application/json": {
+                        "schema": {"$ref": "#/components/schemas/Order"}
+                    }}}}
+                },
+                "/me": {
+                    "get": {"responses": {"200": {"content": {"application/json": {
+                        "schema": {
+                            "type": "object",
+                            "required": ["name"],
+                            "properties": {"name": {"type": "string", "example": "Jane"}}
+                        }
+                    }}}}}
+                }
+            },
+            "components": {
+                "schemas": {
+                    "User": {
+                        "type": "object",
+                        "required": ["id", "email", "role", "age"],
+                        "properties": {
+                            "id": {"type": "string", "format": "uuid"},
+                            "email": {"type": "string", "format": "email"},
+                            "role": {"type": "string", "enum": ["admin", "viewer"]},
+                            "age": {"type": "integer", "minimum": 18, "maximum": 30},
+                            "tags": {"type": "array", "items": {"type": "string"}, "maxItems": 2}
+                        }
+                    },
+                    "Order": {
+                        "allOf": [
+                            {"type": "object", "required": ["total"], "properties": {"total": {"type": "number", "minimum": 1, "maximum": 5}}},
+                            {"type": "object", "required": ["placedAt"], "properties": {"placedAt": {"type": "string", "format": "date-time"}}}
+                        ]
+                    }
+                }
+            }
+        })
+    }
+
+    #[test]
+    fn test_bootstrap_resources() {
+        let data = bootstrap(&spec(), 3, 1).unwrap();
+        let obj = data.as_object().unwrap();
+
+        let keys: Vec<&str> = obj.keys().map(String::as_str).collect();
+        assert_eq!(keys, vec!["api/v1/orders", "me", "users"]);
+        assert!(data["users"].is_array());
+        assert!(data["api/v1/orders"].is_array());
+        assert_eq!(data["me"], json!({"name": "Jane"}));
+    }
+
+    #[test]
+    fn test_bootstrap_records_honour_schema() {
+        let data = bootstrap(&spec(), 5, 1).unwrap();
+        let users = data["users"].as_array().unwrap();
+        assert_eq!(users.len(), 5);
+
+        let mut ids = vec![];
+        for user in users {
+            assert!(format::matches("uuid", user["id"].as_str().unwrap()));
+            assert!(format::matches("email", user["email"].as_str().unwrap()));
+            assert!(["admin", "viewer"].contains(&user["role"].as_str().unwrap()));
+            assert!((18..=30).contains(&user["age"].as_i64().unwrap()));
+            if let Some(tags) = user.get("tags") {
+                assert!(tags.as_array().unwrap().len() <= 2);
+            }
+            ids.push(user["id"].clone());
+        }
+        ids.dedup();
+        assert_eq!(ids.len(), 5);
+
+        for (i, order) in data["api/v1/orders"].as_array().unwrap().iter().enumerate() {
+            assert_eq!(order["id"], json!(i + 1));
+            let total = order["total"].as_f64().unwrap();
+            assert!((1.0..=5.0).contains(&total));
+            assert!(format::matches(
+                "date-time",
+                order["placedAt"].as_str().unwrap()
+            ));
+        }
+    }
+
+    #[test]
+    fn test_bootstrap_is_reproducible() {
+        assert_eq!(
+            bootstrap(&spec(), 3, 42).unwrap(),
+            bootstrap(&spec(), 3, 42).unwrap()
+        );
+        assert_ne!(
+            bootstrap(&spec(), 3, 42).unwrap(),
+            bootstrap(&spec(), 3, 43).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_generate_array_bounds() {
+        let spec = json!({});
+        let mut generator = Generator {
+            spec: &spec,
+            faker: Faker::new(1),
+        };
+        for _ in 0..10 {
+            let empty = generator.generate(&json!({"type": "array", "maxItems": 0}), "", 0);
+            assert_eq!(empty, json!([]));
+        }
+        let huge = generator.generate(&json!({"type": "array", "minItems": 1000000000}), "", 0);
+        assert_eq!(huge.as_array().unwrap().len(), MAX_ITEMS as usize);
+    }
+
+    #[test]
+    fn test_generate_recursive_combinators() {
+        let spec = json!({
+            "components": {
+                "schemas": {
+                    "Node": {"oneOf": [{"$ref": "#/components/schemas/Node"}]},
+                    "Tree": {"anyOf": [{"$ref": "#/components/schemas/Node"}]}
+                }
+            }
+        });
+        let mut generator = Generator {
+            spec: &spec,
+            faker: Faker::new(1),
+        };
+        for name in ["Node", "Tree"] {
+            let schema = json!({"$ref": format!("#/components/schemas/{name}")});
+            assert!(generator.generate(&schema, "name", 0).is_string());
+        }
+    }
+
+    #[test]
+    fn test_bootstrap_without_resources() {
+        assert!(matches!(
+            bootstrap(&json!({"openapi": "3.1.0"}), 3, 1),
+            Err(MocksError::InvalidArgs(_))
+        ));
+        assert!(matches!(
+            bootstrap(&json!({"paths": {"/{id}": {}}}), 3, 1),
+            Err(MocksError::InvalidArgs(_))
+        ));
+    }
+}
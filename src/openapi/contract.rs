@@ -0,0 +1,566 @@
+use crate::error::MocksError;
+use crate::openapi::bootstrap::resolve;
+use crate::schema::validator::{self, Patterns, Validator, Violation};
+use axum::http::HeaderMap;
+use regex::Regex;
+use serde_json::{json, Value};
+
+/// How contract violations are handled
+#[derive(clap::ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ContractMode {
+    /// Reject violating requests and responses
+    Strict,
+    /// Log violations and let traffic through
+    Warn,
+}
+
+/// A path template of the spec, compiled for matching
+#[derive(Debug)]
+struct Route {
+    template: String,
+    pattern: Regex,
+    params: Vec<String>,
+}
+
+/// An operation of the spec matched by a request
+#[derive(Debug)]
+pub struct Matched<'a> {
+    /// Path template of the spec (e.g. `/posts/{id}`)
+    pub template: &'a str,
+    operation: &'a Value,
+    path_item: &'a Value,
+    path_params: Vec<(String, String)>,
+}
+
+/// An OpenAPI spec used to validate traffic against the agreed API contract
+#[derive(Debug)]
+pub struct Contract {
+    spec: Value,
+    pub mode: ContractMode,
+    routes: Vec<Route>,
+    patterns: Patterns,
+}
+
+impl Contract {
+    pub fn new(spec: Value, mode: ContractMode) -> Result<Contract, MocksError> {
+        let patterns = validator::compile(&spec)
+            .map_err(|e| MocksError::InvalidArgs(format!("Invalid spec: {e}")))?;
+        let mut routes: Vec<Route> = spec
+            .get("paths")
+            .and_then(Value::as_object)
+            .map(|paths| paths.keys().filter_map(|t| compile(t)).collect())
+            .unwrap_or_default();
+        // Literal paths such as `/users/me` win over templates such as `/users/{id}`
+        routes.sort_by_key(|route| route.params.len());
+
+        Ok(Contract {
+            spec,
+            mode,
+            routes,
+            patterns,
+        })
+    }
+
+    /// Find the operation of the spec serving a request
+    pub fn find(&self, method: &str, path: &str) -> Option<Matched<'_>> {
+        let method = method.to_lowercase();
+        self.routes.iter().find_map(|route| {
+            let captures = route.pattern.captures(path)?;
+            let path_item = self.spec.get("paths")?.get(&route.template)?;
+            let operation = path_item.get(&method)?;
+            let path_params = route
+                .params
+                .iter()
+                .zip(captures.iter().skip(1))
+                .map(|(name, value)| {
+                    let value = value.map_or("", |m| m.as_str());
+                    (name.to_string(), value.to_string())
+                })
+                .collect();
+            Some(Matched {
+                template: &route.template,
+                operation,
+                path_item,
+                path_params,
+            })
+        })
+    }
+
+    /// Validate a request against the spec
+    ///
+    /// # Arguments
+    /// * `method` - HTTP method
+    /// * `path` - Request path without the query string
+    /// * `query` - Decoded query parameters
+    /// * `headers` - Request headers
+    /// * `body` - Raw request body
+    pub fn check_request(
+        &self,
+        method: &str,
+        path: &str,
+        query: &[(String, String)],
+        headers: &HeaderMap,
+        body: &[u8],
+    ) -> Vec<Violation> {
+        let Some(matched) = self.find(method, path) else {
+            return vec![Violation::new(
+                "",
+                "path",
+                format!(
+                    "{} {path} is not defined in the spec.",
+                    method.to_uppercase()
+                ),
+            )];
+        };
+
+        let validator = Validator::new(&self.spec).patterns(&self.patterns);
+        let mut violations = vec![];
+        for parameter in self.parameters(&matched) {
+            let (Some(name), Some(location)) = (
+                parameter.get("name").and_then(Value::as_str),
+                parameter.get("in").and_then(Value::as_str),
+            ) else {
+                continue;
+            };
+            let raw = match location {
+                "path" => matched
+                    .path_params
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, v)| v.to_string()),
+                "query" => query
+                    .iter()
+                    .find(|(n, _)| n == name)
+                    .map(|(_, v)| v.to_string()),
+                "header" => headers
+                    .get(name)
+                    .and_then(|v| v.to_str().ok())
+                    .map(str::to_string),
+                _ => continue,
+            };
+            let location_path = format!("/{location}/{name}");
+
+            match raw {
+                None if parameter.get("required") == Some(&Value::Bool(true)) => {
+                    violations.push(Violation::new(
+                        &location_path,
+                        "required",
+                        format!("Parameter {name} is required."),
+                    ));
+                }
+                None => {}
+                Some(raw) => {
+                    let Some(schema) = parameter.get("schema") else {
+                        continue;
+                    };
+                    let value = coerce(resolve(&self.spec, schema), &raw);
+                    violations.extend(prefixed(
+                        &location_path,
+                        validator.validate_with(schema, &value),
+                    ));
+                }
+            }
+        }
+
+        let request_body = matched
+            .operation
+            .get("requestBody")
+            .map(|b| resolve(&self.spec, b));
+        if let Some(request_body) = request_body {
+            if body.is_empty() {
+                if request_body.get("required") == Some(&Value::Bool(true)) {
+                    violations.push(Violation::new(
+                        "/body",
+                        "required",
+                        "Request body is required.",
+                    ));
+                }
+            } else if let Some(schema) = json_schema(request_body) {
+                violations.extend(self.check_body(&validator, schema, body));
+            }
+        }
+
+        violations
+    }
+
+    /// Validate a response against the spec
+    ///
+    /// # Arguments
+    /// * `method` - HTTP method of the request
+    /// * `path` - Request path without the query string
+    /// * `status` - Response status code
+    /// * `body` - Raw response body
+    pub fn check_response(
+        &self,
+        method: &str,
+        path: &str,
+        status: u16,
+        body: &[u8],
+    ) -> Vec<Violation> {
+        let Some(matched) = self.find(method, path) else {
+            return vec![];
+        };
+        let Some(responses) = matched
+            .operation
+            .get("responses")
+            .and_then(Value::as_object)
+        else {
+            return vec![];
+        };
+
+        let response = [
+            status.to_string(),
+            format!("{}XX", status / 100),
+            "default".to_string(),
+        ]
+        .iter()
+        .find_map(|key| responses.get(key))
+        .map(|r| resolve(&self.spec, r));
+        let Some(response) = response else {
+            return vec![Violation::new(
+                "/status",
+                "responses",
+                format!(
+                    "Status {status} is not documented for {} {}.",
+                    method.to_uppercase(),
+                    matched.template
+                ),
+            )];
+        };
+
+        match json_schema(response) {
+            Some(schema) if !body.is_empty() => {
+                let validator = Validator::new(&self.spec).patterns(&self.patterns);
+                let violations = self.check_body(&validator, schema, body);
+                // Mocks wraps collections and single objects in their resource name
+                // (`{"posts": [...]}`), while specs usually describe the bare value
+                match unwrap_resource(path, body) {
+                    Some((resource, inner)) if !violations.is_empty() => prefixed(
+                        &format!("/body/{resource}"),
+                        validator.validate_with(schema, &inner),
+                    ),
+                    _ => violations,
+                }
+            }
+            _ => vec![],
+        }
+    }
+
+    fn check_body(&self, validator: &Validator, schema: &Value, body: &[u8]) -> Vec<Violation> {
+        match serde_json::from_slice::<Value>(body) {
+            Ok(instance) => prefixed("/body", validator.validate_with(schema, &instance)),
+            Err(e) => vec![Violation::new(
+                "/body",
+                "type",
+                format!("Body is not valid JSON: {e}"),
+            )],
+        }
+    }
+
+    /// Path-level parameters overridden by operation-level ones
+    fn parameters<'a>(&'a self, matched: &Matched<'a>) -> Vec<&'a Value> {
+        let mut parameters: Vec<&Value> = vec![];
+        for source in [matched.path_item, matched.operation] {
+            let Some(list) = source.get("parameters").and_then(Value::as_array) else {
+                continue;
+            };
+            for parameter in list.iter().map(|p| resolve(&self.spec, p)) {
+                let key = |p: &Value| (p.get("name").cloned(), p.get("in").cloned());
+                parameters.retain(|existing| key(existing) != key(parameter));
+                parameters.push(parameter);
+            }
+        }
+        parameters
+    }
+}
+
+impl ContractMode {
+    pub fn is_strict(&self) -> bool {
+        *self == ContractMode::Strict
+    }
+
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ContractMode::Strict => "strict",
+            ContractMode::Warn => "warn",
+        }
+    }
+}
+
+/// Serialize violations for an error response body
+pub fn violations_to_value(message: &str, violations: &[Violation]) -> Value {
+    let violations: Vec<Value> = violations.iter().map(Violation::to_value).collect();
+    json!({"error": message, "violations": violations})
+}
+
+fn compile(template: &str) -> Option<Route> {
+    let mut pattern = String::from("^");
+    let mut params = vec![];
+    for segment in template.trim_matches('/').split('/') {
+        pattern.push('/');
+        match segment.strip_prefix('{').and_then(|s| s.strip_suffix('}')) {
+            Some(name) => {
+                params.push(name.to_string());
+                pattern.push_str("([^/]+)");
+            }
+            None => pattern.push_str(&regex::escape(segment)),
+        }
+    }
+    pattern.push_str("/?$");
+
+    Regex::new(&pattern).ok().map(|pattern| Route {
+        template: template.to_string(),
+        pattern,
+        params,
+    })
+}
+
+/// Value of a body wrapped in the resource name of its path (e.g. `{"posts": [...]}` at `/posts`)
+fn unwrap_resource<'a>(path: &'a str, body: &[u8]) -> Option<(&'a str, Value)> {
+    let resource = path.rsplit('/').find(|segment| !segment.is_empty())?;
+    match serde_json::from_slice::<Value>(body).ok()? {
+        Value::Object(mut object) if object.len() == 1 => {
+            Some((resource, object.remove(resource)?))
+        }
+        _ => None,
+    }
+}
+
+fn json_schema(object: &Value) -> Option<&Value> {
+    let content = object.get("content")?.as_object()?;
+    content
+        .iter()
+        .find(|(media_type, _)| media_type.contains("json"))
+        .and_then(|(_, media)| media.get("schema"))
+}
+
+/// Convert a raw parameter into the JSON type expected by its schema
+fn coerce(schema: &Value, raw: &str) -> Value {
+    let parsed = match schema.get("type").and_then(Value::as_str) {
+        Some("integer") => raw.parse::<i64>().ok().map(Value::from),
+        Some("number") => raw.parse::<f64>().ok().map(Value::from),
+        Some("boolean") => raw.parse::<bool>().ok().map(Value::from),
+        Some("array") => {
+            let items = schema.get("items").cloned().unwrap_or(json!({}));
+            Some(raw.split(',').map(|item| coerce(&items, item)).collect())
+        }
+        _ => None,
+    };
+    parsed.unwrap_or_else(|| Value::String(raw.to_string()))
+}
+
+fn prefixed(prefix: &str, violations: Vec<Violation>) -> Vec<Violation> {
+    violations
+        .into_iter()
+        .map(|mut violation| {
+            violation.path = format!("{prefix}{}", violation.path);
+            violation
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::HeaderValue;
+
+    fn contract() -> Contract {
+        let spec = json!({
+            "openapi": "3.0.3",
+            "paths": {
+                "/posts": {
+                    "get": {
+                        "parameters": [
+                            {"name": "limit", "in": "query", "schema": {"type": "integer", "maximum": 10}},
+                            {"name": "X-Tenant", "in": "header", "required": true, "schema": {"type": "string"}}
+                        ],
+                        "responses": {
+                            "200": {"content": {"application/json": {"schema": {"type": "array", "items": {"$ref": "#/components/schemas/Post"}}}}}
+                        }
+                    },
+                    "post": {
+                        "requestBody": {
+                            "required": true,
+                            "content": {"application/json": {"schema": {"$ref": "#/components/schemas/Post"}}}
+                        },
+                        "responses": {
+                            "201": {"content": {"application/json": {"schema": {"$ref": "#/components/schemas/Post"}}}},
+                            "4XX": {"description": "error"}
+                        }
+                    }
+                },
+                "/posts/latest": {"get": {"responses": {"200": {"description": "ok"}}}},
+                "/posts/{id}": {
+                    "parameters": [{"name": "id", "in": "path", "required": true, "schema": {"type": "integer"}}],
+                    "get": {"responses": {"200": {"description": "ok"}}}
+                }
+            },
+            "components": {
+                "schemas": {
+                    "Post": {
+                        "type": "object",
+                        "required": ["id", "title"],
+                        "properties": {"id": {"type": "integer"}, "title": {"type": "string"}}
+                    }
+                }
+            }
+        });
+        Contract::new(spec, ContractMode::Strict).unwrap()
+    }
+
+    fn tenant() -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-tenant", HeaderValue::from_static("acme"));
+        headers
+    }
+
+    #[test]
+    fn test_find() {
+        let contract = contract();
+        assert_eq!(contract.find("GET", "/posts").unwrap().template, "/posts");
+        assert_eq!(
+            contract.find("GET", "/posts/latest").unwrap().template,
+            "/posts/latest"
+        );
+        let matched = contract.find("GET", "/posts/1").unwrap();
+        assert_eq!(matched.template, "/posts/{id}");
+        assert_eq!(
+            matched.path_params,
+            vec![("id".to_string(), "1".to_string())]
+        );
+        assert!(contract.find("DELETE", "/posts/1").is_none());
+        assert!(contract.find("GET", "/comments").is_none());
+    }
+
+    #[test]
+    fn test_new_invalid_schema() {
+        let spec = json!({
+            "paths": {},
+            "components": {"schemas": {"Node": {"$ref": "#/components/schemas/Node"}}}
+        });
+        assert!(matches!(
+            Contract::new(spec, ContractMode::Strict),
+            Err(MocksError::InvalidArgs(_))
+        ));
+    }
+
+    #[test]
+    fn test_check_request_unknown_path() {
+        let violations = contract().check_request("GET", "/comments", &[], &HeaderMap::new(), b"");
+        assert_eq!(violations.len(), 1);
+        assert_eq!(violations[0].keyword, "path");
+    }
+
+    #[test]
+    fn test_check_request_parameters() {
+        let contract = contract();
+        let query = vec![("limit".to_string(), "5".to_string())];
+        assert!(contract
+            .check_request("GET", "/posts", &query, &tenant(), b"")
+            .is_empty());
+
+        let query = vec![("limit".to_string(), "50".to_string())];
+        let violations = contract.check_request("GET", "/posts", &query, &HeaderMap::new(), b"");
+        let found: Vec<(&str, &str)> = violations
+            .iter()
+            .map(|v| (v.path.as_str(), v.keyword.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            vec![
+                ("/query/limit", "maximum"),
+                ("/header/X-Tenant", "required")
+            ]
+        );
+
+        let violations = contract.check_request("GET", "/posts/abc", &[], &HeaderMap::new(), b"");
+        assert_eq!(violations[0].path, "/path/id");
+        assert_eq!(violations[0].keyword, "type");
+    }
+
+    #[test]
+    fn test_check_request_body() {
+        let contract = contract();
+        assert!(contract
+            .check_request(
+                "POST",
+                "/posts",
+                &[],
+                &HeaderMap::new(),
+                br#"{"id":1,"title":"a"}"#
+            )
+            .is_empty());
+
+        let violations = contract.check_request("POST", "/posts", &[], &HeaderMap::new(), b"");
+        assert_eq!(violations[0].path, "/body");
+        assert_eq!(violations[0].keyword, "required");
+
+        let violations =
+            contract.check_request("POST", "/posts", &[], &HeaderMap::new(), br#"{"id":"1"}"#);
+        let paths: Vec<&str> = violations.iter().map(|v| v.path.as_str()).collect();
+        assert_eq!(paths, vec!["/body/title", "/body/id"]);
+
+        let violations = contract.check_request("POST", "/posts", &[], &HeaderMap::new(), b"{");
+        assert_eq!(violations[0].keyword, "type");
+    }
+
+    #[test]
+    fn test_check_response() {
+        let contract = contract();
+        assert!(contract
+            .check_response("POST", "/posts", 201, br#"{"id":1,"title":"a"}"#)
+            .is_empty());
+        assert!(contract
+            .check_response("POST", "/posts", 409, br#"{"error":"Duplicate ID."}"#)
+            .is_empty());
+
+        let violations = contract.check_response("POST", "/posts", 201, br#"{"id":1}"#);
+        assert_eq!(violations[0].path, "/body/title");
+
+        let violations = contract.check_response("POST", "/posts", 500, b"");
+        assert_eq!(violations[0].keyword, "responses");
+    }
+
+    #[test]
+    fn test_check_response_list() {
+        let contract = contract();
+        // Lists are wrapped in their resource name by mocks
+        assert!(contract
+            .check_response("GET", "/posts", 200, br#"{"posts":[{"id":1,"title":"a"}]}"#)
+            .is_empty());
+        assert!(contract
+            .check_response("GET", "/posts", 200, br#"[{"id":1,"title":"a"}]"#)
+            .is_empty());
+
+        let violations = contract.check_response("GET", "/posts", 200, br#"{"posts":[{"id":1}]}"#);
+        assert_eq!(violations[0].path, "/body/posts/0/title");
+        let violations = contract.check_response("GET", "/posts", 200, br#"{"items":[]}"#);
+        assert!(!violations.is_empty());
+    }
+
+    #[test]
+    fn test_coerce() {
+        assert_eq!(coerce(&json!({"type": "integer"}), "1"), json!(1));
+        assert_eq!(coerce(&json!({"type": "integer"}), "a"), json!("a"));
+        assert_eq!(coerce(&json!({"type": "boolean"}), "true"), json!(true));
+        assert_eq!(
+            coerce(
+                &json!({"type": "array", "items": {"type": "number"}}),
+                "1,2.5"
+            ),
+            json!([1.0, 2.5])
+        );
+    }
+
+    #[test]
+    fn test_violations_to_value() {
+        let violations = vec![Violation::new(
+            "/body",
+            "required",
+            "Request body is required.",
+        )];
+        let value = violations_to_value("Contract violation.", &violations);
+        assert_eq!(value["error"], "Contract violation.");
+        assert_eq!(value["violations"][0]["path"], "/body");
+    }
+}
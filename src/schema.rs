@@ -0,0 +1,322 @@
+use crate::error::MocksError;
+use crate::schema::validator::{compile, Patterns, Validator, Violation};
+use crate::storage::Storage;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+
+pub mod format;
+pub mod infer;
+pub mod validator;
+
+const SCHEMAS_KEY: &str = "schemas";
+const SCHEMAS_DIR: &str = "schemas";
+
+/// JSON Schemas attached to resources
+///
+/// Schemas are declared inline under `_mocks.schemas` in the storage file, or as
+/// `schemas/{resource}.json` files next to it. Inline schemas take precedence.
+#[derive(Debug, Clone, Default)]
+pub struct Schemas {
+    schemas: HashMap<String, Value>,
+    patterns: Patterns,
+}
+
+impl Schemas {
+    /// Load the schemas declared for a storage
+    ///
+    /// # Arguments
+    /// * `storage` - The storage whose config and directory are searched
+    pub fn load(storage: &Storage) -> Result<Schemas, MocksError> {
+        let mut schemas = HashMap::new();
+
+        let dir = Path::new(&storage.file)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .join(SCHEMAS_DIR);
+        if dir.is_dir() {
+            let entries =
+                fs::read_dir(&dir).map_err(|e| MocksError::FailedReadFile(e.to_string()))?;
+            for entry in entries.flatten() {
+                let path = entry.path();
+                if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                    continue;
+                }
+                let Some(resource) = path.file_stem().and_then(|s| s.to_str()) else {
+                    continue;
+                };
+                let text = fs::read_to_string(&path)
+                    .map_err(|e| MocksError::FailedReadFile(e.to_string()))?;
+                let schema: Value = serde_json::from_str(&text)
+                    .map_err(|e| MocksError::FailedReadFile(format!("{}: {e}", path.display())))?;
+                schemas.insert(resource.to_string(), schema);
+            }
+        }
+
+        if let Some(Value::Object(inline)) = storage.config.get(SCHEMAS_KEY) {
+            for (key, schema) in inline {
+                schemas.insert(resource_name(key).to_string(), schema.clone());
+            }
+        }
+
+        let mut patterns = Patterns::new();
+        for (resource, schema) in &schemas {
+            let compiled = compile(schema).map_err(|e| {
+                MocksError::InvalidArgs(format!("Invalid schema of {resource}: {e}"))
+            })?;
+            patterns.extend(compiled);
+        }
+
+        Ok(Schemas { schemas, patterns })
+    }
+
+    #[cfg(test)]
+    pub fn from_map(schemas: HashMap<String, Value>) -> Schemas {
+        let patterns = schemas
+            .values()
+            .flat_map(|schema| compile(schema).unwrap())
+            .collect();
+        Schemas { schemas, patterns }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.schemas.is_empty()
+    }
+
+    /// Schema attached to a resource
+    pub fn get(&self, resource: &str) -> Option<&Value> {
+        self.schemas.get(resource_name(resource))
+    }
+
+    /// Validate a request body against the schema of a resource
+    ///
+    /// Resources without a schema accept any body.
+    ///
+    /// # Arguments
+    /// * `resource` - The resource name or storage key
+    /// * `instance` - The value to validate
+    /// * `partial` - Whether missing required properties are allowed (PATCH)
+    pub fn validate(
+        &self,
+        resource: &str,
+        instance: &Value,
+        partial: bool,
+    ) -> Result<(), Vec<Violation>> {
+        let Some(schema) = self.get(resource) else {
+            return Ok(());
+        };
+
+        let violations = Validator::new(schema)
+            .patterns(&self.patterns)
+            .partial(partial)
+            .validate(instance);
+        if violations.is_empty() {
+            Ok(())
+        } else {
+            Err(violations)
+        }
+    }
+}
+
+/// Infer schemas for every resource of a storage
+///
+/// Returns the schemas keyed by storage key.
+pub fn infer_all(storage: &Storage) -> Value {
+    Value::Object(infer::infer(&storage.data))
+}
+
+/// Infer schemas for every resource of a storage and write them as sidecar files
+///
+/// # Arguments
+/// * `storage` - The storage to infer schemas from
+/// * `dir` - Directory receiving one `{resource}.json` file per resource
+///
+/// # Returns
+/// * `Result<Vec<String>, MocksError>` - Paths of the written files
+///
+/// Sidecar schemas are matched by resource name, so nothing is written when two
+/// storage keys share their last segment (e.g. `v1/users` and `v2/users`).
+pub fn write_inferred(storage: &Storage, dir: &str) -> Result<Vec<String>, MocksError> {
+    let inferred = infer::infer(&storage.data);
+    let mut keys: HashMap<&str, &str> = HashMap::new();
+    for key in inferred.keys() {
+        if let Some(other) = keys.insert(resource_name(key), key) {
+            return Err(MocksError::InvalidArgs(format!(
+                "{other} and {key} would both be written to {}.json.",
+                resource_name(key)
+            )));
+        }
+    }
+
+    let dir = Path::new(dir);
+    fs::create_dir_all(dir)
+        .map_err(|e| MocksError::FailedWriteFile(format!("Failed to create directory: {e}")))?;
+
+    let mut written = vec![];
+    for (key, schema) in &inferred {
+        let path = dir.join(format!("{}.json", resource_name(key)));
+        let text = serde_json::to_string_pretty(schema)
+            .map_err(|e| MocksError::FailedWriteFile(e.to_string()))?;
+        fs::write(&path, text).map_err(|e| MocksError::FailedWriteFile(e.to_string()))?;
+        written.push(path.display().to_string());
+    }
+
+    Ok(written)
+}
+
+/// Last path segment of a storage key (e.g. `api/v1/users` -> `users`)
+pub fn resource_name(key: &str) -> &str {
+    key.rsplit('/').next().unwrap_or(key)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn create_storage(dir: &TempDir, data: &Value) -> Storage {
+        let file_path = dir.path().join("storage.json");
+        fs::write(&file_path, data.to_string()).unwrap();
+        Storage::new(file_path.to_str().unwrap(), false).unwrap()
+    }
+
+    #[test]
+    fn test_load_inline_schemas() {
+        let dir = TempDir::new().unwrap();
+        let storage = create_storage(
+            &dir,
+            &json!({
+                "api/v1/posts": [],
+                "_mocks": {"schemas": {"api/v1/posts": {"required": ["title"]}}}
+            }),
+        );
+
+        let schemas = Schemas::load(&storage).unwrap();
+        assert!(!schemas.is_empty());
+        assert!(schemas.get("posts").is_some());
+        assert!(schemas.get("api/v1/posts").is_some());
+        assert!(schemas.get("comments").is_none());
+    }
+
+    #[test]
+    fn test_load_sidecar_schemas() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("schemas")).unwrap();
+        fs::write(
+            dir.path().join("schemas").join("posts.json"),
+            r#"{"required": ["title"]}"#,
+        )
+        .unwrap();
+        fs::write(dir.path().join("schemas").join("README.md"), "ignored").unwrap();
+        let storage = create_storage(&dir, &json!({"posts": []}));
+
+        let schemas = Schemas::load(&storage).unwrap();
+        assert_eq!(schemas.get("posts"), Some(&json!({"required": ["title"]})));
+    }
+
+    #[test]
+    fn test_load_invalid_sidecar_schema() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("schemas")).unwrap();
+        fs::write(dir.path().join("schemas").join("posts.json"), "{").unwrap();
+        let storage = create_storage(&dir, &json!({"posts": []}));
+
+        assert!(matches!(
+            Schemas::load(&storage),
+            Err(MocksError::FailedReadFile(_))
+        ));
+    }
+
+    #[test]
+    fn test_load_invalid_inline_schema() {
+        let dir = TempDir::new().unwrap();
+        for schema in [
+            json!({"$ref": "#"}),
+            json!({"properties": {"code": {"type": "string", "pattern": "["}}}),
+        ] {
+            let storage = create_storage(
+                &dir,
+                &json!({"posts": [], "_mocks": {"schemas": {"posts": schema}}}),
+            );
+            assert!(matches!(
+                Schemas::load(&storage),
+                Err(MocksError::InvalidArgs(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_validate() {
+        let dir = TempDir::new().unwrap();
+        let storage = create_storage(
+            &dir,
+            &json!({
+                "posts": [],
+                "_mocks": {"schemas": {"posts": {"required": ["title"]}}}
+            }),
+        );
+        let schemas = Schemas::load(&storage).unwrap();
+
+        assert!(schemas
+            .validate("posts", &json!({"title": "a"}), false)
+            .is_ok());
+        assert!(schemas.validate("posts", &json!({}), true).is_ok());
+        assert!(schemas.validate("comments", &json!({}), false).is_ok());
+
+        let violations = schemas.validate("posts", &json!({}), false).unwrap_err();
+        assert_eq!(violations[0].path, "/title");
+        assert_eq!(violations[0].keyword, "required");
+    }
+
+    #[test]
+    fn test_infer_all() {
+        let dir = TempDir::new().unwrap();
+        let storage = create_storage(&dir, &json!({"posts": [{"id": 1}], "profile": {}}));
+
+        let schemas = infer_all(&storage);
+        assert_eq!(
+            schemas["posts"]["properties"]["id"],
+            json!({"type": "integer"})
+        );
+        assert_eq!(
+            schemas["profile"],
+            json!({"type": "object", "properties": {}})
+        );
+    }
+
+    #[test]
+    fn test_write_inferred_round_trip() {
+        let dir = TempDir::new().unwrap();
+        let storage = create_storage(
+            &dir,
+            &json!({"api/v1/posts": [{"id": 1, "title": "first post"}]}),
+        );
+
+        let out_dir = dir.path().join("schemas");
+        let written = write_inferred(&storage, out_dir.to_str().unwrap()).unwrap();
+        assert_eq!(written.len(), 1);
+        assert!(written[0].ends_with("posts.json"));
+
+        // Written schemas are picked up as sidecar schemas
+        let schemas = Schemas::load(&storage).unwrap();
+        assert!(schemas
+            .validate("posts", &json!({"id": 2, "title": "a"}), false)
+            .is_ok());
+        assert!(schemas.validate("posts", &json!({"id": 2}), false).is_err());
+    }
+
+    #[test]
+    fn test_write_inferred_name_clash() {
+        let dir = TempDir::new().unwrap();
+        let mut storage = create_storage(&dir, &json!({"v1/users": [{"id": 1}]}));
+        // Loading rejects such files, but the data can still be changed afterwards
+        storage.data["v2/users"] = json!([{"id": "a"}]);
+
+        let out_dir = dir.path().join("schemas");
+        let result = write_inferred(&storage, out_dir.to_str().unwrap());
+        assert!(matches!(result, Err(MocksError::InvalidArgs(_))));
+        assert!(!out_dir.join("users.json").exists());
+    }
+}
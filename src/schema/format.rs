@@ -0,0 +1,69 @@
+use regex::Regex;
+use std::sync::LazyLock;
+
+static UUID: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^[0-9a-fA-F]{8}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{4}-[0-9a-fA-F]{12}$")
+        .unwrap()
+});
+static ULID: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[0-7][0-9A-HJKMNP-TV-Za-hjkmnp-tv-z]{25}$").unwrap());
+static DATE_TIME: LazyLock<Regex> = LazyLock::new(|| {
+    Regex::new(r"^\d{4}-\d{2}-\d{2}[Tt ]\d{2}:\d{2}:\d{2}(\.\d+)?([Zz]|[+-]\d{2}:\d{2})$").unwrap()
+});
+static DATE: LazyLock<Regex> = LazyLock::new(|| Regex::new(r"^\d{4}-\d{2}-\d{2}$").unwrap());
+static EMAIL: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[^@\s]+@[^@\s]+\.[^@\s]+$").unwrap());
+static URI: LazyLock<Regex> =
+    LazyLock::new(|| Regex::new(r"^[a-zA-Z][a-zA-Z0-9+.-]*://\S+$").unwrap());
+
+/// Formats recognised by validation and inference, in detection order
+pub const FORMATS: [&str; 6] = ["uuid", "ulid", "date-time", "date", "email", "uri"];
+
+/// Check whether `value` conforms to the named `format`
+///
+/// Unknown formats are treated as annotations and always match.
+pub fn matches(format: &str, value: &str) -> bool {
+    match format {
+        "uuid" => UUID.is_match(value),
+        "ulid" => ULID.is_match(value),
+        "date-time" => DATE_TIME.is_match(value),
+        "date" => DATE.is_match(value),
+        "email" => EMAIL.is_match(value),
+        "uri" => URI.is_match(value),
+        _ => true,
+    }
+}
+
+/// Detect the first known format that `value` conforms to
+pub fn detect(value: &str) -> Option<&'static str> {
+    FORMATS.into_iter().find(|format| matches(format, value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("uuid", "67e55044-10b1-426f-9247-bb680e5fe0c8"));
+        assert!(!matches("uuid", "67e55044"));
+        assert!(matches("ulid", "01J7BAKH37HPG116ZRRFKHBDGB"));
+        assert!(!matches("ulid", "01J7BAKH37HPG116ZRRFKHBDGU"));
+        assert!(matches("date-time", "2024-09-10T12:34:56Z"));
+        assert!(matches("date-time", "2024-09-10T12:34:56.789+09:00"));
+        assert!(!matches("date-time", "2024-09-10"));
+        assert!(matches("date", "2024-09-10"));
+        assert!(matches("email", "john@example.com"));
+        assert!(!matches("email", "john@example"));
+        assert!(matches("uri", "https://example.com/a"));
+        assert!(matches("unknown", "anything"));
+    }
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(detect("01J7BAKH37HPG116ZRRFKHBDGB"), Some("ulid"));
+        assert_eq!(detect("2024-09-10T12:34:56Z"), Some("date-time"));
+        assert_eq!(detect("john@example.com"), Some("email"));
+        assert_eq!(detect("first post"), None);
+    }
+}
@@ -0,0 +1,259 @@
+use crate::schema::format;
+use crate::storage::StorageData;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+
+/// Maximum number of distinct string values reported as an enum
+const ENUM_LIMIT: usize = 5;
+
+/// Infer a JSON Schema for every resource in the storage data
+///
+/// Array resources are described by the schema of their items, unified across
+/// all items; object resources by the schema of the object itself.
+pub fn infer(data: &StorageData) -> Map<String, Value> {
+    let mut schemas = Map::new();
+    if let Value::Object(obj) = data {
+        for (key, value) in obj {
+            let schema = match value {
+                Value::Array(items) => infer_items(items),
+                Value::Object(_) => infer_items(std::slice::from_ref(value)),
+                _ => continue,
+            };
+            schemas.insert(key.to_string(), schema);
+        }
+    }
+    schemas
+}
+
+/// Infer a single schema satisfied by all `items`
+pub fn infer_items(items: &[Value]) -> Value {
+    let mut shape = Shape::default();
+    for item in items {
+        shape.add(item);
+    }
+    shape.to_schema()
+}
+
+/// Accumulated observations of the values found at one location
+#[derive(Debug, Default)]
+struct Shape {
+    nulls: usize,
+    booleans: usize,
+    integers: usize,
+    numbers: usize,
+    strings: usize,
+    distinct: BTreeSet<String>,
+    /// `None` until a string is seen, `Some(None)` once formats disagree
+    format: Option<Option<&'static str>>,
+    arrays: usize,
+    items: Option<Box<Shape>>,
+    objects: usize,
+    properties: BTreeMap<String, Shape>,
+}
+
+impl Shape {
+    fn add(&mut self, value: &Value) {
+        match value {
+            Value::Null => self.nulls += 1,
+            Value::Bool(_) => self.booleans += 1,
+            Value::Number(n) if n.is_f64() => self.numbers += 1,
+            Value::Number(_) => self.integers += 1,
+            Value::String(s) => {
+                self.strings += 1;
+                if self.distinct.len() <= ENUM_LIMIT {
+                    self.distinct.insert(s.to_string());
+                }
+                let detected = format::detect(s);
+                self.format = match self.format {
+                    None => Some(detected),
+                    Some(previous) if previous == detected => Some(previous),
+                    Some(_) => Some(None),
+                };
+            }
+            Value::Array(values) => {
+                self.arrays += 1;
+                let items = self.items.get_or_insert_with(Box::default);
+                for value in values {
+                    items.add(value);
+                }
+            }
+            Value::Object(obj) => {
+                self.objects += 1;
+                for (key, value) in obj {
+                    self.properties
+                        .entry(key.to_string())
+                        .or_default()
+                        .add(value);
+                }
+            }
+        }
+    }
+
+    fn samples(&self) -> usize {
+        self.nulls
+            + self.booleans
+            + self.integers
+            + self.numbers
+            + self.strings
+            + self.arrays
+            + self.objects
+    }
+
+    fn to_schema(&self) -> Value {
+        let mut types = vec![];
+        let mut schema = Map::new();
+
+        if self.objects > 0 {
+            types.push("object");
+            let mut properties = Map::new();
+            let mut required = vec![];
+            for (key, shape) in &self.properties {
+                properties.insert(key.to_string(), shape.to_schema());
+                if shape.samples() == self.objects {
+                    required.push(json!(key));
+                }
+            }
+            schema.insert("properties".to_string(), Value::Object(properties));
+            if !required.is_empty() {
+                schema.insert("required".to_string(), Value::Array(required));
+            }
+        }
+
+        if self.arrays > 0 {
+            types.push("array");
+            let items = self.items.as_ref().map_or(json!({}), |items| {
+                if items.samples() == 0 {
+                    json!({})
+                } else {
+                    items.to_schema()
+                }
+            });
+            schema.insert("items".to_string(), items);
+        }
+
+        if self.strings > 0 {
+            types.push("string");
+            if let Some(Some(name)) = self.format {
+                schema.insert("format".to_string(), json!(name));
+            } else if self.distinct.len() <= ENUM_LIMIT && self.distinct.len() < self.strings {
+                // Only repeated values suggest an enumeration
+                let values: Vec<Value> = self.distinct.iter().map(|s| json!(s)).collect();
+                schema.insert("enum".to_string(), Value::Array(values));
+            }
+        }
+
+        if self.numbers > 0 {
+            types.push("number");
+        } else if self.integers > 0 {
+            types.push("integer");
+        }
+
+        if self.booleans > 0 {
+            types.push("boolean");
+        }
+
+        if self.nulls > 0 {
+            types.push("null");
+        }
+
+        match types.as_slice() {
+            [] => {}
+            [single] => {
+                schema.insert("type".to_string(), json!(single));
+            }
+            _ => {
+                schema.insert("type".to_string(), json!(types));
+            }
+        }
+
+        Value::Object(schema)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::validator::Validator;
+
+    #[test]
+    fn test_infer_array_resource() {
+        let data = json!({
+            "posts": [
+                {"id": "01J7BAKH37HPG116ZRRFKHBDGB", "title": "first post", "views": 100, "status": "draft"},
+                {"id": "01J7BAKH37GE8B688PT4RC7TP4", "title": "second post", "views": 10, "status": "draft", "rating": 4.5},
+                {"id": "01J7BAQE1GMD78FN3J0FJCNS8T", "title": "third post", "views": 0, "status": "published", "rating": null}
+            ]
+        });
+
+        let schemas = infer(&data);
+        assert_eq!(
+            schemas["posts"],
+            json!({
+                "type": "object",
+                "properties": {
+                    "id": {"type": "string", "format": "ulid"},
+                    "rating": {"type": ["number", "null"]},
+                    "status": {"type": "string", "enum": ["draft", "published"]},
+                    "title": {"type": "string"},
+                    "views": {"type": "integer"}
+                },
+                "required": ["id", "status", "title", "views"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_infer_object_resource() {
+        let data = json!({
+            "profile": {"id": 1, "email": "john@example.com", "tags": ["a", "b"], "address": {"city": "Tokyo"}},
+            "title": "ignored"
+        });
+
+        let schemas = infer(&data);
+        assert_eq!(schemas.len(), 1);
+        assert_eq!(
+            schemas["profile"],
+            json!({
+                "type": "object",
+                "properties": {
+                    "address": {
+                        "type": "object",
+                        "properties": {"city": {"type": "string"}},
+                        "required": ["city"]
+                    },
+                    "email": {"type": "string", "format": "email"},
+                    "id": {"type": "integer"},
+                    "tags": {"type": "array", "items": {"type": "string"}}
+                },
+                "required": ["address", "email", "id", "tags"]
+            })
+        );
+    }
+
+    #[test]
+    fn test_infer_mixed_formats_and_empty() {
+        assert_eq!(
+            infer_items(&[json!("2024-09-10T12:34:56Z"), json!("2024-09-10")]),
+            json!({"type": "string"})
+        );
+        assert_eq!(infer_items(&[]), json!({}));
+        assert_eq!(
+            infer_items(&[json!([])]),
+            json!({"type": "array", "items": {}})
+        );
+    }
+
+    #[test]
+    fn test_inferred_schema_validates_source_data() {
+        let items = vec![
+            json!({"id": 1, "name": "John", "nickname": null}),
+            json!({"id": 2, "name": "Jane", "nickname": "JJ", "age": 30}),
+        ];
+        let schema = infer_items(&items);
+        let validator = Validator::new(&schema);
+        for item in &items {
+            assert!(validator.validate(item).is_empty());
+        }
+        assert!(!validator.validate(&json!({"id": "3"})).is_empty());
+    }
+}
@@ -0,0 +1,729 @@
+use crate::schema::format;
+use regex::Regex;
+use serde_json::{json, Value};
+use std::cell::Cell;
+use std::collections::HashMap;
+
+/// Maximum nesting of schemas checked against a single instance
+///
+/// [`compile`] rejects reference cycles up front; this only guards documents that were
+/// not compiled.
+const MAX_DEPTH: usize = 256;
+
+/// Keys whose values are instances rather than schemas
+const DATA_KEYS: [&str; 5] = ["const", "default", "enum", "example", "examples"];
+
+/// Compiled `pattern`s of a schema document, keyed by their source
+pub type Patterns = HashMap<String, Regex>;
+
+/// A single schema violation found in a JSON instance
+#[derive(Debug, Clone, Eq, PartialEq)]
+pub struct Violation {
+    /// JSON Pointer to the offending value (empty for the root)
+    pub path: String,
+    /// Schema keyword that failed
+    pub keyword: String,
+    pub message: String,
+}
+
+impl Violation {
+    pub fn new(path: &str, keyword: &str, message: impl Into<String>) -> Self {
+        Self {
+            path: path.to_string(),
+            keyword: keyword.to_string(),
+            message: message.into(),
+        }
+    }
+
+    pub fn to_value(&self) -> Value {
+        json!({
+            "path": self.path,
+            "keyword": self.keyword,
+            "message": self.message,
+        })
+    }
+}
+
+/// JSON Schema validator supporting the commonly used subset of the specification
+///
+/// Local `$ref`s (`#/...`) are resolved against the root document, which allows
+/// validating against schemas embedded in larger documents such as OpenAPI specs.
+pub struct Validator<'a> {
+    root: &'a Value,
+    partial: bool,
+    patterns: Option<&'a Patterns>,
+    depth: Cell<usize>,
+}
+
+impl<'a> Validator<'a> {
+    pub fn new(root: &'a Value) -> Self {
+        Self {
+            root,
+            partial: false,
+            patterns: None,
+            depth: Cell::new(0),
+        }
+    }
+
+    /// Use the patterns compiled from the root with [`compile`]
+    pub fn patterns(mut self, patterns: &'a Patterns) -> Self {
+        self.patterns = Some(patterns);
+        self
+    }
+
+    /// Skip `required` at the top level, for partial updates
+    pub fn partial(mut self, partial: bool) -> Self {
+        self.partial = partial;
+        self
+    }
+
+    /// Validate `instance` against the root schema
+    pub fn validate(&self, instance: &Value) -> Vec<Violation> {
+        self.validate_with(self.root, instance)
+    }
+
+    /// Validate `instance` against `schema`, resolving references against the root
+    pub fn validate_with(&self, schema: &Value, instance: &Value) -> Vec<Violation> {
+        let mut violations = vec![];
+        self.check(schema, instance, "", &mut violations);
+        violations
+    }
+
+    fn check(&self, schema: &Value, instance: &Value, path: &str, out: &mut Vec<Violation>) {
+        if self.depth.get() >= MAX_DEPTH {
+            out.push(Violation::new(path, "$ref", "Schema is nested too deeply."));
+            return;
+        }
+        self.depth.set(self.depth.get() + 1);
+        self.check_schema(schema, instance, path, out);
+        self.depth.set(self.depth.get() - 1);
+    }
+
+    fn check_schema(&self, schema: &Value, instance: &Value, path: &str, out: &mut Vec<Violation>) {
+        let schema = match schema {
+            Value::Bool(true) => return,
+            Value::Bool(false) => {
+                out.push(Violation::new(path, "false", "No value is allowed here."));
+                return;
+            }
+            Value::Object(schema) => schema,
+            _ => return,
+        };
+
+        if let Some(reference) = schema.get("$ref").and_then(Value::as_str) {
+            match self.resolve(reference) {
+                Some(target) => self.check(target, instance, path, out),
+                None => out.push(Violation::new(
+                    path,
+                    "$ref",
+                    format!("Unresolvable reference {reference}."),
+                )),
+            }
+        }
+
+        if instance.is_null() && schema.get("nullable") == Some(&Value::Bool(true)) {
+            return;
+        }
+
+        if let Some(expected) = schema.get("type") {
+            let types: Vec<&str> = match expected {
+                Value::String(t) => vec![t.as_str()],
+                Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+                _ => vec![],
+            };
+            if !types.is_empty() && !types.iter().any(|t| is_type(instance, t)) {
+                out.push(Violation::new(
+                    path,
+                    "type",
+                    format!(
+                        "Expected {}, got {}.",
+                        types.join(" or "),
+                        type_name(instance)
+                    ),
+                ));
+                return;
+            }
+        }
+
+        if let Some(Value::Array(options)) = schema.get("enum") {
+            if !options.contains(instance) {
+                out.push(Violation::new(
+                    path,
+                    "enum",
+                    format!("Value must be one of {}.", Value::Array(options.clone())),
+                ));
+            }
+        }
+
+        if let Some(constant) = schema.get("const") {
+            if constant != instance {
+                out.push(Violation::new(
+                    path,
+                    "const",
+                    format!("Value must be {constant}."),
+                ));
+            }
+        }
+
+        match instance {
+            Value::Object(obj) => self.check_object(schema, obj, path, out),
+            Value::Array(items) => self.check_array(schema, items, path, out),
+            Value::String(s) => check_string(schema, s, self.patterns, path, out),
+            Value::Number(n) => {
+                if let Some(n) = n.as_f64() {
+                    check_number(schema, n, path, out);
+                }
+            }
+            _ => {}
+        }
+
+        self.check_combinators(schema, instance, path, out);
+    }
+
+    fn check_object(
+        &self,
+        schema: &serde_json::Map<String, Value>,
+        obj: &serde_json::Map<String, Value>,
+        path: &str,
+        out: &mut Vec<Violation>,
+    ) {
+        let is_root = path.is_empty();
+        if !(self.partial && is_root) {
+            if let Some(Value::Array(required)) = schema.get("required") {
+                for key in required.iter().filter_map(Value::as_str) {
+                    if !obj.contains_key(key) {
+                        out.push(Violation::new(
+                            &child_path(path, key),
+                            "required",
+                            format!("Property {key} is required."),
+                        ));
+                    }
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (key, value) in obj {
+            let child = child_path(path, key);
+            match properties.and_then(|p| p.get(key)) {
+                Some(property) => self.check(property, value, &child, out),
+                None => match schema.get("additionalProperties") {
+                    Some(Value::Bool(false)) => out.push(Violation::new(
+                        &child,
+                        "additionalProperties",
+                        format!("Property {key} is not allowed."),
+                    )),
+                    Some(additional @ Value::Object(_)) => {
+                        self.check(additional, value, &child, out)
+                    }
+                    _ => {}
+                },
+            }
+        }
+    }
+
+    fn check_array(
+        &self,
+        schema: &serde_json::Map<String, Value>,
+        items: &[Value],
+        path: &str,
+        out: &mut Vec<Violation>,
+    ) {
+        if let Some(min) = schema.get("minItems").and_then(Value::as_u64) {
+            if (items.len() as u64) < min {
+                out.push(Violation::new(
+                    path,
+                    "minItems",
+                    format!("Expected at least {min} items."),
+                ));
+            }
+        }
+        if let Some(max) = schema.get("maxItems").and_then(Value::as_u64) {
+            if (items.len() as u64) > max {
+                out.push(Violation::new(
+                    path,
+                    "maxItems",
+                    format!("Expected at most {max} items."),
+                ));
+            }
+        }
+        if schema.get("uniqueItems") == Some(&Value::Bool(true)) {
+            let duplicated = items
+                .iter()
+                .enumerate()
+                .any(|(i, item)| items[..i].contains(item));
+            if duplicated {
+                out.push(Violation::new(path, "uniqueItems", "Items must be unique."));
+            }
+        }
+        if let Some(item_schema) = schema.get("items") {
+            for (i, item) in items.iter().enumerate() {
+                self.check(item_schema, item, &child_path(path, &i.to_string()), out);
+            }
+        }
+    }
+
+    fn check_combinators(
+        &self,
+        schema: &serde_json::Map<String, Value>,
+        instance: &Value,
+        path: &str,
+        out: &mut Vec<Violation>,
+    ) {
+        if let Some(Value::Array(all)) = schema.get("allOf") {
+            for sub in all {
+                self.check(sub, instance, path, out);
+            }
+        }
+        if let Some(Value::Array(any)) = schema.get("anyOf") {
+            if !any.iter().any(|sub| self.is_valid(sub, instance, path)) {
+                out.push(Violation::new(
+                    path,
+                    "anyOf",
+                    "Value does not match any of the allowed schemas.",
+                ));
+            }
+        }
+        if let Some(Value::Array(one)) = schema.get("oneOf") {
+            let matched = one
+                .iter()
+                .filter(|sub| self.is_valid(sub, instance, path))
+                .count();
+            if matched != 1 {
+                out.push(Violation::new(
+                    path,
+                    "oneOf",
+                    format!("Value must match exactly one schema, matched {matched}."),
+                ));
+            }
+        }
+        if let Some(not) = schema.get("not") {
+            if self.is_valid(not, instance, path) {
+                out.push(Violation::new(
+                    path,
+                    "not",
+                    "Value must not match the schema.",
+                ));
+            }
+        }
+    }
+
+    fn is_valid(&self, schema: &Value, instance: &Value, path: &str) -> bool {
+        let mut violations = vec![];
+        self.check(schema, instance, path, &mut violations);
+        violations.is_empty()
+    }
+
+    fn resolve(&self, reference: &str) -> Option<&'a Value> {
+        let pointer = reference.strip_prefix('#')?;
+        self.root.pointer(pointer)
+    }
+}
+
+fn check_string(
+    schema: &serde_json::Map<String, Value>,
+    s: &str,
+    patterns: Option<&Patterns>,
+    path: &str,
+    out: &mut Vec<Violation>,
+) {
+    let length = s.chars().count() as u64;
+    if let Some(min) = schema.get("minLength").and_then(Value::as_u64) {
+        if length < min {
+            out.push(Violation::new(
+                path,
+                "minLength",
+                format!("Expected at least {min} characters."),
+            ));
+        }
+    }
+    if let Some(max) = schema.get("maxLength").and_then(Value::as_u64) {
+        if length > max {
+            out.push(Violation::new(
+                path,
+                "maxLength",
+                format!("Expected at most {max} characters."),
+            ));
+        }
+    }
+    if let Some(pattern) = schema.get("pattern").and_then(Value::as_str) {
+        let compiled;
+        let re = match patterns.and_then(|patterns| patterns.get(pattern)) {
+            Some(re) => Some(re),
+            None => {
+                compiled = Regex::new(pattern).ok();
+                compiled.as_ref()
+            }
+        };
+        match re {
+            Some(re) if !re.is_match(s) => out.push(Violation::new(
+                path,
+                "pattern",
+                format!("Value does not match pattern {pattern}."),
+            )),
+            Some(_) => {}
+            None => out.push(Violation::new(
+                path,
+                "pattern",
+                format!("Invalid pattern {pattern}."),
+            )),
+        }
+    }
+    if let Some(name) = schema.get("format").and_then(Value::as_str) {
+        if !format::matches(name, s) {
+            out.push(Violation::new(
+                path,
+                "format",
+                format!("Value is not a valid {name}."),
+            ));
+        }
+    }
+}
+
+fn check_number(
+    schema: &serde_json::Map<String, Value>,
+    n: f64,
+    path: &str,
+    out: &mut Vec<Violation>,
+) {
+    let limit = |keyword: &str| schema.get(keyword).and_then(Value::as_f64);
+
+    if let Some(min) = limit("minimum") {
+        if n < min {
+            out.push(Violation::new(
+                path,
+                "minimum",
+                format!("Value must be >= {min}."),
+            ));
+        }
+    }
+    if let Some(max) = limit("maximum") {
+        if n > max {
+            out.push(Violation::new(
+                path,
+                "maximum",
+                format!("Value must be <= {max}."),
+            ));
+        }
+    }
+    if let Some(min) = limit("exclusiveMinimum") {
+        if n <= min {
+            out.push(Violation::new(
+                path,
+                "exclusiveMinimum",
+                format!("Value must be > {min}."),
+            ));
+        }
+    }
+    if let Some(max) = limit("exclusiveMaximum") {
+        if n >= max {
+            out.push(Violation::new(
+                path,
+                "exclusiveMaximum",
+                format!("Value must be < {max}."),
+            ));
+        }
+    }
+    if let Some(divisor) = limit("multipleOf") {
+        if divisor > 0.0 && (n / divisor).fract().abs() > f64::EPSILON {
+            out.push(Violation::new(
+                path,
+                "multipleOf",
+                format!("Value must be a multiple of {divisor}."),
+            ));
+        }
+    }
+}
+
+fn is_type(instance: &Value, expected: &str) -> bool {
+    match expected {
+        "object" => instance.is_object(),
+        "array" => instance.is_array(),
+        "string" => instance.is_string(),
+        "number" => instance.is_number(),
+        "integer" => {
+            instance.is_i64()
+                || instance.is_u64()
+                || instance.as_f64().is_some_and(|n| n.fract() == 0.0)
+        }
+        "boolean" => instance.is_boolean(),
+        "null" => instance.is_null(),
+        _ => true,
+    }
+}
+
+pub fn type_name(instance: &Value) -> &'static str {
+    match instance {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+/// Check a schema document before validating against it
+///
+/// Fails on a `$ref` cycle that never reaches into the instance (e.g. `{"$ref": "#"}`),
+/// which could not be validated, and on an invalid `pattern`.
+///
+/// Returns the compiled patterns of the document.
+pub fn compile(root: &Value) -> Result<Patterns, String> {
+    let mut patterns = Patterns::new();
+    let mut visited = HashMap::new();
+    let mut nodes = vec![root];
+    while let Some(node) = nodes.pop() {
+        match node {
+            Value::Object(map) => {
+                if let Some(Value::String(pattern)) = map.get("pattern") {
+                    if !patterns.contains_key(pattern) {
+                        let re = Regex::new(pattern)
+                            .map_err(|e| format!("Invalid pattern {pattern}: {e}"))?;
+                        patterns.insert(pattern.clone(), re);
+                    }
+                }
+                find_cycle(root, node, &mut visited, &mut vec![])?;
+                nodes.extend(
+                    map.iter()
+                        .filter(|(key, _)| !DATA_KEYS.contains(&key.as_str()))
+                        .map(|(_, value)| value),
+                );
+            }
+            Value::Array(items) => nodes.extend(items),
+            _ => {}
+        }
+    }
+    Ok(patterns)
+}
+
+/// Follow the schemas applied to the same instance as `schema`, failing on a cycle
+///
+/// `visited` maps schemas to whether they were fully explored, and `refs` holds the
+/// references followed to reach `schema`.
+fn find_cycle<'a>(
+    root: &'a Value,
+    schema: &'a Value,
+    visited: &mut HashMap<*const Value, bool>,
+    refs: &mut Vec<&'a str>,
+) -> Result<(), String> {
+    match visited.get(&(schema as *const Value)) {
+        Some(true) => return Ok(()),
+        Some(false) => {
+            let reference = refs.last().copied().unwrap_or("#");
+            return Err(format!("Circular schema reference {reference}."));
+        }
+        None => {}
+    }
+    let Value::Object(map) = schema else {
+        return Ok(());
+    };
+
+    visited.insert(schema, false);
+    if let Some(reference) = map.get("$ref").and_then(Value::as_str) {
+        let target = reference
+            .strip_prefix('#')
+            .and_then(|pointer| root.pointer(pointer));
+        if let Some(target) = target {
+            refs.push(reference);
+            find_cycle(root, target, visited, refs)?;
+            refs.pop();
+        }
+    }
+    for keyword in ["allOf", "anyOf", "oneOf"] {
+        if let Some(Value::Array(schemas)) = map.get(keyword) {
+            for schema in schemas {
+                find_cycle(root, schema, visited, refs)?;
+            }
+        }
+    }
+    if let Some(schema) = map.get("not") {
+        find_cycle(root, schema, visited, refs)?;
+    }
+    visited.insert(schema, true);
+    Ok(())
+}
+
+fn child_path(path: &str, key: &str) -> String {
+    format!("{path}/{}", key.replace('~', "~0").replace('/', "~1"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn post_schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["id", "title"],
+            "properties": {
+                "id": {"type": "string", "format": "ulid"},
+                "title": {"type": "string", "minLength": 1, "maxLength": 20},
+                "views": {"type": "integer", "minimum": 0},
+                "status": {"enum": ["draft", "published"]},
+                "tags": {"type": "array", "items": {"type": "string"}, "uniqueItems": true}
+            },
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_validate_valid_instance() {
+        let schema = post_schema();
+        let instance = json!({"id":"01J7BAKH37HPG116ZRRFKHBDGB","title":"first post","views":100});
+        assert!(Validator::new(&schema).validate(&instance).is_empty());
+    }
+
+    #[test]
+    fn test_validate_collects_violations() {
+        let schema = post_schema();
+        let instance = json!({"id":"not-a-ulid","views":-1,"status":"deleted","extra":true});
+        let violations = Validator::new(&schema).validate(&instance);
+
+        let keywords: Vec<(&str, &str)> = violations
+            .iter()
+            .map(|v| (v.path.as_str(), v.keyword.as_str()))
+            .collect();
+        assert!(keywords.contains(&("/title", "required")));
+        assert!(keywords.contains(&("/id", "format")));
+        assert!(keywords.contains(&("/views", "minimum")));
+        assert!(keywords.contains(&("/status", "enum")));
+        assert!(keywords.contains(&("/extra", "additionalProperties")));
+    }
+
+    #[test]
+    fn test_validate_type_mismatch() {
+        let schema = post_schema();
+        let instance = json!({"id":"01J7BAKH37HPG116ZRRFKHBDGB","title":1,"tags":["a","a",1]});
+        let violations = Validator::new(&schema).validate(&instance);
+
+        assert!(violations.contains(&Violation::new(
+            "/title",
+            "type",
+            "Expected string, got integer."
+        )));
+        assert!(violations
+            .iter()
+            .any(|v| v.path == "/tags" && v.keyword == "uniqueItems"));
+        assert!(violations
+            .iter()
+            .any(|v| v.path == "/tags/2" && v.keyword == "type"));
+    }
+
+    #[test]
+    fn test_validate_partial_skips_root_required() {
+        let schema = post_schema();
+        let instance = json!({"views": 1});
+        assert!(!Validator::new(&schema).validate(&instance).is_empty());
+        assert!(Validator::new(&schema)
+            .partial(true)
+            .validate(&instance)
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_with_refs_and_combinators() {
+        let root = json!({
+            "components": {
+                "schemas": {
+                    "Name": {"type": "string", "pattern": "^[A-Z]"},
+                    "User": {
+                        "type": "object",
+                        "properties": {
+                            "name": {"$ref": "#/components/schemas/Name"},
+                            "nickname": {"type": "string", "nullable": true},
+                            "age": {"oneOf": [{"type": "integer"}, {"type": "null"}]}
+                        }
+                    }
+                }
+            }
+        });
+        let validator = Validator::new(&root);
+        let schema = json!({"$ref": "#/components/schemas/User"});
+
+        assert!(validator
+            .validate_with(&schema, &json!({"name":"John","nickname":null,"age":null}))
+            .is_empty());
+
+        let violations = validator.validate_with(&schema, &json!({"name":"john","age":"old"}));
+        let keywords: Vec<&str> = violations.iter().map(|v| v.keyword.as_str()).collect();
+        assert_eq!(keywords, vec!["oneOf", "pattern"]);
+    }
+
+    #[test]
+    fn test_compile_patterns() {
+        let root = json!({
+            "type": "object",
+            "properties": {
+                "code": {"type": "string", "pattern": "^[A-Z]{3}$"},
+                "pattern": {"type": "string", "default": {"pattern": "("}}
+            }
+        });
+        let patterns = compile(&root).unwrap();
+        assert_eq!(patterns.len(), 1);
+
+        let validator = Validator::new(&root).patterns(&patterns);
+        assert!(validator.validate(&json!({"code": "ABC"})).is_empty());
+        assert_eq!(
+            validator.validate(&json!({"code": "abc"}))[0].keyword,
+            "pattern"
+        );
+
+        let invalid = json!({"type": "string", "pattern": "("});
+        assert!(compile(&invalid)
+            .unwrap_err()
+            .starts_with("Invalid pattern ("));
+        assert_eq!(
+            Validator::new(&invalid).validate(&json!("a")),
+            vec![Violation::new("", "pattern", "Invalid pattern (.")]
+        );
+    }
+
+    #[test]
+    fn test_compile_reference_cycles() {
+        let root = json!({"$ref": "#"});
+        assert_eq!(compile(&root).unwrap_err(), "Circular schema reference #.");
+
+        let root = json!({
+            "definitions": {
+                "A": {"allOf": [{"$ref": "#/definitions/B"}]},
+                "B": {"$ref": "#/definitions/A"}
+            }
+        });
+        assert!(compile(&root)
+            .unwrap_err()
+            .starts_with("Circular schema reference #/definitions/"));
+
+        // References nested under properties or items consume the instance
+        let tree = json!({
+            "type": "object",
+            "properties": {
+                "children": {"type": "array", "items": {"$ref": "#"}}
+            }
+        });
+        assert!(compile(&tree).is_ok());
+        assert!(Validator::new(&tree)
+            .validate(&json!({"children": [{"children": []}]}))
+            .is_empty());
+    }
+
+    #[test]
+    fn test_validate_reference_cycle_is_bounded() {
+        let root = json!({"$ref": "#"});
+        let violations = Validator::new(&root).validate(&json!(1));
+        assert_eq!(
+            violations,
+            vec![Violation::new("", "$ref", "Schema is nested too deeply.")]
+        );
+    }
+
+    #[test]
+    fn test_violation_to_value() {
+        let violation = Violation::new("/title", "required", "Property title is required.");
+        assert_eq!(
+            violation.to_value(),
+            json!({"path":"/title","keyword":"required","message":"Property title is required."})
+        );
+    }
+}
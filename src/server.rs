@@ -1,26 +1,109 @@
+mod auth;
+mod chaos;
 mod context;
+mod contract;
+mod cors;
+mod delay;
+mod etag;
+mod fallback;
 mod handler;
+mod proxy;
+mod rate_limit;
+mod render;
+mod routes;
+mod scenario;
+mod scope;
 mod state;
+mod tls;
+mod webhook;
 
 use crate::error::MocksError;
+use crate::openapi::contract::Contract;
+use crate::schema::Schemas;
+use crate::server::auth::authenticate;
+use crate::server::chaos::chaos;
+use crate::server::contract::check_contract;
+use crate::server::cors::cors;
+use crate::server::delay::delay;
+use crate::server::fallback::proxy_fallback;
 use crate::server::handler::delete::delete;
+use crate::server::handler::events::{events, resource_events};
 use crate::server::handler::get::{get_all, get_one};
 use crate::server::handler::hc::hc;
+use crate::server::handler::oidc::{
+    authorize, authorize_page, discovery, exchange_token, jwks, userinfo,
+};
+use crate::server::handler::openapi::openapi;
 use crate::server::handler::patch::{patch, patch_one};
 use crate::server::handler::post::post;
 use crate::server::handler::put::{put, put_one};
+use crate::server::handler::record::{record, Recording};
+use crate::server::handler::scenarios::{get_scenarios, put_scenario, reset_scenarios};
+use crate::server::handler::socket::socket;
+use crate::server::handler::token::token;
+use crate::server::handler::webhooks::{
+    delete_webhook, get_deliveries, get_webhooks, post_webhook,
+};
+use crate::server::rate_limit::rate_limit;
+use crate::server::routes::CannedRoute;
+use crate::server::scenario::Scenarios;
 use crate::server::state::{AppState, SharedState};
+use crate::server::tls::Https;
+use crate::server::webhook::Webhooks;
+use crate::storage::recorder::Recorder;
 use crate::storage::Storage;
-use axum::routing::get;
-use axum::Router;
+use axum::middleware::from_fn_with_state;
+use axum::routing::{get, MethodRouter};
+use axum::serve::ListenerExt;
+use axum::{Extension, Router};
 use colored::*;
 use serde_json::Value;
+use std::collections::BTreeMap;
 use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
 use tokio::net::TcpListener;
 
+pub use crate::server::auth::Auth;
+pub use crate::server::chaos::{Chaos, Faults};
+pub use crate::server::cors::{Cors, Headers, Methods, Origins};
+pub use crate::server::delay::{Delay, Delays};
+pub use crate::server::fallback::{Fallback, ProxyHeader};
+pub use crate::server::proxy::Upstream;
+pub use crate::server::rate_limit::{LimitKey, Rate, RateLimiter};
+pub use crate::server::tls::Tls;
+
+/// Paths served by mocks itself rather than the mocked API
+const INTERNAL_PREFIXES: [&str; 2] = ["/_hc", "/_mocks"];
+
+/// Largest request body buffered by middleware, the default limit of axum extractors
+const REQUEST_BODY_LIMIT: usize = 2 * 1024 * 1024;
+/// Largest response body buffered by middleware
+const RESPONSE_BODY_LIMIT: usize = 64 * 1024 * 1024;
+
 /// Mock server module
 pub struct Server {}
 
+/// Optional behaviors of the mock server
+#[derive(Debug, Default)]
+pub struct ServerOptions {
+    /// OpenAPI contract to validate traffic against
+    pub contract: Option<Contract>,
+    /// Authentication required from clients
+    pub auth: Option<Auth>,
+    /// Latency added to requests
+    pub delays: Delays,
+    /// Faults injected into requests
+    pub chaos: Option<Chaos>,
+    /// Limit of requests per client
+    pub rate_limit: Option<RateLimiter>,
+    /// Cross-origin policy for browser clients
+    pub cors: Option<Cors>,
+    /// Certificate to serve HTTPS with
+    pub tls: Option<Tls>,
+    /// Upstream serving the requests the mock does not
+    pub fallback: Option<Fallback>,
+}
+
 impl Server {
     /// Starts the mock server
     ///
@@ -28,36 +111,117 @@ impl Server {
     /// * `socket_addr` - The socket address to bind the server to
     /// * `url` - The base URL of the server
     /// * `storage` - The storage instance to use
+    /// * `options` - Optional behaviors of the server
     ///
     /// # Returns
     /// * `Result<(), MocksError>` - Ok if the server starts successfully, Err otherwise
-    pub async fn startup(socket_addr: SocketAddr, storage: Storage) -> Result<(), MocksError> {
+    pub async fn startup(
+        socket_addr: SocketAddr,
+        storage: Storage,
+        mut options: ServerOptions,
+    ) -> Result<(), MocksError> {
+        let schemas = Schemas::load(&storage)?;
+        let scenarios = Scenarios::load(&storage)?;
+        let routes = CannedRoute::load(&storage, &scenarios)?;
+        let webhooks = Arc::new(Webhooks::load(&storage)?);
+
         let listener = TcpListener::bind(socket_addr)
             .await
             .map_err(|e| MocksError::Exception(e.to_string()))?;
 
-        print_endpoints(storage.resources());
+        print_endpoints(storage.resources(), &routes);
+        if let Some(fallback) = &mut options.fallback {
+            fallback.mock(storage.resources(), &routes);
+        }
+
+        tokio::spawn(webhooks.clone().dispatch(storage.subscribe()));
 
         let data = storage.data.clone();
-        let state = AppState::new(storage);
-        let router = create_router(state, &data);
+        let state = AppState::new(storage, scenarios);
+        let tls = options.tls.take();
+        let mut router = apply_options(create_router(state, &data, schemas, &routes)?, options)
+            .layer(Extension(webhooks));
+        if tls.is_some() {
+            router = router.layer(Extension(Https));
+        }
+        let service = router.into_make_service_with_connect_info::<SocketAddr>();
+        match tls {
+            // The no-op tap gives TLS connections the `SocketAddr` connect info of TCP ones
+            Some(tls) => axum::serve(tls.listener(listener).tap_io(|_| {}), service).await,
+            None => axum::serve(listener, service).await,
+        }
+        .map_err(|e| MocksError::Exception(e.to_string()))
+    }
+
+    /// Starts a proxy recording the exchanges with an upstream API
+    ///
+    /// # Arguments
+    /// * `socket_addr` - The socket address to bind the proxy to
+    /// * `upstream` - The upstream requests are forwarded to
+    /// * `recorder` - The recorder folding exchanges into the storage file
+    ///
+    /// # Returns
+    /// * `Result<(), MocksError>` - Ok if the proxy starts successfully, Err otherwise
+    pub async fn record(
+        socket_addr: SocketAddr,
+        upstream: Upstream,
+        recorder: Recorder,
+    ) -> Result<(), MocksError> {
+        let listener = TcpListener::bind(socket_addr)
+            .await
+            .map_err(|e| MocksError::Exception(e.to_string()))?;
+
+        let recording = Recording {
+            upstream,
+            recorder: Mutex::new(recorder),
+        };
+        let router = Router::new()
+            .fallback(record)
+            .with_state(Arc::new(recording));
         axum::serve(listener, router)
             .await
             .map_err(|e| MocksError::Exception(e.to_string()))
     }
 }
 
-fn print_endpoints(resources: Vec<String>) {
+fn print_endpoints(resources: Vec<String>, routes: &[CannedRoute]) {
     println!("{}", "Available Endpoints:".blue().bold());
     println!(
         "   {:<7} {}",
         "/_hc".bright_cyan(),
         "(Health Check)".bright_black()
     );
+    println!(
+        "   {} {}",
+        "/_mocks/openapi.json".bright_cyan(),
+        "(OpenAPI)".bright_black()
+    );
+    println!(
+        "   {} {}",
+        "/_mocks/events".bright_cyan(),
+        "(Change Events)".bright_black()
+    );
+    println!(
+        "   {} {}",
+        "/_mocks/ws".bright_cyan(),
+        "(WebSocket)".bright_black()
+    );
+    println!(
+        "   {} {}",
+        "/_mocks/webhooks".bright_cyan(),
+        "(Webhooks)".bright_black()
+    );
 
     for resource in resources {
         println!("   {}", format!("/{resource}").bright_cyan());
     }
+    for route in routes {
+        println!(
+            "   {} {}",
+            route.path.bright_cyan(),
+            format!("({} -> {})", route.method, route.default.status.as_u16()).bright_black()
+        );
+    }
     println!();
 }
 
@@ -88,20 +252,99 @@ fn convert_to_resource_paths(value: &Value) -> Vec<String> {
     paths
 }
 
-fn create_router(state: SharedState, value: &Value) -> Router {
+fn create_router(
+    state: SharedState,
+    value: &Value,
+    schemas: Schemas,
+    routes: &[CannedRoute],
+) -> Result<Router, MocksError> {
     let hc_router = Router::new().route("/", get(hc));
+    let admin_router = Router::new()
+        .route("/openapi.json", get(openapi))
+        .route("/events", get(events))
+        .route("/ws", get(socket))
+        .route("/webhooks", get(get_webhooks).post(post_webhook))
+        .route("/webhooks/deliveries", get(get_deliveries))
+        .route("/webhooks/{id}", axum::routing::delete(delete_webhook))
+        .route("/scenarios", get(get_scenarios))
+        .route("/scenarios/reset", axum::routing::post(reset_scenarios))
+        .route("/scenarios/{name}", axum::routing::put(put_scenario))
+        .route("/token", axum::routing::post(token))
+        .route("/oidc/.well-known/openid-configuration", get(discovery))
+        .route("/oidc/jwks", get(jwks))
+        .route("/oidc/authorize", get(authorize_page).post(authorize))
+        .route("/oidc/token", axum::routing::post(exchange_token))
+        .route("/oidc/userinfo", get(userinfo).post(userinfo));
     let storage_router = Router::new()
         .route("/", get(get_all).post(post).put(put_one).patch(patch_one))
-        .route("/{id}", get(get_one).put(put).patch(patch).delete(delete));
+        .route("/{id}", get(get_one).put(put).patch(patch).delete(delete))
+        .route("/_events", get(resource_events));
 
-    let mut router = Router::new().nest("/_hc", hc_router);
+    let mut router = Router::new()
+        .nest("/_hc", hc_router)
+        .nest("/_mocks", admin_router);
 
-    let resource_paths = convert_to_resource_paths(value);
-    for path in resource_paths {
-        router = router.nest(path.as_str(), storage_router.clone());
+    let mut resources = Router::new();
+    for path in convert_to_resource_paths(value) {
+        resources = resources.nest(path.as_str(), storage_router.clone());
     }
 
-    router.with_state(state)
+    // Canned routes are registered ahead of the resource endpoints they may shadow,
+    // which still serve the methods the routes do not declare
+    let mut canned: BTreeMap<&str, MethodRouter<SharedState>> = BTreeMap::new();
+    for route in routes {
+        let method_router = match canned.remove(route.path.as_str()) {
+            Some(existing) => existing.merge(route.method_router()?),
+            None => route.method_router()?,
+        };
+        canned.insert(&route.path, method_router);
+    }
+    let fallthrough = resources.clone().with_state(state.clone());
+    for (path, method_router) in canned {
+        router = router.route(path, method_router.fallback_service(fallthrough.clone()));
+    }
+    router = router.merge(resources);
+
+    if !schemas.is_empty() {
+        router = router.layer(Extension(Arc::new(schemas)));
+    }
+
+    Ok(router.with_state(state))
+}
+
+fn apply_options(mut router: Router, options: ServerOptions) -> Router {
+    if let Some(fallback) = options.fallback {
+        router = router.layer(from_fn_with_state(Arc::new(fallback), proxy_fallback));
+    }
+    if let Some(contract) = options.contract {
+        router = router.layer(from_fn_with_state(Arc::new(contract), check_contract));
+    }
+    if let Some(auth) = options.auth {
+        let auth = Arc::new(auth);
+        router = router
+            .layer(from_fn_with_state(auth.clone(), authenticate))
+            .layer(Extension(auth));
+    }
+    if let Some(faults) = options.chaos.filter(|c| !c.is_empty()) {
+        router = router.layer(from_fn_with_state(Arc::new(faults), chaos));
+    }
+    if !options.delays.is_empty() {
+        router = router.layer(from_fn_with_state(Arc::new(options.delays), delay));
+    }
+    if let Some(limiter) = options.rate_limit {
+        router = router.layer(from_fn_with_state(Arc::new(limiter), rate_limit));
+    }
+    if let Some(policy) = options.cors {
+        router = router.layer(from_fn_with_state(Arc::new(policy), cors));
+    }
+    router
+}
+
+/// Whether a path is served by mocks itself (health check and admin endpoints)
+fn is_internal(path: &str) -> bool {
+    INTERNAL_PREFIXES
+        .iter()
+        .any(|prefix| path == *prefix || path.starts_with(&format!("{prefix}/")))
 }
 
 #[cfg(test)]
@@ -114,14 +357,14 @@ mod tests {
     fn test_print_endpoints() {
         let resources = vec!["users".to_string(), "posts".to_string()];
         // Just check that it does not panic; do not check output content
-        print_endpoints(resources);
+        print_endpoints(resources, &[]);
     }
 
     #[test]
     fn test_print_endpoints_with_empty_resources() {
         let resources: Vec<String> = vec![];
         // Just check that it does not panic; do not check output content
-        print_endpoints(resources);
+        print_endpoints(resources, &[]);
     }
 
     #[test]
@@ -154,8 +397,111 @@ mod tests {
         let tmpfile = NamedTempFile::new().unwrap();
         std::fs::write(tmpfile.path(), "{\"users\": []}").unwrap();
         let storage = Storage::new(tmpfile.path().to_str().unwrap(), true).unwrap();
-        let state = AppState::new(storage);
+        let state = AppState::new(storage, Scenarios::default());
         // Just check that router can be created
-        let _ = create_router(state, &value);
+        let _ = create_router(state, &value, Schemas::default(), &[]).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_router_canned_routes_fall_through() {
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let value = json!({
+            "posts": [{"id": 1, "title": "a"}],
+            "_mocks": {"routes": [
+                {"method": "GET", "path": "/posts/stats", "body": {"count": 1}},
+                {"method": "POST", "path": "/posts", "status": 202}
+            ]}
+        });
+        let storage = Storage::from_value(&value);
+        let routes = CannedRoute::load(&storage, &Scenarios::default()).unwrap();
+        let data = storage.data.clone();
+        let router = create_router(
+            AppState::new(storage, Scenarios::default()),
+            &data,
+            Schemas::default(),
+            &routes,
+        )
+        .unwrap();
+        let send = |method: &str, uri: &str| {
+            let req = Request::builder().method(method).uri(uri);
+            router.clone().oneshot(req.body(Body::empty()).unwrap())
+        };
+
+        assert_eq!(
+            send("POST", "/posts").await.unwrap().status(),
+            StatusCode::ACCEPTED
+        );
+        assert_eq!(
+            send("GET", "/posts").await.unwrap().status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            send("GET", "/posts/stats").await.unwrap().status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            send("GET", "/posts/1").await.unwrap().status(),
+            StatusCode::OK
+        );
+        assert_eq!(
+            send("DELETE", "/posts/stats").await.unwrap().status(),
+            StatusCode::NOT_FOUND
+        );
+    }
+
+    #[tokio::test]
+    async fn test_router_conditional_requests() {
+        use axum::body::Body;
+        use axum::http::{Request, StatusCode};
+        use tower::ServiceExt;
+
+        let value = json!({"posts": [{"id": 1, "title": "a"}]});
+        let storage = Storage::from_value(&value);
+        let router = create_router(
+            AppState::new(storage, Scenarios::default()),
+            &value,
+            Schemas::default(),
+            &[],
+        )
+        .unwrap();
+
+        let send = |method: &str, header: Option<(&str, &str)>, body: &str| {
+            let mut req = Request::builder()
+                .method(method)
+                .uri("/posts/1")
+                .header("content-type", "application/json");
+            if let Some((name, value)) = header {
+                req = req.header(name, value);
+            }
+            router
+                .clone()
+                .oneshot(req.body(Body::from(body.to_string())).unwrap())
+        };
+
+        let res = send("GET", None, "").await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let etag = res.headers()["etag"].to_str().unwrap().to_string();
+
+        let res = send("GET", Some(("if-none-match", &etag)), "")
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+
+        let res = send("PATCH", Some(("if-match", "\"stale\"")), r#"{"title":"b"}"#)
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
+
+        let res = send("PATCH", Some(("if-match", &etag)), r#"{"title":"b"}"#)
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_ne!(res.headers()["etag"], etag.as_str());
+
+        let res = send("DELETE", Some(("if-match", &etag)), "").await.unwrap();
+        assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
     }
 }
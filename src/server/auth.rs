@@ -0,0 +1,898 @@
+pub mod jwt;
+pub mod oidc;
+
+use crate::error::MocksError;
+use crate::server::auth::jwt::Jwt;
+use crate::server::auth::oidc::Oidc;
+use crate::server::scope::Scoped;
+use crate::storage::Storage;
+use axum::extract::{Request, State};
+use axum::http::header::{AUTHORIZATION, WWW_AUTHENTICATE};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use base64::engine::general_purpose::STANDARD;
+use base64::Engine;
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::fmt;
+use std::path::Path;
+use std::sync::Arc;
+
+const AUTH_KEY: &str = "auth";
+const REALM: &str = "mocks";
+const DEFAULT_API_KEY_HEADER: &str = "x-api-key";
+/// Admin endpoints served without authentication, see [`is_public`]
+const PUBLIC_PATHS: [&str; 8] = [
+    "/_mocks/openapi.json",
+    "/_mocks/token",
+    "/_mocks/ws",
+    "/_mocks/oidc/.well-known/openid-configuration",
+    "/_mocks/oidc/jwks",
+    "/_mocks/oidc/authorize",
+    "/_mocks/oidc/token",
+    "/_mocks/oidc/userinfo",
+];
+
+/// How a client authenticated
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Scheme {
+    ApiKey,
+    Basic,
+    Bearer,
+}
+
+impl fmt::Display for Scheme {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Scheme::ApiKey => write!(f, "ApiKey"),
+            Scheme::Basic => write!(f, "Basic"),
+            Scheme::Bearer => write!(f, "Bearer"),
+        }
+    }
+}
+
+/// Authenticated client of a request, added to the request extensions
+#[derive(Debug, Clone, PartialEq)]
+pub struct Principal {
+    pub scheme: Scheme,
+    /// Claims of the token, or those configured for the API key or user
+    pub claims: Value,
+}
+
+/// Authentication a request needs
+#[derive(Debug, Clone, PartialEq)]
+pub enum Requirement {
+    Public,
+    Authenticated,
+    /// Authenticated with claims matching these
+    Claims(Map<String, Value>),
+}
+
+impl Requirement {
+    fn parse(value: &Value) -> Result<Requirement, String> {
+        match value {
+            Value::Bool(false) => Ok(Requirement::Public),
+            Value::Bool(true) => Ok(Requirement::Authenticated),
+            Value::Object(obj) => match obj.get("claims") {
+                Some(Value::Object(claims)) if obj.len() == 1 => {
+                    Ok(Requirement::Claims(claims.clone()))
+                }
+                _ => Err(format!("{value} must be {{\"claims\": {{...}}}}")),
+            },
+            _ => Err(format!(
+                "{value} must be true, false or {{\"claims\": {{...}}}}"
+            )),
+        }
+    }
+}
+
+#[derive(Debug)]
+struct ApiKeys {
+    header: HeaderName,
+    /// Accepted keys with their claims
+    keys: HashMap<String, Value>,
+}
+
+/// Result of reading the credentials of a request
+#[derive(Debug, PartialEq)]
+enum Credentials {
+    Missing,
+    Valid(Principal),
+    Invalid(Scheme),
+}
+
+/// Authentication of the mocked API
+///
+/// Clients authenticate with any of the schemes configured under `_mocks.auth`:
+///
+/// ```json
+/// {"_mocks": {"auth": {
+///   "apiKey": {"header": "X-API-Key", "keys": ["k1", {"key": "k2", "claims": {"role": "admin"}}]},
+///   "basic": {"users": [{"username": "alice", "password": "secret", "claims": {"role": "admin"}}]},
+///   "jwt": {"secret": "change-me", "issuer": "https://mocks.local"}
+/// }}}
+/// ```
+///
+/// Every endpoint requires authentication unless a resource opts out with `"auth": false`,
+/// or requires claims with `"auth": {"claims": {"role": "admin"}}`, optionally per method.
+/// Custom routes declare the same `auth` on their definition.
+/// An [`Oidc`] provider issuing tokens to the users of the storage can be added with `oidc`.
+#[derive(Debug)]
+pub struct Auth {
+    api_keys: Option<ApiKeys>,
+    /// Users of Basic authentication with their password and claims
+    users: Option<HashMap<String, (String, Value)>>,
+    jwt: Option<Jwt>,
+    oidc: Option<Oidc>,
+    requirements: Scoped<Requirement>,
+    /// Custom routes declaring `auth`, with the method and path they answer
+    routes: Vec<(Method, String, Requirement)>,
+}
+
+impl Auth {
+    /// Read the auth config of a storage
+    ///
+    /// # Returns
+    /// * `Ok(None)` when the storage does not configure auth
+    pub fn load(storage: &Storage) -> Result<Option<Auth>, MocksError> {
+        let Some(config) = storage.config.get(AUTH_KEY) else {
+            return Ok(None);
+        };
+        let base = Path::new(&storage.file).parent().unwrap_or(Path::new(""));
+
+        let api_keys = config.get("apiKey").map(parse_api_keys).transpose()?;
+        let users = config.get("basic").map(parse_users).transpose()?;
+        let jwt = config
+            .get("jwt")
+            .map(|jwt| Jwt::parse(jwt, base))
+            .transpose()?;
+        if api_keys.is_none() && users.is_none() && jwt.is_none() {
+            return Err(invalid("configure at least one of apiKey, basic or jwt"));
+        }
+        let oidc = config.get("oidc").map(Oidc::parse).transpose()?;
+        if oidc.is_some() && !jwt.as_ref().is_some_and(Jwt::can_sign) {
+            return Err(invalid("oidc needs a jwt signing key"));
+        }
+
+        let requirements = Scoped::load(
+            storage,
+            AUTH_KEY,
+            Some(Requirement::Authenticated),
+            Requirement::parse,
+        )?;
+        let routes = parse_routes(storage)?;
+
+        Ok(Some(Auth {
+            api_keys,
+            users,
+            jwt,
+            oidc,
+            requirements,
+            routes,
+        }))
+    }
+
+    pub fn jwt(&self) -> Option<&Jwt> {
+        self.jwt.as_ref()
+    }
+
+    pub fn oidc(&self) -> Option<&Oidc> {
+        self.oidc.as_ref()
+    }
+
+    /// Names of the configured schemes, e.g. `ApiKey, Bearer (HS256)`
+    pub fn describe(&self) -> String {
+        let mut schemes = vec![];
+        if let Some(api_keys) = &self.api_keys {
+            schemes.push(format!("ApiKey ({})", api_keys.header));
+        }
+        if self.users.is_some() {
+            schemes.push(Scheme::Basic.to_string());
+        }
+        if let Some(jwt) = &self.jwt {
+            schemes.push(format!("Bearer ({:?})", jwt.algorithm()));
+        }
+        schemes.join(", ")
+    }
+
+    /// Authenticate a request against the requirement of its endpoint
+    ///
+    /// # Returns
+    /// * `Ok(Some(principal))` when the client authenticated
+    /// * `Ok(None)` for a public endpoint called without credentials
+    /// * `Err(rejection)` with the 401 or 403 response otherwise
+    pub fn check(
+        &self,
+        method: &Method,
+        path: &str,
+        headers: &HeaderMap,
+    ) -> Result<Option<Principal>, Rejection> {
+        match (self.credentials(headers), self.requirement(method, path)) {
+            (Credentials::Valid(principal), Requirement::Claims(claims))
+                if !has_claims(&principal.claims, claims) =>
+            {
+                Err(Rejection::forbidden(principal.scheme))
+            }
+            (Credentials::Valid(principal), _) => Ok(Some(principal)),
+            (_, Requirement::Public) => Ok(None),
+            (Credentials::Missing, _) => Err(self.unauthorized(None)),
+            (Credentials::Invalid(scheme), _) => Err(self.unauthorized(Some(scheme))),
+        }
+    }
+
+    /// Authenticate the client of a connection whose calls are authorized one by one
+    /// with [`Auth::authorize`], e.g. a WebSocket
+    ///
+    /// # Returns
+    /// * `Ok(None)` for a client without credentials
+    /// * `Err(rejection)` with the 401 response for invalid credentials
+    pub fn principal(&self, headers: &HeaderMap) -> Result<Option<Principal>, Rejection> {
+        match self.credentials(headers) {
+            Credentials::Valid(principal) => Ok(Some(principal)),
+            Credentials::Missing => Ok(None),
+            Credentials::Invalid(scheme) => Err(self.unauthorized(Some(scheme))),
+        }
+    }
+
+    /// Check a client may call an endpoint, as [`Auth::check`] does for a request
+    pub fn authorize(
+        &self,
+        method: &Method,
+        path: &str,
+        principal: Option<&Principal>,
+    ) -> Result<(), MocksError> {
+        match (principal, self.requirement(method, path)) {
+            (_, Requirement::Public) => Ok(()),
+            (None, _) => Err(MocksError::Unauthorized),
+            (Some(principal), Requirement::Claims(claims))
+                if !has_claims(&principal.claims, claims) =>
+            {
+                Err(MocksError::Forbidden)
+            }
+            (Some(_), _) => Ok(()),
+        }
+    }
+
+    /// Requirement of an endpoint, declared by its custom route or resource
+    fn requirement(&self, method: &Method, path: &str) -> &Requirement {
+        let route = self.routes.iter().find(|(route_method, route_path, _)| {
+            (route_method == method || (route_method == Method::GET && method == Method::HEAD))
+                && matches_route(route_path, path)
+        });
+        match route {
+            Some((_, _, requirement)) => requirement,
+            None => self
+                .requirements
+                .find(method, path)
+                .unwrap_or(&Requirement::Authenticated),
+        }
+    }
+
+    fn credentials(&self, headers: &HeaderMap) -> Credentials {
+        if let Some((scheme, value)) = headers
+            .get(AUTHORIZATION)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| v.trim().split_once(' '))
+        {
+            let value = value.trim();
+            if scheme.eq_ignore_ascii_case("bearer") {
+                return self.bearer(value);
+            }
+            if scheme.eq_ignore_ascii_case("basic") {
+                return self.basic(value);
+            }
+        }
+
+        if let Some(api_keys) = &self.api_keys {
+            if let Some(key) = headers.get(&api_keys.header) {
+                return match key.to_str().ok().and_then(|k| api_keys.keys.get(k)) {
+                    Some(claims) => Credentials::Valid(Principal {
+                        scheme: Scheme::ApiKey,
+                        claims: claims.clone(),
+                    }),
+                    None => Credentials::Invalid(Scheme::ApiKey),
+                };
+            }
+        }
+        Credentials::Missing
+    }
+
+    fn bearer(&self, token: &str) -> Credentials {
+        match self.jwt.as_ref().map(|jwt| jwt.verify(token)) {
+            Some(Ok(claims)) => Credentials::Valid(Principal {
+                scheme: Scheme::Bearer,
+                claims,
+            }),
+            _ => Credentials::Invalid(Scheme::Bearer),
+        }
+    }
+
+    fn basic(&self, encoded: &str) -> Credentials {
+        let decoded = STANDARD
+            .decode(encoded)
+            .ok()
+            .and_then(|bytes| String::from_utf8(bytes).ok());
+        let user = decoded.as_deref().and_then(|d| d.split_once(':'));
+        match (user, &self.users) {
+            (Some((username, password)), Some(users)) => match users.get(username) {
+                Some((expected, claims)) if expected == password => Credentials::Valid(Principal {
+                    scheme: Scheme::Basic,
+                    claims: claims.clone(),
+                }),
+                _ => Credentials::Invalid(Scheme::Basic),
+            },
+            _ => Credentials::Invalid(Scheme::Basic),
+        }
+    }
+
+    /// 401 challenging the client with every configured scheme
+    fn unauthorized(&self, invalid: Option<Scheme>) -> Rejection {
+        let mut challenges = vec![];
+        if self.jwt.is_some() {
+            challenges.push(match invalid {
+                Some(Scheme::Bearer) => {
+                    format!("Bearer realm=\"{REALM}\", error=\"invalid_token\"")
+                }
+                _ => format!("Bearer realm=\"{REALM}\""),
+            });
+        }
+        if self.users.is_some() {
+            challenges.push(format!("Basic realm=\"{REALM}\""));
+        }
+        if let Some(api_keys) = &self.api_keys {
+            challenges.push(format!(
+                "ApiKey realm=\"{REALM}\", header=\"{}\"",
+                api_keys.header
+            ));
+        }
+        Rejection {
+            error: MocksError::Unauthorized,
+            challenges,
+        }
+    }
+}
+
+/// 401 or 403 response of a request failing authentication
+#[derive(Debug, PartialEq)]
+pub struct Rejection {
+    pub error: MocksError,
+    /// Values of the `WWW-Authenticate` headers
+    pub challenges: Vec<String>,
+}
+
+impl Rejection {
+    fn forbidden(scheme: Scheme) -> Rejection {
+        let challenge = match scheme {
+            Scheme::Bearer => format!("Bearer realm=\"{REALM}\", error=\"insufficient_scope\""),
+            other => format!("{other} realm=\"{REALM}\""),
+        };
+        Rejection {
+            error: MocksError::Forbidden,
+            challenges: vec![challenge],
+        }
+    }
+}
+
+impl IntoResponse for Rejection {
+    fn into_response(self) -> Response {
+        let mut response = self.error.into_response();
+        for challenge in self.challenges {
+            if let Ok(value) = HeaderValue::from_str(&challenge) {
+                response.headers_mut().append(WWW_AUTHENTICATE, value);
+            }
+        }
+        response
+    }
+}
+
+/// Whether claims satisfy required ones
+///
+/// A required value matches an equal claim, an element of an array claim, or a word of a
+/// space-delimited claim such as `scope`. A required array matches any of its values.
+fn has_claims(claims: &Value, required: &Map<String, Value>) -> bool {
+    required
+        .iter()
+        .all(|(name, expected)| claims.get(name).is_some_and(|c| matches(c, expected)))
+}
+
+fn matches(claim: &Value, expected: &Value) -> bool {
+    if let Value::Array(any) = expected {
+        return any.iter().any(|e| matches(claim, e));
+    }
+    match (claim, expected) {
+        _ if claim == expected => true,
+        (Value::Array(values), _) => values.contains(expected),
+        (Value::String(words), Value::String(word)) => words.split_whitespace().any(|w| w == word),
+        _ => false,
+    }
+}
+
+fn parse_api_keys(config: &Value) -> Result<ApiKeys, MocksError> {
+    let header = match config.get("header") {
+        Some(Value::String(header)) => HeaderName::from_bytes(header.as_bytes())
+            .map_err(|_| invalid(&format!("invalid apiKey header {header}")))?,
+        Some(other) => return Err(invalid(&format!("apiKey header {other} is not a string"))),
+        None => HeaderName::from_static(DEFAULT_API_KEY_HEADER),
+    };
+
+    let mut keys = HashMap::new();
+    let declared = config
+        .get("keys")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("apiKey keys must be an array"))?;
+    for key in declared {
+        match key {
+            Value::String(key) => {
+                keys.insert(key.clone(), json!({}));
+            }
+            Value::Object(obj) => {
+                let name = obj
+                    .get("key")
+                    .and_then(Value::as_str)
+                    .ok_or_else(|| invalid(&format!("{key} has no key")))?;
+                keys.insert(name.to_string(), parse_claims(obj.get("claims"))?);
+            }
+            other => return Err(invalid(&format!("{other} is not an API key"))),
+        }
+    }
+    Ok(ApiKeys { header, keys })
+}
+
+fn parse_users(config: &Value) -> Result<HashMap<String, (String, Value)>, MocksError> {
+    let declared = config
+        .get("users")
+        .and_then(Value::as_array)
+        .ok_or_else(|| invalid("basic users must be an array"))?;
+
+    let mut users = HashMap::new();
+    for user in declared {
+        let field = |name: &str| {
+            user.get(name)
+                .and_then(Value::as_str)
+                .ok_or_else(|| invalid(&format!("{user} has no {name}")))
+        };
+        let username = field("username")?;
+        let mut claims = parse_claims(user.get("claims"))?;
+        if let Some(claims) = claims.as_object_mut() {
+            claims
+                .entry("sub")
+                .or_insert_with(|| username.to_string().into());
+        }
+        users.insert(
+            username.to_string(),
+            (field("password")?.to_string(), claims),
+        );
+    }
+    Ok(users)
+}
+
+fn parse_claims(claims: Option<&Value>) -> Result<Value, MocksError> {
+    match claims {
+        None => Ok(json!({})),
+        Some(Value::Object(claims)) => Ok(Value::Object(claims.clone())),
+        Some(other) => Err(invalid(&format!("claims {other} must be an object"))),
+    }
+}
+
+/// Requirements of the custom routes declaring `auth`
+fn parse_routes(storage: &Storage) -> Result<Vec<(Method, String, Requirement)>, MocksError> {
+    let Some(routes) = storage.config.get("routes").and_then(Value::as_array) else {
+        return Ok(vec![]);
+    };
+    let mut parsed = vec![];
+    for route in routes {
+        let (Some(auth), Some(path)) = (
+            route.get(AUTH_KEY),
+            route.get("path").and_then(Value::as_str),
+        ) else {
+            continue;
+        };
+        let method = route.get("method").and_then(Value::as_str).unwrap_or("GET");
+        // Invalid methods are reported when the routes are loaded
+        let Ok(method) = Method::from_bytes(method.to_uppercase().as_bytes()) else {
+            continue;
+        };
+        let requirement = Requirement::parse(auth)
+            .map_err(|e| invalid(&format!("route {method} {path}: {e}")))?;
+        parsed.push((method, path.to_string(), requirement));
+    }
+    Ok(parsed)
+}
+
+/// Whether a request path matches a route path with `{param}` and `{*rest}` segments
+fn matches_route(route: &str, path: &str) -> bool {
+    let mut segments = path.trim_matches('/').split('/');
+    for expected in route.trim_matches('/').split('/') {
+        if expected.starts_with("{*") {
+            return true;
+        }
+        match segments.next() {
+            Some(segment) if expected.starts_with('{') => {
+                if segment.is_empty() {
+                    return false;
+                }
+            }
+            Some(segment) if segment == expected => {}
+            _ => return false,
+        }
+    }
+    segments.next().is_none()
+}
+
+fn invalid(message: &str) -> MocksError {
+    MocksError::InvalidArgs(format!("Invalid auth: {message}"))
+}
+
+/// Whether a path stays open to anonymous clients
+///
+/// Clients need these endpoints to get credentials, and the WebSocket, OIDC token and
+/// userinfo endpoints check the credentials they are given themselves. Other admin
+/// endpoints (webhooks, scenarios, events) require authentication.
+fn is_public(path: &str) -> bool {
+    path == "/_hc"
+        || path.starts_with("/_hc/")
+        || PUBLIC_PATHS.contains(&path.trim_end_matches('/'))
+}
+
+/// Middleware rejecting requests failing authentication with 401 or 403
+///
+/// The principal of an authenticated request is added to its extensions.
+pub async fn authenticate(State(auth): State<Arc<Auth>>, mut req: Request, next: Next) -> Response {
+    if is_public(req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    match auth.check(req.method(), req.uri().path(), req.headers()) {
+        Ok(principal) => {
+            if let Some(principal) = principal {
+                req.extensions_mut().insert(principal);
+            }
+            next.run(req).await
+        }
+        Err(rejection) => rejection.into_response(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::get;
+    use axum::{Extension, Router};
+    use tower::ServiceExt;
+
+    fn load(config: Value) -> Result<Option<Auth>, MocksError> {
+        let data = json!({"posts": [], "users": [], "_mocks": config});
+        let storage = Storage::from_value(&data);
+        Auth::load(&storage)
+    }
+
+    fn auth() -> Auth {
+        load(json!({
+            "auth": {
+                "apiKey": {"keys": ["k1", {"key": "k2", "claims": {"role": "admin"}}]},
+                "basic": {"users": [{"username": "alice", "password": "secret"}]},
+                "jwt": {"secret": "s3cret"}
+            },
+            "resources": {
+                "posts": {"auth": {"GET": false, "*": {"claims": {"scope": "write"}}}}
+            }
+        }))
+        .unwrap()
+        .unwrap()
+    }
+
+    fn headers(name: &str, value: &str) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        headers.insert(
+            HeaderName::from_bytes(name.as_bytes()).unwrap(),
+            HeaderValue::from_str(value).unwrap(),
+        );
+        headers
+    }
+
+    fn bearer(auth: &Auth, claims: Value) -> HeaderMap {
+        let claims = claims.as_object().unwrap().clone();
+        let (token, _) = auth.jwt().unwrap().mint(claims).unwrap();
+        headers("authorization", &format!("Bearer {token}"))
+    }
+
+    #[test]
+    fn test_credentials() {
+        let auth = auth();
+
+        assert_eq!(auth.credentials(&HeaderMap::new()), Credentials::Missing);
+        assert_eq!(
+            auth.credentials(&headers("x-api-key", "k2")),
+            Credentials::Valid(Principal {
+                scheme: Scheme::ApiKey,
+                claims: json!({"role": "admin"})
+            })
+        );
+        assert_eq!(
+            auth.credentials(&headers("x-api-key", "k3")),
+            Credentials::Invalid(Scheme::ApiKey)
+        );
+
+        // alice:secret
+        assert_eq!(
+            auth.credentials(&headers("authorization", "Basic YWxpY2U6c2VjcmV0")),
+            Credentials::Valid(Principal {
+                scheme: Scheme::Basic,
+                claims: json!({"sub": "alice"})
+            })
+        );
+        // alice:wrong
+        assert_eq!(
+            auth.credentials(&headers("authorization", "basic YWxpY2U6d3Jvbmc=")),
+            Credentials::Invalid(Scheme::Basic)
+        );
+
+        let Credentials::Valid(principal) = auth.credentials(&bearer(&auth, json!({"sub": "1"})))
+        else {
+            panic!("token should be valid");
+        };
+        assert_eq!(principal.scheme, Scheme::Bearer);
+        assert_eq!(principal.claims["sub"], "1");
+        assert_eq!(
+            auth.credentials(&headers("authorization", "Bearer nope")),
+            Credentials::Invalid(Scheme::Bearer)
+        );
+    }
+
+    #[test]
+    fn test_check() {
+        let auth = auth();
+        let check = |method: Method, path: &str, headers: &HeaderMap| {
+            auth.check(&method, path, headers).map_err(|r| r.error)
+        };
+        let none = HeaderMap::new();
+        let writer = bearer(&auth, json!({"scope": "read write"}));
+        let reader = bearer(&auth, json!({"scope": "read"}));
+
+        assert_eq!(
+            check(Method::GET, "/users", &none),
+            Err(MocksError::Unauthorized)
+        );
+        assert!(check(Method::GET, "/users", &headers("x-api-key", "k1"))
+            .unwrap()
+            .is_some());
+
+        // GET /posts is public, but still authenticates clients sending credentials
+        assert_eq!(check(Method::GET, "/posts/1", &none), Ok(None));
+        assert!(check(Method::GET, "/posts", &reader).unwrap().is_some());
+
+        assert_eq!(
+            check(Method::POST, "/posts", &none),
+            Err(MocksError::Unauthorized)
+        );
+        assert_eq!(
+            check(Method::POST, "/posts", &reader),
+            Err(MocksError::Forbidden)
+        );
+        assert!(check(Method::POST, "/posts", &writer).is_ok());
+    }
+
+    #[test]
+    fn test_authorize() {
+        let auth = auth();
+        let principal = |claims: Value| Principal {
+            scheme: Scheme::Bearer,
+            claims,
+        };
+        let reader = principal(json!({"scope": "read"}));
+        let writer = principal(json!({"scope": "read write"}));
+
+        assert_eq!(auth.authorize(&Method::GET, "/posts", None), Ok(()));
+        assert_eq!(
+            auth.authorize(&Method::GET, "/users", None),
+            Err(MocksError::Unauthorized)
+        );
+        assert_eq!(
+            auth.authorize(&Method::GET, "/users", Some(&reader)),
+            Ok(())
+        );
+        assert_eq!(
+            auth.authorize(&Method::DELETE, "/posts/1", Some(&reader)),
+            Err(MocksError::Forbidden)
+        );
+        assert_eq!(
+            auth.authorize(&Method::DELETE, "/posts/1", Some(&writer)),
+            Ok(())
+        );
+
+        assert_eq!(
+            auth.principal(&HeaderMap::new()).map_err(|r| r.error),
+            Ok(None)
+        );
+        assert!(auth
+            .principal(&headers("x-api-key", "k1"))
+            .unwrap()
+            .is_some());
+        assert_eq!(
+            auth.principal(&headers("x-api-key", "k3"))
+                .map_err(|r| r.error),
+            Err(MocksError::Unauthorized)
+        );
+    }
+
+    #[test]
+    fn test_check_routes() {
+        let auth = load(json!({
+            "auth": {"apiKey": {"keys": ["k1"]}},
+            "routes": [
+                {"method": "POST", "path": "/login", "auth": false},
+                {"path": "/reports/{id}", "auth": {"claims": {"role": "admin"}}},
+                {"path": "/health"}
+            ]
+        }))
+        .unwrap()
+        .unwrap();
+        let check = |method: Method, path: &str| {
+            auth.check(&method, path, &HeaderMap::new())
+                .map_err(|r| r.error)
+        };
+
+        assert_eq!(check(Method::POST, "/login"), Ok(None));
+        assert_eq!(check(Method::GET, "/login"), Err(MocksError::Unauthorized));
+        assert_eq!(check(Method::GET, "/health"), Err(MocksError::Unauthorized));
+        assert_eq!(
+            auth.check(&Method::GET, "/reports/1", &headers("x-api-key", "k1"))
+                .map_err(|r| r.error),
+            Err(MocksError::Forbidden)
+        );
+        assert_eq!(
+            check(Method::GET, "/reports/1/pdf"),
+            Err(MocksError::Unauthorized)
+        );
+    }
+
+    #[test]
+    fn test_matches_route() {
+        assert!(matches_route("/login", "/login"));
+        assert!(matches_route("/users/{id}", "/users/1"));
+        assert!(matches_route("/files/{*path}", "/files/a/b"));
+        assert!(!matches_route("/users/{id}", "/users"));
+        assert!(!matches_route("/users/{id}", "/users/1/posts"));
+        assert!(!matches_route("/login", "/logout"));
+    }
+
+    #[test]
+    fn test_challenges() {
+        let auth = auth();
+        let rejection = auth
+            .check(
+                &Method::GET,
+                "/users",
+                &headers("authorization", "Bearer x"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            rejection.challenges,
+            vec![
+                "Bearer realm=\"mocks\", error=\"invalid_token\"",
+                "Basic realm=\"mocks\"",
+                "ApiKey realm=\"mocks\", header=\"x-api-key\"",
+            ]
+        );
+
+        let reader = bearer(&auth, json!({"scope": "read"}));
+        let rejection = auth.check(&Method::PUT, "/posts/1", &reader).unwrap_err();
+        assert_eq!(
+            rejection.challenges,
+            vec!["Bearer realm=\"mocks\", error=\"insufficient_scope\""]
+        );
+    }
+
+    #[test]
+    fn test_has_claims() {
+        let claims = json!({"role": "admin", "groups": ["a", "b"], "scope": "read write"});
+        let required = |value: Value| value.as_object().unwrap().clone();
+
+        assert!(has_claims(&claims, &required(json!({"role": "admin"}))));
+        assert!(has_claims(&claims, &required(json!({"groups": "b"}))));
+        assert!(has_claims(&claims, &required(json!({"scope": "write"}))));
+        assert!(has_claims(
+            &claims,
+            &required(json!({"role": ["owner", "admin"]}))
+        ));
+        assert!(!has_claims(&claims, &required(json!({"role": "owner"}))));
+        assert!(!has_claims(&claims, &required(json!({"tenant": "x"}))));
+        assert!(!has_claims(&claims, &required(json!({"scope": "rea"}))));
+    }
+
+    #[test]
+    fn test_load() {
+        assert!(load(json!({})).unwrap().is_none());
+        assert_eq!(
+            auth().describe(),
+            "ApiKey (x-api-key), Basic, Bearer (HS256)"
+        );
+
+        for config in [
+            json!({"auth": {}}),
+            json!({"auth": {"apiKey": {"keys": "k1"}}}),
+            json!({"auth": {"apiKey": {"header": "a b", "keys": []}}}),
+            json!({"auth": {"apiKey": {"keys": [{"claims": {}}]}}}),
+            json!({"auth": {"basic": {"users": [{"username": "a"}]}}}),
+            json!({"auth": {"basic": {"users": [{"username": "a", "password": "b", "claims": 1}]}}}),
+            json!({"auth": {"jwt": {}}}),
+            json!({"auth": {"apiKey": {"keys": []}, "oidc": {"clients": []}}}),
+            json!({"auth": {"jwt": {"secret": "s"}, "oidc": {"clients": {}}}}),
+            json!({"auth": {"apiKey": {"keys": []}}, "resources": {"posts": {"auth": "yes"}}}),
+            json!({"auth": {"apiKey": {"keys": []}}, "routes": [{"path": "/login", "auth": "no"}]}),
+        ] {
+            assert!(load(config.clone()).is_err(), "{config} should be invalid");
+        }
+    }
+
+    #[tokio::test]
+    async fn test_authenticate() {
+        let auth = Arc::new(auth());
+        let app = Router::new()
+            .route(
+                "/users",
+                get(|Extension(principal): Extension<Principal>| async move {
+                    principal.claims["sub"]
+                        .as_str()
+                        .unwrap_or_default()
+                        .to_string()
+                }),
+            )
+            .route("/_hc", get(|| async { "ok" }))
+            .layer(from_fn_with_state(auth, authenticate));
+        let send = |path: &str, authorization: Option<&str>| {
+            let mut req = Request::builder().uri(path);
+            if let Some(authorization) = authorization {
+                req = req.header("authorization", authorization);
+            }
+            app.clone().oneshot(req.body(Body::empty()).unwrap())
+        };
+
+        let res = send("/users", None).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(res.headers().get_all(WWW_AUTHENTICATE).iter().count(), 3);
+
+        let res = send("/users", Some("Basic YWxpY2U6c2VjcmV0"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        assert_eq!(&body[..], b"alice");
+
+        assert_eq!(send("/_hc", None).await.unwrap().status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_authenticate_admin_endpoints() {
+        let app = Router::new()
+            .route(
+                "/_mocks/webhooks",
+                axum::routing::post(|| async { "created" }),
+            )
+            .route("/_mocks/token", axum::routing::post(|| async { "token" }))
+            .layer(from_fn_with_state(Arc::new(auth()), authenticate));
+        let send = |path: &str, authorization: Option<&str>| {
+            let mut req = Request::builder().method("POST").uri(path);
+            if let Some(authorization) = authorization {
+                req = req.header("authorization", authorization);
+            }
+            app.clone().oneshot(req.body(Body::empty()).unwrap())
+        };
+
+        let res = send("/_mocks/webhooks", None).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        let res = send("/_mocks/webhooks", Some("Basic YWxpY2U6c2VjcmV0"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        let res = send("/_mocks/token", None).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+}
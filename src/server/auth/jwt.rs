@@ -0,0 +1,312 @@
+use crate::error::MocksError;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{decode, decode_header, encode, Algorithm, DecodingKey, EncodingKey, Header};
+use jsonwebtoken::{get_current_timestamp, Validation};
+use serde_json::{json, Map, Value};
+use std::collections::HashSet;
+use std::fmt;
+use std::fs;
+use std::path::Path;
+
+/// Lifetime of minted tokens, in seconds, unless configured with `expiresIn`
+const DEFAULT_EXPIRES_IN: u64 = 3600;
+
+/// Verifier and issuer of Bearer JSON Web Tokens
+///
+/// Tokens are signed with HS256 given a `secret`, or with RS256 given a local `jwks` file
+/// of public keys (and a PEM `privateKey` to mint tokens with):
+///
+/// ```json
+/// {"jwt": {"jwks": "jwks.json", "privateKey": "private.pem", "issuer": "https://mocks.local"}}
+/// ```
+pub struct Jwt {
+    algorithm: Algorithm,
+    /// Verification keys with their key ID
+    keys: Vec<(Option<String>, DecodingKey)>,
+    /// Public keys of RS256, as published
+    jwks: Option<JwkSet>,
+    /// Key signing minted tokens with its key ID
+    signing: Option<(EncodingKey, Option<String>)>,
+    issuer: Option<String>,
+    audience: Option<String>,
+    expires_in: u64,
+}
+
+impl fmt::Debug for Jwt {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Jwt")
+            .field("algorithm", &self.algorithm)
+            .field("keys", &self.keys.len())
+            .field("signing", &self.signing.is_some())
+            .field("issuer", &self.issuer)
+            .field("audience", &self.audience)
+            .field("expires_in", &self.expires_in)
+            .finish()
+    }
+}
+
+impl Jwt {
+    /// Parse the `jwt` config of `_mocks.auth`
+    ///
+    /// # Arguments
+    /// * `config` - The `jwt` object
+    /// * `base` - Directory the `jwks` and `privateKey` files are relative to
+    pub fn parse(config: &Value, base: &Path) -> Result<Jwt, MocksError> {
+        let string = |key: &str| -> Result<Option<String>, MocksError> {
+            match config.get(key) {
+                None => Ok(None),
+                Some(Value::String(s)) => Ok(Some(s.clone())),
+                Some(other) => Err(invalid(&format!("jwt {key} must be a string, got {other}"))),
+            }
+        };
+        let read = |file: &str| {
+            fs::read(base.join(file))
+                .map_err(|e| MocksError::FailedReadFile(format!("{file}: {e}")))
+        };
+
+        let (algorithm, keys, jwks, signing) = match (string("secret")?, string("jwks")?) {
+            (Some(secret), None) => (
+                Algorithm::HS256,
+                vec![(None, DecodingKey::from_secret(secret.as_bytes()))],
+                None,
+                Some((EncodingKey::from_secret(secret.as_bytes()), None)),
+            ),
+            (None, Some(jwks)) => {
+                let set: JwkSet = serde_json::from_slice(&read(&jwks)?)
+                    .map_err(|e| invalid(&format!("{jwks} is not a JWKS: {e}")))?;
+                let mut keys = vec![];
+                for jwk in &set.keys {
+                    let key = DecodingKey::from_jwk(jwk)
+                        .map_err(|e| invalid(&format!("{jwks} has an invalid key: {e}")))?;
+                    keys.push((jwk.common.key_id.clone(), key));
+                }
+                if keys.is_empty() {
+                    return Err(invalid(&format!("{jwks} has no keys")));
+                }
+
+                let signing = match string("privateKey")? {
+                    Some(file) => {
+                        let key = EncodingKey::from_rsa_pem(&read(&file)?)
+                            .map_err(|e| invalid(&format!("{file} is not an RSA key: {e}")))?;
+                        // Tokens are signed with the only key of the set unless one is named
+                        let kid = match string("keyId")? {
+                            Some(kid) => Some(kid),
+                            None if keys.len() == 1 => keys[0].0.clone(),
+                            None => None,
+                        };
+                        Some((key, kid))
+                    }
+                    None => None,
+                };
+                (Algorithm::RS256, keys, Some(set), signing)
+            }
+            _ => return Err(invalid("jwt needs either a secret or a jwks file")),
+        };
+
+        let expires_in = match config.get("expiresIn") {
+            None => DEFAULT_EXPIRES_IN,
+            Some(value) => value
+                .as_u64()
+                .ok_or_else(|| invalid("jwt expiresIn must be a number of seconds"))?,
+        };
+
+        Ok(Jwt {
+            algorithm,
+            keys,
+            jwks,
+            signing,
+            issuer: string("issuer")?,
+            audience: string("audience")?,
+            expires_in,
+        })
+    }
+
+    pub fn algorithm(&self) -> Algorithm {
+        self.algorithm
+    }
+
+    pub fn issuer(&self) -> Option<&str> {
+        self.issuer.as_deref()
+    }
+
+    /// Public keys verifying the tokens, as a JWKS document (empty for HS256)
+    pub fn jwks(&self) -> Value {
+        match &self.jwks {
+            Some(set) => serde_json::to_value(set).unwrap_or_else(|_| json!({"keys": []})),
+            None => json!({"keys": []}),
+        }
+    }
+
+    /// Whether tokens can be minted, i.e. a signing key is configured
+    pub fn can_sign(&self) -> bool {
+        self.signing.is_some()
+    }
+
+    /// Verify a token, returning its claims
+    pub fn verify(&self, token: &str) -> Result<Value, String> {
+        let header = decode_header(token).map_err(|e| e.to_string())?;
+        if header.alg != self.algorithm {
+            return Err(format!("unexpected algorithm {:?}", header.alg));
+        }
+        let key = match &header.kid {
+            Some(kid) => self
+                .keys
+                .iter()
+                .find(|(id, _)| id.as_deref() == Some(kid.as_str()))
+                .ok_or_else(|| format!("unknown key {kid}"))?,
+            None => self.keys.first().ok_or("no key")?,
+        };
+
+        let mut validation = Validation::new(self.algorithm);
+        validation.required_spec_claims = HashSet::new();
+        if let Some(issuer) = &self.issuer {
+            validation.set_issuer(&[issuer]);
+        }
+        match &self.audience {
+            Some(audience) => validation.set_audience(&[audience]),
+            None => validation.validate_aud = false,
+        }
+
+        decode::<Value>(token, &key.1, &validation)
+            .map(|data| data.claims)
+            .map_err(|e| e.to_string())
+    }
+
+    /// Sign a token carrying the requested claims
+    ///
+    /// `iat`, `exp`, and the configured `iss` and `aud` are added unless requested.
+    ///
+    /// # Returns
+    /// * The token and its lifetime in seconds
+    pub fn mint(&self, claims: Map<String, Value>) -> Result<(String, u64), MocksError> {
+        let (key, kid) = self
+            .signing
+            .as_ref()
+            .ok_or_else(|| MocksError::InvalidArgs("No signing key is configured.".to_string()))?;
+
+        let now = get_current_timestamp();
+        let mut minted = Map::new();
+        minted.insert("iat".to_string(), now.into());
+        minted.insert("exp".to_string(), (now + self.expires_in).into());
+        if let Some(issuer) = &self.issuer {
+            minted.insert("iss".to_string(), issuer.clone().into());
+        }
+        if let Some(audience) = &self.audience {
+            minted.insert("aud".to_string(), audience.clone().into());
+        }
+        minted.extend(claims);
+
+        let expires_in = minted
+            .get("exp")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| MocksError::InvalidArgs("exp must be a timestamp.".to_string()))?
+            .saturating_sub(now);
+
+        let mut header = Header::new(self.algorithm);
+        header.kid = kid.clone();
+        let token = encode(&header, &Value::Object(minted), key)
+            .map_err(|e| MocksError::Exception(e.to_string()))?;
+        Ok((token, expires_in))
+    }
+}
+
+fn invalid(message: &str) -> MocksError {
+    MocksError::InvalidArgs(format!("Invalid auth: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn testdata() -> &'static Path {
+        Path::new(concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/src/server/auth/testdata"
+        ))
+    }
+
+    fn claims(value: Value) -> Map<String, Value> {
+        value.as_object().unwrap().clone()
+    }
+
+    #[test]
+    fn test_hs256() {
+        let jwt = Jwt::parse(&json!({"secret": "s3cret", "expiresIn": 60}), testdata()).unwrap();
+        assert_eq!(jwt.algorithm(), Algorithm::HS256);
+        assert_eq!(jwt.jwks(), json!({"keys": []}));
+
+        let (token, expires_in) = jwt.mint(claims(json!({"sub": "alice"}))).unwrap();
+        assert_eq!(expires_in, 60);
+        let verified = jwt.verify(&token).unwrap();
+        assert_eq!(verified["sub"], "alice");
+        assert!(verified["exp"].as_u64().unwrap() > verified["iat"].as_u64().unwrap());
+
+        let other = Jwt::parse(&json!({"secret": "other"}), testdata()).unwrap();
+        assert!(other.verify(&token).is_err());
+        assert!(jwt.verify("not.a.token").is_err());
+    }
+
+    #[test]
+    fn test_rs256() {
+        let config = json!({"jwks": "jwks.json", "privateKey": "private.pem"});
+        let jwt = Jwt::parse(&config, testdata()).unwrap();
+        assert_eq!(jwt.algorithm(), Algorithm::RS256);
+        assert!(jwt.can_sign());
+
+        let (token, _) = jwt.mint(claims(json!({"sub": "bob"}))).unwrap();
+        assert_eq!(decode_header(&token).unwrap().kid.as_deref(), Some("test"));
+        assert_eq!(jwt.verify(&token).unwrap()["sub"], "bob");
+
+        // A verifier without the private key cannot mint, but still verifies
+        let verifier = Jwt::parse(&json!({"jwks": "jwks.json"}), testdata()).unwrap();
+        assert!(!verifier.can_sign());
+        assert_eq!(verifier.jwks()["keys"][0]["kid"], "test");
+        assert!(verifier.jwks()["keys"][0].get("d").is_none());
+        assert!(verifier.mint(Map::new()).is_err());
+        assert!(verifier.verify(&token).is_ok());
+
+        // HS256 tokens are rejected by an RS256 verifier
+        let hs256 = Jwt::parse(&json!({"secret": "s"}), testdata()).unwrap();
+        let (token, _) = hs256.mint(Map::new()).unwrap();
+        assert!(verifier.verify(&token).is_err());
+    }
+
+    #[test]
+    fn test_issuer_and_audience() {
+        let config = json!({"secret": "s", "issuer": "mocks", "audience": "app"});
+        let jwt = Jwt::parse(&config, testdata()).unwrap();
+        let (token, _) = jwt.mint(Map::new()).unwrap();
+        let verified = jwt.verify(&token).unwrap();
+        assert_eq!(verified["iss"], "mocks");
+        assert_eq!(verified["aud"], "app");
+
+        let (token, _) = jwt.mint(claims(json!({"aud": "other"}))).unwrap();
+        assert!(jwt.verify(&token).is_err());
+    }
+
+    #[test]
+    fn test_expired() {
+        let jwt = Jwt::parse(&json!({"secret": "s"}), testdata()).unwrap();
+        let (token, expires_in) = jwt.mint(claims(json!({"exp": 1}))).unwrap();
+        assert_eq!(expires_in, 0);
+        assert!(jwt.verify(&token).is_err());
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for config in [
+            json!({}),
+            json!({"secret": "s", "jwks": "jwks.json"}),
+            json!({"secret": 1}),
+            json!({"secret": "s", "expiresIn": "1h"}),
+            json!({"jwks": "missing.json"}),
+            json!({"jwks": "private.pem"}),
+            json!({"jwks": "jwks.json", "privateKey": "jwks.json"}),
+        ] {
+            assert!(
+                Jwt::parse(&config, testdata()).is_err(),
+                "{config} should be invalid"
+            );
+        }
+    }
+}
@@ -0,0 +1,518 @@
+use crate::error::MocksError;
+use crate::storage::Storage;
+use axum::http::StatusCode;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use ring::digest::{digest, SHA256};
+use ring::rand::{SecureRandom, SystemRandom};
+use serde_json::{json, Map, Value};
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const DEFAULT_USERS: &str = "users";
+const DEFAULT_USERNAME_FIELD: &str = "username";
+const PASSWORD_FIELD: &str = "password";
+
+/// Lifetime of authorization codes
+const CODE_TTL: Duration = Duration::from_secs(60);
+
+/// Error of the OAuth 2.0 endpoints, in the shape clients expect (RFC 6749 §5.2)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OAuthError {
+    pub status: StatusCode,
+    pub error: &'static str,
+    pub description: String,
+}
+
+impl OAuthError {
+    pub fn new(error: &'static str, description: &str) -> OAuthError {
+        let status = match error {
+            "invalid_client" | "invalid_token" => StatusCode::UNAUTHORIZED,
+            _ => StatusCode::BAD_REQUEST,
+        };
+        OAuthError {
+            status,
+            error,
+            description: description.to_string(),
+        }
+    }
+}
+
+impl IntoResponse for OAuthError {
+    fn into_response(self) -> Response {
+        let body = json!({"error": self.error, "error_description": self.description});
+        (self.status, Json(body)).into_response()
+    }
+}
+
+/// Application allowed to request tokens
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Client {
+    pub id: String,
+    /// Secret of a confidential client; public clients must use PKCE instead
+    secret: Option<String>,
+    redirect_uris: Vec<String>,
+}
+
+impl Client {
+    /// Redirect URI of an authorization request, defaulting to the only registered one
+    pub fn redirect_uri(&self, requested: Option<&str>) -> Option<String> {
+        match requested {
+            Some(uri) => self.redirect_uris.iter().find(|r| *r == uri).cloned(),
+            None if self.redirect_uris.len() == 1 => Some(self.redirect_uris[0].clone()),
+            None => None,
+        }
+    }
+
+    pub fn is_public(&self) -> bool {
+        self.secret.is_none()
+    }
+}
+
+/// Signed-in user and the access granted to a client
+#[derive(Debug, Clone, PartialEq)]
+pub struct Session {
+    pub client_id: String,
+    /// Claims of the user, or `None` for a client acting on its own behalf
+    pub user: Option<Value>,
+    pub scope: String,
+}
+
+/// Authorization code waiting to be exchanged for tokens
+#[derive(Debug, Clone)]
+pub struct Code {
+    pub session: Session,
+    pub redirect_uri: String,
+    /// PKCE challenge with its method (`S256` or `plain`)
+    pub challenge: Option<(String, String)>,
+    pub nonce: Option<String>,
+    pub expires: Instant,
+}
+
+#[derive(Debug, Default)]
+struct Grants {
+    codes: HashMap<String, Code>,
+    refresh_tokens: HashMap<String, Session>,
+}
+
+/// Minimal OpenID Connect provider signing in the users of a resource
+///
+/// ```json
+/// {"_mocks": {"auth": {
+///   "jwt": {"jwks": "jwks.json", "privateKey": "private.pem"},
+///   "oidc": {"clients": [{"clientId": "spa", "redirectUris": ["http://localhost:5173/callback"]}]}
+/// }}}
+/// ```
+///
+/// Users are the records of `users` (or the configured resource), signing in with their
+/// `username` (or the configured field) and `password`. Codes and refresh tokens are kept
+/// in memory.
+#[derive(Debug)]
+pub struct Oidc {
+    clients: Vec<Client>,
+    users: String,
+    username_field: String,
+    grants: Mutex<Grants>,
+    rng: SystemRandom,
+}
+
+impl Oidc {
+    /// Parse the `oidc` config of `_mocks.auth`
+    pub fn parse(config: &Value) -> Result<Oidc, MocksError> {
+        let string = |value: Option<&Value>, name: &str| -> Result<Option<String>, MocksError> {
+            match value {
+                None => Ok(None),
+                Some(Value::String(s)) => Ok(Some(s.clone())),
+                Some(other) => Err(invalid(&format!("{name} {other} must be a string"))),
+            }
+        };
+
+        let declared = config
+            .get("clients")
+            .and_then(Value::as_array)
+            .ok_or_else(|| invalid("oidc clients must be an array"))?;
+        let mut clients = vec![];
+        for client in declared {
+            let id = string(client.get("clientId"), "clientId")?
+                .ok_or_else(|| invalid(&format!("{client} has no clientId")))?;
+            let redirect_uris = match client.get("redirectUris") {
+                None => vec![],
+                Some(Value::Array(uris)) => uris
+                    .iter()
+                    .map(|uri| string(Some(uri), "redirect URI").map(Option::unwrap_or_default))
+                    .collect::<Result<_, _>>()?,
+                Some(other) => {
+                    return Err(invalid(&format!("redirectUris {other} must be an array")))
+                }
+            };
+            clients.push(Client {
+                id,
+                secret: string(client.get("clientSecret"), "clientSecret")?,
+                redirect_uris,
+            });
+        }
+
+        Ok(Oidc {
+            clients,
+            users: string(config.get("users"), "users")?
+                .unwrap_or_else(|| DEFAULT_USERS.to_string()),
+            username_field: string(config.get("usernameField"), "usernameField")?
+                .unwrap_or_else(|| DEFAULT_USERNAME_FIELD.to_string()),
+            grants: Mutex::new(Grants::default()),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    pub fn client(&self, id: &str) -> Option<&Client> {
+        self.clients.iter().find(|c| c.id == id)
+    }
+
+    /// Authenticate a client at the token endpoint
+    pub fn authenticate_client(
+        &self,
+        id: &str,
+        secret: Option<&str>,
+    ) -> Result<&Client, OAuthError> {
+        match self.client(id) {
+            Some(client) if client.secret.as_deref() == secret || client.is_public() => Ok(client),
+            _ => Err(OAuthError::new(
+                "invalid_client",
+                "Unknown client or invalid secret.",
+            )),
+        }
+    }
+
+    /// Claims of the user signing in with a username and password
+    pub fn sign_in(&self, storage: &Storage, username: &str, password: &str) -> Option<Value> {
+        let users = storage.get_all(&self.users, None).ok()?;
+        users
+            .as_array()?
+            .iter()
+            .find(|user| {
+                user.get(&self.username_field).and_then(Value::as_str) == Some(username)
+                    && user.get(PASSWORD_FIELD).and_then(Value::as_str) == Some(password)
+            })
+            .map(|user| self.claims(user))
+    }
+
+    /// Claims of the user with a subject, as returned by the userinfo endpoint
+    pub fn user(&self, storage: &Storage, sub: &str) -> Option<Value> {
+        storage
+            .get_one(&self.users, sub, None)
+            .ok()
+            .map(|user| self.claims(&user))
+    }
+
+    /// Claims of a user record: its fields but the password, with `sub` as its ID
+    fn claims(&self, user: &Value) -> Value {
+        let mut claims = user.as_object().cloned().unwrap_or_default();
+        claims.remove(PASSWORD_FIELD);
+        if let Some(id) = claims.remove("id") {
+            let sub = match id {
+                Value::String(id) => id,
+                other => other.to_string(),
+            };
+            claims.insert("sub".to_string(), sub.into());
+        }
+        if let Some(username) = claims.get(&self.username_field).cloned() {
+            claims.entry("preferred_username").or_insert(username);
+        }
+        Value::Object(claims)
+    }
+
+    /// Store a code, returning its value
+    pub fn issue_code(&self, code: Code) -> String {
+        let value = self.random();
+        self.grants().codes.insert(value.clone(), code);
+        value
+    }
+
+    /// Exchange a code, which can only be used once
+    ///
+    /// # Arguments
+    /// * `value` - The code
+    /// * `client` - The authenticated client
+    /// * `redirect_uri` - Redirect URI of the authorization request
+    /// * `verifier` - PKCE verifier of the challenge of the authorization request
+    pub fn redeem_code(
+        &self,
+        value: &str,
+        client: &Client,
+        redirect_uri: Option<&str>,
+        verifier: Option<&str>,
+    ) -> Result<Code, OAuthError> {
+        let invalid_grant = |description: &str| OAuthError::new("invalid_grant", description);
+
+        let code = self
+            .grants()
+            .codes
+            .remove(value)
+            .filter(|code| code.expires > Instant::now())
+            .ok_or_else(|| invalid_grant("Unknown or expired code."))?;
+        if code.session.client_id != client.id {
+            return Err(invalid_grant("Code was issued to another client."));
+        }
+        if redirect_uri.is_some_and(|uri| uri != code.redirect_uri) {
+            return Err(invalid_grant("redirect_uri does not match."));
+        }
+        match (&code.challenge, verifier) {
+            (Some((challenge, method)), Some(verifier)) => {
+                if !verify_pkce(challenge, method, verifier) {
+                    return Err(invalid_grant("code_verifier does not match."));
+                }
+            }
+            (Some(_), None) => return Err(invalid_grant("code_verifier is required.")),
+            (None, _) if client.is_public() => {
+                return Err(invalid_grant("Public clients must use PKCE."))
+            }
+            (None, _) => {}
+        }
+        Ok(code)
+    }
+
+    /// Store a refresh token, returning its value
+    pub fn issue_refresh_token(&self, session: Session) -> String {
+        let value = self.random();
+        self.grants().refresh_tokens.insert(value.clone(), session);
+        value
+    }
+
+    /// Exchange a refresh token, which is rotated on use
+    pub fn redeem_refresh_token(
+        &self,
+        value: &str,
+        client: &Client,
+    ) -> Result<Session, OAuthError> {
+        let mut grants = self.grants();
+        let owned = grants
+            .refresh_tokens
+            .get(value)
+            .is_some_and(|session| session.client_id == client.id);
+        owned
+            .then(|| grants.refresh_tokens.remove(value))
+            .flatten()
+            .ok_or_else(|| OAuthError::new("invalid_grant", "Unknown refresh token."))
+    }
+
+    /// Session of a client acting on its own behalf
+    pub fn client_session(&self, client: &Client, scope: Option<&str>) -> Session {
+        Session {
+            client_id: client.id.clone(),
+            user: None,
+            scope: scope.unwrap_or_default().to_string(),
+        }
+    }
+
+    fn grants(&self) -> std::sync::MutexGuard<'_, Grants> {
+        let mut grants = match self.grants.lock() {
+            Ok(grants) => grants,
+            Err(e) => e.into_inner(),
+        };
+        let now = Instant::now();
+        grants.codes.retain(|_, code| code.expires > now);
+        grants
+    }
+
+    /// Unguessable token of 256 bits
+    fn random(&self) -> String {
+        let mut bytes = [0u8; 32];
+        // The system RNG only fails when the OS has no entropy source at all
+        self.rng.fill(&mut bytes).expect("system random source");
+        URL_SAFE_NO_PAD.encode(bytes)
+    }
+}
+
+/// Expiry of a code issued now
+pub fn code_expiry() -> Instant {
+    Instant::now() + CODE_TTL
+}
+
+/// Check a PKCE verifier against its challenge (RFC 7636)
+fn verify_pkce(challenge: &str, method: &str, verifier: &str) -> bool {
+    match method {
+        "S256" => URL_SAFE_NO_PAD.encode(digest(&SHA256, verifier.as_bytes())) == challenge,
+        "plain" => verifier == challenge,
+        _ => false,
+    }
+}
+
+/// Claims of a token granted in a session
+///
+/// # Arguments
+/// * `session` - The granted session
+/// * `extra` - Claims specific to the token, such as `aud` or `nonce`
+pub fn session_claims(session: &Session, extra: Map<String, Value>) -> Map<String, Value> {
+    let mut claims = session
+        .user
+        .as_ref()
+        .and_then(Value::as_object)
+        .cloned()
+        .unwrap_or_else(|| {
+            let mut claims = Map::new();
+            claims.insert("sub".to_string(), session.client_id.clone().into());
+            claims
+        });
+    claims.extend(extra);
+    claims
+}
+
+fn invalid(message: &str) -> MocksError {
+    MocksError::InvalidArgs(format!("Invalid auth: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn oidc() -> Oidc {
+        Oidc::parse(&json!({
+            "clients": [
+                {"clientId": "spa", "redirectUris": ["http://app/callback"]},
+                {"clientId": "api", "clientSecret": "s3cret"}
+            ],
+            "usernameField": "email"
+        }))
+        .unwrap()
+    }
+
+    fn code(oidc: &Oidc, client_id: &str, challenge: Option<(&str, &str)>) -> String {
+        oidc.issue_code(Code {
+            session: Session {
+                client_id: client_id.to_string(),
+                user: Some(json!({"sub": "1"})),
+                scope: "openid".to_string(),
+            },
+            redirect_uri: "http://app/callback".to_string(),
+            challenge: challenge.map(|(c, m)| (c.to_string(), m.to_string())),
+            nonce: None,
+            expires: code_expiry(),
+        })
+    }
+
+    #[test]
+    fn test_parse() {
+        let oidc = oidc();
+        let spa = oidc.client("spa").unwrap();
+        assert!(spa.is_public());
+        assert_eq!(
+            spa.redirect_uri(None),
+            Some("http://app/callback".to_string())
+        );
+        assert_eq!(spa.redirect_uri(Some("http://evil/callback")), None);
+        assert!(!oidc.client("api").unwrap().is_public());
+
+        for config in [
+            json!({}),
+            json!({"clients": [{}]}),
+            json!({"clients": [{"clientId": "a", "redirectUris": "http://app"}]}),
+            json!({"clients": [], "users": 1}),
+        ] {
+            assert!(Oidc::parse(&config).is_err(), "{config} should be invalid");
+        }
+    }
+
+    #[test]
+    fn test_authenticate_client() {
+        let oidc = oidc();
+        assert!(oidc.authenticate_client("spa", None).is_ok());
+        assert!(oidc.authenticate_client("api", Some("s3cret")).is_ok());
+        for (id, secret) in [("api", None), ("api", Some("wrong")), ("other", None)] {
+            let error = oidc.authenticate_client(id, secret).unwrap_err();
+            assert_eq!(error.error, "invalid_client");
+            assert_eq!(error.status, StatusCode::UNAUTHORIZED);
+        }
+    }
+
+    #[test]
+    fn test_sign_in() {
+        let data = json!({"users": [{"id": 1, "email": "a@example.com", "password": "pw", "role": "admin"}]});
+        let storage = Storage::from_value(&data);
+        let oidc = oidc();
+
+        let claims = json!({"sub": "1", "email": "a@example.com", "preferred_username": "a@example.com", "role": "admin"});
+        assert_eq!(
+            oidc.sign_in(&storage, "a@example.com", "pw"),
+            Some(claims.clone())
+        );
+        assert_eq!(oidc.sign_in(&storage, "a@example.com", "wrong"), None);
+        assert_eq!(oidc.user(&storage, "1"), Some(claims));
+        assert_eq!(oidc.user(&storage, "2"), None);
+    }
+
+    #[test]
+    fn test_redeem_code_with_pkce() {
+        let oidc = oidc();
+        let spa = oidc.client("spa").unwrap().clone();
+        // RFC 7636 appendix B
+        let verifier = "dBjftJeZ4CVP-mB92K27uhbUJU1p1r_wW1gFWFOEjXk";
+        let challenge = "E9Melhoa2OwvFrEMTJguCHaoeK1t8URWbuGJSstw-cM";
+
+        let value = code(&oidc, "spa", Some((challenge, "S256")));
+        let error = oidc
+            .redeem_code(&value, &spa, None, Some("wrong"))
+            .unwrap_err();
+        assert_eq!(error.error, "invalid_grant");
+        // Codes are single use, even after a failed exchange
+        assert!(oidc
+            .redeem_code(&value, &spa, None, Some(verifier))
+            .is_err());
+
+        let value = code(&oidc, "spa", Some((challenge, "S256")));
+        let redeemed = oidc
+            .redeem_code(&value, &spa, Some("http://app/callback"), Some(verifier))
+            .unwrap();
+        assert_eq!(redeemed.session.user, Some(json!({"sub": "1"})));
+
+        let value = code(&oidc, "spa", Some(("plain-verifier", "plain")));
+        assert!(oidc
+            .redeem_code(&value, &spa, Some("http://other"), Some("plain-verifier"))
+            .is_err());
+
+        // Public clients must use PKCE
+        let value = code(&oidc, "spa", None);
+        assert!(oidc.redeem_code(&value, &spa, None, None).is_err());
+    }
+
+    #[test]
+    fn test_redeem_code_of_confidential_client() {
+        let oidc = oidc();
+        let api = oidc.client("api").unwrap().clone();
+        let spa = oidc.client("spa").unwrap().clone();
+
+        let value = code(&oidc, "api", None);
+        assert!(oidc.redeem_code(&value, &spa, None, None).is_err());
+        let value = code(&oidc, "api", None);
+        assert!(oidc.redeem_code(&value, &api, None, None).is_ok());
+    }
+
+    #[test]
+    fn test_refresh_token_rotation() {
+        let oidc = oidc();
+        let spa = oidc.client("spa").unwrap().clone();
+        let api = oidc.client("api").unwrap().clone();
+        let session = oidc.client_session(&spa, Some("openid"));
+
+        let token = oidc.issue_refresh_token(session.clone());
+        assert!(oidc.redeem_refresh_token(&token, &api).is_err());
+        assert_eq!(oidc.redeem_refresh_token(&token, &spa), Ok(session));
+        assert!(oidc.redeem_refresh_token(&token, &spa).is_err());
+    }
+
+    #[test]
+    fn test_session_claims() {
+        let session = Session {
+            client_id: "api".to_string(),
+            user: None,
+            scope: String::new(),
+        };
+        let mut extra = Map::new();
+        extra.insert("scope".to_string(), "read".into());
+        assert_eq!(
+            Value::Object(session_claims(&session, extra)),
+            json!({"sub": "api", "scope": "read"})
+        );
+    }
+}
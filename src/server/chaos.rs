@@ -0,0 +1,412 @@
+use crate::error::MocksError;
+use crate::fake::Rng;
+use crate::server::scope::Scoped;
+use crate::server::{is_internal, RESPONSE_BODY_LIMIT};
+use crate::storage::Storage;
+use axum::body::{to_bytes, Body, Bytes};
+use axum::extract::{Request, State};
+use axum::http::header::CONTENT_LENGTH;
+use axum::http::{Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use futures_util::{stream, StreamExt};
+use serde_json::{json, Value};
+use std::fmt;
+use std::io;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const INJECTED_ERROR: &str = "Injected fault.";
+
+/// A failure injected in place of the normal response
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Respond with a server error without handling the request
+    Error(StatusCode),
+    /// Close the connection without a response
+    Drop,
+    /// Never respond, until the client gives up
+    Hang,
+    /// Abort the connection halfway through the response body
+    Truncate,
+    /// Respond with a body that is not valid JSON
+    Malformed,
+}
+
+/// Probabilities of the faults injected into a request
+///
+/// Written as `error=0.1,status=503,drop=0.05`, or as a JSON object in the storage config.
+/// At most one fault is injected per request, so the probabilities must add up to 1 or less.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Faults {
+    error: f64,
+    status: StatusCode,
+    drop: f64,
+    hang: f64,
+    truncate: f64,
+    malformed: f64,
+}
+
+impl Default for Faults {
+    fn default() -> Self {
+        Faults {
+            error: 0.0,
+            status: StatusCode::INTERNAL_SERVER_ERROR,
+            drop: 0.0,
+            hang: 0.0,
+            truncate: 0.0,
+            malformed: 0.0,
+        }
+    }
+}
+
+impl FromStr for Faults {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let pairs = value
+            .split(',')
+            .map(|pair| {
+                let (name, number) = pair
+                    .split_once('=')
+                    .ok_or_else(|| format!("expected NAME=VALUE, got {pair}"))?;
+                let number = number
+                    .trim()
+                    .parse::<f64>()
+                    .map_err(|_| format!("invalid number {number}"))?;
+                Ok((name.trim().to_string(), number))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Faults::from_pairs(pairs)
+    }
+}
+
+impl fmt::Display for Faults {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut pairs = vec![];
+        for (name, p) in [
+            ("error", self.error),
+            ("drop", self.drop),
+            ("hang", self.hang),
+            ("truncate", self.truncate),
+            ("malformed", self.malformed),
+        ] {
+            if p > 0.0 {
+                pairs.push(format!("{name}={p}"));
+            }
+        }
+        if self.error > 0.0 {
+            pairs.push(format!("status={}", self.status.as_u16()));
+        }
+        write!(f, "{}", pairs.join(","))
+    }
+}
+
+impl Faults {
+    /// Read faults declared as a JSON object, e.g. `{"error": 0.1, "status": 503}`
+    pub fn from_value(value: &Value) -> Result<Faults, String> {
+        let obj = value
+            .as_object()
+            .ok_or_else(|| format!("{value} is not an object of faults"))?;
+        let pairs = obj
+            .iter()
+            .map(|(name, number)| {
+                let number = number
+                    .as_f64()
+                    .ok_or_else(|| format!("{name} must be a number"))?;
+                Ok((name.to_string(), number))
+            })
+            .collect::<Result<Vec<_>, String>>()?;
+        Faults::from_pairs(pairs)
+    }
+
+    fn from_pairs(pairs: Vec<(String, f64)>) -> Result<Faults, String> {
+        let mut faults = Faults::default();
+        for (name, number) in pairs {
+            if name == "status" {
+                faults.status = u16::try_from(number as u64)
+                    .ok()
+                    .filter(|s| (500..600).contains(s) && number.fract() == 0.0)
+                    .and_then(|s| StatusCode::from_u16(s).ok())
+                    .ok_or_else(|| format!("status {number} is not a 5xx status"))?;
+                continue;
+            }
+            let p = match name.as_str() {
+                "error" => &mut faults.error,
+                "drop" => &mut faults.drop,
+                "hang" => &mut faults.hang,
+                "truncate" => &mut faults.truncate,
+                "malformed" => &mut faults.malformed,
+                _ => return Err(format!("unknown fault {name}")),
+            };
+            if !(0.0..=1.0).contains(&number) {
+                return Err(format!("probability of {name} must be between 0 and 1"));
+            }
+            *p = number;
+        }
+
+        let total = faults.error + faults.drop + faults.hang + faults.truncate + faults.malformed;
+        if total > 1.0 {
+            return Err(format!("probabilities add up to {total}, more than 1"));
+        }
+        Ok(faults)
+    }
+
+    /// Draw the fault of a request, if any
+    pub fn pick(&self, rng: &mut Rng) -> Option<Fault> {
+        let mut r = rng.next_f64();
+        for (p, fault) in [
+            (self.error, Fault::Error(self.status)),
+            (self.drop, Fault::Drop),
+            (self.hang, Fault::Hang),
+            (self.truncate, Fault::Truncate),
+            (self.malformed, Fault::Malformed),
+        ] {
+            if r < p {
+                return Some(fault);
+            }
+            r -= p;
+        }
+        None
+    }
+}
+
+/// Faults injected into every endpoint
+///
+/// Global faults (`--chaos`) can be overridden per resource and method in the storage config:
+///
+/// ```json
+/// {"_mocks": {"resources": {"posts": {"chaos": {"error": 0.2, "status": 503}}, "comments": {"chaos": {"POST": {"drop": 0.1}}}}}}
+/// ```
+#[derive(Debug)]
+pub struct Chaos {
+    faults: Scoped<Faults>,
+    rng: Mutex<Rng>,
+}
+
+impl Chaos {
+    /// Read the faults of resources from the storage config
+    ///
+    /// # Arguments
+    /// * `storage` - The storage whose config declares the faults of resources
+    /// * `global` - Faults of endpoints without their own
+    /// * `seed` - Seed making the injected faults reproducible, random when omitted
+    pub fn load(
+        storage: &Storage,
+        global: Option<Faults>,
+        seed: Option<u64>,
+    ) -> Result<Chaos, MocksError> {
+        let faults = Scoped::load(storage, "chaos", global, Faults::from_value)?;
+        let seed = seed.unwrap_or_else(|| {
+            SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default()
+        });
+        Ok(Chaos {
+            faults,
+            rng: Mutex::new(Rng::new(seed)),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.faults.is_empty()
+    }
+
+    /// Draw the fault of a request, if any
+    pub fn pick(&self, method: &Method, path: &str) -> Option<Fault> {
+        let faults = self.faults.find(method, path)?;
+        let mut rng = self.rng.lock().ok()?;
+        faults.pick(&mut rng)
+    }
+}
+
+/// Middleware injecting faults into requests
+pub async fn chaos(State(chaos): State<Arc<Chaos>>, req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    let fault = if is_internal(path) {
+        None
+    } else {
+        chaos.pick(req.method(), path)
+    };
+
+    match fault {
+        None => next.run(req).await,
+        Some(Fault::Error(status)) => {
+            (status, Json(json!({ "error": INJECTED_ERROR }))).into_response()
+        }
+        Some(Fault::Drop) => (StatusCode::OK, aborted()).into_response(),
+        Some(Fault::Hang) => std::future::pending().await,
+        Some(Fault::Truncate) => {
+            let (mut parts, body) = next.run(req).await.into_parts();
+            let body = to_bytes(body, RESPONSE_BODY_LIMIT)
+                .await
+                .unwrap_or_default();
+            parts.headers.remove(CONTENT_LENGTH);
+            Response::from_parts(parts, aborted_after(body.slice(..body.len() / 2)))
+        }
+        Some(Fault::Malformed) => {
+            let (mut parts, body) = next.run(req).await.into_parts();
+            let body = to_bytes(body, RESPONSE_BODY_LIMIT)
+                .await
+                .unwrap_or_default();
+            parts.headers.remove(CONTENT_LENGTH);
+            Response::from_parts(parts, Body::from(malformed(&body)))
+        }
+    }
+}
+
+/// Body failing right away, so the connection is closed before a response is sent
+fn aborted() -> Body {
+    Body::from_stream(stream::iter([Err::<Bytes, _>(injected_io_error())]))
+}
+
+/// Body sending a chunk, then failing so the connection is closed mid-response
+fn aborted_after(chunk: Bytes) -> Body {
+    let failure = stream::once(async {
+        // Let the response head and the chunk be flushed before failing
+        tokio::task::yield_now().await;
+        Err(injected_io_error())
+    });
+    Body::from_stream(stream::iter([Ok(chunk)]).chain(failure))
+}
+
+fn injected_io_error() -> io::Error {
+    io::Error::new(io::ErrorKind::ConnectionAborted, INJECTED_ERROR)
+}
+
+/// The body without its last character, which breaks any JSON document
+fn malformed(body: &Bytes) -> Bytes {
+    if body.is_empty() {
+        Bytes::from_static(b"{")
+    } else {
+        body.slice(..body.len() - 1)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::get;
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use tower::ServiceExt;
+
+    fn load(config: Value, global: Option<&str>) -> Chaos {
+        let data = json!({"posts": [], "_mocks": config});
+        let storage = Storage::from_value(&data);
+        Chaos::load(&storage, global.map(|g| g.parse().unwrap()), Some(7)).unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        let faults: Faults = "error=0.1,status=503,drop=0.05".parse().unwrap();
+        assert_eq!(faults.error, 0.1);
+        assert_eq!(faults.status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(faults.drop, 0.05);
+        assert_eq!(faults.to_string(), "error=0.1,drop=0.05,status=503");
+
+        let faults = Faults::from_value(&json!({"hang": 0.5, "truncate": 0.5})).unwrap();
+        assert_eq!(faults.to_string(), "hang=0.5,truncate=0.5");
+
+        for invalid in [
+            "error",
+            "error=x",
+            "fire=0.1",
+            "error=1.5",
+            "error=0.6,drop=0.6",
+            "status=404",
+        ] {
+            assert!(
+                invalid.parse::<Faults>().is_err(),
+                "{invalid} should be invalid"
+            );
+        }
+        assert!(Faults::from_value(&json!("error=0.1")).is_err());
+    }
+
+    #[test]
+    fn test_pick() {
+        let mut rng = Rng::new(1);
+        let faults: Faults = "error=1".parse().unwrap();
+        assert_eq!(
+            faults.pick(&mut rng),
+            Some(Fault::Error(StatusCode::INTERNAL_SERVER_ERROR))
+        );
+        assert_eq!(Faults::default().pick(&mut rng), None);
+
+        let faults: Faults = "drop=0.25,malformed=0.25".parse().unwrap();
+        let picked: Vec<_> = (0..1000).map(|_| faults.pick(&mut rng)).collect();
+        let count = |fault| picked.iter().filter(|p| **p == fault).count();
+        assert!((150..350).contains(&count(Some(Fault::Drop))));
+        assert!((150..350).contains(&count(Some(Fault::Malformed))));
+        assert!((400..600).contains(&count(None)));
+    }
+
+    #[test]
+    fn test_pick_is_seeded() {
+        let config = json!({"resources": {"posts": {"chaos": {"error": 0.5}}}});
+        let draws = |chaos: Chaos| -> Vec<_> {
+            (0..20)
+                .map(|_| chaos.pick(&Method::GET, "/posts"))
+                .collect()
+        };
+        assert_eq!(draws(load(config.clone(), None)), draws(load(config, None)));
+        assert_eq!(load(json!({}), None).pick(&Method::GET, "/posts"), None);
+    }
+
+    async fn call(faults: &str, path: &str) -> (StatusCode, Result<Bytes, String>) {
+        let chaos = Arc::new(load(json!({}), Some(faults)));
+        let app = Router::new()
+            .route("/posts", get(|| async { Json(json!({"id": 1})) }))
+            .route("/_hc", get(|| async { StatusCode::NO_CONTENT }))
+            .layer(from_fn_with_state(chaos, super::chaos));
+        let req = Request::builder().uri(path).body(Body::empty()).unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        let status = res.status();
+        let body = res
+            .into_body()
+            .collect()
+            .await
+            .map(|b| b.to_bytes())
+            .map_err(|e| e.to_string());
+        (status, body)
+    }
+
+    #[tokio::test]
+    async fn test_chaos() {
+        let (status, body) = call("error=1,status=503", "/posts").await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body.unwrap()).unwrap(),
+            json!({"error": INJECTED_ERROR})
+        );
+
+        let (_, body) = call("drop=1", "/posts").await;
+        assert!(body.is_err());
+
+        let (status, body) = call("truncate=1", "/posts").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(body.is_err());
+
+        let (status, body) = call("malformed=1", "/posts").await;
+        assert_eq!(status, StatusCode::OK);
+        assert!(serde_json::from_slice::<Value>(&body.unwrap()).is_err());
+
+        let (status, _) = call("error=1", "/_hc").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+    }
+
+    #[tokio::test]
+    async fn test_chaos_hang() {
+        let res = tokio::time::timeout(
+            std::time::Duration::from_millis(50),
+            call("hang=1", "/posts"),
+        )
+        .await;
+        assert!(res.is_err());
+    }
+}
@@ -1,10 +1,14 @@
+use crate::schema::validator::Violation;
+use crate::schema::Schemas;
 use axum::extract::rejection::JsonRejection;
-use axum::extract::FromRequest;
+use axum::extract::{FromRequest, FromRequestParts, RawPathParams};
 use axum::http::StatusCode;
 use axum::Json;
 use serde_json::{json, Value};
+use std::sync::Arc;
 
 const INVALID_JSON_REQUEST: &str = "Invalid JSON format in request body.";
+const SCHEMA_VIOLATION: &str = "Request body does not match the resource schema.";
 
 #[derive(Debug, Clone, Default)]
 pub struct Payload(pub Value);
@@ -20,14 +24,7 @@ where
         req: axum::http::Request<axum::body::Body>,
         state: &S,
     ) -> Result<Self, Self::Rejection> {
-        let Json(value) = Json::<Value>::from_request(req, state)
-            .await
-            .map_err(|e| to_rejection(&e.to_string()))?;
-
-        if !value.is_object() {
-            return Err(to_rejection(INVALID_JSON_REQUEST));
-        }
-
+        let value = extract_object(req, state, true).await?;
         Ok(Payload(value))
     }
 }
@@ -46,13 +43,7 @@ where
         req: axum::http::Request<axum::body::Body>,
         state: &S,
     ) -> Result<Self, Self::Rejection> {
-        let Json(value) = Json::<Value>::from_request(req, state)
-            .await
-            .map_err(|e| to_rejection(&e.to_string()))?;
-
-        if !value.is_object() {
-            return Err(to_rejection(INVALID_JSON_REQUEST));
-        }
+        let value = extract_object(req, state, false).await?;
 
         // ID is required for updates
         if value.get("id").is_none() {
@@ -63,11 +54,57 @@ where
     }
 }
 
+/// Extract a JSON object body and validate it against the resource schema, if any
+///
+/// Schemas are provided through an `Arc<Schemas>` request extension.
+async fn extract_object<S>(
+    req: axum::http::Request<axum::body::Body>,
+    state: &S,
+    partial: bool,
+) -> Result<Value, (StatusCode, Json<Value>)>
+where
+    S: Send + Sync,
+    Json<Value>: FromRequest<S, Rejection = JsonRejection>,
+{
+    let (mut parts, body) = req.into_parts();
+    let schemas = parts.extensions.get::<Arc<Schemas>>().cloned();
+    let resource = match RawPathParams::from_request_parts(&mut parts, state).await {
+        Ok(params) => params
+            .iter()
+            .find(|(key, _)| *key == "resource")
+            .map(|(_, value)| value.to_string()),
+        Err(_) => None,
+    };
+    let req = axum::http::Request::from_parts(parts, body);
+
+    let Json(value) = Json::<Value>::from_request(req, state)
+        .await
+        .map_err(|e| to_rejection(&e.to_string()))?;
+
+    if !value.is_object() {
+        return Err(to_rejection(INVALID_JSON_REQUEST));
+    }
+
+    if let (Some(schemas), Some(resource)) = (schemas, resource) {
+        schemas
+            .validate(&resource, &value, partial)
+            .map_err(|violations| to_violation_rejection(&violations))?;
+    }
+
+    Ok(value)
+}
+
 fn to_rejection(message: &str) -> (StatusCode, Json<Value>) {
     let json = Json::from(json!({"error": message}));
     (StatusCode::BAD_REQUEST, json)
 }
 
+fn to_violation_rejection(violations: &[Violation]) -> (StatusCode, Json<Value>) {
+    let violations: Vec<Value> = violations.iter().map(Violation::to_value).collect();
+    let json = Json::from(json!({"error": SCHEMA_VIOLATION, "violations": violations}));
+    (StatusCode::UNPROCESSABLE_ENTITY, json)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -158,4 +195,82 @@ mod tests {
         let (status, _) = result.unwrap_err();
         assert_eq!(status, StatusCode::BAD_REQUEST);
     }
+
+    async fn run_with_schemas(method: &str, uri: &str, body: Value) -> (StatusCode, Value) {
+        use axum::routing::post;
+        use axum::{Extension, Router};
+        use http_body_util::BodyExt;
+        use tower::ServiceExt;
+
+        async fn create(PayloadWithId(value): PayloadWithId) -> Json<Value> {
+            Json(value)
+        }
+        async fn update(Payload(value): Payload) -> Json<Value> {
+            Json(value)
+        }
+
+        let schemas = Schemas::from_map(
+            [(
+                "posts".to_string(),
+                json!({
+                    "type": "object",
+                    "required": ["id", "title"],
+                    "properties": {"title": {"type": "string"}}
+                }),
+            )]
+            .into(),
+        );
+        let router = Router::new()
+            .nest(
+                "/{resource}",
+                Router::new()
+                    .route("/", post(create))
+                    .route("/{id}", axum::routing::patch(update)),
+            )
+            .layer(Extension(Arc::new(schemas)));
+
+        let request = Request::builder()
+            .uri(uri)
+            .method(method)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let response = router.oneshot(request).await.unwrap();
+        let status = response.status();
+        let bytes = response.into_body().collect().await.unwrap().to_bytes();
+        (status, serde_json::from_slice(&bytes).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_payload_with_id_schema_violation() {
+        let (status, body) = run_with_schemas("POST", "/posts", json!({"id": 1, "title": 1})).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(
+            body["violations"],
+            json!([{"path": "/title", "keyword": "type", "message": "Expected string, got integer."}])
+        );
+
+        let (status, body) = run_with_schemas("POST", "/posts", json!({"id": 1})).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["violations"][0]["keyword"], "required");
+    }
+
+    #[tokio::test]
+    async fn test_payload_with_id_schema_valid() {
+        let (status, _) = run_with_schemas("POST", "/posts", json!({"id": 1, "title": "a"})).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Resources without a schema accept any object
+        let (status, _) = run_with_schemas("POST", "/comments", json!({"id": 1})).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_payload_schema_partial() {
+        let (status, _) = run_with_schemas("PATCH", "/posts/1", json!({"title": "a"})).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, _) = run_with_schemas("PATCH", "/posts/1", json!({"title": false})).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    }
 }
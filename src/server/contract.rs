@@ -0,0 +1,256 @@
+use crate::console::print_contract_violations;
+use crate::openapi::contract::{violations_to_value, Contract};
+use crate::schema::validator::Violation;
+use crate::server::{is_internal, REQUEST_BODY_LIMIT, RESPONSE_BODY_LIMIT};
+use axum::body::{to_bytes, Body, Bytes, HttpBody};
+use axum::extract::{Query, Request, State};
+use axum::http::header::CONTENT_TYPE;
+use axum::http::StatusCode;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::Value;
+use std::sync::Arc;
+
+const REQUEST_VIOLATION: &str = "Request does not match the OpenAPI spec.";
+const RESPONSE_VIOLATION: &str = "Response does not match the OpenAPI spec.";
+
+/// Middleware validating requests and responses against the OpenAPI spec
+///
+/// In strict mode a violating request is rejected with 400 before reaching the handler,
+/// and a violating response is replaced with 500. In warn mode violations are logged only.
+pub async fn check_contract(
+    State(contract): State<Arc<Contract>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = req.uri().path().to_string();
+    // Paths served by mocks itself are not part of the contract
+    if is_internal(&path) {
+        return next.run(req).await;
+    }
+
+    let method = req.method().to_string();
+    let query = Query::<Vec<(String, String)>>::try_from_uri(req.uri())
+        .map(|Query(query)| query)
+        .unwrap_or_default();
+
+    let (parts, body) = req.into_parts();
+    // A body known to be too large to check is let through untouched in warn mode,
+    // while one found out while reading it can only be rejected
+    if body.size_hint().lower() > REQUEST_BODY_LIMIT as u64 && !contract.mode.is_strict() {
+        report(
+            &format!("{method} {path} request"),
+            &[body_too_large()],
+            &Bytes::new(),
+        );
+        return next.run(Request::from_parts(parts, body)).await;
+    }
+    let body = match to_bytes(body, REQUEST_BODY_LIMIT).await {
+        Ok(body) => body,
+        Err(_) => {
+            return reject(
+                StatusCode::PAYLOAD_TOO_LARGE,
+                REQUEST_VIOLATION,
+                &[body_too_large()],
+            )
+        }
+    };
+
+    let violations = contract.check_request(&method, &path, &query, &parts.headers, &body);
+    if !violations.is_empty() {
+        if contract.mode.is_strict() {
+            return reject(StatusCode::BAD_REQUEST, REQUEST_VIOLATION, &violations);
+        }
+        report(&format!("{method} {path} request"), &violations, &body);
+    }
+
+    let response = next.run(Request::from_parts(parts, body.into())).await;
+    // Event streams never end, and are not described by response schemas
+    if response
+        .headers()
+        .get(CONTENT_TYPE)
+        .is_some_and(|v| v.as_bytes().starts_with(b"text/event-stream"))
+    {
+        return response;
+    }
+
+    let (parts, body) = response.into_parts();
+    let body = match to_bytes(body, RESPONSE_BODY_LIMIT).await {
+        Ok(body) => body,
+        Err(e) => return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response(),
+    };
+
+    let violations = contract.check_response(&method, &path, parts.status.as_u16(), &body);
+    if !violations.is_empty() {
+        if contract.mode.is_strict() {
+            return reject(
+                StatusCode::INTERNAL_SERVER_ERROR,
+                RESPONSE_VIOLATION,
+                &violations,
+            );
+        }
+        report(&format!("{method} {path} response"), &violations, &body);
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+fn reject(status: StatusCode, message: &str, violations: &[Violation]) -> Response {
+    (status, Json(violations_to_value(message, violations))).into_response()
+}
+
+fn body_too_large() -> Violation {
+    Violation::new(
+        "/body",
+        "size",
+        format!("Body exceeds {REQUEST_BODY_LIMIT} bytes and cannot be checked."),
+    )
+}
+
+fn report(label: &str, violations: &[Violation], body: &Bytes) {
+    let body = serde_json::from_slice::<Value>(body).ok();
+    print_contract_violations(label, violations, body.as_ref());
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::openapi::contract::ContractMode;
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::get;
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use serde_json::json;
+    use tower::ServiceExt;
+
+    fn app(mode: ContractMode) -> Router {
+        app_listing(mode, json!([{"id": 1}]))
+    }
+
+    /// App whose `GET /posts` answers with `list`
+    fn app_listing(mode: ContractMode, list: Value) -> Router {
+        let spec = json!({
+            "paths": {
+                "/posts": {
+                    "get": {
+                        "parameters": [{"name": "limit", "in": "query", "schema": {"type": "integer"}}],
+                        "responses": {
+                            "200": {
+                                "content": {
+                                    "application/json": {
+                                        "schema": {"type": "array", "items": {"type": "object", "required": ["title"]}}
+                                    }
+                                }
+                            }
+                        }
+                    },
+                    "post": {
+                        "requestBody": {
+                            "required": true,
+                            "content": {"application/json": {"schema": {"type": "object"}}}
+                        },
+                        "responses": {"201": {"description": "created"}}
+                    }
+                }
+            }
+        });
+        let contract = Arc::new(Contract::new(spec, mode).unwrap());
+        Router::new()
+            .route(
+                "/posts",
+                get(|| async { Json(list) })
+                    .post(|| async { (StatusCode::CREATED, Json(json!({"id": 1}))) }),
+            )
+            .route("/_hc", get(|| async { "ok" }))
+            .layer(from_fn_with_state(contract, check_contract))
+    }
+
+    async fn send(app: Router, method: &str, uri: &str, body: &str) -> (StatusCode, Value) {
+        let req = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("content-type", "application/json")
+            .body(Body::from(body.to_string()))
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        let status = res.status();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        (
+            status,
+            serde_json::from_slice(&body)
+                .unwrap_or(Value::String(String::from_utf8_lossy(&body).to_string())),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_strict_rejects_invalid_request() {
+        let (status, body) = send(app(ContractMode::Strict), "GET", "/posts?limit=a", "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["error"], REQUEST_VIOLATION);
+        assert_eq!(body["violations"][0]["path"], "/query/limit");
+
+        let (status, _) = send(app(ContractMode::Strict), "POST", "/posts", "").await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_strict_rejects_invalid_response() {
+        let (status, body) = send(app(ContractMode::Strict), "GET", "/posts?limit=1", "").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["error"], RESPONSE_VIOLATION);
+        assert_eq!(body["violations"][0]["path"], "/body/0/title");
+    }
+
+    #[tokio::test]
+    async fn test_strict_passes_wrapped_list() {
+        let list = json!({"posts": [{"id": 1, "title": "a"}]});
+        let (status, body) = send(
+            app_listing(ContractMode::Strict, list.clone()),
+            "GET",
+            "/posts",
+            "",
+        )
+        .await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, list);
+
+        let list = json!({"posts": [{"id": 1}]});
+        let (status, body) =
+            send(app_listing(ContractMode::Strict, list), "GET", "/posts", "").await;
+        assert_eq!(status, StatusCode::INTERNAL_SERVER_ERROR);
+        assert_eq!(body["violations"][0]["path"], "/body/posts/0/title");
+    }
+
+    #[tokio::test]
+    async fn test_request_body_limit() {
+        let body = format!("\"{}\"", "a".repeat(REQUEST_BODY_LIMIT));
+        let (status, response) = send(app(ContractMode::Strict), "POST", "/posts", &body).await;
+        assert_eq!(status, StatusCode::PAYLOAD_TOO_LARGE);
+        assert_eq!(response["error"], REQUEST_VIOLATION);
+        assert_eq!(response["violations"][0]["keyword"], "size");
+
+        let (status, _) = send(app(ContractMode::Warn), "POST", "/posts", &body).await;
+        assert_eq!(status, StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_strict_passes_valid_exchange() {
+        let (status, body) = send(app(ContractMode::Strict), "POST", "/posts", "{}").await;
+        assert_eq!(status, StatusCode::CREATED);
+        assert_eq!(body, json!({"id": 1}));
+    }
+
+    #[tokio::test]
+    async fn test_warn_lets_traffic_through() {
+        let (status, body) = send(app(ContractMode::Warn), "GET", "/posts?limit=a", "").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, json!([{"id": 1}]));
+    }
+
+    #[tokio::test]
+    async fn test_internal_paths_are_skipped() {
+        let (status, _) = send(app(ContractMode::Strict), "GET", "/_hc", "").await;
+        assert_eq!(status, StatusCode::OK);
+    }
+}
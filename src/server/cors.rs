@@ -0,0 +1,482 @@
+use axum::extract::{Request, State};
+use axum::http::header::{
+    ACCESS_CONTROL_ALLOW_CREDENTIALS, ACCESS_CONTROL_ALLOW_HEADERS, ACCESS_CONTROL_ALLOW_METHODS,
+    ACCESS_CONTROL_ALLOW_ORIGIN, ACCESS_CONTROL_EXPOSE_HEADERS, ACCESS_CONTROL_MAX_AGE,
+    ACCESS_CONTROL_REQUEST_HEADERS, ACCESS_CONTROL_REQUEST_METHOD, CACHE_CONTROL, CONTENT_LANGUAGE,
+    CONTENT_LENGTH, CONTENT_TYPE, EXPIRES, LAST_MODIFIED, ORIGIN, PRAGMA, VARY,
+};
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::fmt;
+use std::str::FromStr;
+use std::sync::Arc;
+
+/// Response headers browsers expose to scripts without `Access-Control-Expose-Headers`
+const SAFELISTED_HEADERS: [HeaderName; 7] = [
+    CACHE_CONTROL,
+    CONTENT_LANGUAGE,
+    CONTENT_LENGTH,
+    CONTENT_TYPE,
+    EXPIRES,
+    LAST_MODIFIED,
+    PRAGMA,
+];
+
+/// Origins allowed to call the API, written as `*` or a comma-separated list
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Origins {
+    Any,
+    List(Vec<String>),
+}
+
+impl FromStr for Origins {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        if value.trim() == "*" {
+            return Ok(Origins::Any);
+        }
+        let origins = split(value)
+            .map(|origin| {
+                let origin = origin.trim_end_matches('/');
+                match origin.split_once("://") {
+                    Some((scheme, host)) if !scheme.is_empty() && !host.is_empty() => {
+                        Ok(origin.to_string())
+                    }
+                    _ => Err(format!(
+                        "invalid origin {origin}, expected e.g. http://localhost:5173"
+                    )),
+                }
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if origins.is_empty() {
+            return Err("expected * or a list of origins".to_string());
+        }
+        Ok(Origins::List(origins))
+    }
+}
+
+impl fmt::Display for Origins {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Origins::Any => write!(f, "*"),
+            Origins::List(origins) => write!(f, "{}", origins.join(", ")),
+        }
+    }
+}
+
+/// Methods allowed in cross-origin requests, written as `GET,POST`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Methods(Vec<Method>);
+
+impl Default for Methods {
+    fn default() -> Self {
+        Methods(vec![
+            Method::GET,
+            Method::HEAD,
+            Method::POST,
+            Method::PUT,
+            Method::PATCH,
+            Method::DELETE,
+            Method::OPTIONS,
+        ])
+    }
+}
+
+impl FromStr for Methods {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let methods = split(value)
+            .map(|method| {
+                Method::from_bytes(method.to_ascii_uppercase().as_bytes())
+                    .map_err(|_| format!("invalid method {method}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if methods.is_empty() {
+            return Err("expected a list of methods".to_string());
+        }
+        Ok(Methods(methods))
+    }
+}
+
+impl fmt::Display for Methods {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let methods: Vec<&str> = self.0.iter().map(Method::as_str).collect();
+        write!(f, "{}", methods.join(","))
+    }
+}
+
+/// Request headers allowed in cross-origin requests, written as `Authorization,X-API-Key`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Headers(Vec<HeaderName>);
+
+impl FromStr for Headers {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let headers = split(value)
+            .map(|header| {
+                HeaderName::from_bytes(header.as_bytes())
+                    .map_err(|_| format!("invalid header name {header}"))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        if headers.is_empty() {
+            return Err("expected a list of headers".to_string());
+        }
+        Ok(Headers(headers))
+    }
+}
+
+impl fmt::Display for Headers {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let headers: Vec<&str> = self.0.iter().map(HeaderName::as_str).collect();
+        write!(f, "{}", headers.join(","))
+    }
+}
+
+fn split(value: &str) -> impl Iterator<Item = &str> {
+    value.split(',').map(str::trim).filter(|s| !s.is_empty())
+}
+
+/// Cross-Origin Resource Sharing policy of the server
+///
+/// Preflight requests are answered with 204 before reaching any other layer, so they are
+/// neither authenticated nor rate limited. Other responses to an allowed origin carry the
+/// `Access-Control-Allow-*` headers, and expose every non-safelisted header they set.
+#[derive(Debug)]
+pub struct Cors {
+    origins: Origins,
+    methods: Methods,
+    /// Allowed request headers; those requested by the preflight are allowed when unset
+    headers: Option<Headers>,
+    credentials: bool,
+    /// Seconds browsers may cache a preflight response
+    max_age: Option<u64>,
+}
+
+impl Cors {
+    pub fn new(
+        origins: Origins,
+        methods: Option<Methods>,
+        headers: Option<Headers>,
+        credentials: bool,
+        max_age: Option<u64>,
+    ) -> Cors {
+        Cors {
+            origins,
+            methods: methods.unwrap_or_default(),
+            headers,
+            credentials,
+            max_age,
+        }
+    }
+
+    /// Description of the policy, e.g. `http://localhost:5173 (credentials, max-age 600s)`
+    pub fn describe(&self) -> String {
+        let mut options = vec![];
+        if self.methods != Methods::default() {
+            options.push(self.methods.to_string());
+        }
+        if self.credentials {
+            options.push("credentials".to_string());
+        }
+        if let Some(max_age) = self.max_age {
+            options.push(format!("max-age {max_age}s"));
+        }
+        if options.is_empty() {
+            self.origins.to_string()
+        } else {
+            format!("{} ({})", self.origins, options.join(", "))
+        }
+    }
+
+    /// Value of `Access-Control-Allow-Origin` for a request origin, if it is allowed
+    ///
+    /// `*` is only sent without credentials, which browsers reject along with a wildcard.
+    fn allow_origin(&self, origin: &HeaderValue) -> Option<HeaderValue> {
+        match &self.origins {
+            Origins::Any if !self.credentials => Some(HeaderValue::from_static("*")),
+            Origins::Any => Some(origin.clone()),
+            Origins::List(origins) => origin
+                .to_str()
+                .ok()
+                .filter(|o| origins.iter().any(|allowed| allowed == o))
+                .map(|_| origin.clone()),
+        }
+    }
+
+    /// Whether `Access-Control-Allow-Origin` depends on the request origin
+    fn varies(&self) -> bool {
+        !matches!(self.origins, Origins::Any) || self.credentials
+    }
+
+    /// Write the headers answering a preflight request
+    fn write_preflight_headers(
+        &self,
+        allow_origin: HeaderValue,
+        request: &HeaderMap,
+        headers: &mut HeaderMap,
+    ) {
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        insert(
+            headers,
+            ACCESS_CONTROL_ALLOW_METHODS,
+            &self.methods.to_string(),
+        );
+        let allowed_headers = match &self.headers {
+            Some(allowed) => Some(allowed.to_string()),
+            None => request
+                .get(ACCESS_CONTROL_REQUEST_HEADERS)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string),
+        };
+        if let Some(allowed_headers) = allowed_headers {
+            insert(headers, ACCESS_CONTROL_ALLOW_HEADERS, &allowed_headers);
+        }
+        if self.credentials {
+            insert(headers, ACCESS_CONTROL_ALLOW_CREDENTIALS, "true");
+        }
+        if let Some(max_age) = self.max_age {
+            headers.insert(ACCESS_CONTROL_MAX_AGE, HeaderValue::from(max_age));
+        }
+        let mut vary = vec![];
+        if self.varies() {
+            vary.push(ORIGIN.as_str());
+        }
+        vary.push(ACCESS_CONTROL_REQUEST_METHOD.as_str());
+        if self.headers.is_none() {
+            vary.push(ACCESS_CONTROL_REQUEST_HEADERS.as_str());
+        }
+        append(headers, VARY, &vary.join(", "));
+    }
+
+    /// Write the headers of an actual cross-origin response
+    fn write_headers(&self, allow_origin: HeaderValue, headers: &mut HeaderMap) {
+        let exposed: Vec<&str> = headers
+            .keys()
+            .filter(|name| !SAFELISTED_HEADERS.contains(name))
+            .map(HeaderName::as_str)
+            .collect();
+        let exposed = exposed.join(", ");
+
+        headers.insert(ACCESS_CONTROL_ALLOW_ORIGIN, allow_origin);
+        if !exposed.is_empty() {
+            insert(headers, ACCESS_CONTROL_EXPOSE_HEADERS, &exposed);
+        }
+        if self.credentials {
+            insert(headers, ACCESS_CONTROL_ALLOW_CREDENTIALS, "true");
+        }
+    }
+}
+
+fn insert(headers: &mut HeaderMap, name: HeaderName, value: &str) {
+    if let Ok(value) = HeaderValue::from_str(value) {
+        headers.insert(name, value);
+    }
+}
+
+fn append(headers: &mut HeaderMap, name: HeaderName, value: &str) {
+    if let Ok(value) = HeaderValue::from_str(value) {
+        headers.append(name, value);
+    }
+}
+
+/// Whether a request is a CORS preflight rather than an actual `OPTIONS` request
+fn is_preflight(req: &Request) -> bool {
+    req.method() == Method::OPTIONS && req.headers().contains_key(ACCESS_CONTROL_REQUEST_METHOD)
+}
+
+/// Middleware answering preflight requests and adding CORS headers to responses
+pub async fn cors(State(cors): State<Arc<Cors>>, req: Request, next: Next) -> Response {
+    let allow_origin = req
+        .headers()
+        .get(ORIGIN)
+        .and_then(|origin| cors.allow_origin(origin));
+
+    if is_preflight(&req) {
+        let mut response = StatusCode::NO_CONTENT.into_response();
+        // Disallowed origins get no CORS headers, which makes the browser block the request
+        if let Some(allow_origin) = allow_origin {
+            cors.write_preflight_headers(allow_origin, req.headers(), response.headers_mut());
+        }
+        return response;
+    }
+
+    let mut response = next.run(req).await;
+    if let Some(allow_origin) = allow_origin {
+        cors.write_headers(allow_origin, response.headers_mut());
+    }
+    if cors.varies() {
+        append(response.headers_mut(), VARY, ORIGIN.as_str());
+    }
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::header::{ETAG, WWW_AUTHENTICATE};
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    fn app(cors: Cors) -> Router {
+        Router::new()
+            .route(
+                "/posts",
+                get(|| async { ([(ETAG, "\"1\""), (CONTENT_TYPE, "text/plain")], "ok") }),
+            )
+            .route(
+                "/users",
+                get(|| async {
+                    (
+                        StatusCode::UNAUTHORIZED,
+                        [(WWW_AUTHENTICATE, "Basic realm=\"mocks\"")],
+                    )
+                }),
+            )
+            .layer(from_fn_with_state(Arc::new(cors), super::cors))
+    }
+
+    fn preflight(origin: &str) -> Request {
+        Request::builder()
+            .method(Method::OPTIONS)
+            .uri("/posts/1")
+            .header(ORIGIN, origin)
+            .header(ACCESS_CONTROL_REQUEST_METHOD, "PATCH")
+            .header(ACCESS_CONTROL_REQUEST_HEADERS, "authorization,content-type")
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    fn get_req(path: &str, origin: &str) -> Request {
+        Request::builder()
+            .uri(path)
+            .header(ORIGIN, origin)
+            .body(Body::empty())
+            .unwrap()
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!("*".parse(), Ok(Origins::Any));
+        assert_eq!(
+            "http://localhost:5173/, https://app.example.com".parse(),
+            Ok(Origins::List(vec![
+                "http://localhost:5173".to_string(),
+                "https://app.example.com".to_string()
+            ]))
+        );
+        for invalid in ["", "localhost:5173", "http://"] {
+            assert!(
+                invalid.parse::<Origins>().is_err(),
+                "{invalid} should be invalid"
+            );
+        }
+
+        assert_eq!(
+            "get, post".parse::<Methods>().unwrap().to_string(),
+            "GET,POST"
+        );
+        assert!("GET,P OST".parse::<Methods>().is_err());
+        assert_eq!(
+            "Authorization,X-API-Key"
+                .parse::<Headers>()
+                .unwrap()
+                .to_string(),
+            "authorization,x-api-key"
+        );
+        assert!("a b".parse::<Headers>().is_err());
+    }
+
+    #[test]
+    fn test_describe() {
+        let cors = Cors::new(Origins::Any, None, None, false, None);
+        assert_eq!(cors.describe(), "*");
+        let origins = "http://localhost:5173".parse().unwrap();
+        let cors = Cors::new(origins, "GET".parse().ok(), None, true, Some(600));
+        assert_eq!(
+            cors.describe(),
+            "http://localhost:5173 (GET, credentials, max-age 600s)"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_preflight() {
+        let app = app(Cors::new(Origins::Any, None, None, false, Some(600)));
+        let res = app.oneshot(preflight("http://app")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        let headers = res.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "*");
+        assert_eq!(
+            headers[ACCESS_CONTROL_ALLOW_METHODS],
+            "GET,HEAD,POST,PUT,PATCH,DELETE,OPTIONS"
+        );
+        assert_eq!(
+            headers[ACCESS_CONTROL_ALLOW_HEADERS],
+            "authorization,content-type"
+        );
+        assert_eq!(headers[ACCESS_CONTROL_MAX_AGE], "600");
+        assert!(headers.get(ACCESS_CONTROL_ALLOW_CREDENTIALS).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_preflight_with_origins() {
+        let origins = "http://app".parse().unwrap();
+        let headers = "authorization".parse().ok();
+        let app = app(Cors::new(origins, None, headers, true, None));
+
+        let res = app.clone().oneshot(preflight("http://app")).await.unwrap();
+        let headers = res.headers();
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "http://app");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_HEADERS], "authorization");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[VARY], "origin, access-control-request-method");
+
+        let res = app.oneshot(preflight("http://evil")).await.unwrap();
+        assert_eq!(res.status(), StatusCode::NO_CONTENT);
+        assert!(res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_cors() {
+        let app = app(Cors::new(Origins::Any, None, None, true, None));
+
+        let res = app
+            .clone()
+            .oneshot(get_req("/posts", "http://app"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let headers = res.headers();
+        // Credentials cannot be sent with a wildcard, so the origin is reflected
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_ORIGIN], "http://app");
+        assert_eq!(headers[ACCESS_CONTROL_ALLOW_CREDENTIALS], "true");
+        assert_eq!(headers[ACCESS_CONTROL_EXPOSE_HEADERS], "etag");
+        assert_eq!(headers[VARY], "origin");
+
+        // Error responses are readable too
+        let res = app
+            .clone()
+            .oneshot(get_req("/users", "http://app"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+        assert_eq!(
+            res.headers()[ACCESS_CONTROL_EXPOSE_HEADERS],
+            "www-authenticate"
+        );
+
+        // Same-origin requests are left alone
+        let req = Request::builder()
+            .uri("/posts")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        assert!(res.headers().get(ACCESS_CONTROL_ALLOW_ORIGIN).is_none());
+    }
+}
@@ -0,0 +1,296 @@
+use crate::error::MocksError;
+use crate::fake::Rng;
+use crate::server::is_internal;
+use crate::server::scope::Scoped;
+use crate::storage::Storage;
+use axum::extract::{Request, State};
+use axum::http::Method;
+use axum::middleware::Next;
+use axum::response::Response;
+use std::fmt;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+/// How a delay range is sampled
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Distribution {
+    Uniform,
+    /// Centered on the middle of the range, clamped to it
+    Normal,
+}
+
+/// Latency added before a request is handled
+///
+/// Written as `200ms`, `1s`, `100..800ms` or `100ms..2s`, optionally followed by
+/// `:normal` or `:uniform` (the default) to choose how a range is sampled.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Delay {
+    min: Duration,
+    max: Duration,
+    distribution: Distribution,
+}
+
+impl FromStr for Delay {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (range, distribution) = match value.trim().rsplit_once(':') {
+            Some((range, "uniform")) => (range, Distribution::Uniform),
+            Some((range, "normal")) => (range, Distribution::Normal),
+            Some((_, other)) => return Err(format!("unknown distribution {other}")),
+            None => (value.trim(), Distribution::Uniform),
+        };
+
+        let (min, max) = match range.split_once("..") {
+            Some((min, max)) => {
+                let max = parse_duration(max, "ms")?;
+                // `100..800ms` shares the unit of the upper bound
+                let unit = max.1;
+                (parse_duration(min, unit)?.0, max.0)
+            }
+            None => {
+                let delay = parse_duration(range, "ms")?.0;
+                (delay, delay)
+            }
+        };
+        if min > max {
+            return Err(format!("{range} is an empty range"));
+        }
+
+        Ok(Delay {
+            min,
+            max,
+            distribution,
+        })
+    }
+}
+
+impl fmt::Display for Delay {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.min == self.max {
+            return write!(f, "{}ms", self.min.as_millis());
+        }
+        write!(f, "{}..{}ms", self.min.as_millis(), self.max.as_millis())?;
+        if self.distribution == Distribution::Normal {
+            write!(f, ":normal")?;
+        }
+        Ok(())
+    }
+}
+
+impl Delay {
+    /// Draw the latency of a request
+    pub fn sample(&self, rng: &mut Rng) -> Duration {
+        if self.min == self.max {
+            return self.min;
+        }
+        let (min, max) = (self.min.as_secs_f64(), self.max.as_secs_f64());
+        let secs = match self.distribution {
+            Distribution::Uniform => rng.range_f64(min, max),
+            Distribution::Normal => {
+                // Box-Muller transform; the range spans six standard deviations
+                let u1 = 1.0 - rng.next_f64();
+                let u2 = rng.next_f64();
+                let z = (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos();
+                ((min + max) / 2.0 + z * (max - min) / 6.0).clamp(min, max)
+            }
+        };
+        Duration::from_secs_f64(secs)
+    }
+}
+
+fn parse_duration<'a>(
+    value: &'a str,
+    default_unit: &'a str,
+) -> Result<(Duration, &'a str), String> {
+    let value = value.trim();
+    let (number, unit) = match value.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => value.split_at(i),
+        None => (value, default_unit),
+    };
+    let number = number
+        .parse::<u64>()
+        .map_err(|_| format!("invalid duration {value}"))?;
+    match unit {
+        "ms" => Ok((Duration::from_millis(number), "ms")),
+        "s" => Ok((Duration::from_secs(number), "s")),
+        _ => Err(format!("unknown unit in {value}, expected ms or s")),
+    }
+}
+
+/// Latency of every endpoint
+///
+/// A global delay (`--delay`) can be overridden per resource and method in the storage config:
+///
+/// ```json
+/// {"_mocks": {"resources": {"posts": {"delay": "300ms"}, "comments": {"delay": {"GET": "100..500ms", "*": "50ms"}}}}}
+/// ```
+#[derive(Debug, Default)]
+pub struct Delays {
+    delays: Scoped<Delay>,
+    rng: Mutex<Option<Rng>>,
+}
+
+impl Delays {
+    /// Read the delays of resources from the storage config
+    ///
+    /// # Arguments
+    /// * `storage` - The storage whose config declares the delays of resources
+    /// * `global` - Delay of endpoints without one of their own
+    pub fn load(storage: &Storage, global: Option<Delay>) -> Result<Delays, MocksError> {
+        let delays = Scoped::load(storage, "delay", global, |value| {
+            value
+                .as_str()
+                .ok_or_else(|| format!("{value} is not a delay"))?
+                .parse()
+        })?;
+        Ok(Delays {
+            delays,
+            rng: Mutex::new(None),
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.delays.is_empty()
+    }
+
+    /// Delay of a request
+    pub fn find(&self, method: &Method, path: &str) -> Option<&Delay> {
+        self.delays.find(method, path)
+    }
+
+    fn sample(&self, delay: &Delay) -> Duration {
+        let Ok(mut rng) = self.rng.lock() else {
+            return delay.min;
+        };
+        let rng = rng.get_or_insert_with(|| {
+            let seed = SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .map(|d| d.as_nanos() as u64)
+                .unwrap_or_default();
+            Rng::new(seed)
+        });
+        delay.sample(rng)
+    }
+}
+
+/// Middleware holding requests for their configured delay
+pub async fn delay(State(delays): State<Arc<Delays>>, req: Request, next: Next) -> Response {
+    let path = req.uri().path();
+    if !is_internal(path) {
+        if let Some(delay) = delays.find(req.method(), path) {
+            tokio::time::sleep(delays.sample(delay)).await;
+        }
+    }
+    next.run(req).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::{json, Value};
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    fn load(config: Value, global: Option<&str>) -> Result<Delays, MocksError> {
+        let data = json!({"posts": [], "api/v1/comments": [], "_mocks": config});
+        let storage = Storage::from_value(&data);
+        Delays::load(&storage, global.map(|g| g.parse().unwrap()))
+    }
+
+    #[test]
+    fn test_parse() {
+        let delay = |min, max, distribution| Delay {
+            min: ms(min),
+            max: ms(max),
+            distribution,
+        };
+        assert_eq!("200ms".parse(), Ok(delay(200, 200, Distribution::Uniform)));
+        assert_eq!("200".parse(), Ok(delay(200, 200, Distribution::Uniform)));
+        assert_eq!("2s".parse(), Ok(delay(2000, 2000, Distribution::Uniform)));
+        assert_eq!(
+            "100..800ms".parse(),
+            Ok(delay(100, 800, Distribution::Uniform))
+        );
+        assert_eq!(
+            "1..2s:normal".parse(),
+            Ok(delay(1000, 2000, Distribution::Normal))
+        );
+        assert_eq!(
+            "500ms..1s".parse(),
+            Ok(delay(500, 1000, Distribution::Uniform))
+        );
+
+        for invalid in ["", "fast", "10m", "800..100ms", "1..2s:gamma", "-1ms"] {
+            assert!(
+                invalid.parse::<Delay>().is_err(),
+                "{invalid} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn test_display() {
+        for delay in ["200ms", "100..800ms", "1000..2000ms:normal"] {
+            assert_eq!(delay.parse::<Delay>().unwrap().to_string(), delay);
+        }
+    }
+
+    #[test]
+    fn test_sample() {
+        let mut rng = Rng::new(1);
+        for delay in ["100..800ms", "100..800ms:normal"] {
+            let delay: Delay = delay.parse().unwrap();
+            for _ in 0..100 {
+                let sampled = delay.sample(&mut rng);
+                assert!(sampled >= ms(100) && sampled <= ms(800));
+            }
+        }
+        let delay: Delay = "200ms".parse().unwrap();
+        assert_eq!(delay.sample(&mut rng), ms(200));
+    }
+
+    #[test]
+    fn test_find() {
+        let config = json!({
+            "resources": {
+                "posts": {"delay": "300ms"},
+                "comments": {"delay": {"get": "100..500ms", "*": "50ms"}}
+            }
+        });
+        let delays = load(config, Some("10ms")).unwrap();
+        let find = |method: Method, path: &str| delays.find(&method, path).map(Delay::to_string);
+
+        assert_eq!(find(Method::GET, "/posts"), Some("300ms".to_string()));
+        assert_eq!(find(Method::DELETE, "/posts/1"), Some("300ms".to_string()));
+        assert_eq!(
+            find(Method::GET, "/api/v1/comments/1"),
+            Some("100..500ms".to_string())
+        );
+        assert_eq!(
+            find(Method::POST, "/api/v1/comments"),
+            Some("50ms".to_string())
+        );
+        assert_eq!(find(Method::GET, "/postsx"), Some("10ms".to_string()));
+
+        let delays = load(json!({}), None).unwrap();
+        assert!(delays.is_empty());
+        assert_eq!(delays.find(&Method::GET, "/posts"), None);
+    }
+
+    #[test]
+    fn test_load_invalid() {
+        for delay in [
+            json!(1),
+            json!("soon"),
+            json!({"GET": 1}),
+            json!({"G T": "1ms"}),
+        ] {
+            let config = json!({"resources": {"posts": {"delay": delay}}});
+            assert!(load(config, None).is_err(), "{delay} should be invalid");
+        }
+    }
+}
@@ -0,0 +1,196 @@
+use crate::error::MocksError;
+use axum::extract::FromRequestParts;
+use axum::http::header::{ETAG, IF_MATCH, IF_NONE_MATCH};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::Json;
+use serde_json::Value;
+use std::convert::Infallible;
+
+const FNV_OFFSET: u64 = 0xcbf2_9ce4_8422_2325;
+const FNV_PRIME: u64 = 0x0100_0000_01b3;
+
+/// Compute the entity tag of a stored value
+///
+/// The tag is a FNV-1a hash of the serialized value, so it is stable across restarts
+/// and changes whenever the content (including a `version` field) changes.
+pub fn etag(value: &Value) -> String {
+    let hash = value.to_string().bytes().fold(FNV_OFFSET, |hash, byte| {
+        (hash ^ byte as u64).wrapping_mul(FNV_PRIME)
+    });
+    format!("\"{hash:016x}\"")
+}
+
+/// Conditional request headers (`If-Match` and `If-None-Match`)
+#[derive(Debug, Clone, Default)]
+pub struct Preconditions {
+    if_match: Option<String>,
+    if_none_match: Option<String>,
+}
+
+impl<S> FromRequestParts<S> for Preconditions
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> Result<Self, Self::Rejection> {
+        let header = |name: HeaderName| {
+            parts
+                .headers
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .map(str::to_string)
+        };
+        Ok(Preconditions {
+            if_match: header(IF_MATCH),
+            if_none_match: header(IF_NONE_MATCH),
+        })
+    }
+}
+
+impl Preconditions {
+    /// Whether a mutation of a value should proceed
+    ///
+    /// Lazily fetches the current value only when `If-Match` is sent.
+    pub fn check<F>(&self, current: F) -> Result<(), MocksError>
+    where
+        F: FnOnce() -> Result<Value, MocksError>,
+    {
+        let Some(if_match) = &self.if_match else {
+            return Ok(());
+        };
+        if matches(if_match, &etag(&current()?), false) {
+            Ok(())
+        } else {
+            Err(MocksError::PreconditionFailed)
+        }
+    }
+
+    /// Respond to a read with the body and its tag, or 304 when the client's copy is current
+    ///
+    /// The tag is computed from the value as rendered for this request, before any
+    /// wrapping in the body, so that it matches the stored value when nothing is templated.
+    pub fn respond(&self, rendered: &Value, body: Value) -> Response {
+        let tag = etag(rendered);
+        let not_modified = self
+            .if_none_match
+            .as_deref()
+            .is_some_and(|header| matches(header, &tag, true));
+        if not_modified {
+            (StatusCode::NOT_MODIFIED, tagged(&tag)).into_response()
+        } else {
+            (StatusCode::OK, tagged(&tag), Json(body)).into_response()
+        }
+    }
+}
+
+/// Respond to a write with the value and its tag
+///
+/// Like for reads, the tag is computed from the value as rendered for the request,
+/// so that it can be sent back in `If-Match`.
+pub fn with_etag(status: StatusCode, rendered: &Value, value: Value) -> Response {
+    (status, tagged(&etag(rendered)), Json(value)).into_response()
+}
+
+fn tagged(tag: &str) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Ok(value) = HeaderValue::from_str(tag) {
+        headers.insert(ETAG, value);
+    }
+    headers
+}
+
+/// Whether a tag appears in an `If-Match` / `If-None-Match` header
+///
+/// `If-None-Match` uses the weak comparison, which ignores the `W/` prefix.
+fn matches(header: &str, tag: &str, weak: bool) -> bool {
+    header.split(',').map(str::trim).any(|candidate| {
+        if candidate == "*" {
+            return true;
+        }
+        match candidate.strip_prefix("W/") {
+            Some(candidate) => weak && candidate == tag,
+            None => candidate == tag,
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn preconditions(if_match: Option<&str>, if_none_match: Option<&str>) -> Preconditions {
+        Preconditions {
+            if_match: if_match.map(str::to_string),
+            if_none_match: if_none_match.map(str::to_string),
+        }
+    }
+
+    #[test]
+    fn test_etag() {
+        let tag = etag(&json!({"id": 1, "title": "a"}));
+        assert_eq!(tag, etag(&json!({"title": "a", "id": 1})));
+        assert_ne!(tag, etag(&json!({"id": 1, "title": "b"})));
+        assert!(tag.starts_with('"') && tag.ends_with('"'));
+        assert_eq!(tag.len(), 18);
+    }
+
+    #[test]
+    fn test_matches() {
+        let tag = "\"abc\"";
+        assert!(matches("\"abc\"", tag, false));
+        assert!(matches("\"x\", \"abc\"", tag, false));
+        assert!(matches("*", tag, false));
+        assert!(!matches("\"x\"", tag, false));
+        assert!(!matches("W/\"abc\"", tag, false));
+        assert!(matches("W/\"abc\"", tag, true));
+    }
+
+    #[test]
+    fn test_check() {
+        let value = json!({"id": 1});
+        let tag = etag(&value);
+        assert!(preconditions(None, None)
+            .check(|| panic!("must not fetch"))
+            .is_ok());
+        assert!(preconditions(Some(&tag), None)
+            .check(|| Ok(value.clone()))
+            .is_ok());
+        assert_eq!(
+            preconditions(Some("\"stale\""), None).check(|| Ok(value.clone())),
+            Err(MocksError::PreconditionFailed)
+        );
+        assert_eq!(
+            preconditions(Some("*"), None).check(|| Err(MocksError::ObjectNotFound)),
+            Err(MocksError::ObjectNotFound)
+        );
+    }
+
+    #[test]
+    fn test_respond() {
+        let value = json!({"id": 1});
+        let tag = etag(&value);
+
+        let res = preconditions(None, None).respond(&value, value.clone());
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[ETAG], tag.as_str());
+
+        let res = preconditions(None, Some(&tag)).respond(&value, value.clone());
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(res.headers()[ETAG], tag.as_str());
+
+        let res = preconditions(None, Some("\"stale\"")).respond(&value, value.clone());
+        assert_eq!(res.status(), StatusCode::OK);
+    }
+
+    #[test]
+    fn test_with_etag() {
+        let value = json!({"id": 1});
+        let res = with_etag(StatusCode::CREATED, &value, value.clone());
+        assert_eq!(res.status(), StatusCode::CREATED);
+        assert_eq!(res.headers()[ETAG], etag(&value).as_str());
+    }
+}
@@ -0,0 +1,541 @@
+use crate::error::MocksError;
+use crate::server::is_internal;
+use crate::server::proxy::{Forwarded, Upstream};
+use crate::server::routes::CannedRoute;
+use crate::server::tls::Https;
+use axum::body::Body;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::header::{AUTHORIZATION, CACHE_CONTROL, CONTENT_LOCATION, COOKIE, HOST, LOCATION};
+use axum::http::request::Parts;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const X_FORWARDED_FOR: HeaderName = HeaderName::from_static("x-forwarded-for");
+const X_FORWARDED_HOST: HeaderName = HeaderName::from_static("x-forwarded-host");
+const X_FORWARDED_PROTO: HeaderName = HeaderName::from_static("x-forwarded-proto");
+
+/// Tells whether a response came from the cache of the fallback
+const X_MOCKS_CACHE: HeaderName = HeaderName::from_static("x-mocks-cache");
+
+/// A header set on requests forwarded to the upstream, written as `NAME: VALUE` (`NAME:` removes it)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxyHeader {
+    name: HeaderName,
+    value: Option<HeaderValue>,
+}
+
+impl FromStr for ProxyHeader {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (name, value) = value.split_once(':').ok_or_else(|| {
+            format!("invalid header {value}, expected e.g. Authorization: Bearer abc")
+        })?;
+        let name = HeaderName::from_bytes(name.trim().as_bytes())
+            .map_err(|_| format!("invalid header name {name}"))?;
+        let value = match value.trim() {
+            "" => None,
+            value => Some(
+                HeaderValue::from_str(value)
+                    .map_err(|_| format!("invalid header value {value}"))?,
+            ),
+        };
+        Ok(ProxyHeader { name, value })
+    }
+}
+
+impl fmt::Display for ProxyHeader {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}:", self.name)?;
+        match self.value.as_ref().and_then(|v| v.to_str().ok()) {
+            Some(value) => write!(f, " {value}"),
+            None => Ok(()),
+        }
+    }
+}
+
+/// Upstream serving the requests no resource or route of the mock serves
+#[derive(Debug)]
+pub struct Fallback {
+    upstream: Upstream,
+    headers: Vec<ProxyHeader>,
+    cache: Option<Cache>,
+    /// Paths served by the mock, with `{param}` segments
+    mocked: Vec<String>,
+}
+
+/// Responses of the upstream to `GET` requests, kept for a time
+#[derive(Debug)]
+struct Cache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Instant, Forwarded)>>,
+}
+
+impl Fallback {
+    /// Create a fallback to an upstream
+    ///
+    /// # Arguments
+    /// * `upstream` - The upstream unserved requests are forwarded to
+    /// * `headers` - Headers set on the forwarded requests
+    /// * `cache` - Seconds successful `GET` responses are cached for, if cached
+    pub fn new(upstream: Upstream, headers: Vec<ProxyHeader>, cache: Option<u64>) -> Fallback {
+        let cache = cache.map(|secs| Cache {
+            ttl: Duration::from_secs(secs),
+            entries: Mutex::new(HashMap::new()),
+        });
+        Fallback {
+            upstream,
+            headers,
+            cache,
+            mocked: vec![],
+        }
+    }
+
+    /// Keep serving the resources, their items and the routes of the mock
+    pub fn mock(&mut self, resources: Vec<String>, routes: &[CannedRoute]) {
+        self.mocked = resources
+            .into_iter()
+            .flat_map(|resource| [format!("/{resource}"), format!("/{resource}/{{id}}")])
+            .chain(routes.iter().map(|route| route.path.clone()))
+            .collect();
+    }
+
+    /// Whether a path is served by the mock rather than the upstream
+    fn mocks(&self, path: &str) -> bool {
+        self.mocked.iter().any(|pattern| matches(pattern, path))
+    }
+
+    /// Description of the fallback (e.g. "http://localhost:8080 (cached 30s)")
+    pub fn describe(&self) -> String {
+        match &self.cache {
+            Some(cache) => format!(
+                "{} (cached {}s)",
+                self.upstream.describe(),
+                cache.ttl.as_secs()
+            ),
+            None => self.upstream.describe(),
+        }
+    }
+
+    async fn respond(&self, mut parts: Parts, body: Body) -> Result<Response, MocksError> {
+        let https = parts.extensions.get::<Https>().is_some();
+        let host = parts
+            .headers
+            .get(HOST)
+            .and_then(|v| v.to_str().ok())
+            .or_else(|| parts.uri.authority().map(|a| a.as_str()))
+            .unwrap_or("localhost")
+            .to_string();
+        let scheme = if https { "https" } else { "http" };
+        let origin = format!("{scheme}://{host}");
+
+        let key = (parts.method == Method::GET).then(|| cache_key(&parts));
+        if let (Some(cache), Some(key)) = (&self.cache, &key) {
+            if let Some(hit) = cache.get(key, Instant::now()) {
+                return Ok(with_cache_status(hit.into_response(), "HIT"));
+            }
+        }
+
+        self.rewrite_request(&mut parts.headers, &host, scheme);
+        let method = parts.method.clone();
+        let mut forwarded = self
+            .upstream
+            .forward(Request::from_parts(parts, body))
+            .await?;
+        for name in [LOCATION, CONTENT_LOCATION] {
+            let location = forwarded
+                .headers
+                .get(&name)
+                .and_then(|v| v.to_str().ok())
+                .and_then(|location| self.upstream.local_location(location, &origin))
+                .and_then(|location| HeaderValue::from_str(&location).ok());
+            if let Some(location) = location {
+                forwarded.headers.insert(name, location);
+            }
+        }
+
+        let Some(cache) = &self.cache else {
+            return Ok(forwarded.into_response());
+        };
+        match key {
+            Some(key) => {
+                if forwarded.status.is_success() && is_storable(&forwarded.headers) {
+                    cache.put(key, forwarded.clone(), Instant::now());
+                }
+                Ok(with_cache_status(forwarded.into_response(), "MISS"))
+            }
+            None => {
+                // Changes made upstream may show in any cached response
+                if !method.is_safe() && forwarded.status.is_success() {
+                    cache.clear();
+                }
+                Ok(forwarded.into_response())
+            }
+        }
+    }
+
+    /// Tell the upstream about the original request and set the configured headers
+    fn rewrite_request(&self, headers: &mut HeaderMap, host: &str, scheme: &'static str) {
+        if let Ok(host) = HeaderValue::from_str(host) {
+            headers.insert(X_FORWARDED_HOST, host);
+        }
+        headers.insert(X_FORWARDED_PROTO, HeaderValue::from_static(scheme));
+        for header in &self.headers {
+            match &header.value {
+                Some(value) => headers.insert(header.name.clone(), value.clone()),
+                None => headers.remove(&header.name),
+            };
+        }
+    }
+}
+
+impl Cache {
+    fn get(&self, key: &str, now: Instant) -> Option<Forwarded> {
+        let mut entries = self.entries.lock().ok()?;
+        match entries.get(key) {
+            Some((stored, forwarded)) if now.duration_since(*stored) < self.ttl => {
+                Some(forwarded.clone())
+            }
+            Some(_) => {
+                entries.remove(key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    fn put(&self, key: String, forwarded: Forwarded, now: Instant) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.retain(|_, (stored, _)| now.duration_since(*stored) < self.ttl);
+            entries.insert(key, (now, forwarded));
+        }
+    }
+
+    fn clear(&self) {
+        if let Ok(mut entries) = self.entries.lock() {
+            entries.clear();
+        }
+    }
+}
+
+/// Whether a path matches a route path, whose `{param}` segments match any segment
+fn matches(pattern: &str, path: &str) -> bool {
+    let mut segments = path.trim_end_matches('/').split('/');
+    for expected in pattern.trim_end_matches('/').split('/') {
+        let Some(segment) = segments.next() else {
+            return false;
+        };
+        if expected.starts_with("{*") {
+            return !segment.is_empty();
+        }
+        let is_param = expected.starts_with('{') && expected.ends_with('}');
+        let matched = if is_param {
+            !segment.is_empty()
+        } else {
+            segment == expected
+        };
+        if !matched {
+            return false;
+        }
+    }
+    segments.next().is_none()
+}
+
+/// Cached responses are shared only between requests of the same client
+fn cache_key(parts: &Parts) -> String {
+    let header = |name| {
+        parts
+            .headers
+            .get(name)
+            .and_then(|v| v.to_str().ok())
+            .unwrap_or("")
+    };
+    format!(
+        "{}\n{}\n{}",
+        parts.uri,
+        header(AUTHORIZATION),
+        header(COOKIE)
+    )
+}
+
+fn is_storable(headers: &HeaderMap) -> bool {
+    !headers
+        .get_all(CACHE_CONTROL)
+        .iter()
+        .filter_map(|v| v.to_str().ok())
+        .flat_map(|v| v.split(','))
+        .any(|directive| {
+            let directive = directive.trim();
+            directive.eq_ignore_ascii_case("no-store") || directive.eq_ignore_ascii_case("private")
+        })
+}
+
+fn with_cache_status(mut response: Response, status: &'static str) -> Response {
+    response
+        .headers_mut()
+        .insert(X_MOCKS_CACHE, HeaderValue::from_static(status));
+    response
+}
+
+/// Middleware forwarding the requests no resource or route serves to the upstream
+pub async fn proxy_fallback(
+    State(fallback): State<Arc<Fallback>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let path = req.uri().path();
+    if is_internal(path) || fallback.mocks(path) {
+        return next.run(req).await;
+    }
+
+    let (mut parts, body) = req.into_parts();
+    if let Some(ConnectInfo(addr)) = parts.extensions.get::<ConnectInfo<SocketAddr>>() {
+        let forwarded_for = match parts
+            .headers
+            .get(&X_FORWARDED_FOR)
+            .and_then(|v| v.to_str().ok())
+        {
+            Some(previous) => format!("{previous}, {}", addr.ip()),
+            None => addr.ip().to_string(),
+        };
+        if let Ok(value) = HeaderValue::from_str(&forwarded_for) {
+            parts.headers.insert(X_FORWARDED_FOR, value);
+        }
+    }
+    fallback
+        .respond(parts, body)
+        .await
+        .unwrap_or_else(IntoResponse::into_response)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::Schemas;
+    use crate::server::scenario::Scenarios;
+    use crate::server::state::AppState;
+    use crate::server::{apply_options, create_router, ServerOptions};
+    use crate::storage::Storage;
+    use axum::body::Bytes;
+    use axum::http::StatusCode;
+    use axum::routing::any;
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+    use tower::ServiceExt;
+
+    /// Serve a stand-in upstream echoing requests and counting them
+    async fn upstream() -> (String, Arc<AtomicUsize>) {
+        let count = Arc::new(AtomicUsize::new(0));
+        let counted = count.clone();
+        let app = Router::new().fallback(any(move |request: Request| {
+            let count = counted.clone();
+            async move {
+                let n = count.fetch_add(1, Ordering::SeqCst) + 1;
+                let header = |name: &str| {
+                    request
+                        .headers()
+                        .get(name)
+                        .and_then(|v| v.to_str().ok())
+                        .map(str::to_string)
+                };
+                let echo = json!({
+                    "n": n,
+                    "method": request.method().as_str(),
+                    "uri": request.uri().to_string(),
+                    "x-forwarded-host": header("x-forwarded-host"),
+                    "x-forwarded-proto": header("x-forwarded-proto"),
+                    "x-api-key": header("x-api-key"),
+                    "cookie": header("cookie"),
+                });
+                let location = format!("http://{}/legacy/new", header("host").unwrap_or_default());
+                (
+                    [
+                        ("location", location),
+                        ("content-type", "application/json".to_string()),
+                    ],
+                    echo.to_string(),
+                )
+            }
+        }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{addr}/legacy"), count)
+    }
+
+    fn app(dir: &TempDir, mut fallback: Fallback) -> Router {
+        let file = dir.path().join("storage.json");
+        let data = json!({
+            "posts": [{"id": 1, "title": "a"}],
+            "_mocks": {"routes": [{"method": "GET", "path": "/login", "body": {"token": "t"}}]}
+        });
+        std::fs::write(&file, data.to_string()).unwrap();
+        let storage = Storage::new(file.to_str().unwrap(), false).unwrap();
+        let scenarios = Scenarios::default();
+        let routes = CannedRoute::load(&storage, &scenarios).unwrap();
+        fallback.mock(storage.resources(), &routes);
+        let value = storage.data.clone();
+        let router = create_router(
+            AppState::new(storage, scenarios),
+            &value,
+            Schemas::default(),
+            &routes,
+        )
+        .unwrap();
+        let options = ServerOptions {
+            fallback: Some(fallback),
+            ..ServerOptions::default()
+        };
+        apply_options(router, options)
+    }
+
+    async fn send(app: &Router, method: &str, uri: &str) -> (StatusCode, HeaderMap, Value) {
+        let request = Request::builder()
+            .method(method)
+            .uri(uri)
+            .header(HOST, "localhost:3000")
+            .header(COOKIE, "session=1")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        let (parts, body) = response.into_parts();
+        let body = body.collect().await.unwrap().to_bytes();
+        let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+        (parts.status, parts.headers, body)
+    }
+
+    #[test]
+    fn test_parse_header() {
+        let header: ProxyHeader = "X-Api-Key: secret".parse().unwrap();
+        assert_eq!(header.name, "x-api-key");
+        assert_eq!(header.value, Some(HeaderValue::from_static("secret")));
+        assert_eq!(header.to_string(), "x-api-key: secret");
+
+        let header: ProxyHeader = "Cookie:".parse().unwrap();
+        assert_eq!(header.value, None);
+        assert_eq!(header.to_string(), "cookie:");
+
+        assert!("X-Api-Key".parse::<ProxyHeader>().is_err());
+        assert!("Bad Name: x".parse::<ProxyHeader>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_proxy_fallback() {
+        let dir = TempDir::new().unwrap();
+        let (target, count) = upstream().await;
+        let headers = vec![
+            "X-Api-Key: secret".parse().unwrap(),
+            "Cookie:".parse().unwrap(),
+        ];
+        let fallback = Fallback::new(Upstream::new(&target).unwrap(), headers, None);
+        let app = app(&dir, fallback);
+
+        // Resources, their items and routes are mocked
+        let (status, _, body) = send(&app, "GET", "/posts").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["posts"][0]["title"], "a");
+        let (status, _, _) = send(&app, "GET", "/posts/9").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        let (_, _, body) = send(&app, "GET", "/login").await;
+        assert_eq!(body, json!({"token": "t"}));
+        assert_eq!(count.load(Ordering::SeqCst), 0);
+
+        // Anything else is forwarded
+        let (status, headers, body) = send(&app, "GET", "/orders?page=2").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["uri"], "/legacy/orders?page=2");
+        assert_eq!(body["x-forwarded-host"], "localhost:3000");
+        assert_eq!(body["x-forwarded-proto"], "http");
+        assert_eq!(body["x-api-key"], "secret");
+        assert_eq!(body["cookie"], Value::Null);
+        assert_eq!(headers[LOCATION], "http://localhost:3000/new");
+        assert!(!headers.contains_key(X_MOCKS_CACHE));
+
+        let (_, _, body) = send(&app, "DELETE", "/orders/1/items/2").await;
+        assert_eq!(body["method"], "DELETE");
+        assert_eq!(body["uri"], "/legacy/orders/1/items/2");
+        let (status, _, body) = send(&app, "POST", "/posts/1/comments").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["uri"], "/legacy/posts/1/comments");
+
+        // Internal endpoints are never forwarded
+        let (status, _, _) = send(&app, "GET", "/_mocks/unknown").await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(count.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_fallback_cache() {
+        let dir = TempDir::new().unwrap();
+        let (target, _) = upstream().await;
+        let fallback = Fallback::new(Upstream::new(&target).unwrap(), vec![], Some(60));
+        assert!(fallback.describe().ends_with("/legacy (cached 60s)"));
+        let app = app(&dir, fallback);
+
+        let (_, headers, body) = send(&app, "GET", "/orders").await;
+        assert_eq!(headers[X_MOCKS_CACHE], "MISS");
+        assert_eq!(body["n"], 1);
+        let (_, headers, body) = send(&app, "GET", "/orders").await;
+        assert_eq!(headers[X_MOCKS_CACHE], "HIT");
+        assert_eq!(body["n"], 1);
+
+        // Changes upstream invalidate the cache
+        send(&app, "POST", "/orders").await;
+        let (_, headers, body) = send(&app, "GET", "/orders").await;
+        assert_eq!(headers[X_MOCKS_CACHE], "MISS");
+        assert_eq!(body["n"], 3);
+    }
+
+    #[test]
+    fn test_matches() {
+        assert!(matches("/posts", "/posts"));
+        assert!(matches("/posts", "/posts/"));
+        assert!(matches("/posts/{id}", "/posts/1"));
+        assert!(!matches("/posts/{id}", "/posts"));
+        assert!(!matches("/posts/{id}", "/posts/1/comments"));
+        assert!(!matches("/posts", "/postsx"));
+        assert!(matches("/users/{id}/avatar", "/users/7/avatar"));
+        assert!(matches("/files/{*path}", "/files/a/b.txt"));
+        assert!(!matches("/files/{*path}", "/files"));
+    }
+
+    #[test]
+    fn test_cache_expiry() {
+        let cache = Cache {
+            ttl: Duration::from_secs(1),
+            entries: Mutex::new(HashMap::new()),
+        };
+        let forwarded = Forwarded {
+            status: axum::http::StatusCode::OK,
+            headers: HeaderMap::new(),
+            body: Bytes::from("ok"),
+        };
+        let now = Instant::now();
+        cache.put("/a".to_string(), forwarded, now);
+        assert!(cache.get("/a", now + Duration::from_millis(500)).is_some());
+        assert!(cache.get("/a", now + Duration::from_secs(2)).is_none());
+        assert!(cache.get("/a", now).is_none());
+    }
+
+    #[test]
+    fn test_is_storable() {
+        let mut headers = HeaderMap::new();
+        assert!(is_storable(&headers));
+        headers.insert(
+            CACHE_CONTROL,
+            HeaderValue::from_static("max-age=0, private"),
+        );
+        assert!(!is_storable(&headers));
+    }
+}
@@ -1,12 +1,32 @@
 pub mod delete;
+pub mod events;
 pub mod get;
 pub mod hc;
+pub mod oidc;
+pub mod openapi;
 pub mod patch;
 pub mod post;
 pub mod put;
+pub mod record;
+pub mod scenarios;
+pub mod socket;
+pub mod token;
+pub mod webhooks;
+
+use crate::server::auth::Principal;
+use axum::Extension;
+use serde_json::Value;
+
+/// Claims of the authenticated client of a request
+fn claims(principal: &Option<Extension<Principal>>) -> Option<&Value> {
+    principal
+        .as_ref()
+        .map(|Extension(principal)| &principal.claims)
+}
 
 #[cfg(test)]
 mod tests {
+    use crate::server::scenario::Scenarios;
     use crate::server::state::AppState;
     use crate::server::state::SharedState;
     use crate::storage::Storage;
@@ -14,6 +34,6 @@ mod tests {
     pub(crate) fn init_state() -> SharedState {
         let storage = Storage::new("storage.json", false)
             .unwrap_or_else(|e| panic!("Failed to init storage: {e}"));
-        AppState::new(storage)
+        AppState::new(storage, Scenarios::default())
     }
 }
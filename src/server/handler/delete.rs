@@ -1,25 +1,39 @@
 use crate::error::MocksError;
+use crate::server::auth::Principal;
+use crate::server::etag::Preconditions;
+use crate::server::handler::claims;
+use crate::server::render::{render, RequestContext};
 use crate::server::state::SharedState;
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::Json;
+use axum::{Extension, Json};
 
 pub async fn delete(
     Path((resource, id)): Path<(String, String)>,
     state: State<SharedState>,
+    principal: Option<Extension<Principal>>,
+    ctx: RequestContext,
+    preconditions: Preconditions,
 ) -> Result<impl IntoResponse, MocksError> {
     let mut state = state
         .lock()
         .map_err(|e| MocksError::Exception(e.to_string()))?;
 
-    let value = state.storage.delete(&resource, &id)?;
+    let owner = state.storage.owner(&resource, claims(&principal));
+    preconditions.check(|| {
+        let current = state.storage.get_one(&resource, &id, owner.as_ref())?;
+        Ok(render(current, &ctx))
+    })?;
+    let value = state.storage.delete(&resource, &id, owner.as_ref())?;
     Ok((StatusCode::OK, Json(value)))
 }
 
 #[cfg(test)]
 mod tests {
+    use crate::server::etag::Preconditions;
     use crate::server::handler::delete::delete;
+    use crate::server::render::RequestContext;
     use axum::extract::{Path, State};
 
     #[tokio::test]
@@ -29,6 +43,14 @@ mod tests {
             "posts".to_string(),
             "01J7BAKH37HPG116ZRRFKHBDGB".to_string(),
         ));
-        assert!(delete(path, State(state)).await.is_ok());
+        assert!(delete(
+            path,
+            State(state),
+            None,
+            RequestContext::default(),
+            Preconditions::default(),
+        )
+        .await
+        .is_ok());
     }
 }
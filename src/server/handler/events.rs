@@ -0,0 +1,223 @@
+use crate::error::MocksError;
+use crate::server::auth::{Auth, Principal};
+use crate::server::handler::claims;
+use crate::server::state::SharedState;
+use crate::storage::change::Change;
+use axum::extract::{Path, State};
+use axum::response::sse::{Event, KeepAlive, Sse};
+use axum::response::IntoResponse;
+use axum::Extension;
+use futures_util::{future, stream, Stream, StreamExt};
+use serde_json::Value;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+/// Stream the changes made to all resources
+///
+/// Like the other `/_mocks` endpoints, this stream is unauthenticated and unscoped,
+/// so with auth configured it leaves out the items an auth requirement or an owner protects.
+pub async fn events(
+    state: State<SharedState>,
+    auth: Option<Extension<Arc<Auth>>>,
+) -> Result<impl IntoResponse, MocksError> {
+    let state = state
+        .lock()
+        .map_err(|e| MocksError::Exception(e.to_string()))?;
+
+    let data = match auth {
+        Some(_) => Change::to_summary,
+        None => Change::to_value,
+    };
+    Ok(stream_changes(state.storage.subscribe(), |_| true, data))
+}
+
+/// Stream the changes made to a resource, limited to the items of the client if owned
+pub async fn resource_events(
+    Path(resource): Path<String>,
+    state: State<SharedState>,
+    principal: Option<Extension<Principal>>,
+) -> Result<impl IntoResponse, MocksError> {
+    let state = state
+        .lock()
+        .map_err(|e| MocksError::Exception(e.to_string()))?;
+
+    state.storage.get_all(&resource, None)?;
+    let owner = state.storage.owner(&resource, claims(&principal));
+    Ok(stream_changes(
+        state.storage.subscribe(),
+        move |change| {
+            change.resource == resource
+                && owner
+                    .as_ref()
+                    .map_or(true, |owner| owner.owns(&change.value))
+        },
+        Change::to_value,
+    ))
+}
+
+/// Server-Sent Events of the changes passing a filter, named after their operation
+///
+/// # Arguments
+/// * `receiver` - The changes of the storage
+/// * `filter` - Whether a change is streamed
+/// * `data` - Data of the event of a change
+fn stream_changes<F>(
+    receiver: Receiver<Change>,
+    filter: F,
+    data: fn(&Change) -> Value,
+) -> Sse<impl Stream<Item = Result<Event, Infallible>>>
+where
+    F: Fn(&Change) -> bool + Send + 'static,
+{
+    let changes = stream::unfold(receiver, |mut receiver| async move {
+        loop {
+            match receiver.recv().await {
+                Ok(change) => return Some((change, receiver)),
+                // A slow client misses the oldest changes rather than stopping
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return None,
+            }
+        }
+    });
+    let events = changes
+        .filter(move |change| future::ready(filter(change)))
+        .map(move |change| {
+            Ok(Event::default()
+                .event(change.operation.as_str())
+                .data(data(&change).to_string()))
+        });
+    Sse::new(events).keep_alive(KeepAlive::default())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::handler::tests::init_state;
+    use crate::server::scenario::Scenarios;
+    use crate::server::state::AppState;
+    use crate::storage::Storage;
+    use axum::body::Body;
+    use axum::http::header::CONTENT_TYPE;
+    use axum::response::Response;
+    use http_body_util::BodyExt;
+    use serde_json::json;
+    use std::time::Duration;
+
+    /// Data of the next event of a stream
+    async fn next_event(body: &mut Body) -> (String, Value) {
+        let frame = tokio::time::timeout(Duration::from_secs(1), body.frame())
+            .await
+            .expect("no event")
+            .unwrap()
+            .unwrap();
+        let text = String::from_utf8(frame.into_data().unwrap().to_vec()).unwrap();
+        let field = |name: &str| {
+            text.lines()
+                .find_map(|line| line.strip_prefix(&format!("{name}: ")))
+                .unwrap()
+                .to_string()
+        };
+        (
+            field("event"),
+            serde_json::from_str(&field("data")).unwrap(),
+        )
+    }
+
+    #[tokio::test]
+    async fn test_events() {
+        let state = init_state();
+        let response: Response = events(State(state.clone()), None)
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(response.headers()[CONTENT_TYPE], "text/event-stream");
+        let mut body = response.into_body();
+
+        let input = json!({"id": "01J8593X0V7Q34X011BYD92CHQ", "title": "new", "views": 0});
+        state
+            .lock()
+            .unwrap()
+            .storage
+            .insert("posts", &input, None)
+            .unwrap();
+        let (event, data) = next_event(&mut body).await;
+        assert_eq!(event, "insert");
+        assert_eq!(
+            data,
+            json!({"operation": "insert", "resource": "posts", "id": "01J8593X0V7Q34X011BYD92CHQ", "value": input})
+        );
+
+        state
+            .lock()
+            .unwrap()
+            .storage
+            .delete("posts", "01J8593X0V7Q34X011BYD92CHQ", None)
+            .unwrap();
+        let (event, data) = next_event(&mut body).await;
+        assert_eq!(event, "delete");
+        assert_eq!(data["value"]["title"], "new");
+    }
+
+    #[tokio::test]
+    async fn test_events_with_auth() {
+        let storage = Storage::from_value(&json!({
+            "todos": [],
+            "_mocks": {"auth": {"apiKey": {"keys": ["k1"]}}}
+        }));
+        let auth = Auth::load(&storage)
+            .unwrap()
+            .map(|auth| Extension(Arc::new(auth)));
+        let state = AppState::new(storage, Scenarios::default());
+        let response = events(State(state.clone()), auth)
+            .await
+            .unwrap()
+            .into_response();
+        let mut body = response.into_body();
+
+        let input = json!({"id": 1, "title": "private"});
+        state
+            .lock()
+            .unwrap()
+            .storage
+            .insert("todos", &input, None)
+            .unwrap();
+        // Items are left out of the unauthenticated stream
+        let (_, data) = next_event(&mut body).await;
+        assert_eq!(
+            data,
+            json!({"operation": "insert", "resource": "todos", "id": 1})
+        );
+    }
+
+    #[tokio::test]
+    async fn test_resource_events() {
+        let state = init_state();
+        let response = resource_events(Path("profile".to_string()), State(state.clone()), None)
+            .await
+            .unwrap()
+            .into_response();
+        let mut body = response.into_body();
+
+        {
+            let mut state = state.lock().unwrap();
+            let input = json!({"id": "01J8593X0V7Q34X011BYD92CHR", "title": "other"});
+            state.storage.insert("posts", &input, None).unwrap();
+            let input = json!({"name": "John Smith", "age": 26});
+            state.storage.update_one("profile", &input).unwrap();
+        }
+        // Only changes of the resource are streamed
+        let (event, data) = next_event(&mut body).await;
+        assert_eq!(event, "update");
+        assert_eq!(data["resource"], "profile");
+        assert_eq!(data["id"], Value::Null);
+        assert_eq!(data["value"]["age"], 26);
+
+        let error = resource_events(Path("unknown".to_string()), State(state), None)
+            .await
+            .err()
+            .unwrap();
+        assert_eq!(error, MocksError::ResourceNotFound);
+    }
+}
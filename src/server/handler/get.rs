@@ -1,55 +1,80 @@
 use crate::error::MocksError;
+use crate::server::auth::Principal;
+use crate::server::etag::Preconditions;
+use crate::server::handler::claims;
+use crate::server::render::{query_references, render, RequestContext};
 use crate::server::state::SharedState;
 use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::Json;
+use axum::Extension;
 use serde_json::json;
 use std::collections::HashMap;
 
 pub async fn get_all(
     Path(resource): Path<String>,
-    Query(params): Query<HashMap<String, String>>,
+    Query(mut params): Query<HashMap<String, String>>,
     state: State<SharedState>,
+    principal: Option<Extension<Principal>>,
+    ctx: RequestContext,
+    preconditions: Preconditions,
 ) -> Result<impl IntoResponse, MocksError> {
     let state = state
         .lock()
         .map_err(|e| MocksError::Exception(e.to_string()))?;
 
+    let owner = state.storage.owner(&resource, claims(&principal));
+    let value = state.storage.get_all(&resource, owner.as_ref())?;
+    // Query parameters used by templated values are not search filters
+    for name in query_references(&value) {
+        params.remove(&name);
+    }
     let value = if params.is_empty() {
-        state.storage.get_all(&resource)?
+        value
     } else {
-        state.storage.get_all_with_filter(&resource, &params)?
+        state
+            .storage
+            .get_all_with_filter(&resource, &params, owner.as_ref())?
     };
+    let rendered = render(value, &ctx);
     let response = json!({
-        resource: value
+        resource: rendered.clone()
     });
 
-    Ok((StatusCode::OK, Json(response)))
+    Ok(preconditions.respond(&rendered, response))
 }
 
 pub async fn get_one(
     Path((resource, id)): Path<(String, String)>,
-    Query(params): Query<HashMap<String, String>>,
+    Query(mut params): Query<HashMap<String, String>>,
     state: State<SharedState>,
+    principal: Option<Extension<Principal>>,
+    ctx: RequestContext,
+    preconditions: Preconditions,
 ) -> Result<impl IntoResponse, MocksError> {
-    // Prohibit query parameters for ID endpoints
-    if !params.is_empty() {
-        return Err(MocksError::QueryParamsNotAllowed);
-    }
     let state = state
         .lock()
         .map_err(|e| MocksError::Exception(e.to_string()))?;
 
-    let value = state.storage.get_one(&resource, &id)?;
-    Ok((StatusCode::OK, Json(value)))
+    let owner = state.storage.owner(&resource, claims(&principal));
+    let value = state.storage.get_one(&resource, &id, owner.as_ref())?;
+    // Prohibit query parameters for ID endpoints, except those used by templated values
+    for name in query_references(&value) {
+        params.remove(&name);
+    }
+    if !params.is_empty() {
+        return Err(MocksError::QueryParamsNotAllowed);
+    }
+    let rendered = render(value, &ctx);
+    Ok(preconditions.respond(&rendered, rendered.clone()))
 }
 
 #[cfg(test)]
 mod tests {
     use crate::error::MocksError;
+    use crate::server::etag::Preconditions;
     use crate::server::handler::get::{get_all, get_one};
     use crate::server::handler::tests::init_state;
+    use crate::server::render::RequestContext;
     use axum::extract::{Path, Query, State};
     use std::collections::HashMap;
 
@@ -58,7 +83,16 @@ mod tests {
         let state = init_state();
         let path: Path<String> = Path("posts".to_string());
         let query: Query<HashMap<String, String>> = Query(HashMap::new());
-        assert!(get_all(path, query, State(state)).await.is_ok());
+        assert!(get_all(
+            path,
+            query,
+            State(state),
+            None,
+            RequestContext::default(),
+            Preconditions::default()
+        )
+        .await
+        .is_ok());
     }
 
     #[tokio::test]
@@ -68,7 +102,16 @@ mod tests {
         let mut params = HashMap::new();
         params.insert("title.contains".to_string(), "post".to_string());
         let query: Query<HashMap<String, String>> = Query(params);
-        assert!(get_all(path, query, State(state)).await.is_ok());
+        assert!(get_all(
+            path,
+            query,
+            State(state),
+            None,
+            RequestContext::default(),
+            Preconditions::default()
+        )
+        .await
+        .is_ok());
     }
 
     #[tokio::test]
@@ -79,7 +122,16 @@ mod tests {
             "01J7BAKH37HPG116ZRRFKHBDGB".to_string(),
         ));
         let query: Query<HashMap<String, String>> = Query(HashMap::new());
-        assert!(get_one(path, query, State(state)).await.is_ok());
+        assert!(get_one(
+            path,
+            query,
+            State(state),
+            None,
+            RequestContext::default(),
+            Preconditions::default()
+        )
+        .await
+        .is_ok());
     }
 
     #[tokio::test]
@@ -93,11 +145,129 @@ mod tests {
         params.insert("name".to_string(), "test".to_string());
         let query: Query<HashMap<String, String>> = Query(params);
 
-        match get_one(path, query, State(state)).await {
+        match get_one(
+            path,
+            query,
+            State(state),
+            None,
+            RequestContext::default(),
+            Preconditions::default(),
+        )
+        .await
+        {
             Err(MocksError::QueryParamsNotAllowed) => {
                 // Expected error
             }
             _ => panic!("Expected QueryParamsNotAllowed error"),
         }
     }
+
+    #[tokio::test]
+    async fn test_get_renders_templated_values() {
+        use crate::server::scenario::Scenarios;
+        use crate::server::state::AppState;
+        use crate::storage::Storage;
+        use axum::http::{HeaderMap, HeaderValue};
+        use axum::response::IntoResponse;
+        use http_body_util::BodyExt;
+        use serde_json::{json, Value};
+
+        let data = json!({"greetings": [{"id": 1, "text": "Hi {{request.query.name}}", "by": "{{request.header.X-User}}"}]});
+        let storage = Storage::from_value(&data);
+        let state = AppState::new(storage, Scenarios::default());
+
+        let mut headers = HeaderMap::new();
+        headers.insert("x-user", HeaderValue::from_static("alice"));
+        let ctx = RequestContext {
+            query: HashMap::from([("name".to_string(), "bob".to_string())]),
+            headers,
+            ..Default::default()
+        };
+        let params = ctx.query.clone();
+
+        let res = get_one(
+            Path(("greetings".to_string(), "1".to_string())),
+            Query(params.clone()),
+            State(state.clone()),
+            None,
+            ctx.clone(),
+            Preconditions::default(),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let value: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value, json!({"id": 1, "text": "Hi bob", "by": "alice"}));
+
+        let res = get_all(
+            Path("greetings".to_string()),
+            Query(params),
+            State(state),
+            None,
+            ctx,
+            Preconditions::default(),
+        )
+        .await
+        .unwrap()
+        .into_response();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        let value: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(value["greetings"][0]["text"], "Hi bob");
+    }
+
+    #[tokio::test]
+    async fn test_get_tags_rendered_values() {
+        use crate::server::scenario::Scenarios;
+        use crate::server::state::AppState;
+        use crate::storage::Storage;
+        use axum::extract::FromRequestParts;
+        use axum::http::header::{ETAG, IF_NONE_MATCH};
+        use axum::http::{Request, StatusCode};
+        use axum::response::IntoResponse;
+        use serde_json::json;
+
+        let data = json!({"greetings": [{"id": 1, "text": "Hi {{request.query.name}}"}]});
+        let storage = Storage::from_value(&data);
+        let state = AppState::new(storage, Scenarios::default());
+
+        let get = |name: &str, if_none_match: Option<String>| {
+            let state = state.clone();
+            let query = HashMap::from([("name".to_string(), name.to_string())]);
+            async move {
+                let mut request = Request::builder();
+                if let Some(tag) = if_none_match {
+                    request = request.header(IF_NONE_MATCH, tag);
+                }
+                let (mut parts, _) = request.body(()).unwrap().into_parts();
+                let preconditions = Preconditions::from_request_parts(&mut parts, &())
+                    .await
+                    .unwrap();
+                let ctx = RequestContext {
+                    query: query.clone(),
+                    ..Default::default()
+                };
+                get_one(
+                    Path(("greetings".to_string(), "1".to_string())),
+                    Query(query),
+                    State(state),
+                    None,
+                    ctx,
+                    preconditions,
+                )
+                .await
+                .unwrap()
+                .into_response()
+            }
+        };
+
+        let res = get("bob", None).await;
+        let tag = res.headers()[ETAG].to_str().unwrap().to_string();
+        let res = get("bob", Some(tag.clone())).await;
+        assert_eq!(res.status(), StatusCode::NOT_MODIFIED);
+        // The stored value is the same, but the client's copy is stale
+        let res = get("carol", Some(tag.clone())).await;
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_ne!(res.headers()[ETAG].to_str().unwrap(), tag);
+    }
 }
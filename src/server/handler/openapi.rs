@@ -0,0 +1,38 @@
+use crate::error::MocksError;
+use crate::openapi::generate;
+use crate::schema::Schemas;
+use crate::server::state::SharedState;
+use axum::extract::State;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use std::sync::Arc;
+
+pub async fn openapi(
+    state: State<SharedState>,
+    schemas: Option<Extension<Arc<Schemas>>>,
+) -> Result<impl IntoResponse, MocksError> {
+    let state = state
+        .lock()
+        .map_err(|e| MocksError::Exception(e.to_string()))?;
+
+    let schemas = schemas.map(|Extension(s)| s).unwrap_or_default();
+    let document = generate(&state.storage, &schemas);
+    Ok((StatusCode::OK, Json(document)))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::handler::openapi::openapi;
+    use crate::server::handler::tests::init_state;
+    use axum::extract::State;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+
+    #[tokio::test]
+    async fn test_openapi() {
+        let state = init_state();
+        let resp = openapi(State(state), None).await.into_response();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+}
@@ -1,42 +1,69 @@
 use crate::error::MocksError;
+use crate::server::auth::Principal;
 use crate::server::context::Payload;
+use crate::server::etag::{with_etag, Preconditions};
+use crate::server::handler::claims;
+use crate::server::render::{render, RequestContext};
 use crate::server::state::SharedState;
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::Json;
+use axum::Extension;
 
 pub async fn patch(
     Path((resource, id)): Path<(String, String)>,
     state: State<SharedState>,
+    principal: Option<Extension<Principal>>,
+    ctx: RequestContext,
+    preconditions: Preconditions,
     Payload(input): Payload,
 ) -> Result<impl IntoResponse, MocksError> {
     let mut state = state
         .lock()
         .map_err(|e| MocksError::Exception(e.to_string()))?;
 
-    let value = state.storage.update(&resource, &id, &input)?;
-    Ok((StatusCode::OK, Json(value)))
+    let owner = state.storage.owner(&resource, claims(&principal));
+    preconditions.check(|| {
+        let current = state.storage.get_one(&resource, &id, owner.as_ref())?;
+        Ok(render(current, &ctx))
+    })?;
+    let value = state
+        .storage
+        .update(&resource, &id, &input, owner.as_ref())?;
+    Ok(with_etag(
+        StatusCode::OK,
+        &render(value.clone(), &ctx),
+        value,
+    ))
 }
 
 pub async fn patch_one(
     Path(resource): Path<String>,
     state: State<SharedState>,
+    ctx: RequestContext,
+    preconditions: Preconditions,
     Payload(input): Payload,
 ) -> Result<impl IntoResponse, MocksError> {
     let mut state = state
         .lock()
         .map_err(|e| MocksError::Exception(e.to_string()))?;
 
+    preconditions.check(|| Ok(render(state.storage.get_all(&resource, None)?, &ctx)))?;
     let value = state.storage.update_one(&resource, &input)?;
-    Ok((StatusCode::OK, Json(value)))
+    Ok(with_etag(
+        StatusCode::OK,
+        &render(value.clone(), &ctx),
+        value,
+    ))
 }
 
 #[cfg(test)]
 mod tests {
     use crate::server::context::Payload;
+    use crate::server::etag::Preconditions;
     use crate::server::handler::patch::{patch, patch_one};
     use crate::server::handler::tests::init_state;
+    use crate::server::render::RequestContext;
     use axum::extract::{Path, State};
     use serde_json::json;
 
@@ -48,7 +75,16 @@ mod tests {
             "01J7BAKH37HPG116ZRRFKHBDGB".to_string(),
         ));
         let payload = json!({"title":"patched post","views":200});
-        assert!(patch(path, State(state), Payload(payload)).await.is_ok());
+        assert!(patch(
+            path,
+            State(state),
+            None,
+            RequestContext::default(),
+            Preconditions::default(),
+            Payload(payload)
+        )
+        .await
+        .is_ok());
     }
 
     #[tokio::test]
@@ -56,8 +92,14 @@ mod tests {
         let state = init_state();
         let path: Path<String> = Path("profile".to_string());
         let payload = json!({"name":"Jane Smith","age":30});
-        assert!(patch_one(path, State(state), Payload(payload))
-            .await
-            .is_ok());
+        assert!(patch_one(
+            path,
+            State(state),
+            RequestContext::default(),
+            Preconditions::default(),
+            Payload(payload)
+        )
+        .await
+        .is_ok());
     }
 }
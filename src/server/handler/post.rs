@@ -1,22 +1,33 @@
 use crate::error::MocksError;
+use crate::server::auth::Principal;
 use crate::server::context::PayloadWithId;
+use crate::server::etag::with_etag;
+use crate::server::handler::claims;
+use crate::server::render::{render, RequestContext};
 use crate::server::state::SharedState;
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::Json;
+use axum::Extension;
 
 pub async fn post(
     Path(resource): Path<String>,
     state: State<SharedState>,
+    principal: Option<Extension<Principal>>,
+    ctx: RequestContext,
     PayloadWithId(input): PayloadWithId,
 ) -> Result<impl IntoResponse, MocksError> {
     let mut state = state
         .lock()
         .map_err(|e| MocksError::Exception(e.to_string()))?;
 
-    let value = state.storage.insert(&resource, &input)?;
-    Ok((StatusCode::CREATED, Json(value)))
+    let owner = state.storage.owner(&resource, claims(&principal));
+    let value = state.storage.insert(&resource, &input, owner.as_ref())?;
+    Ok(with_etag(
+        StatusCode::CREATED,
+        &render(value.clone(), &ctx),
+        value,
+    ))
 }
 
 #[cfg(test)]
@@ -24,6 +35,7 @@ mod tests {
     use crate::server::context::PayloadWithId;
     use crate::server::handler::post::post;
     use crate::server::handler::tests::init_state;
+    use crate::server::render::RequestContext;
     use axum::extract::{Path, State};
     use serde_json::json;
 
@@ -32,8 +44,14 @@ mod tests {
         let state = init_state();
         let path: Path<String> = Path("posts".to_string());
         let payload = json!({"id":"01J8593X0V7Q34X011BYD92CHP","title":"posted post","views":0});
-        assert!(post(path, State(state), PayloadWithId(payload))
-            .await
-            .is_ok());
+        assert!(post(
+            path,
+            State(state),
+            None,
+            RequestContext::default(),
+            PayloadWithId(payload),
+        )
+        .await
+        .is_ok());
     }
 }
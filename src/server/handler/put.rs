@@ -1,42 +1,69 @@
 use crate::error::MocksError;
+use crate::server::auth::Principal;
 use crate::server::context::PayloadWithId;
+use crate::server::etag::{with_etag, Preconditions};
+use crate::server::handler::claims;
+use crate::server::render::{render, RequestContext};
 use crate::server::state::SharedState;
 use axum::extract::{Path, State};
 use axum::http::StatusCode;
 use axum::response::IntoResponse;
-use axum::Json;
+use axum::Extension;
 
 pub async fn put(
     Path((resource, id)): Path<(String, String)>,
     state: State<SharedState>,
+    principal: Option<Extension<Principal>>,
+    ctx: RequestContext,
+    preconditions: Preconditions,
     PayloadWithId(input): PayloadWithId,
 ) -> Result<impl IntoResponse, MocksError> {
     let mut state = state
         .lock()
         .map_err(|e| MocksError::Exception(e.to_string()))?;
 
-    let value = state.storage.replace(&resource, &id, &input)?;
-    Ok((StatusCode::OK, Json(value)))
+    let owner = state.storage.owner(&resource, claims(&principal));
+    preconditions.check(|| {
+        let current = state.storage.get_one(&resource, &id, owner.as_ref())?;
+        Ok(render(current, &ctx))
+    })?;
+    let value = state
+        .storage
+        .replace(&resource, &id, &input, owner.as_ref())?;
+    Ok(with_etag(
+        StatusCode::OK,
+        &render(value.clone(), &ctx),
+        value,
+    ))
 }
 
 pub async fn put_one(
     Path(resource): Path<String>,
     state: State<SharedState>,
+    ctx: RequestContext,
+    preconditions: Preconditions,
     PayloadWithId(input): PayloadWithId,
 ) -> Result<impl IntoResponse, MocksError> {
     let mut state = state
         .lock()
         .map_err(|e| MocksError::Exception(e.to_string()))?;
 
+    preconditions.check(|| Ok(render(state.storage.get_all(&resource, None)?, &ctx)))?;
     let value = state.storage.replace_one(&resource, &input)?;
-    Ok((StatusCode::OK, Json(value)))
+    Ok(with_etag(
+        StatusCode::OK,
+        &render(value.clone(), &ctx),
+        value,
+    ))
 }
 
 #[cfg(test)]
 mod tests {
     use crate::server::context::PayloadWithId;
+    use crate::server::etag::Preconditions;
     use crate::server::handler::put::{put, put_one};
     use crate::server::handler::tests::init_state;
+    use crate::server::render::RequestContext;
     use axum::extract::{Path, State};
     use serde_json::json;
 
@@ -48,9 +75,16 @@ mod tests {
             "01J7BAKH37HPG116ZRRFKHBDGB".to_string(),
         ));
         let payload = json!({"id":"01J7BAKH37HPG116ZRRFKHBDGB","title":"putted post","views":200});
-        assert!(put(path, State(state), PayloadWithId(payload))
-            .await
-            .is_ok());
+        assert!(put(
+            path,
+            State(state),
+            None,
+            RequestContext::default(),
+            Preconditions::default(),
+            PayloadWithId(payload)
+        )
+        .await
+        .is_ok());
     }
 
     #[tokio::test]
@@ -58,8 +92,59 @@ mod tests {
         let state = init_state();
         let path: Path<String> = Path("profile".to_string());
         let payload = json!({"id":1,"name":"John Smith","age":25});
-        assert!(put_one(path, State(state), PayloadWithId(payload))
-            .await
-            .is_ok());
+        assert!(put_one(
+            path,
+            State(state),
+            RequestContext::default(),
+            Preconditions::default(),
+            PayloadWithId(payload)
+        )
+        .await
+        .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_put_if_match_templated() {
+        use crate::server::handler::get::get_one;
+        use crate::server::scenario::Scenarios;
+        use crate::server::state::AppState;
+        use crate::storage::Storage;
+        use axum::body::Body;
+        use axum::http::header::{CONTENT_TYPE, ETAG, IF_MATCH};
+        use axum::http::{Request, StatusCode};
+        use axum::routing::get;
+        use axum::Router;
+        use tower::ServiceExt;
+
+        let data = json!({"greetings": [{"id": 1, "text": "Hi {{request.header.X-User}}"}]});
+        let state = AppState::new(Storage::from_value(&data), Scenarios::default());
+        let app = Router::new()
+            .route("/{resource}/{id}", get(get_one).put(put))
+            .with_state(state);
+        let send = |method: &str, if_match: Option<&str>| {
+            let mut request = Request::builder()
+                .method(method)
+                .uri("/greetings/1")
+                .header("x-user", "alice")
+                .header(CONTENT_TYPE, "application/json");
+            if let Some(tag) = if_match {
+                request = request.header(IF_MATCH, tag);
+            }
+            let body = json!({"id": 1, "text": "Bye {{request.header.X-User}}"});
+            app.clone()
+                .oneshot(request.body(Body::from(body.to_string())).unwrap())
+        };
+
+        let res = send("GET", None).await.unwrap();
+        let tag = res.headers()[ETAG].to_str().unwrap().to_string();
+        let res = send("PUT", Some(&tag)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+
+        // The tag of the write is the one the next read returns
+        let written = res.headers()[ETAG].to_str().unwrap().to_string();
+        let res = send("GET", None).await.unwrap();
+        assert_eq!(res.headers()[ETAG].to_str().unwrap(), written);
+        let res = send("PUT", Some(&tag)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::PRECONDITION_FAILED);
     }
 }
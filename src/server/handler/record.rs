@@ -0,0 +1,113 @@
+use crate::console::{print_error, print_recorded};
+use crate::error::MocksError;
+use crate::server::proxy::Upstream;
+use crate::storage::recorder::{Exchange, Recorder};
+use axum::extract::{Request, State};
+use axum::response::{IntoResponse, Response};
+use std::sync::{Arc, Mutex};
+
+/// Upstream requests are forwarded to and the recorder of their exchanges
+pub struct Recording {
+    pub upstream: Upstream,
+    pub recorder: Mutex<Recorder>,
+}
+
+pub async fn record(
+    State(recording): State<Arc<Recording>>,
+    request: Request,
+) -> Result<Response, MocksError> {
+    let method = request.method().clone();
+    let uri = request.uri().clone();
+    let forwarded = recording.upstream.forward(request).await?;
+
+    let headers: Vec<(String, String)> = forwarded
+        .headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect();
+    let exchange = Exchange {
+        method: method.as_str(),
+        path: uri.path(),
+        query: uri.query(),
+        status: forwarded.status.as_u16(),
+        headers: &headers,
+        body: &forwarded.body,
+    };
+    let recorded = recording
+        .recorder
+        .lock()
+        .map_err(|e| MocksError::Exception(e.to_string()))?
+        .record(&exchange);
+    // A failure to record still answers the client with the upstream response
+    match recorded {
+        Ok(recorded) => print_recorded(
+            method.as_str(),
+            &uri.to_string(),
+            exchange.status,
+            &recorded.to_string(),
+        ),
+        Err(e) => print_error(&e),
+    }
+
+    Ok(forwarded.into_response())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::routing::get;
+    use axum::{Json, Router};
+    use http_body_util::BodyExt;
+    use serde_json::{json, Value};
+    use tempfile::TempDir;
+    use tokio::net::TcpListener;
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_record() {
+        let upstream = Router::new().route(
+            "/users",
+            get(|| async { Json(json!([{"id": 1, "name": "Ann"}])) }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, upstream).await.unwrap() });
+
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("storage.json");
+        let recording = Recording {
+            upstream: Upstream::new(&format!("http://{addr}")).unwrap(),
+            recorder: Mutex::new(Recorder::new(file.to_str().unwrap()).unwrap()),
+        };
+        let app = Router::new()
+            .fallback(record)
+            .with_state(Arc::new(recording));
+
+        let request = Request::builder()
+            .uri("/users")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.clone().oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        let body = response.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            json!([{"id": 1, "name": "Ann"}])
+        );
+
+        let request = Request::builder()
+            .uri("/missing")
+            .body(Body::empty())
+            .unwrap();
+        let response = app.oneshot(request).await.unwrap();
+        assert_eq!(response.status(), StatusCode::NOT_FOUND);
+
+        let stored = std::fs::read_to_string(&file).unwrap();
+        let stored: Value = serde_json::from_str(&stored).unwrap();
+        assert_eq!(stored["users"], json!([{"id": 1, "name": "Ann"}]));
+        assert_eq!(stored["_mocks"]["routes"][0]["path"], "/missing");
+        assert_eq!(stored["_mocks"]["routes"][0]["status"], 404);
+    }
+}
@@ -0,0 +1,86 @@
+use crate::error::MocksError;
+use crate::server::state::SharedState;
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::Json;
+use serde_json::Value;
+
+pub async fn get_scenarios(state: State<SharedState>) -> Result<impl IntoResponse, MocksError> {
+    let state = state
+        .lock()
+        .map_err(|e| MocksError::Exception(e.to_string()))?;
+
+    Ok((StatusCode::OK, Json(state.scenarios.to_value())))
+}
+
+pub async fn put_scenario(
+    Path(name): Path<String>,
+    state: State<SharedState>,
+    Json(input): Json<Value>,
+) -> Result<impl IntoResponse, MocksError> {
+    let mut state = state
+        .lock()
+        .map_err(|e| MocksError::Exception(e.to_string()))?;
+
+    let next = input
+        .get("state")
+        .and_then(Value::as_str)
+        .ok_or_else(|| MocksError::BadRequest("state is required.".to_string()))?;
+    state.scenarios.set(&name, next)?;
+    Ok((StatusCode::OK, Json(state.scenarios.to_value())))
+}
+
+pub async fn reset_scenarios(state: State<SharedState>) -> Result<impl IntoResponse, MocksError> {
+    let mut state = state
+        .lock()
+        .map_err(|e| MocksError::Exception(e.to_string()))?;
+
+    state.scenarios.reset();
+    Ok((StatusCode::OK, Json(state.scenarios.to_value())))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::error::MocksError;
+    use crate::server::handler::scenarios::{get_scenarios, put_scenario, reset_scenarios};
+    use crate::server::handler::tests::init_state;
+    use axum::extract::{Path, State};
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use axum::Json;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_get_scenarios() {
+        let state = init_state();
+        let resp = get_scenarios(State(state)).await.into_response();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_put_scenario() {
+        let state = init_state();
+        let result = put_scenario(
+            Path("missing".to_string()),
+            State(state.clone()),
+            Json(json!({"state": "done"})),
+        )
+        .await;
+        assert!(matches!(result, Err(MocksError::ObjectNotFound)));
+
+        let result = put_scenario(Path("missing".to_string()), State(state), Json(json!({}))).await;
+        let error = result.err().unwrap();
+        assert_eq!(
+            error,
+            MocksError::BadRequest("state is required.".to_string())
+        );
+        assert_eq!(error.into_response().status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_reset_scenarios() {
+        let state = init_state();
+        assert!(reset_scenarios(State(state)).await.is_ok());
+    }
+}
@@ -0,0 +1,517 @@
+use crate::error::MocksError;
+use crate::schema::Schemas;
+use crate::server::auth::{Auth, Principal};
+use crate::server::state::SharedState;
+use crate::storage::change::Change;
+use crate::storage::owner::Owner;
+use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
+use axum::extract::{Query, State};
+use axum::http::header::AUTHORIZATION;
+use axum::http::{HeaderMap, HeaderValue, Method};
+use axum::response::{IntoResponse, Response};
+use axum::Extension;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use tokio::sync::broadcast::error::RecvError;
+
+// Error codes defined by JSON-RPC 2.0
+const PARSE_ERROR: i64 = -32700;
+const INVALID_REQUEST: i64 = -32600;
+const METHOD_NOT_FOUND: i64 = -32601;
+const INVALID_PARAMS: i64 = -32602;
+
+const METHODS: [&str; 7] = [
+    "subscribe",
+    "unsubscribe",
+    "get",
+    "insert",
+    "replace",
+    "update",
+    "delete",
+];
+
+/// Accept a WebSocket mirroring resource changes and taking CRUD commands
+///
+/// With auth configured, the client authenticates with the headers of the upgrade request,
+/// or an `access_token` query parameter as browsers cannot set them.
+/// Its commands are then authorized and scoped to its items like HTTP requests.
+pub async fn socket(
+    ws: WebSocketUpgrade,
+    State(state): State<SharedState>,
+    Query(params): Query<HashMap<String, String>>,
+    mut headers: HeaderMap,
+    auth: Option<Extension<Arc<Auth>>>,
+    schemas: Option<Extension<Arc<Schemas>>>,
+) -> Response {
+    let auth = auth.map(|Extension(auth)| auth);
+    let schemas = schemas.map(|Extension(schemas)| schemas);
+
+    if let Some(value) = params
+        .get("access_token")
+        .and_then(|token| HeaderValue::from_str(&format!("Bearer {token}")).ok())
+    {
+        headers.insert(AUTHORIZATION, value);
+    }
+    let principal = match auth.as_ref().map(|auth| auth.principal(&headers)) {
+        Some(Ok(principal)) => principal,
+        Some(Err(rejection)) => return rejection.into_response(),
+        None => None,
+    };
+
+    let session = Session::new(state, schemas, auth, principal);
+    ws.on_upgrade(move |socket| serve(socket, session))
+}
+
+async fn serve(mut socket: WebSocket, mut session: Session) {
+    let Ok(mut changes) = session.subscribe() else {
+        return;
+    };
+    loop {
+        let outgoing = tokio::select! {
+            message = socket.recv() => match message {
+                Some(Ok(Message::Text(text))) => session.handle(text.as_str()),
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                // Pings are answered by the socket itself
+                Some(Ok(_)) => None,
+            },
+            change = changes.recv() => match change {
+                Ok(change) => session.notification(&change),
+                // A slow client misses the oldest changes rather than being closed
+                Err(RecvError::Lagged(_)) => None,
+                Err(RecvError::Closed) => break,
+            },
+        };
+        if let Some(outgoing) = outgoing {
+            if socket
+                .send(Message::Text(outgoing.to_string().into()))
+                .await
+                .is_err()
+            {
+                break;
+            }
+        }
+    }
+}
+
+/// State of a WebSocket connection: its client and the resources it subscribed to
+struct Session {
+    state: SharedState,
+    schemas: Option<Arc<Schemas>>,
+    auth: Option<Arc<Auth>>,
+    principal: Option<Principal>,
+    /// Subscribed resources, with the owner their notifications are limited to
+    subscriptions: BTreeMap<String, Option<Owner>>,
+}
+
+/// Error of a JSON-RPC call, coded after the HTTP status for storage errors
+#[derive(Debug, PartialEq)]
+struct RpcError {
+    code: i64,
+    message: String,
+    data: Option<Value>,
+}
+
+impl Session {
+    fn new(
+        state: SharedState,
+        schemas: Option<Arc<Schemas>>,
+        auth: Option<Arc<Auth>>,
+        principal: Option<Principal>,
+    ) -> Self {
+        Session {
+            state,
+            schemas,
+            auth,
+            principal,
+            subscriptions: BTreeMap::new(),
+        }
+    }
+
+    fn subscribe(&self) -> Result<tokio::sync::broadcast::Receiver<Change>, MocksError> {
+        let state = self
+            .state
+            .lock()
+            .map_err(|e| MocksError::Exception(e.to_string()))?;
+        Ok(state.storage.subscribe())
+    }
+
+    /// Handle a JSON-RPC request, returning its response unless it is a notification
+    fn handle(&mut self, text: &str) -> Option<Value> {
+        let request: Value = match serde_json::from_str(text) {
+            Ok(request) => request,
+            Err(e) => return Some(failure(Value::Null, RpcError::new(PARSE_ERROR, e))),
+        };
+        let id = request.get("id").cloned();
+        let Some(method) = request.get("method").and_then(Value::as_str) else {
+            let error = RpcError::new(INVALID_REQUEST, "method is required");
+            return Some(failure(id.unwrap_or(Value::Null), error));
+        };
+        let params = match request.get("params") {
+            None => Map::new(),
+            Some(Value::Object(params)) => params.clone(),
+            Some(_) => {
+                let error = RpcError::new(INVALID_PARAMS, "params must be an object");
+                return Some(failure(id.unwrap_or(Value::Null), error));
+            }
+        };
+
+        let result = self.call(method, &params);
+        let id = id?;
+        Some(match result {
+            Ok(result) => json!({"jsonrpc": "2.0", "id": id, "result": result}),
+            Err(error) => failure(id, error),
+        })
+    }
+
+    /// Notification of a change to a subscribed resource, limited to the client's items if owned
+    fn notification(&self, change: &Change) -> Option<Value> {
+        let owner = self.subscriptions.get(&change.resource)?;
+        if owner
+            .as_ref()
+            .is_some_and(|owner| !owner.owns(&change.value))
+        {
+            return None;
+        }
+        Some(json!({
+            "jsonrpc": "2.0",
+            "method": change.operation.as_str(),
+            "params": change.to_value(),
+        }))
+    }
+
+    fn call(&mut self, method: &str, params: &Map<String, Value>) -> Result<Value, RpcError> {
+        if !METHODS.contains(&method) {
+            let message = format!("Unknown method: {method}");
+            return Err(RpcError::new(METHOD_NOT_FOUND, message));
+        }
+        let resource = &string_param(params, "resource")?;
+        let id = match params.get("id") {
+            Some(_) => Some(string_param(params, "id")?),
+            None => None,
+        };
+        if let (Some(auth), Some(http_method)) = (&self.auth, http_method(method)) {
+            let path = match &id {
+                Some(id) => format!("/{resource}/{id}"),
+                None => format!("/{resource}"),
+            };
+            auth.authorize(&http_method, &path, self.principal.as_ref())?;
+        }
+        let mut state = self
+            .state
+            .lock()
+            .map_err(|e| MocksError::Exception(e.to_string()))?;
+        let storage = &mut state.storage;
+        let owner = storage.owner(resource, self.principal.as_ref().map(|p| &p.claims));
+
+        match method {
+            "subscribe" => {
+                storage.get_all(resource, None)?;
+                self.subscriptions.insert(resource.to_string(), owner);
+                Ok(json!({"subscriptions": self.subscriptions.keys().collect::<Vec<_>>()}))
+            }
+            "unsubscribe" => {
+                self.subscriptions.remove(resource);
+                Ok(json!({"subscriptions": self.subscriptions.keys().collect::<Vec<_>>()}))
+            }
+            "get" => match id {
+                Some(id) => Ok(storage.get_one(resource, &id, owner.as_ref())?),
+                None => Ok(storage.get_all(resource, owner.as_ref())?),
+            },
+            "insert" => {
+                let value = self.value_param(params, resource, false)?;
+                if value.get("id").is_none() {
+                    return Err(RpcError::new(INVALID_PARAMS, "value.id is required"));
+                }
+                Ok(storage.insert(resource, value, owner.as_ref())?)
+            }
+            "replace" => {
+                let value = self.value_param(params, resource, false)?;
+                match id {
+                    Some(id) => Ok(storage.replace(resource, &id, value, owner.as_ref())?),
+                    None => Ok(storage.replace_one(resource, value)?),
+                }
+            }
+            "update" => {
+                let value = self.value_param(params, resource, true)?;
+                match id {
+                    Some(id) => Ok(storage.update(resource, &id, value, owner.as_ref())?),
+                    None => Ok(storage.update_one(resource, value)?),
+                }
+            }
+            "delete" => {
+                let id = id.ok_or_else(|| RpcError::new(INVALID_PARAMS, "id is required"))?;
+                Ok(storage.delete(resource, &id, owner.as_ref())?)
+            }
+            _ => unreachable!("methods are checked above"),
+        }
+    }
+
+    /// The object given as `value`, validated against the resource schema
+    fn value_param<'a>(
+        &self,
+        params: &'a Map<String, Value>,
+        resource: &str,
+        partial: bool,
+    ) -> Result<&'a Value, RpcError> {
+        let value = params
+            .get("value")
+            .filter(|value| value.is_object())
+            .ok_or_else(|| RpcError::new(INVALID_PARAMS, "value must be an object"))?;
+        if let Some(schemas) = &self.schemas {
+            schemas
+                .validate(resource, value, partial)
+                .map_err(|violations| RpcError {
+                    code: INVALID_PARAMS,
+                    message: "Value does not match the resource schema.".to_string(),
+                    data: Some(json!({
+                        "violations": violations.iter().map(|v| v.to_value()).collect::<Vec<_>>()
+                    })),
+                })?;
+        }
+        Ok(value)
+    }
+}
+
+impl RpcError {
+    fn new(code: i64, message: impl ToString) -> Self {
+        RpcError {
+            code,
+            message: message.to_string(),
+            data: None,
+        }
+    }
+}
+
+impl From<MocksError> for RpcError {
+    fn from(error: MocksError) -> Self {
+        RpcError::new(i64::from(error.status().as_u16()), error)
+    }
+}
+
+/// HTTP method whose auth requirement applies to a command
+fn http_method(method: &str) -> Option<Method> {
+    match method {
+        "subscribe" | "get" => Some(Method::GET),
+        "insert" => Some(Method::POST),
+        "replace" => Some(Method::PUT),
+        "update" => Some(Method::PATCH),
+        "delete" => Some(Method::DELETE),
+        _ => None,
+    }
+}
+
+/// A string parameter, numbers being accepted as IDs
+fn string_param(params: &Map<String, Value>, name: &str) -> Result<String, RpcError> {
+    match params.get(name) {
+        Some(Value::String(value)) => Ok(value.clone()),
+        Some(Value::Number(value)) => Ok(value.to_string()),
+        _ => Err(RpcError::new(
+            INVALID_PARAMS,
+            format!("{name} must be a string"),
+        )),
+    }
+}
+
+fn failure(id: Value, error: RpcError) -> Value {
+    let mut body = json!({"code": error.code, "message": error.message});
+    if let Some(data) = error.data {
+        body["data"] = data;
+    }
+    json!({"jsonrpc": "2.0", "id": id, "error": body})
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::auth::Scheme;
+    use crate::server::handler::tests::init_state;
+    use crate::server::scenario::Scenarios;
+    use crate::server::state::AppState;
+    use crate::storage::Storage;
+
+    fn session() -> Session {
+        Session::new(init_state(), None, None, None)
+    }
+
+    /// A session on owned todos and admin reports, as the client with these claims
+    fn authenticated(claims: Option<Value>) -> Session {
+        let storage = Storage::from_value(&json!({
+            "todos": [{"id": 1, "user_id": 1}, {"id": 2, "user_id": 2}],
+            "reports": [],
+            "_mocks": {
+                "auth": {"apiKey": {"keys": ["k1"]}},
+                "resources": {
+                    "todos": {"owner": "user_id"},
+                    "reports": {"auth": {"claims": {"role": "admin"}}}
+                }
+            }
+        }));
+        let auth = Auth::load(&storage).unwrap().map(Arc::new);
+        let principal = claims.map(|claims| Principal {
+            scheme: Scheme::Bearer,
+            claims,
+        });
+        Session::new(
+            AppState::new(storage, Scenarios::default()),
+            None,
+            auth,
+            principal,
+        )
+    }
+
+    #[test]
+    fn test_subscribe() {
+        let mut session = session();
+        let response = session.handle(
+            r#"{"jsonrpc": "2.0", "id": 1, "method": "subscribe", "params": {"resource": "posts"}}"#,
+        );
+        assert_eq!(
+            response,
+            Some(json!({"jsonrpc": "2.0", "id": 1, "result": {"subscriptions": ["posts"]}}))
+        );
+
+        let change = Change {
+            operation: crate::storage::change::Operation::Insert,
+            resource: "posts".to_string(),
+            id: json!("1"),
+            value: json!({"id": "1"}),
+        };
+        let notification = session.notification(&change).unwrap();
+        assert_eq!(notification["method"], "insert");
+        assert_eq!(notification["params"], change.to_value());
+
+        let change = Change {
+            resource: "comments".to_string(),
+            ..change
+        };
+        assert_eq!(session.notification(&change), None);
+
+        let response = session.handle(
+            r#"{"jsonrpc": "2.0", "id": 2, "method": "subscribe", "params": {"resource": "unknown"}}"#,
+        );
+        assert_eq!(response.unwrap()["error"]["code"], 404);
+
+        let response = session.handle(
+            r#"{"jsonrpc": "2.0", "id": 3, "method": "unsubscribe", "params": {"resource": "posts"}}"#,
+        );
+        assert_eq!(response.unwrap()["result"]["subscriptions"], json!([]));
+    }
+
+    #[test]
+    fn test_commands() {
+        let mut session = session();
+        let mut call = |method: &str, params: Value| {
+            let request = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+            session.handle(&request.to_string()).unwrap()
+        };
+
+        let value = json!({"id": "01J8593X0V7Q34X011BYD92CHS", "title": "socket", "views": 0});
+        let response = call("insert", json!({"resource": "posts", "value": value}));
+        assert_eq!(response["result"], value);
+
+        let params =
+            json!({"resource": "posts", "id": "01J8593X0V7Q34X011BYD92CHS", "value": {"views": 1}});
+        let response = call("update", params);
+        assert_eq!(response["result"]["views"], 1);
+
+        let params = json!({"resource": "posts", "id": "01J8593X0V7Q34X011BYD92CHS"});
+        let response = call("get", params.clone());
+        assert_eq!(response["result"]["title"], "socket");
+
+        let response = call("delete", params.clone());
+        assert_eq!(response["result"]["views"], 1);
+        let response = call("get", params);
+        assert_eq!(response["error"]["code"], 404);
+
+        let response = call(
+            "update",
+            json!({"resource": "profile", "value": {"age": 30}}),
+        );
+        assert_eq!(response["result"]["age"], 30);
+
+        let response = call(
+            "insert",
+            json!({"resource": "posts", "value": {"title": "a"}}),
+        );
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+    }
+
+    #[test]
+    fn test_auth() {
+        let call = |session: &mut Session, method: &str, params: Value| {
+            let request = json!({"jsonrpc": "2.0", "id": 1, "method": method, "params": params});
+            session.handle(&request.to_string()).unwrap()
+        };
+
+        let mut anonymous = authenticated(None);
+        let response = call(&mut anonymous, "get", json!({"resource": "todos"}));
+        assert_eq!(response["error"]["code"], 401);
+        let response = call(&mut anonymous, "subscribe", json!({"resource": "todos"}));
+        assert_eq!(response["error"]["code"], 401);
+
+        let mut alice = authenticated(Some(json!({"sub": "1"})));
+        let response = call(&mut alice, "get", json!({"resource": "reports"}));
+        assert_eq!(response["error"]["code"], 403);
+
+        // Commands are limited to the client's items
+        let response = call(&mut alice, "get", json!({"resource": "todos"}));
+        assert_eq!(response["result"], json!([{"id": 1, "user_id": 1}]));
+        for (method, params) in [
+            ("get", json!({"resource": "todos", "id": 2})),
+            (
+                "update",
+                json!({"resource": "todos", "id": 2, "value": {"done": true}}),
+            ),
+            ("delete", json!({"resource": "todos", "id": 2})),
+        ] {
+            let response = call(&mut alice, method, params);
+            assert_eq!(response["error"]["code"], 404, "{method} should be hidden");
+        }
+        let response = call(
+            &mut alice,
+            "insert",
+            json!({"resource": "todos", "value": {"id": 3, "user_id": 2}}),
+        );
+        assert_eq!(response["result"], json!({"id": 3, "user_id": 1}));
+
+        // Notifications too
+        call(&mut alice, "subscribe", json!({"resource": "todos"}));
+        let change = |user_id: i64| Change {
+            operation: crate::storage::change::Operation::Update,
+            resource: "todos".to_string(),
+            id: json!(1),
+            value: json!({"id": 1, "user_id": user_id}),
+        };
+        assert!(alice.notification(&change(1)).is_some());
+        assert_eq!(alice.notification(&change(2)), None);
+    }
+
+    #[test]
+    fn test_invalid_requests() {
+        let mut session = session();
+        let response = session.handle("not json").unwrap();
+        assert_eq!(response["id"], Value::Null);
+        assert_eq!(response["error"]["code"], PARSE_ERROR);
+
+        let response = session.handle(r#"{"jsonrpc": "2.0", "id": 1}"#).unwrap();
+        assert_eq!(response["error"]["code"], INVALID_REQUEST);
+
+        let response = session
+            .handle(r#"{"jsonrpc": "2.0", "id": 1, "method": "drop", "params": {}}"#)
+            .unwrap();
+        assert_eq!(response["error"]["code"], METHOD_NOT_FOUND);
+
+        let response = session
+            .handle(r#"{"jsonrpc": "2.0", "id": 1, "method": "get", "params": []}"#)
+            .unwrap();
+        assert_eq!(response["error"]["code"], INVALID_PARAMS);
+
+        // Notifications are carried out without a response
+        let response = session.handle(
+            r#"{"jsonrpc": "2.0", "method": "subscribe", "params": {"resource": "posts"}}"#,
+        );
+        assert_eq!(response, None);
+        assert!(session.subscriptions.contains_key("posts"));
+    }
+}
@@ -0,0 +1,95 @@
+use crate::error::MocksError;
+use crate::server::auth::Auth;
+use axum::body::Bytes;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use serde_json::{json, Map, Value};
+use std::sync::Arc;
+
+/// Mint a Bearer token carrying the claims of the request body
+pub async fn token(
+    auth: Option<Extension<Arc<Auth>>>,
+    body: Bytes,
+) -> Result<impl IntoResponse, MocksError> {
+    let jwt = auth
+        .as_ref()
+        .and_then(|Extension(auth)| auth.jwt())
+        .filter(|jwt| jwt.can_sign())
+        .ok_or(MocksError::ResourceNotFound)?;
+
+    let claims = if body.is_empty() {
+        Map::new()
+    } else {
+        match serde_json::from_slice::<Value>(&body) {
+            Ok(Value::Object(claims)) => claims,
+            _ => return Err(MocksError::InvalidRequest),
+        }
+    };
+
+    let (token, expires_in) = jwt.mint(claims)?;
+    Ok((
+        StatusCode::OK,
+        Json(json!({
+            "access_token": token,
+            "token_type": "Bearer",
+            "expires_in": expires_in,
+        })),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::server::auth::Auth;
+    use crate::server::handler::token::token;
+    use crate::storage::Storage;
+    use axum::body::Bytes;
+    use axum::http::StatusCode;
+    use axum::response::IntoResponse;
+    use axum::Extension;
+    use serde_json::{json, Value};
+    use std::sync::Arc;
+
+    fn auth(jwt: Value) -> Extension<Arc<Auth>> {
+        let data = json!({"posts": [], "_mocks": {"auth": {"jwt": jwt}}});
+        let storage = Storage::from_value(&data);
+        Extension(Arc::new(Auth::load(&storage).unwrap().unwrap()))
+    }
+
+    #[tokio::test]
+    async fn test_token() {
+        let auth = auth(json!({"secret": "s3cret", "expiresIn": 60}));
+        let resp = token(Some(auth.clone()), Bytes::from(r#"{"sub": "1"}"#))
+            .await
+            .into_response();
+        assert_eq!(resp.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(resp.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: Value = serde_json::from_slice(&body).unwrap();
+        assert_eq!(body["token_type"], "Bearer");
+        assert_eq!(body["expires_in"], 60);
+
+        let Extension(verifier) = &auth;
+        let claims = verifier
+            .jwt()
+            .unwrap()
+            .verify(body["access_token"].as_str().unwrap())
+            .unwrap();
+        assert_eq!(claims["sub"], "1");
+
+        let resp = token(Some(auth.clone()), Bytes::new())
+            .await
+            .into_response();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let resp = token(Some(auth), Bytes::from("[1]")).await.into_response();
+        assert_eq!(resp.status(), StatusCode::BAD_REQUEST);
+    }
+
+    #[tokio::test]
+    async fn test_token_without_jwt() {
+        let resp = token(None, Bytes::new()).await.into_response();
+        assert_eq!(resp.status(), StatusCode::NOT_FOUND);
+    }
+}
@@ -0,0 +1,108 @@
+use crate::error::MocksError;
+use crate::server::webhook::Webhooks;
+use axum::body::Bytes;
+use axum::extract::Path;
+use axum::http::StatusCode;
+use axum::response::IntoResponse;
+use axum::{Extension, Json};
+use serde_json::Value;
+use std::sync::Arc;
+
+pub async fn get_webhooks(
+    webhooks: Option<Extension<Arc<Webhooks>>>,
+) -> Result<impl IntoResponse, MocksError> {
+    Ok((StatusCode::OK, Json(registered(webhooks)?.to_value()?)))
+}
+
+/// Register a webhook from the JSON body
+pub async fn post_webhook(
+    webhooks: Option<Extension<Arc<Webhooks>>>,
+    body: Bytes,
+) -> Result<impl IntoResponse, MocksError> {
+    let webhooks = registered(webhooks)?;
+    let input: Value = serde_json::from_slice(&body).map_err(|_| MocksError::InvalidRequest)?;
+    Ok((StatusCode::CREATED, Json(webhooks.register(&input)?)))
+}
+
+pub async fn delete_webhook(
+    Path(id): Path<String>,
+    webhooks: Option<Extension<Arc<Webhooks>>>,
+) -> Result<impl IntoResponse, MocksError> {
+    registered(webhooks)?.unregister(&id)?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+pub async fn get_deliveries(
+    webhooks: Option<Extension<Arc<Webhooks>>>,
+) -> Result<impl IntoResponse, MocksError> {
+    Ok((StatusCode::OK, Json(registered(webhooks)?.deliveries()?)))
+}
+
+fn registered(webhooks: Option<Extension<Arc<Webhooks>>>) -> Result<Arc<Webhooks>, MocksError> {
+    webhooks
+        .map(|Extension(webhooks)| webhooks)
+        .ok_or(MocksError::ResourceNotFound)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Storage;
+    use serde_json::json;
+
+    fn webhooks() -> Option<Extension<Arc<Webhooks>>> {
+        let storage = Storage::from_value(&json!({"orders": []}));
+        Some(Extension(Arc::new(Webhooks::load(&storage).unwrap())))
+    }
+
+    #[tokio::test]
+    async fn test_post_webhook() {
+        let webhooks = webhooks();
+        let body = Bytes::from(r#"{"url": "http://localhost:4000/hooks", "resource": "orders"}"#);
+        let resp = post_webhook(webhooks.clone(), body)
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(resp.status(), StatusCode::CREATED);
+
+        let body = Bytes::from(r#"{"url": "localhost"}"#);
+        let error = post_webhook(webhooks.clone(), body).await.err().unwrap();
+        assert_eq!(
+            error,
+            MocksError::BadRequest(
+                "Invalid webhook: localhost must be an http:// or https:// URL".to_string()
+            )
+        );
+        assert_eq!(error.into_response().status(), StatusCode::BAD_REQUEST);
+
+        let result = post_webhook(webhooks.clone(), Bytes::from("{")).await;
+        assert!(matches!(result, Err(MocksError::InvalidRequest)));
+
+        let resp = get_webhooks(webhooks).await.unwrap().into_response();
+        assert_eq!(resp.status(), StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_delete_webhook() {
+        let webhooks = webhooks();
+        let body = Bytes::from(r#"{"url": "http://localhost:4000/hooks"}"#);
+        post_webhook(webhooks.clone(), body).await.unwrap();
+
+        let resp = delete_webhook(Path("1".to_string()), webhooks.clone())
+            .await
+            .unwrap()
+            .into_response();
+        assert_eq!(resp.status(), StatusCode::NO_CONTENT);
+        let result = delete_webhook(Path("1".to_string()), webhooks).await;
+        assert!(matches!(result, Err(MocksError::ObjectNotFound)));
+    }
+
+    #[tokio::test]
+    async fn test_get_deliveries() {
+        let resp = get_deliveries(webhooks()).await.unwrap().into_response();
+        assert_eq!(resp.status(), StatusCode::OK);
+
+        let result = get_deliveries(None).await;
+        assert!(matches!(result, Err(MocksError::ResourceNotFound)));
+    }
+}
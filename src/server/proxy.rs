@@ -0,0 +1,329 @@
+use crate::error::MocksError;
+use axum::body::{Body, Bytes};
+use axum::extract::Request;
+use axum::http::header::{
+    ACCEPT_ENCODING, CONNECTION, CONTENT_LENGTH, HOST, PROXY_AUTHENTICATE, PROXY_AUTHORIZATION, TE,
+    TRAILER, TRANSFER_ENCODING, UPGRADE,
+};
+use axum::http::uri::PathAndQuery;
+use axum::http::{HeaderMap, HeaderName, StatusCode, Uri};
+use axum::response::{IntoResponse, Response};
+use http_body_util::{BodyExt, Full};
+use hyper_rustls::{HttpsConnector, HttpsConnectorBuilder};
+use hyper_util::client::legacy::connect::HttpConnector;
+use hyper_util::client::legacy::Client;
+use hyper_util::rt::TokioExecutor;
+use std::sync::Arc;
+use tokio_rustls::rustls::crypto::ring::default_provider;
+
+/// Headers of a single connection, never forwarded (RFC 9110 section 7.6.1)
+const HOP_BY_HOP_HEADERS: [HeaderName; 8] = [
+    CONNECTION,
+    PROXY_AUTHENTICATE,
+    PROXY_AUTHORIZATION,
+    TE,
+    TRAILER,
+    TRANSFER_ENCODING,
+    UPGRADE,
+    HeaderName::from_static("keep-alive"),
+];
+
+/// Client of `http://` and `https://` URLs, trusting the native root certificates
+pub type HttpClient = Client<HttpsConnector<HttpConnector>, Full<Bytes>>;
+
+/// An upstream API requests are forwarded to
+#[derive(Debug, Clone)]
+pub struct Upstream {
+    client: HttpClient,
+    target: Uri,
+}
+
+/// Response of the upstream to a forwarded request, read in full
+#[derive(Debug, Clone)]
+pub struct Forwarded {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: Bytes,
+}
+
+impl Upstream {
+    /// Create a client of an upstream API
+    ///
+    /// # Arguments
+    /// * `target` - Base URL of the upstream (e.g. `https://api.example.com/v1`)
+    pub fn new(target: &str) -> Result<Upstream, MocksError> {
+        let target: Uri = target
+            .trim_end_matches('/')
+            .parse()
+            .map_err(|e| invalid(&format!("{target}: {e}")))?;
+        match target.scheme_str() {
+            Some("http") | Some("https") if target.authority().is_some() => {}
+            _ => {
+                return Err(invalid(&format!(
+                    "{target} must be an http:// or https:// URL"
+                )))
+            }
+        }
+        if target.query().is_some() {
+            return Err(invalid(&format!("{target} must not have a query")));
+        }
+
+        Ok(Upstream {
+            client: http_client()?,
+            target,
+        })
+    }
+
+    /// Base URL of the upstream
+    pub fn describe(&self) -> String {
+        self.target.to_string().trim_end_matches('/').to_string()
+    }
+
+    /// Location at the mock of a `Location` given by the upstream, if it points at the target
+    ///
+    /// # Arguments
+    /// * `location` - The location given by the upstream, absolute or relative to its host
+    /// * `origin` - Scheme and host of the mock (e.g. `http://localhost:3000`)
+    pub fn local_location(&self, location: &str, origin: &str) -> Option<String> {
+        let target = self.describe();
+        let base = self.target.path().trim_end_matches('/');
+        let path = match location.strip_prefix(&target) {
+            Some(path) => path,
+            None if location.starts_with('/') && !base.is_empty() => location.strip_prefix(base)?,
+            None => return None,
+        };
+        match path.chars().next() {
+            None | Some('/') | Some('?') | Some('#') => Some(format!("{origin}{path}")),
+            _ => None,
+        }
+    }
+
+    /// Forward a request to the upstream and read its response
+    pub async fn forward(&self, request: Request) -> Result<Forwarded, MocksError> {
+        let (parts, body) = request.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(|e| MocksError::Exception(e.to_string()))?
+            .to_bytes();
+
+        let mut forwarded = axum::http::Request::builder()
+            .method(parts.method)
+            .uri(self.url(&parts.uri)?)
+            .body(Full::new(body))
+            .map_err(|e| MocksError::Exception(e.to_string()))?;
+        let headers = forwarded.headers_mut();
+        *headers = parts.headers;
+        strip_hop_by_hop(headers);
+        // The client sets the upstream host, and bodies are recorded uncompressed
+        headers.remove(HOST);
+        headers.remove(ACCEPT_ENCODING);
+
+        let response = self
+            .client
+            .request(forwarded)
+            .await
+            .map_err(|e| MocksError::BadGateway(error_chain(&e)))?;
+        let (parts, body) = response.into_parts();
+        let body = body
+            .collect()
+            .await
+            .map_err(|e| MocksError::BadGateway(e.to_string()))?
+            .to_bytes();
+
+        let mut headers = parts.headers;
+        strip_hop_by_hop(&mut headers);
+        headers.remove(CONTENT_LENGTH);
+        Ok(Forwarded {
+            status: parts.status,
+            headers,
+            body,
+        })
+    }
+
+    /// URL of a request path at the upstream, below the path of the target
+    fn url(&self, uri: &Uri) -> Result<Uri, MocksError> {
+        let base = self.target.path().trim_end_matches('/');
+        let path_and_query = uri.path_and_query().map_or("/", PathAndQuery::as_str);
+        let path_and_query: PathAndQuery = format!("{base}{path_and_query}")
+            .parse()
+            .map_err(|e| MocksError::Exception(format!("{e}")))?;
+
+        let mut parts = self.target.clone().into_parts();
+        parts.path_and_query = Some(path_and_query);
+        Uri::from_parts(parts).map_err(|e| MocksError::Exception(e.to_string()))
+    }
+}
+
+impl IntoResponse for Forwarded {
+    fn into_response(self) -> Response {
+        (self.status, self.headers, Body::from(self.body)).into_response()
+    }
+}
+
+/// Create a client of `http://` and `https://` URLs
+pub fn http_client() -> Result<HttpClient, MocksError> {
+    let connector = HttpsConnectorBuilder::new()
+        .with_provider_and_native_roots(Arc::new(default_provider()))
+        .map_err(|e| MocksError::Exception(format!("Failed to load root certificates: {e}")))?
+        .https_or_http()
+        .enable_all_versions()
+        .build();
+    Ok(Client::builder(TokioExecutor::new()).build(connector))
+}
+
+fn strip_hop_by_hop(headers: &mut HeaderMap) {
+    // Connection also lists the headers specific to the connection
+    let listed: Vec<HeaderName> = headers
+        .get_all(CONNECTION)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|name| HeaderName::from_bytes(name.trim().as_bytes()).ok())
+        .collect();
+    for name in HOP_BY_HOP_HEADERS.iter().chain(&listed) {
+        headers.remove(name);
+    }
+}
+
+/// Message of an error with its causes, as client errors hide the reason behind "client error"
+pub fn error_chain(error: &dyn std::error::Error) -> String {
+    let mut message = error.to_string();
+    let mut source = error.source();
+    while let Some(cause) = source {
+        message = format!("{message}: {cause}");
+        source = cause.source();
+    }
+    message
+}
+
+fn invalid(message: &str) -> MocksError {
+    MocksError::InvalidArgs(format!("Invalid upstream: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::routing::any;
+    use axum::Router;
+    use tokio::net::TcpListener;
+
+    /// Serve a stand-in upstream echoing the requests it receives
+    async fn echo_upstream() -> String {
+        let app = Router::new().fallback(any(|request: Request| async move {
+            let (parts, body) = request.into_parts();
+            let body = body.collect().await.unwrap().to_bytes();
+            let echo = serde_json::json!({
+                "method": parts.method.as_str(),
+                "uri": parts.uri.to_string(),
+                "host": parts.headers.get(HOST).and_then(|v| v.to_str().ok()),
+                "x-trace": parts.headers.get("x-trace").and_then(|v| v.to_str().ok()),
+                "keep-alive": parts.headers.contains_key("keep-alive"),
+                "body": String::from_utf8_lossy(&body),
+            });
+            (
+                StatusCode::ACCEPTED,
+                [("x-upstream", "yes"), ("keep-alive", "timeout=5")],
+                echo.to_string(),
+            )
+        }));
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        format!("http://{addr}")
+    }
+
+    #[test]
+    fn test_new() {
+        let upstream = Upstream::new("https://api.example.com/v1/").unwrap();
+        assert_eq!(upstream.describe(), "https://api.example.com/v1");
+
+        assert!(Upstream::new("api.example.com").is_err());
+        assert!(Upstream::new("ftp://api.example.com").is_err());
+        assert!(Upstream::new("http://api.example.com/?a=1").is_err());
+    }
+
+    #[test]
+    fn test_url() {
+        let upstream = Upstream::new("https://api.example.com/v1").unwrap();
+        let uri: Uri = "/users/1?fields=name".parse().unwrap();
+        assert_eq!(
+            upstream.url(&uri).unwrap().to_string(),
+            "https://api.example.com/v1/users/1?fields=name"
+        );
+
+        let upstream = Upstream::new("http://localhost:8080").unwrap();
+        let uri: Uri = "/users".parse().unwrap();
+        assert_eq!(
+            upstream.url(&uri).unwrap().to_string(),
+            "http://localhost:8080/users"
+        );
+    }
+
+    #[test]
+    fn test_local_location() {
+        let origin = "http://localhost:3000";
+        let upstream = Upstream::new("https://api.example.com/v1").unwrap();
+        let location = |value| upstream.local_location(value, origin);
+        assert_eq!(
+            location("https://api.example.com/v1/users/1").as_deref(),
+            Some("http://localhost:3000/users/1")
+        );
+        assert_eq!(
+            location("/v1/users?page=2").as_deref(),
+            Some("http://localhost:3000/users?page=2")
+        );
+        assert_eq!(location("https://api.example.com/v10/users"), None);
+        assert_eq!(location("https://auth.example.com/login"), None);
+        assert_eq!(location("/login"), None);
+
+        let upstream = Upstream::new("http://localhost:8080").unwrap();
+        assert_eq!(
+            upstream
+                .local_location("http://localhost:8080/users/1", origin)
+                .as_deref(),
+            Some("http://localhost:3000/users/1")
+        );
+        // Relative to the host, the location is already right at the mock
+        assert_eq!(upstream.local_location("/users/1", origin), None);
+    }
+
+    #[tokio::test]
+    async fn test_forward() {
+        let target = echo_upstream().await;
+        let upstream = Upstream::new(&format!("{target}/api")).unwrap();
+
+        let request = axum::http::Request::builder()
+            .method("POST")
+            .uri("/users?page=2")
+            .header(HOST, "localhost:3000")
+            .header("x-trace", "abc")
+            .header("keep-alive", "timeout=5")
+            .body(Body::from("hello"))
+            .unwrap();
+        let forwarded = upstream.forward(request).await.unwrap();
+
+        assert_eq!(forwarded.status, StatusCode::ACCEPTED);
+        assert_eq!(forwarded.headers["x-upstream"], "yes");
+        assert!(!forwarded.headers.contains_key("keep-alive"));
+        let echo: serde_json::Value = serde_json::from_slice(&forwarded.body).unwrap();
+        assert_eq!(echo["method"], "POST");
+        assert_eq!(echo["uri"], "/api/users?page=2");
+        assert_eq!(echo["host"], target.trim_start_matches("http://"));
+        assert_eq!(echo["x-trace"], "abc");
+        assert_eq!(echo["keep-alive"], false);
+        assert_eq!(echo["body"], "hello");
+    }
+
+    #[tokio::test]
+    async fn test_forward_unreachable() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let upstream = Upstream::new(&format!("http://{addr}")).unwrap();
+        let request = Request::new(Body::empty());
+        let error = upstream.forward(request).await.unwrap_err();
+        assert_eq!(error.status(), StatusCode::BAD_GATEWAY);
+    }
+}
@@ -0,0 +1,345 @@
+use crate::error::MocksError;
+use crate::server::is_internal;
+use axum::extract::{ConnectInfo, Request, State};
+use axum::http::header::RETRY_AFTER;
+use axum::http::{HeaderMap, HeaderName, HeaderValue};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use std::collections::HashMap;
+use std::fmt;
+use std::net::SocketAddr;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+const LIMIT_HEADER: &str = "x-ratelimit-limit";
+const REMAINING_HEADER: &str = "x-ratelimit-remaining";
+const RESET_HEADER: &str = "x-ratelimit-reset";
+
+/// Number of requests allowed per period, written as `10/s`, `100/min` or `1000/h`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    count: u32,
+    period: Duration,
+}
+
+impl FromStr for Rate {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let (count, period) = value
+            .split_once('/')
+            .ok_or_else(|| format!("expected COUNT/PERIOD, got {value}"))?;
+        let count = count
+            .trim()
+            .parse::<u32>()
+            .ok()
+            .filter(|c| *c > 0)
+            .ok_or_else(|| format!("invalid count {count}"))?;
+        let period = match period.trim() {
+            "s" | "sec" | "second" => Duration::from_secs(1),
+            "m" | "min" | "minute" => Duration::from_secs(60),
+            "h" | "hour" => Duration::from_secs(3600),
+            other => return Err(format!("unknown period {other}, expected s, min or h")),
+        };
+        Ok(Rate { count, period })
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let period = match self.period.as_secs() {
+            1 => "s",
+            60 => "min",
+            _ => "h",
+        };
+        write!(f, "{}/{period}", self.count)
+    }
+}
+
+/// What a rate limit is counted by
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum LimitKey {
+    /// One limit shared by all clients
+    Global,
+    /// A limit per client IP address
+    Ip,
+    /// A limit per value of a header, such as an API key
+    Header(HeaderName),
+}
+
+impl FromStr for LimitKey {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "global" => Ok(LimitKey::Global),
+            "ip" => Ok(LimitKey::Ip),
+            _ => {
+                let name = value
+                    .strip_prefix("header:")
+                    .ok_or_else(|| format!("expected global, ip or header:NAME, got {value}"))?;
+                HeaderName::from_bytes(name.as_bytes())
+                    .map(LimitKey::Header)
+                    .map_err(|_| format!("invalid header name {name}"))
+            }
+        }
+    }
+}
+
+impl fmt::Display for LimitKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LimitKey::Global => write!(f, "global"),
+            LimitKey::Ip => write!(f, "ip"),
+            LimitKey::Header(name) => write!(f, "header:{name}"),
+        }
+    }
+}
+
+impl LimitKey {
+    /// Client a request is counted for
+    fn client(&self, req: &Request) -> String {
+        match self {
+            LimitKey::Global => String::new(),
+            LimitKey::Ip => req
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip().to_string())
+                .unwrap_or_default(),
+            LimitKey::Header(name) => req
+                .headers()
+                .get(name)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Bucket {
+    tokens: f64,
+    updated: Instant,
+}
+
+/// Outcome of a request against its bucket
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Decision {
+    pub allowed: bool,
+    pub limit: u32,
+    pub remaining: u32,
+    /// Time until the bucket is full again
+    pub reset: Duration,
+    /// Time until the next request is allowed
+    pub retry_after: Duration,
+}
+
+impl Decision {
+    /// Write the `X-RateLimit-*` headers, and `Retry-After` when the request is rejected
+    fn write_headers(&self, headers: &mut HeaderMap) {
+        let mut insert = |name: HeaderName, value: u64| {
+            headers.insert(name, HeaderValue::from(value));
+        };
+        insert(HeaderName::from_static(LIMIT_HEADER), self.limit.into());
+        insert(
+            HeaderName::from_static(REMAINING_HEADER),
+            self.remaining.into(),
+        );
+        insert(HeaderName::from_static(RESET_HEADER), ceil_secs(self.reset));
+        if !self.allowed {
+            insert(RETRY_AFTER, ceil_secs(self.retry_after).max(1));
+        }
+    }
+}
+
+/// Token-bucket rate limiter
+///
+/// Each client has a bucket of `burst` tokens (the rate count by default), refilled at the rate.
+/// A request takes a token, and is rejected with 429 when the bucket is empty.
+#[derive(Debug)]
+pub struct RateLimiter {
+    rate: Rate,
+    burst: u32,
+    key: LimitKey,
+    buckets: Mutex<HashMap<String, Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(rate: Rate, burst: Option<u32>, key: LimitKey) -> RateLimiter {
+        RateLimiter {
+            rate,
+            burst: burst.unwrap_or(rate.count).max(1),
+            key,
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Take a token from the bucket of a client
+    ///
+    /// # Arguments
+    /// * `client` - The client the request is counted for
+    /// * `now` - The time of the request
+    pub fn take(&self, client: &str, now: Instant) -> Decision {
+        let burst = f64::from(self.burst);
+        let per_sec = f64::from(self.rate.count) / self.rate.period.as_secs_f64();
+
+        let mut buckets = match self.buckets.lock() {
+            Ok(buckets) => buckets,
+            Err(e) => e.into_inner(),
+        };
+        let bucket = buckets.entry(client.to_string()).or_insert(Bucket {
+            tokens: burst,
+            updated: now,
+        });
+        let elapsed = now.saturating_duration_since(bucket.updated).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * per_sec).min(burst);
+        bucket.updated = now;
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+        Decision {
+            allowed,
+            limit: self.burst,
+            remaining: bucket.tokens.floor() as u32,
+            reset: Duration::from_secs_f64((burst - bucket.tokens) / per_sec),
+            retry_after: Duration::from_secs_f64((1.0 - bucket.tokens).max(0.0) / per_sec),
+        }
+    }
+
+    /// Description of the limit, e.g. `10/s (burst 20, per ip)`
+    pub fn describe(&self) -> String {
+        format!("{} (burst {}, per {})", self.rate, self.burst, self.key)
+    }
+}
+
+fn ceil_secs(duration: Duration) -> u64 {
+    duration.as_secs_f64().ceil() as u64
+}
+
+/// Middleware rejecting requests over the rate limit with 429
+pub async fn rate_limit(
+    State(limiter): State<Arc<RateLimiter>>,
+    req: Request,
+    next: Next,
+) -> Response {
+    if is_internal(req.uri().path()) {
+        return next.run(req).await;
+    }
+
+    let decision = limiter.take(&limiter.key.client(&req), Instant::now());
+    let mut response = if decision.allowed {
+        next.run(req).await
+    } else {
+        MocksError::TooManyRequests.into_response()
+    };
+    decision.write_headers(response.headers_mut());
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::body::Body;
+    use axum::http::StatusCode;
+    use axum::middleware::from_fn_with_state;
+    use axum::routing::get;
+    use axum::Router;
+    use tower::ServiceExt;
+
+    #[test]
+    fn test_parse_rate() {
+        assert_eq!("10/s".parse::<Rate>().unwrap().to_string(), "10/s");
+        assert_eq!("100/minute".parse::<Rate>().unwrap().to_string(), "100/min");
+        assert_eq!("1000/h".parse::<Rate>().unwrap().to_string(), "1000/h");
+        for invalid in ["10", "0/s", "x/s", "10/day"] {
+            assert!(
+                invalid.parse::<Rate>().is_err(),
+                "{invalid} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn test_parse_key() {
+        assert_eq!("global".parse(), Ok(LimitKey::Global));
+        assert_eq!("ip".parse(), Ok(LimitKey::Ip));
+        assert_eq!(
+            "header:X-API-Key".parse(),
+            Ok(LimitKey::Header(HeaderName::from_static("x-api-key")))
+        );
+        assert!("user".parse::<LimitKey>().is_err());
+        assert!("header:a b".parse::<LimitKey>().is_err());
+    }
+
+    #[test]
+    fn test_take() {
+        let limiter = RateLimiter::new("2/s".parse().unwrap(), None, LimitKey::Global);
+        let start = Instant::now();
+
+        let first = limiter.take("", start);
+        assert!(first.allowed);
+        assert_eq!(first.limit, 2);
+        assert_eq!(first.remaining, 1);
+        assert!(limiter.take("", start).allowed);
+
+        let rejected = limiter.take("", start);
+        assert!(!rejected.allowed);
+        assert_eq!(rejected.remaining, 0);
+        assert_eq!(rejected.retry_after, Duration::from_millis(500));
+        assert_eq!(rejected.reset, Duration::from_secs(1));
+
+        // Other clients have their own bucket
+        assert!(limiter.take("other", start).allowed);
+
+        // Half a second refills one token
+        assert!(limiter.take("", start + Duration::from_millis(500)).allowed);
+        assert!(!limiter.take("", start + Duration::from_millis(500)).allowed);
+    }
+
+    #[test]
+    fn test_take_with_burst() {
+        let limiter = RateLimiter::new("1/min".parse().unwrap(), Some(3), LimitKey::Ip);
+        let now = Instant::now();
+        let allowed = (0..5).filter(|_| limiter.take("a", now).allowed).count();
+        assert_eq!(allowed, 3);
+        assert_eq!(limiter.describe(), "1/min (burst 3, per ip)");
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit() {
+        let limiter = Arc::new(RateLimiter::new(
+            "1/h".parse().unwrap(),
+            None,
+            "header:x-api-key".parse().unwrap(),
+        ));
+        let app = Router::new()
+            .route("/posts", get(|| async { "ok" }))
+            .route("/_hc", get(|| async { "ok" }))
+            .layer(from_fn_with_state(limiter, rate_limit));
+        let send = |path: &str, key: &str| {
+            let req = Request::builder()
+                .uri(path)
+                .header("x-api-key", key)
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(req)
+        };
+
+        let res = send("/posts", "a").await.unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        assert_eq!(res.headers()[LIMIT_HEADER], "1");
+        assert_eq!(res.headers()[REMAINING_HEADER], "0");
+        assert!(res.headers().get(RETRY_AFTER).is_none());
+
+        let res = send("/posts", "a").await.unwrap();
+        assert_eq!(res.status(), StatusCode::TOO_MANY_REQUESTS);
+        assert_eq!(res.headers()[RETRY_AFTER], "3600");
+        assert_eq!(res.headers()[RESET_HEADER], "3600");
+
+        assert_eq!(send("/posts", "b").await.unwrap().status(), StatusCode::OK);
+        assert_eq!(send("/_hc", "a").await.unwrap().status(), StatusCode::OK);
+    }
+}
@@ -0,0 +1,289 @@
+use crate::fake::{encode_ulid, Faker};
+use axum::extract::{FromRequestParts, Query, RawPathParams};
+use axum::http::request::Parts;
+use axum::http::HeaderMap;
+use chrono::{SecondsFormat, Utc};
+use serde_json::Value;
+use std::collections::{HashMap, HashSet};
+use std::convert::Infallible;
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Distinguishes generators created within the same nanosecond
+static SEQUENCE: AtomicU64 = AtomicU64::new(0);
+
+/// Request details available to `{{request.*}}` placeholders in stored values
+#[derive(Debug, Clone, Default)]
+pub struct RequestContext {
+    pub method: String,
+    pub path: String,
+    /// Captures of the matched route (e.g. `id` for `/users/{id}`)
+    pub params: HashMap<String, String>,
+    pub query: HashMap<String, String>,
+    pub headers: HeaderMap,
+}
+
+impl<S> FromRequestParts<S> for RequestContext
+where
+    S: Send + Sync,
+{
+    type Rejection = Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        let params = RawPathParams::from_request_parts(parts, state)
+            .await
+            .map(|params| {
+                params
+                    .iter()
+                    .map(|(name, value)| (name.to_string(), value.to_string()))
+                    .collect()
+            })
+            .unwrap_or_default();
+        let query = Query::<Vec<(String, String)>>::try_from_uri(&parts.uri)
+            .map(|Query(query)| query.into_iter().collect())
+            .unwrap_or_default();
+        Ok(RequestContext {
+            method: parts.method.to_string(),
+            path: parts.uri.path().to_string(),
+            params,
+            query,
+            headers: parts.headers.clone(),
+        })
+    }
+}
+
+/// Render the template expressions in the strings of a stored value
+///
+/// A string made of a single expression is replaced by the value of the expression;
+/// otherwise expressions are interpolated. Unknown expressions are left untouched.
+///
+/// # Arguments
+/// * `value` - Value read from the storage
+/// * `ctx` - The request being served
+pub fn render(value: Value, ctx: &RequestContext) -> Value {
+    if !contains_expression(&value) {
+        return value;
+    }
+    let now = Utc::now();
+    let seed = now.timestamp_nanos_opt().unwrap_or_default() as u64
+        ^ SEQUENCE.fetch_add(1, Ordering::Relaxed).rotate_left(32);
+    let mut renderer = Renderer {
+        ctx,
+        now: now.to_rfc3339_opts(SecondsFormat::Millis, true),
+        millis: now.timestamp_millis(),
+        faker: Faker::new(seed),
+    };
+    renderer.render(value)
+}
+
+struct Renderer<'a> {
+    ctx: &'a RequestContext,
+    now: String,
+    millis: i64,
+    faker: Faker,
+}
+
+impl Renderer<'_> {
+    fn render(&mut self, value: Value) -> Value {
+        match value {
+            Value::String(text) if text.contains("{{") => self.render_text(text),
+            Value::Array(items) => items.into_iter().map(|v| self.render(v)).collect(),
+            Value::Object(obj) => Value::Object(
+                obj.into_iter()
+                    .map(|(key, v)| (key, self.render(v)))
+                    .collect(),
+            ),
+            value => value,
+        }
+    }
+
+    fn render_text(&mut self, text: String) -> Value {
+        if let Some(expression) = text
+            .strip_prefix("{{")
+            .and_then(|t| t.strip_suffix("}}"))
+            .filter(|e| !e.contains("{{"))
+        {
+            return match self.evaluate(expression.trim()) {
+                Some(value) => value,
+                None => Value::String(text),
+            };
+        }
+
+        let mut rendered = String::new();
+        let mut rest = text.as_str();
+        while let Some(start) = rest.find("{{") {
+            let Some(end) = rest[start..].find("}}") else {
+                break;
+            };
+            rendered.push_str(&rest[..start]);
+            let expression = &rest[start + 2..start + end];
+            match self.evaluate(expression.trim()) {
+                Some(Value::String(s)) => rendered.push_str(&s),
+                Some(Value::Null) => {}
+                Some(value) => rendered.push_str(&value.to_string()),
+                None => rendered.push_str(&rest[start..start + end + 2]),
+            }
+            rest = &rest[start + end + 2..];
+        }
+        rendered.push_str(rest);
+        Value::String(rendered)
+    }
+
+    fn evaluate(&mut self, expression: &str) -> Option<Value> {
+        let value = match expression {
+            "now" => Value::from(self.now.clone()),
+            "timestamp" => Value::from(self.millis / 1000),
+            "uuid" => Value::from(self.faker.uuid()),
+            "ulid" => {
+                let random = ((self.faker.rng.next_u64() as u128) << 16)
+                    | (self.faker.rng.next_u64() as u128 >> 48);
+                Value::from(encode_ulid(self.millis as u128, random))
+            }
+            "request.method" => Value::from(self.ctx.method.clone()),
+            "request.path" => Value::from(self.ctx.path.clone()),
+            _ => {
+                if let Some(name) = expression.strip_prefix("request.params.") {
+                    optional(self.ctx.params.get(name).cloned())
+                } else if let Some(name) = expression.strip_prefix("request.query.") {
+                    optional(self.ctx.query.get(name).cloned())
+                } else if let Some(name) = expression.strip_prefix("request.header.") {
+                    let header = self.ctx.headers.get(name);
+                    optional(header.and_then(|v| v.to_str().ok()).map(str::to_string))
+                } else {
+                    return None;
+                }
+            }
+        };
+        Some(value)
+    }
+}
+
+/// Query parameters referenced with `{{request.query.*}}` in a stored value
+///
+/// Such parameters feed the template rather than the search filters.
+pub fn query_references(value: &Value) -> HashSet<String> {
+    let mut names = HashSet::new();
+    collect_query_references(value, &mut names);
+    names
+}
+
+fn collect_query_references(value: &Value, names: &mut HashSet<String>) {
+    match value {
+        Value::String(text) => {
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("{{") {
+                let Some(end) = rest[start..].find("}}") else {
+                    break;
+                };
+                let expression = rest[start + 2..start + end].trim();
+                if let Some(name) = expression.strip_prefix("request.query.") {
+                    names.insert(name.to_string());
+                }
+                rest = &rest[start + end + 2..];
+            }
+        }
+        Value::Array(items) => items
+            .iter()
+            .for_each(|v| collect_query_references(v, names)),
+        Value::Object(obj) => obj
+            .values()
+            .for_each(|v| collect_query_references(v, names)),
+        _ => {}
+    }
+}
+
+fn optional(value: Option<String>) -> Value {
+    value.map_or(Value::Null, Value::String)
+}
+
+fn contains_expression(value: &Value) -> bool {
+    match value {
+        Value::String(text) => text.contains("{{"),
+        Value::Array(items) => items.iter().any(contains_expression),
+        Value::Object(obj) => obj.values().any(contains_expression),
+        _ => false,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::format;
+    use axum::http::HeaderValue;
+    use serde_json::json;
+
+    fn ctx() -> RequestContext {
+        let mut headers = HeaderMap::new();
+        headers.insert("x-user", HeaderValue::from_static("alice"));
+        RequestContext {
+            method: "GET".to_string(),
+            path: "/profile".to_string(),
+            params: HashMap::from([("id".to_string(), "7".to_string())]),
+            query: HashMap::from([("name".to_string(), "bob".to_string())]),
+            headers,
+        }
+    }
+
+    #[test]
+    fn test_render_generated_values() {
+        let value = render(
+            json!({"at": "{{now}}", "id": "{{ uuid }}", "key": "{{ulid}}", "ts": "{{timestamp}}"}),
+            &ctx(),
+        );
+        assert!(format::matches("date-time", value["at"].as_str().unwrap()));
+        assert!(format::matches("uuid", value["id"].as_str().unwrap()));
+        assert!(format::matches("ulid", value["key"].as_str().unwrap()));
+        assert!(value["ts"].is_i64());
+    }
+
+    #[test]
+    fn test_render_is_fresh_per_call() {
+        let first = render(json!("{{uuid}}"), &ctx());
+        let second = render(json!("{{uuid}}"), &ctx());
+        assert_ne!(first, second);
+    }
+
+    #[test]
+    fn test_render_request_values() {
+        let value = render(
+            json!({
+                "greeting": "Hello, {{request.query.name}}!",
+                "user": "{{request.header.X-User}}",
+                "missing": "{{request.header.X-Missing}}",
+                "missingText": "[{{request.query.other}}]",
+                "route": ["{{request.method}} {{request.path}}"],
+                "id": "{{request.params.id}}"
+            }),
+            &ctx(),
+        );
+        assert_eq!(
+            value,
+            json!({
+                "greeting": "Hello, bob!",
+                "user": "alice",
+                "missing": null,
+                "missingText": "[]",
+                "route": ["GET /profile"],
+                "id": "7"
+            })
+        );
+    }
+
+    #[test]
+    fn test_render_leaves_unknown_expressions() {
+        let value = json!({"a": "{{unknown}}", "b": "x {{unknown}} {{request.method}}", "c": 1});
+        assert_eq!(
+            render(value, &ctx()),
+            json!({"a": "{{unknown}}", "b": "x {{unknown}} GET", "c": 1})
+        );
+    }
+
+    #[test]
+    fn test_query_references() {
+        let value = json!([{"a": "{{request.query.name}}", "b": ["x {{ request.query.page }}"]}]);
+        assert_eq!(
+            query_references(&value),
+            HashSet::from(["name".to_string(), "page".to_string()])
+        );
+        assert!(query_references(&json!({"a": "{{now}}"})).is_empty());
+    }
+}
@@ -0,0 +1,549 @@
+use crate::error::MocksError;
+use crate::server::render::{render, RequestContext};
+use crate::server::scenario::Scenarios;
+use crate::server::state::SharedState;
+use crate::storage::Storage;
+use axum::body::{Body, Bytes};
+use axum::extract::State;
+use axum::http::header::CONTENT_TYPE;
+use axum::http::{HeaderMap, HeaderName, HeaderValue, Method, StatusCode};
+use axum::response::{IntoResponse, Response};
+use axum::routing::{on, MethodFilter, MethodRouter};
+use matcher::Conditions;
+use serde_json::Value;
+use std::fs;
+use std::path::Path;
+
+pub mod matcher;
+
+const ROUTES_KEY: &str = "routes";
+
+/// Body of a canned response
+#[derive(Debug, Clone, PartialEq)]
+pub enum CannedBody {
+    Empty,
+    /// JSON body, rendered like stored values
+    Json(Value),
+    /// Raw contents of a body file
+    Raw(Vec<u8>),
+}
+
+/// A response of a custom route
+#[derive(Debug, Clone)]
+pub struct CannedResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: CannedBody,
+    /// Scenario states set when this response is served (`transition`)
+    pub transitions: Vec<(String, String)>,
+}
+
+/// A custom route answering with canned responses
+///
+/// Declared under `_mocks.routes` in the storage file:
+///
+/// ```json
+/// {"method": "POST", "path": "/login", "status": 200, "headers": {"X-Token": "abc"}, "body": {"token": "{{uuid}}"}}
+/// ```
+///
+/// `bodyFile` (relative to the storage file) can be used instead of `body`.
+/// `responses` lists alternative responses selected by a `when` condition on the request,
+/// the first matching one winning over the route-level default.
+/// A response can move [scenarios](Scenarios) to a new state with `transition`.
+#[derive(Debug, Clone)]
+pub struct CannedRoute {
+    pub method: Method,
+    pub path: String,
+    pub rules: Vec<(Conditions, CannedResponse)>,
+    pub default: CannedResponse,
+}
+
+impl CannedRoute {
+    /// Load the routes declared in the config of a storage
+    ///
+    /// # Arguments
+    /// * `storage` - The storage whose config declares the routes
+    /// * `scenarios` - The declared scenarios the routes may read and advance
+    pub fn load(storage: &Storage, scenarios: &Scenarios) -> Result<Vec<CannedRoute>, MocksError> {
+        let Some(routes) = storage.config.get(ROUTES_KEY) else {
+            return Ok(vec![]);
+        };
+        let routes = routes.as_array().ok_or_else(|| {
+            MocksError::InvalidArgs(format!("{ROUTES_KEY} must be an array of routes."))
+        })?;
+        let base = Path::new(&storage.file).parent().unwrap_or(Path::new(""));
+
+        let mut loaded: Vec<CannedRoute> = vec![];
+        for route in routes {
+            let route = CannedRoute::parse(route, base)?;
+            if let Some(name) = route.scenarios().find(|name| !scenarios.contains(name)) {
+                return Err(invalid(&format!("scenario {name} is not declared")));
+            }
+            if loaded
+                .iter()
+                .any(|r| r.method == route.method && r.path == route.path)
+            {
+                return Err(MocksError::InvalidArgs(format!(
+                    "Duplicate route: {} {}",
+                    route.method, route.path
+                )));
+            }
+            loaded.push(route);
+        }
+        Ok(loaded)
+    }
+
+    fn parse(route: &Value, base: &Path) -> Result<CannedRoute, MocksError> {
+        let path = route
+            .get("path")
+            .and_then(Value::as_str)
+            .ok_or_else(|| invalid("path is required"))?;
+        // A leading parameter would shadow the resource endpoints
+        if !path.starts_with('/') || path.starts_with("/{") || path.starts_with("/_") {
+            return Err(invalid(&format!(
+                "{path} must start with / and a literal segment not starting with _"
+            )));
+        }
+
+        let method = route.get("method").and_then(Value::as_str).unwrap_or("GET");
+        let method = Method::from_bytes(method.to_uppercase().as_bytes())
+            .ok()
+            .filter(|method| MethodFilter::try_from(method.clone()).is_ok())
+            .ok_or_else(|| invalid(&format!("unknown method {method}")))?;
+
+        let rules = match route.get("responses") {
+            None => vec![],
+            Some(Value::Array(responses)) => responses
+                .iter()
+                .map(|response| {
+                    let when = response
+                        .get("when")
+                        .ok_or_else(|| invalid("responses require a when condition"))?;
+                    Ok((
+                        Conditions::parse(when)?,
+                        CannedResponse::parse(response, base)?,
+                    ))
+                })
+                .collect::<Result<Vec<_>, MocksError>>()?,
+            Some(_) => return Err(invalid("responses must be an array")),
+        };
+
+        Ok(CannedRoute {
+            method,
+            path: path.to_string(),
+            rules,
+            default: CannedResponse::parse(route, base)?,
+        })
+    }
+
+    /// Names of the scenarios the route reads or advances
+    fn scenarios(&self) -> impl Iterator<Item = &str> {
+        let read = self.rules.iter().flat_map(|(when, _)| when.scenarios());
+        let advanced = self
+            .rules
+            .iter()
+            .map(|(_, response)| response)
+            .chain([&self.default])
+            .flat_map(|response| response.transitions.iter().map(|(name, _)| name.as_str()));
+        read.chain(advanced)
+    }
+
+    /// Select the response to a request
+    ///
+    /// # Arguments
+    /// * `ctx` - The request being served
+    /// * `body` - The request body parsed as JSON, if any
+    /// * `scenarios` - The current scenario states
+    pub fn select(
+        &self,
+        ctx: &RequestContext,
+        body: Option<&Value>,
+        scenarios: &Scenarios,
+    ) -> &CannedResponse {
+        self.rules
+            .iter()
+            .find(|(when, _)| when.matches(ctx, body, scenarios))
+            .map_or(&self.default, |(_, response)| response)
+    }
+
+    /// Method router serving this route
+    pub fn method_router(&self) -> Result<MethodRouter<SharedState>, MocksError> {
+        let route = self.clone();
+        let filter = MethodFilter::try_from(self.method.clone())
+            .map_err(|_| invalid(&format!("unknown method {}", self.method)))?;
+        Ok(on(
+            filter,
+            move |state: State<SharedState>, ctx: RequestContext, body: Bytes| async move {
+                let body = if route.rules.iter().any(|(when, _)| when.uses_body()) {
+                    serde_json::from_slice::<Value>(&body).ok()
+                } else {
+                    None
+                };
+
+                let mut state = state
+                    .lock()
+                    .map_err(|e| MocksError::Exception(e.to_string()))?;
+                let response = route.select(&ctx, body.as_ref(), &state.scenarios);
+                for (name, next) in &response.transitions {
+                    state.scenarios.set(name, next)?;
+                }
+                Ok::<_, MocksError>(response.respond(&ctx))
+            },
+        ))
+    }
+}
+
+impl CannedResponse {
+    fn parse(response: &Value, base: &Path) -> Result<CannedResponse, MocksError> {
+        let status = response
+            .get("status")
+            .and_then(Value::as_u64)
+            .unwrap_or(200);
+        let status = u16::try_from(status)
+            .ok()
+            .and_then(|s| StatusCode::from_u16(s).ok())
+            .ok_or_else(|| invalid(&format!("unknown status {status}")))?;
+
+        let mut headers = HeaderMap::new();
+        if let Some(declared) = response.get("headers").and_then(Value::as_object) {
+            for (name, value) in declared {
+                let name = HeaderName::from_bytes(name.as_bytes())
+                    .map_err(|_| invalid(&format!("header name {name}")))?;
+                let value = value
+                    .as_str()
+                    .map(str::to_string)
+                    .unwrap_or_else(|| value.to_string());
+                let value = HeaderValue::from_str(&value)
+                    .map_err(|_| invalid(&format!("header value {value}")))?;
+                headers.insert(name, value);
+            }
+        }
+
+        let body = match (
+            response.get("body"),
+            response.get("bodyFile").and_then(Value::as_str),
+        ) {
+            (Some(body), _) => CannedBody::Json(body.clone()),
+            (None, Some(file)) => {
+                let bytes = fs::read(base.join(file))
+                    .map_err(|e| MocksError::FailedReadFile(format!("{file}: {e}")))?;
+                match serde_json::from_slice::<Value>(&bytes) {
+                    Ok(value) => CannedBody::Json(value),
+                    Err(_) => CannedBody::Raw(bytes),
+                }
+            }
+            (None, None) => CannedBody::Empty,
+        };
+
+        let transitions = match response.get("transition") {
+            None => vec![],
+            Some(Value::Object(states)) => states
+                .iter()
+                .map(|(name, state)| {
+                    let state = state
+                        .as_str()
+                        .ok_or_else(|| invalid(&format!("transition of {name} must be a state")))?;
+                    Ok((name.to_string(), state.to_string()))
+                })
+                .collect::<Result<Vec<_>, MocksError>>()?,
+            Some(_) => return Err(invalid("transition must be an object")),
+        };
+
+        Ok(CannedResponse {
+            status,
+            headers,
+            body,
+            transitions,
+        })
+    }
+
+    /// Build the response to a request
+    pub fn respond(&self, ctx: &RequestContext) -> Response {
+        let mut headers = self.headers.clone();
+        let body = match &self.body {
+            CannedBody::Empty => Body::empty(),
+            CannedBody::Json(value) => {
+                if !headers.contains_key(CONTENT_TYPE) {
+                    headers.insert(CONTENT_TYPE, HeaderValue::from_static("application/json"));
+                }
+                Body::from(render(value.clone(), ctx).to_string())
+            }
+            CannedBody::Raw(bytes) => {
+                if !headers.contains_key(CONTENT_TYPE) {
+                    let content_type = if std::str::from_utf8(bytes).is_ok() {
+                        "text/plain; charset=utf-8"
+                    } else {
+                        "application/octet-stream"
+                    };
+                    headers.insert(CONTENT_TYPE, HeaderValue::from_static(content_type));
+                }
+                Body::from(bytes.clone())
+            }
+        };
+        (self.status, headers, body).into_response()
+    }
+}
+
+fn invalid(message: &str) -> MocksError {
+    MocksError::InvalidArgs(format!("Invalid route: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::server::state::AppState;
+    use axum::Router;
+    use http_body_util::BodyExt;
+    use serde_json::json;
+    use tempfile::TempDir;
+    use tower::ServiceExt;
+
+    fn storage(dir: &TempDir, config: Value) -> Storage {
+        let file = dir.path().join("storage.json");
+        let data = json!({"posts": [], "_mocks": config});
+        fs::write(&file, data.to_string()).unwrap();
+        Storage::new(file.to_str().unwrap(), false).unwrap()
+    }
+
+    fn load(dir: &TempDir, routes: Value) -> Result<Vec<CannedRoute>, MocksError> {
+        CannedRoute::load(
+            &storage(dir, json!({"routes": routes})),
+            &Scenarios::default(),
+        )
+    }
+
+    fn app(routes: &[CannedRoute], state: SharedState) -> Router {
+        routes
+            .iter()
+            .fold(Router::new(), |app, route| {
+                app.route(&route.path, route.method_router().unwrap())
+            })
+            .with_state(state)
+    }
+
+    async fn call(route: &CannedRoute, method: &str, uri: &str) -> (StatusCode, HeaderMap, String) {
+        let dir = TempDir::new().unwrap();
+        let state = AppState::new(storage(&dir, json!({})), Scenarios::default());
+        let app = app(std::slice::from_ref(route), state);
+        let req = axum::http::Request::builder()
+            .method(method)
+            .uri(uri)
+            .header("x-user", "alice")
+            .body(Body::empty())
+            .unwrap();
+        let res = app.oneshot(req).await.unwrap();
+        let status = res.status();
+        let headers = res.headers().clone();
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        (status, headers, String::from_utf8_lossy(&body).to_string())
+    }
+
+    #[test]
+    fn test_load() {
+        let dir = TempDir::new().unwrap();
+        fs::write(dir.path().join("avatar.txt"), "avatar").unwrap();
+        let routes = load(
+            &dir,
+            json!([
+                {"method": "post", "path": "/login", "status": 201, "headers": {"X-Token": "abc"}, "body": {"ok": true}},
+                {"path": "/users/{id}/avatar", "bodyFile": "avatar.txt"},
+                {"method": "DELETE", "path": "/session", "status": 204}
+            ]),
+        )
+        .unwrap();
+
+        assert_eq!(routes.len(), 3);
+        assert_eq!(routes[0].method, Method::POST);
+        assert_eq!(routes[0].default.status, StatusCode::CREATED);
+        assert_eq!(routes[0].default.headers["x-token"], "abc");
+        assert_eq!(
+            routes[0].default.body,
+            CannedBody::Json(json!({"ok": true}))
+        );
+        assert_eq!(routes[1].method, Method::GET);
+        assert_eq!(routes[1].default.body, CannedBody::Raw(b"avatar".to_vec()));
+        assert_eq!(routes[2].default.body, CannedBody::Empty);
+
+        assert!(load(&dir, json!([])).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_load_invalid() {
+        let dir = TempDir::new().unwrap();
+        for routes in [
+            json!({"path": "/login"}),
+            json!([{"method": "GET"}]),
+            json!([{"path": "login"}]),
+            json!([{"path": "/{resource}/x"}]),
+            json!([{"path": "/_mocks/x"}]),
+            json!([{"path": "/x", "status": 1000}]),
+            json!([{"path": "/x", "bodyFile": "missing.json"}]),
+            json!([{"path": "/x"}, {"method": "get", "path": "/x"}]),
+            json!([{"method": "FETCH", "path": "/x"}]),
+            json!([{"method": "PATCHH", "path": "/x"}]),
+            json!([{"method": "GE T", "path": "/x"}]),
+            json!([{"path": "/x", "responses": {}}]),
+            json!([{"path": "/x", "responses": [{"status": 401}]}]),
+            json!([{"path": "/x", "responses": [{"when": {"query": {"a": {"regex": "("}}}}]}]),
+        ] {
+            assert!(
+                load(&dir, routes.clone()).is_err(),
+                "{routes} should be invalid"
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn test_respond() {
+        let dir = TempDir::new().unwrap();
+        let routes = load(
+            &dir,
+            json!([
+                {"path": "/users/{id}/greeting", "headers": {"X-Mock": "1"}, "body": {"user": "{{request.params.id}}", "by": "{{request.header.X-User}}"}},
+                {"method": "POST", "path": "/logout", "status": 204}
+            ]),
+        )
+        .unwrap();
+
+        let (status, headers, body) = call(&routes[0], "GET", "/users/7/greeting").await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["x-mock"], "1");
+        assert_eq!(headers["content-type"], "application/json");
+        assert_eq!(
+            serde_json::from_str::<Value>(&body).unwrap(),
+            json!({"user": "7", "by": "alice"})
+        );
+
+        let (status, _, body) = call(&routes[1], "POST", "/logout").await;
+        assert_eq!(status, StatusCode::NO_CONTENT);
+        assert!(body.is_empty());
+
+        let (status, _, _) = call(&routes[1], "GET", "/logout").await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn test_respond_with_rules() {
+        let dir = TempDir::new().unwrap();
+        let routes = load(
+            &dir,
+            json!([{
+                "method": "POST",
+                "path": "/documents",
+                "responses": [
+                    {"when": {"headers": {"Authorization": {"present": false}}}, "status": 401},
+                    {"when": {"body": {"$.user.role": "viewer"}}, "status": 403, "body": {"error": "forbidden"}},
+                    {"when": {"query": {"dry_run": "true"}, "body": {"$.title": {"regex": "^draft"}}}, "status": 202}
+                ],
+                "status": 201,
+                "body": {"ok": true}
+            }]),
+        )
+        .unwrap();
+        let route = &routes[0];
+        assert_eq!(route.rules.len(), 3);
+
+        let state = AppState::new(storage(&dir, json!({})), Scenarios::default());
+        let app = app(&routes, state);
+        let send = |uri: &str, authorization: Option<&str>, body: Value| {
+            let mut req = axum::http::Request::builder().method("POST").uri(uri);
+            if let Some(authorization) = authorization {
+                req = req.header("authorization", authorization);
+            }
+            let req = req.body(Body::from(body.to_string())).unwrap();
+            app.clone().oneshot(req)
+        };
+
+        let res = send("/documents", None, json!({})).await.unwrap();
+        assert_eq!(res.status(), StatusCode::UNAUTHORIZED);
+
+        let user = |role: &str| json!({"user": {"role": role}, "title": "draft 1"});
+        let res = send("/documents", Some("Bearer x"), user("viewer"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::FORBIDDEN);
+        let body = res.into_body().collect().await.unwrap().to_bytes();
+        assert_eq!(
+            serde_json::from_slice::<Value>(&body).unwrap(),
+            json!({"error": "forbidden"})
+        );
+
+        let res = send("/documents?dry_run=true", Some("Bearer x"), user("admin"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+
+        let res = send("/documents", Some("Bearer x"), user("admin"))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::CREATED);
+    }
+
+    #[tokio::test]
+    async fn test_respond_with_scenarios() {
+        let dir = TempDir::new().unwrap();
+        let storage = storage(
+            &dir,
+            json!({
+                "scenarios": {"job": "queued"},
+                "routes": [
+                    {
+                        "path": "/jobs/1",
+                        "responses": [
+                            {"when": {"scenario": {"job": "queued"}}, "body": {"status": "pending"}, "transition": {"job": "running"}},
+                            {"when": {"scenario": {"job": "running"}}, "body": {"status": "pending"}, "transition": {"job": "done"}}
+                        ],
+                        "body": {"status": "done"}
+                    },
+                    {"method": "POST", "path": "/jobs/1/retry", "status": 202, "transition": {"job": "queued"}}
+                ]
+            }),
+        );
+        let scenarios = Scenarios::load(&storage).unwrap();
+        let routes = CannedRoute::load(&storage, &scenarios).unwrap();
+        let state = AppState::new(storage, scenarios);
+        let app = app(&routes, state.clone());
+
+        let send = |method: &str, uri: &str| {
+            let req = axum::http::Request::builder()
+                .method(method)
+                .uri(uri)
+                .body(Body::empty())
+                .unwrap();
+            app.clone().oneshot(req)
+        };
+        let mut statuses = vec![];
+        for _ in 0..3 {
+            let res = send("GET", "/jobs/1").await.unwrap();
+            let body = res.into_body().collect().await.unwrap().to_bytes();
+            let body: Value = serde_json::from_slice(&body).unwrap();
+            statuses.push(body["status"].as_str().unwrap().to_string());
+        }
+        assert_eq!(statuses, ["pending", "pending", "done"]);
+        assert_eq!(state.lock().unwrap().scenarios.state("job"), Some("done"));
+
+        let res = send("POST", "/jobs/1/retry").await.unwrap();
+        assert_eq!(res.status(), StatusCode::ACCEPTED);
+        assert_eq!(state.lock().unwrap().scenarios.state("job"), Some("queued"));
+    }
+
+    #[test]
+    fn test_load_undeclared_scenario() {
+        let dir = TempDir::new().unwrap();
+        for routes in [
+            json!([{"path": "/x", "responses": [{"when": {"scenario": {"job": "a"}}}]}]),
+            json!([{"path": "/x", "transition": {"job": "a"}}]),
+            json!([{"path": "/x", "transition": {"job": 1}}]),
+        ] {
+            let storage = storage(
+                &dir,
+                json!({"scenarios": {"order": "created"}, "routes": routes}),
+            );
+            let scenarios = Scenarios::load(&storage).unwrap();
+            assert!(
+                CannedRoute::load(&storage, &scenarios).is_err(),
+                "{routes} should be invalid"
+            );
+        }
+    }
+}
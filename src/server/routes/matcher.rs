@@ -0,0 +1,274 @@
+use crate::error::MocksError;
+use crate::server::render::RequestContext;
+use crate::server::scenario::Scenarios;
+use regex::Regex;
+use serde_json::Value;
+
+/// Expected value of a request part
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    /// Equal to a JSON value (strings also match the text of query params and headers)
+    Equals(Value),
+    /// Text matching a regular expression
+    Regex(Regex),
+    /// Present or absent
+    Present(bool),
+}
+
+/// Conditions on a request, all of which must hold (`when` of a response rule)
+///
+/// ```json
+/// {"query": {"page": "2"}, "headers": {"Authorization": {"present": false}}, "body": {"$.user.role": {"regex": "^view"}}}
+/// ```
+///
+/// `scenario` matches the current state of [scenarios](Scenarios), e.g. `{"scenario": {"job": "pending"}}`.
+#[derive(Debug, Clone, Default)]
+pub struct Conditions {
+    query: Vec<(String, Matcher)>,
+    headers: Vec<(String, Matcher)>,
+    body: Vec<(String, Matcher)>,
+    scenario: Vec<(String, Matcher)>,
+}
+
+impl Conditions {
+    pub fn parse(when: &Value) -> Result<Conditions, MocksError> {
+        let when = when
+            .as_object()
+            .ok_or_else(|| invalid("when must be an object"))?;
+        if let Some(key) = when
+            .keys()
+            .find(|k| !["query", "headers", "body", "scenario"].contains(&k.as_str()))
+        {
+            return Err(invalid(&format!("unknown condition {key}")));
+        }
+
+        let section = |name: &str| -> Result<Vec<(String, Matcher)>, MocksError> {
+            let Some(section) = when.get(name) else {
+                return Ok(vec![]);
+            };
+            section
+                .as_object()
+                .ok_or_else(|| invalid(&format!("{name} must be an object")))?
+                .iter()
+                .map(|(key, expected)| Ok((key.to_string(), Matcher::parse(expected)?)))
+                .collect()
+        };
+
+        Ok(Conditions {
+            query: section("query")?,
+            headers: section("headers")?,
+            body: section("body")?,
+            scenario: section("scenario")?,
+        })
+    }
+
+    /// Whether the conditions read the JSON body of the request
+    pub fn uses_body(&self) -> bool {
+        !self.body.is_empty()
+    }
+
+    /// Names of the scenarios the conditions read
+    pub fn scenarios(&self) -> impl Iterator<Item = &str> {
+        self.scenario.iter().map(|(name, _)| name.as_str())
+    }
+
+    /// Whether a request satisfies every condition
+    ///
+    /// # Arguments
+    /// * `ctx` - The request being served
+    /// * `body` - The request body parsed as JSON, if any
+    /// * `scenarios` - The current scenario states
+    pub fn matches(
+        &self,
+        ctx: &RequestContext,
+        body: Option<&Value>,
+        scenarios: &Scenarios,
+    ) -> bool {
+        let text = |value: Option<&str>| value.map(|v| Value::String(v.to_string()));
+
+        self.query
+            .iter()
+            .all(|(name, m)| m.matches(text(ctx.query.get(name).map(String::as_str)).as_ref()))
+            && self.headers.iter().all(|(name, m)| {
+                let header = ctx.headers.get(name).and_then(|v| v.to_str().ok());
+                m.matches(text(header).as_ref())
+            })
+            && self
+                .body
+                .iter()
+                .all(|(path, m)| m.matches(body.and_then(|b| select(b, path))))
+            && self
+                .scenario
+                .iter()
+                .all(|(name, m)| m.matches(text(scenarios.state(name)).as_ref()))
+    }
+}
+
+impl Matcher {
+    fn parse(expected: &Value) -> Result<Matcher, MocksError> {
+        let Some(obj) = expected.as_object() else {
+            return Ok(Matcher::Equals(expected.clone()));
+        };
+        if let Some(pattern) = obj.get("regex").and_then(Value::as_str) {
+            let regex =
+                Regex::new(pattern).map_err(|e| invalid(&format!("regex {pattern}: {e}")))?;
+            Ok(Matcher::Regex(regex))
+        } else if let Some(present) = obj.get("present").and_then(Value::as_bool) {
+            Ok(Matcher::Present(present))
+        } else if let Some(value) = obj.get("equals") {
+            Ok(Matcher::Equals(value.clone()))
+        } else {
+            Err(invalid(
+                "matchers are a value or an object with regex, present or equals",
+            ))
+        }
+    }
+
+    fn matches(&self, actual: Option<&Value>) -> bool {
+        match (self, actual) {
+            (Matcher::Present(present), actual) => *present == actual.is_some(),
+            (_, None) => false,
+            (Matcher::Equals(expected), Some(actual)) => {
+                expected == actual
+                    || matches!(actual, Value::String(s) if *s == scalar_text(expected))
+            }
+            (Matcher::Regex(regex), Some(actual)) => regex.is_match(&scalar_text(actual)),
+        }
+    }
+}
+
+/// Select a value with a JSONPath-like expression such as `$.user.roles[0]`
+pub fn select<'a>(value: &'a Value, path: &str) -> Option<&'a Value> {
+    let path = path.strip_prefix('$').unwrap_or(path);
+    let mut current = value;
+    for segment in path.split('.').filter(|s| !s.is_empty()) {
+        let (field, indexes) = match segment.find('[') {
+            Some(i) => (&segment[..i], &segment[i..]),
+            None => (segment, ""),
+        };
+        if !field.is_empty() {
+            current = current.get(field)?;
+        }
+        for index in indexes.split('[').filter(|s| !s.is_empty()) {
+            let index = index.strip_suffix(']')?.parse::<usize>().ok()?;
+            current = current.get(index)?;
+        }
+    }
+    Some(current)
+}
+
+fn scalar_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.to_string(),
+        value => value.to_string(),
+    }
+}
+
+fn invalid(message: &str) -> MocksError {
+    MocksError::InvalidArgs(format!("Invalid route rule: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderMap, HeaderValue};
+    use serde_json::json;
+    use std::collections::HashMap;
+
+    fn ctx(query: &[(&str, &str)], headers: &[(&'static str, &'static str)]) -> RequestContext {
+        let mut header_map = HeaderMap::new();
+        for (name, value) in headers {
+            header_map.insert(*name, HeaderValue::from_static(value));
+        }
+        RequestContext {
+            query: query
+                .iter()
+                .map(|(k, v)| (k.to_string(), v.to_string()))
+                .collect::<HashMap<_, _>>(),
+            headers: header_map,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn test_select() {
+        let value = json!({"user": {"roles": ["admin", "viewer"], "id": 1}});
+        assert_eq!(select(&value, "$.user.id"), Some(&json!(1)));
+        assert_eq!(select(&value, "user.roles[1]"), Some(&json!("viewer")));
+        assert_eq!(select(&value, "$"), Some(&value));
+        assert_eq!(select(&value, "$.user.missing"), None);
+        assert_eq!(select(&value, "$.user.roles[5]"), None);
+    }
+
+    #[test]
+    fn test_matches_query_and_headers() {
+        let conditions = Conditions::parse(&json!({
+            "query": {"page": 2},
+            "headers": {"Authorization": {"regex": "^Bearer "}, "X-Debug": {"present": false}}
+        }))
+        .unwrap();
+
+        assert!(conditions.matches(
+            &ctx(&[("page", "2")], &[("authorization", "Bearer abc")]),
+            None,
+            &Scenarios::default()
+        ));
+        assert!(!conditions.matches(
+            &ctx(&[("page", "3")], &[("authorization", "Bearer abc")]),
+            None,
+            &Scenarios::default()
+        ));
+        assert!(!conditions.matches(
+            &ctx(&[("page", "2")], &[("authorization", "Basic abc")]),
+            None,
+            &Scenarios::default()
+        ));
+        assert!(!conditions.matches(
+            &ctx(
+                &[("page", "2")],
+                &[("authorization", "Bearer abc"), ("x-debug", "1")]
+            ),
+            None,
+            &Scenarios::default()
+        ));
+    }
+
+    #[test]
+    fn test_matches_body() {
+        let conditions = Conditions::parse(&json!({
+            "body": {"$.user.role": "viewer", "$.amount": {"regex": "^[0-9]{3,}$"}}
+        }))
+        .unwrap();
+        assert!(conditions.uses_body());
+
+        let body = json!({"user": {"role": "viewer"}, "amount": 1500});
+        assert!(conditions.matches(
+            &RequestContext::default(),
+            Some(&body),
+            &Scenarios::default()
+        ));
+        let body = json!({"user": {"role": "admin"}, "amount": 1500});
+        assert!(!conditions.matches(
+            &RequestContext::default(),
+            Some(&body),
+            &Scenarios::default()
+        ));
+        assert!(!conditions.matches(&RequestContext::default(), None, &Scenarios::default()));
+    }
+
+    #[test]
+    fn test_parse_invalid() {
+        for when in [
+            json!("x"),
+            json!({"cookies": {}}),
+            json!({"query": []}),
+            json!({"query": {"a": {"regex": "("}}}),
+            json!({"query": {"a": {"unknown": 1}}}),
+        ] {
+            assert!(
+                Conditions::parse(&when).is_err(),
+                "{when} should be invalid"
+            );
+        }
+    }
+}
@@ -0,0 +1,130 @@
+use crate::error::MocksError;
+use crate::storage::Storage;
+use serde_json::{Map, Value};
+use std::collections::BTreeMap;
+
+const SCENARIOS_KEY: &str = "scenarios";
+
+/// Named states shared across requests
+///
+/// Declared under `_mocks.scenarios` in the storage file with their initial state:
+///
+/// ```json
+/// {"_mocks": {"scenarios": {"job": "pending", "order": "created"}}}
+/// ```
+///
+/// Canned route responses match the current state in `when.scenario`
+/// and advance it with `transition`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Scenarios {
+    initial: BTreeMap<String, String>,
+    current: BTreeMap<String, String>,
+}
+
+impl Scenarios {
+    /// Load the scenarios declared in the config of a storage
+    ///
+    /// # Arguments
+    /// * `storage` - The storage whose config declares the scenarios
+    pub fn load(storage: &Storage) -> Result<Scenarios, MocksError> {
+        let Some(declared) = storage.config.get(SCENARIOS_KEY) else {
+            return Ok(Scenarios::default());
+        };
+        let invalid = || {
+            MocksError::InvalidArgs(format!(
+                "{SCENARIOS_KEY} must be an object of scenario names and initial states."
+            ))
+        };
+
+        let initial = declared
+            .as_object()
+            .ok_or_else(invalid)?
+            .iter()
+            .map(|(name, state)| {
+                let state = state.as_str().ok_or_else(invalid)?;
+                Ok((name.to_string(), state.to_string()))
+            })
+            .collect::<Result<BTreeMap<_, _>, MocksError>>()?;
+
+        Ok(Scenarios {
+            current: initial.clone(),
+            initial,
+        })
+    }
+
+    /// Whether a scenario is declared
+    pub fn contains(&self, name: &str) -> bool {
+        self.initial.contains_key(name)
+    }
+
+    /// Current state of a scenario
+    pub fn state(&self, name: &str) -> Option<&str> {
+        self.current.get(name).map(String::as_str)
+    }
+
+    /// Move a scenario to a state
+    pub fn set(&mut self, name: &str, state: &str) -> Result<(), MocksError> {
+        let current = self
+            .current
+            .get_mut(name)
+            .ok_or(MocksError::ObjectNotFound)?;
+        *current = state.to_string();
+        Ok(())
+    }
+
+    /// Move every scenario back to its initial state
+    pub fn reset(&mut self) {
+        self.current = self.initial.clone();
+    }
+
+    /// Current states as a JSON object
+    pub fn to_value(&self) -> Value {
+        Value::Object(
+            self.current
+                .iter()
+                .map(|(name, state)| (name.to_string(), Value::String(state.to_string())))
+                .collect::<Map<_, _>>(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn load(config: Value) -> Result<Scenarios, MocksError> {
+        let data = json!({"jobs": [], "_mocks": config});
+        let storage = Storage::from_value(&data);
+        Scenarios::load(&storage)
+    }
+
+    #[test]
+    fn test_load() {
+        let scenarios = load(json!({"scenarios": {"job": "pending"}})).unwrap();
+        assert!(scenarios.contains("job"));
+        assert_eq!(scenarios.state("job"), Some("pending"));
+        assert_eq!(load(json!({})).unwrap(), Scenarios::default());
+
+        assert!(load(json!({"scenarios": ["job"]})).is_err());
+        assert!(load(json!({"scenarios": {"job": 1}})).is_err());
+    }
+
+    #[test]
+    fn test_set_and_reset() {
+        let mut scenarios =
+            load(json!({"scenarios": {"job": "pending", "order": "created"}})).unwrap();
+        scenarios.set("job", "done").unwrap();
+        assert_eq!(
+            scenarios.to_value(),
+            json!({"job": "done", "order": "created"})
+        );
+        assert_eq!(
+            scenarios.set("missing", "done"),
+            Err(MocksError::ObjectNotFound)
+        );
+
+        scenarios.reset();
+        assert_eq!(scenarios.state("job"), Some("pending"));
+    }
+}
@@ -0,0 +1,188 @@
+use crate::error::MocksError;
+use crate::schema::resource_name;
+use crate::storage::Storage;
+use axum::http::Method;
+use serde_json::Value;
+use std::collections::HashMap;
+
+/// Key of a per-method option applying to the other methods
+const ANY_METHOD: &str = "*";
+
+const METHODS: [&str; 7] = ["GET", "POST", "PUT", "PATCH", "DELETE", "HEAD", "OPTIONS"];
+
+/// Option of a resource, per method
+#[derive(Debug, Clone)]
+struct PerMethod<T> {
+    any: Option<T>,
+    methods: HashMap<Method, T>,
+}
+
+/// An option set globally and overridden per resource and method in the storage config
+///
+/// ```json
+/// {"_mocks": {"resources": {"posts": {"delay": "300ms"}, "comments": {"delay": {"GET": "1s", "*": "50ms"}}}}}
+/// ```
+#[derive(Debug, Clone)]
+pub struct Scoped<T> {
+    global: Option<T>,
+    /// Resource keys (e.g. `api/v1/posts`) with their options, longest first
+    resources: Vec<(String, PerMethod<T>)>,
+}
+
+impl<T> Default for Scoped<T> {
+    fn default() -> Self {
+        Scoped {
+            global: None,
+            resources: vec![],
+        }
+    }
+}
+
+impl<T> Scoped<T> {
+    /// Read an option of resources from the storage config
+    ///
+    /// # Arguments
+    /// * `storage` - The storage whose config declares the options of resources
+    /// * `option` - Name of the option under `_mocks.resources.{name}`
+    /// * `global` - Value for requests without one of their own
+    /// * `parse` - Parser of a declared value
+    pub fn load<F>(
+        storage: &Storage,
+        option: &str,
+        global: Option<T>,
+        parse: F,
+    ) -> Result<Scoped<T>, MocksError>
+    where
+        F: Fn(&Value) -> Result<T, String>,
+    {
+        let invalid = |key: &str, e: String| {
+            MocksError::InvalidArgs(format!("Invalid {option} of {key}: {e}"))
+        };
+
+        let mut resources = vec![];
+        for key in storage.resources() {
+            let Some(declared) = storage
+                .config
+                .get("resources")
+                .and_then(|r| r.get(resource_name(&key)))
+                .and_then(|r| r.get(option))
+            else {
+                continue;
+            };
+
+            let mut per_method = PerMethod {
+                any: None,
+                methods: HashMap::new(),
+            };
+            match declared.as_object().filter(|obj| is_per_method(obj)) {
+                Some(methods) => {
+                    for (method, value) in methods {
+                        let value = parse(value).map_err(|e| invalid(&key, e))?;
+                        if method == ANY_METHOD {
+                            per_method.any = Some(value);
+                        } else if let Ok(method) =
+                            Method::from_bytes(method.to_uppercase().as_bytes())
+                        {
+                            per_method.methods.insert(method, value);
+                        }
+                    }
+                }
+                None => per_method.any = Some(parse(declared).map_err(|e| invalid(&key, e))?),
+            }
+            resources.push((key, per_method));
+        }
+        resources.sort_by_key(|(key, _)| std::cmp::Reverse(key.len()));
+
+        Ok(Scoped { global, resources })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.global.is_none() && self.resources.is_empty()
+    }
+
+    /// Value applying to a request
+    pub fn find(&self, method: &Method, path: &str) -> Option<&T> {
+        let path = path.trim_start_matches('/');
+        let resource = self.resources.iter().find(|(key, _)| {
+            path.strip_prefix(key.as_str())
+                .is_some_and(|rest| rest.is_empty() || rest.starts_with('/'))
+        });
+        match resource {
+            Some((_, per_method)) => per_method
+                .methods
+                .get(method)
+                .or(per_method.any.as_ref())
+                .or(self.global.as_ref()),
+            None => self.global.as_ref(),
+        }
+    }
+}
+
+/// Whether a declared object maps methods to values rather than being a value itself
+fn is_per_method(obj: &serde_json::Map<String, Value>) -> bool {
+    !obj.is_empty()
+        && obj
+            .keys()
+            .all(|key| key == ANY_METHOD || METHODS.contains(&key.to_uppercase().as_str()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn load(config: Value, global: Option<i64>) -> Result<Scoped<i64>, MocksError> {
+        let data = json!({"posts": [], "api/v1/comments": [], "_mocks": config});
+        let storage = Storage::from_value(&data);
+        Scoped::load(&storage, "weight", global, |value| {
+            value
+                .as_i64()
+                .ok_or_else(|| format!("{value} is not a number"))
+        })
+    }
+
+    #[test]
+    fn test_find() {
+        let config = json!({
+            "resources": {
+                "posts": {"weight": 3},
+                "comments": {"weight": {"get": 1, "*": 2}}
+            }
+        });
+        let scoped = load(config, Some(9)).unwrap();
+
+        assert_eq!(scoped.find(&Method::GET, "/posts"), Some(&3));
+        assert_eq!(scoped.find(&Method::DELETE, "/posts/1"), Some(&3));
+        assert_eq!(scoped.find(&Method::GET, "/api/v1/comments/1"), Some(&1));
+        assert_eq!(scoped.find(&Method::POST, "/api/v1/comments"), Some(&2));
+        assert_eq!(scoped.find(&Method::GET, "/postsx"), Some(&9));
+
+        let scoped = load(json!({}), None).unwrap();
+        assert!(scoped.is_empty());
+        assert_eq!(scoped.find(&Method::GET, "/posts"), None);
+    }
+
+    #[test]
+    fn test_find_falls_back_to_global() {
+        let config = json!({"resources": {"posts": {"weight": {"POST": 1}}}});
+        let scoped = load(config, Some(9)).unwrap();
+        assert_eq!(scoped.find(&Method::POST, "/posts"), Some(&1));
+        assert_eq!(scoped.find(&Method::GET, "/posts"), Some(&9));
+    }
+
+    #[test]
+    fn test_load_invalid() {
+        for weight in [json!("heavy"), json!({"GET": "heavy"})] {
+            let config = json!({"resources": {"posts": {"weight": weight}}});
+            assert!(load(config, None).is_err(), "{weight} should be invalid");
+        }
+    }
+
+    #[test]
+    fn test_is_per_method() {
+        let obj = |value: Value| value.as_object().unwrap().clone();
+        assert!(is_per_method(&obj(json!({"GET": 1, "*": 2}))));
+        assert!(!is_per_method(&obj(json!({"error": 0.1}))));
+        assert!(!is_per_method(&obj(json!({}))));
+    }
+}
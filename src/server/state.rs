@@ -1,3 +1,4 @@
+use crate::server::scenario::Scenarios;
 use crate::storage::Storage;
 use std::sync::{Arc, Mutex};
 
@@ -5,10 +6,11 @@ pub type SharedState = Arc<Mutex<AppState>>;
 
 pub struct AppState {
     pub storage: Storage,
+    pub scenarios: Scenarios,
 }
 
 impl AppState {
-    pub fn new(storage: Storage) -> SharedState {
-        Arc::new(Mutex::new(AppState { storage }))
+    pub fn new(storage: Storage, scenarios: Scenarios) -> SharedState {
+        Arc::new(Mutex::new(AppState { storage, scenarios }))
     }
 }
@@ -0,0 +1,359 @@
+use crate::error::MocksError;
+use axum::serve::Listener;
+use chrono::{Datelike, Duration as Days, Utc};
+use rcgen::{date_time_ymd, CertificateParams, ExtendedKeyUsagePurpose, KeyPair};
+use std::env;
+use std::fs;
+use std::io;
+use std::net::SocketAddr;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinSet;
+use tokio_rustls::rustls::crypto::ring::default_provider;
+use tokio_rustls::rustls::pki_types::pem::PemObject;
+use tokio_rustls::rustls::pki_types::{CertificateDer, PrivateKeyDer};
+use tokio_rustls::rustls::ServerConfig;
+use tokio_rustls::server::TlsStream;
+use tokio_rustls::TlsAcceptor;
+
+/// Days a generated certificate is valid for
+const VALIDITY_DAYS: i64 = 365;
+
+/// Age after which a cached certificate is generated again, before it expires
+const RENEW_AFTER: Duration = Duration::from_secs(300 * 24 * 60 * 60);
+
+/// Time allowed for a client to complete the TLS handshake
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Names a self-signed certificate is always valid for
+const LOCAL_NAMES: [&str; 3] = ["localhost", "127.0.0.1", "::1"];
+
+/// Extension of requests served over HTTPS, for handlers building absolute URLs
+#[derive(Debug, Clone, Copy)]
+pub struct Https;
+
+/// Certificate the server is served over HTTPS with
+#[derive(Debug)]
+pub struct Tls {
+    config: Arc<ServerConfig>,
+    /// The certificate file, to show users what to trust
+    cert: PathBuf,
+    self_signed: bool,
+    http2: bool,
+}
+
+impl Tls {
+    /// Load a certificate chain and its private key from PEM files
+    pub fn from_files(cert: &Path, key: &Path, http2: bool) -> Result<Tls, MocksError> {
+        let read_error = |path: &Path, e: &dyn std::fmt::Display| {
+            MocksError::FailedReadFile(format!("{}: {e}", path.display()))
+        };
+        let certs = CertificateDer::pem_file_iter(cert)
+            .and_then(|certs| certs.collect::<Result<Vec<_>, _>>())
+            .map_err(|e| read_error(cert, &e))?;
+        if certs.is_empty() {
+            return Err(invalid(&format!("{} has no certificate", cert.display())));
+        }
+        let key = PrivateKeyDer::from_pem_file(key).map_err(|e| read_error(key, &e))?;
+
+        Ok(Tls {
+            config: Arc::new(server_config(certs, key, http2)?),
+            cert: cert.to_path_buf(),
+            self_signed: false,
+            http2,
+        })
+    }
+
+    /// Load the cached self-signed certificate of a host, generating it when missing or old
+    ///
+    /// Certificates are cached under the user cache directory (e.g. `~/.cache/mocks/tls`), so
+    /// a browser exception or a trusted certificate survives restarts.
+    pub fn self_signed(host: &str, http2: bool) -> Result<Tls, MocksError> {
+        let dir = cache_dir();
+        let name = if LOCAL_NAMES.contains(&host) || is_unspecified(host) {
+            "localhost".to_string()
+        } else {
+            host.replace(
+                |c: char| !c.is_ascii_alphanumeric() && c != '.' && c != '-',
+                "_",
+            )
+        };
+        let cert = dir.join(format!("{name}.pem"));
+        let key = dir.join(format!("{name}-key.pem"));
+
+        if !is_fresh(&cert) || !key.exists() {
+            let (cert_pem, key_pem) = generate(host)?;
+            let write = |path: &Path, contents: &str, private: bool| {
+                fs::create_dir_all(&dir)
+                    .and_then(|_| write_file(path, contents, private))
+                    .map_err(|e| MocksError::FailedWriteFile(format!("{}: {e}", path.display())))
+            };
+            write(&cert, &cert_pem, false)?;
+            write(&key, &key_pem, true)?;
+        }
+
+        let mut tls = Tls::from_files(&cert, &key, http2)?;
+        tls.self_signed = true;
+        Ok(tls)
+    }
+
+    /// Description of the certificate, e.g. `self-signed (~/.cache/mocks/tls/localhost.pem), HTTP/2`
+    pub fn describe(&self) -> String {
+        let mut description = format!("{}", self.cert.display());
+        if self.self_signed {
+            description = format!("self-signed ({description})");
+        }
+        if self.http2 {
+            description.push_str(", HTTP/2");
+        }
+        description
+    }
+
+    /// Listener accepting TLS connections on a bound TCP listener
+    pub fn listener(&self, tcp: TcpListener) -> TlsListener {
+        TlsListener {
+            tcp,
+            acceptor: TlsAcceptor::from(self.config.clone()),
+            handshakes: JoinSet::new(),
+        }
+    }
+}
+
+fn server_config(
+    certs: Vec<CertificateDer<'static>>,
+    key: PrivateKeyDer<'static>,
+    http2: bool,
+) -> Result<ServerConfig, MocksError> {
+    let mut config = ServerConfig::builder_with_provider(Arc::new(default_provider()))
+        .with_safe_default_protocol_versions()
+        .and_then(|builder| builder.with_no_client_auth().with_single_cert(certs, key))
+        .map_err(|e| invalid(&e.to_string()))?;
+    // Browsers only speak HTTP/2 to servers offering it in the handshake
+    config.alpn_protocols = if http2 {
+        vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+    } else {
+        vec![b"http/1.1".to_vec()]
+    };
+    Ok(config)
+}
+
+/// Generate a self-signed certificate for local names and a host, as PEM
+fn generate(host: &str) -> Result<(String, String), MocksError> {
+    let mut names: Vec<String> = LOCAL_NAMES.iter().map(|n| n.to_string()).collect();
+    if !LOCAL_NAMES.contains(&host) && !is_unspecified(host) {
+        names.push(host.to_string());
+    }
+
+    let failed =
+        |e: rcgen::Error| MocksError::Exception(format!("Failed to generate a certificate: {e}"));
+    let mut params = CertificateParams::new(names).map_err(failed)?;
+    let date = |date: chrono::DateTime<Utc>| {
+        date_time_ymd(date.year(), date.month() as u8, date.day() as u8)
+    };
+    let now = Utc::now();
+    params.not_before = date(now - Days::days(1));
+    params.not_after = date(now + Days::days(VALIDITY_DAYS));
+    params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ServerAuth];
+
+    let key = KeyPair::generate().map_err(failed)?;
+    let cert = params.self_signed(&key).map_err(failed)?;
+    Ok((cert.pem(), key.serialize_pem()))
+}
+
+/// Whether a cached certificate exists and is young enough to be reused
+fn is_fresh(cert: &Path) -> bool {
+    fs::metadata(cert)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| SystemTime::now().duration_since(modified).ok())
+        .is_some_and(|age| age < RENEW_AFTER)
+}
+
+fn is_unspecified(host: &str) -> bool {
+    host.parse::<std::net::IpAddr>()
+        .is_ok_and(|ip| ip.is_unspecified())
+}
+
+fn cache_dir() -> PathBuf {
+    env::var_os("XDG_CACHE_HOME")
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".cache")))
+        .or_else(|| env::var_os("LOCALAPPDATA").map(PathBuf::from))
+        .unwrap_or_else(env::temp_dir)
+        .join("mocks")
+        .join("tls")
+}
+
+/// Write a file, readable only by its owner when `private` (on unix)
+fn write_file(path: &Path, contents: &str, private: bool) -> io::Result<()> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create(true).truncate(true);
+    #[cfg(unix)]
+    if private {
+        use std::os::unix::fs::{OpenOptionsExt, PermissionsExt};
+        options.mode(0o600);
+        // The mode only applies to new files, e.g. not to a key cached by an older version
+        if path.exists() {
+            fs::set_permissions(path, fs::Permissions::from_mode(0o600))?;
+        }
+    }
+    #[cfg(not(unix))]
+    let _ = private;
+    io::Write::write_all(&mut options.open(path)?, contents.as_bytes())
+}
+
+fn invalid(message: &str) -> MocksError {
+    MocksError::InvalidArgs(format!("Invalid TLS certificate: {message}"))
+}
+
+/// Listener completing the TLS handshake of accepted connections
+///
+/// Handshakes run concurrently, so a slow or stalled client does not hold up the others.
+pub struct TlsListener {
+    tcp: TcpListener,
+    acceptor: TlsAcceptor,
+    handshakes: JoinSet<Option<(TlsStream<TcpStream>, SocketAddr)>>,
+}
+
+impl Listener for TlsListener {
+    type Io = TlsStream<TcpStream>;
+    type Addr = SocketAddr;
+
+    async fn accept(&mut self) -> (Self::Io, Self::Addr) {
+        loop {
+            tokio::select! {
+                accepted = self.tcp.accept() => match accepted {
+                    Ok((stream, addr)) => {
+                        let acceptor = self.acceptor.clone();
+                        self.handshakes.spawn(async move {
+                            let handshake = acceptor.accept(stream);
+                            let stream = tokio::time::timeout(HANDSHAKE_TIMEOUT, handshake).await;
+                            stream.ok()?.ok().map(|stream| (stream, addr))
+                        });
+                    }
+                    // e.g. too many open files; wait for connections to close
+                    Err(_) => tokio::time::sleep(Duration::from_millis(100)).await,
+                },
+                Some(Ok(Some(connection))) = self.handshakes.join_next(),
+                    if !self.handshakes.is_empty() => return connection,
+            }
+        }
+    }
+
+    fn local_addr(&self) -> io::Result<Self::Addr> {
+        self.tcp.local_addr()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_from_files() {
+        let dir = TempDir::new().unwrap();
+        let (cert_pem, key_pem) = generate("mocks.test").unwrap();
+        let cert = dir.path().join("cert.pem");
+        let key = dir.path().join("key.pem");
+        fs::write(&cert, cert_pem).unwrap();
+        fs::write(&key, key_pem).unwrap();
+
+        let tls = Tls::from_files(&cert, &key, true).unwrap();
+        assert_eq!(
+            tls.config.alpn_protocols,
+            vec![b"h2".to_vec(), b"http/1.1".to_vec()]
+        );
+        assert_eq!(tls.describe(), format!("{}, HTTP/2", cert.display()));
+
+        assert!(Tls::from_files(&key, &key, false).is_err());
+        assert!(Tls::from_files(&cert, &cert, false).is_err());
+        assert!(Tls::from_files(&dir.path().join("missing.pem"), &key, false).is_err());
+    }
+
+    #[test]
+    fn test_generate() {
+        let (cert, _) = generate("0.0.0.0").unwrap();
+        let certs: Vec<_> = CertificateDer::pem_slice_iter(cert.as_bytes())
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(certs.len(), 1);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn test_write_file() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = TempDir::new().unwrap();
+        let key = dir.path().join("localhost-key.pem");
+        fs::write(&key, "old").unwrap();
+        fs::set_permissions(&key, fs::Permissions::from_mode(0o644)).unwrap();
+
+        write_file(&key, "key", true).unwrap();
+        assert_eq!(fs::read_to_string(&key).unwrap(), "key");
+        let mode = fs::metadata(&key).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        let new_key = dir.path().join("new-key.pem");
+        write_file(&new_key, "key", true).unwrap();
+        let mode = fs::metadata(&new_key).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+    }
+
+    #[test]
+    fn test_is_fresh() {
+        let dir = TempDir::new().unwrap();
+        let cert = dir.path().join("localhost.pem");
+        assert!(!is_fresh(&cert));
+        fs::write(&cert, "").unwrap();
+        assert!(is_fresh(&cert));
+    }
+
+    #[tokio::test]
+    async fn test_listener() {
+        use tokio_rustls::rustls::pki_types::ServerName;
+        use tokio_rustls::rustls::{ClientConfig, RootCertStore};
+        use tokio_rustls::TlsConnector;
+
+        let (cert_pem, key_pem) = generate("localhost").unwrap();
+        let dir = TempDir::new().unwrap();
+        let (cert, key) = (dir.path().join("cert.pem"), dir.path().join("key.pem"));
+        fs::write(&cert, &cert_pem).unwrap();
+        fs::write(&key, key_pem).unwrap();
+        let tls = Tls::from_files(&cert, &key, true).unwrap();
+
+        let tcp = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = tcp.local_addr().unwrap();
+        let mut listener = tls.listener(tcp);
+        assert_eq!(listener.local_addr().unwrap(), addr);
+
+        let mut roots = RootCertStore::empty();
+        for cert in CertificateDer::pem_slice_iter(cert_pem.as_bytes()) {
+            roots.add(cert.unwrap()).unwrap();
+        }
+        let mut config = ClientConfig::builder_with_provider(Arc::new(default_provider()))
+            .with_safe_default_protocol_versions()
+            .unwrap()
+            .with_root_certificates(roots)
+            .with_no_client_auth();
+        config.alpn_protocols = vec![b"h2".to_vec()];
+        let connector = TlsConnector::from(Arc::new(config));
+
+        // A client that never completes its handshake does not hold up the next one
+        let _stalled = TcpStream::connect(addr).await.unwrap();
+        let client = tokio::spawn(async move {
+            let stream = TcpStream::connect(addr).await.unwrap();
+            let name = ServerName::try_from("localhost").unwrap();
+            connector.connect(name, stream).await.unwrap()
+        });
+
+        let (stream, _) = tokio::time::timeout(Duration::from_secs(5), listener.accept())
+            .await
+            .unwrap();
+        assert_eq!(stream.get_ref().1.alpn_protocol(), Some(&b"h2"[..]));
+        client.await.unwrap();
+    }
+}
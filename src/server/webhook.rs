@@ -0,0 +1,584 @@
+use crate::error::MocksError;
+use crate::server::proxy::{error_chain, http_client, HttpClient};
+use crate::storage::change::{Change, Operation};
+use crate::storage::Storage;
+use axum::body::Bytes;
+use axum::http::header::{CONTENT_TYPE, USER_AGENT};
+use axum::http::{Method, Request, Uri};
+use chrono::{SecondsFormat, Utc};
+use http_body_util::Full;
+use ring::hmac;
+use serde_json::{json, Value};
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex, MutexGuard};
+use std::time::Duration;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::broadcast::Receiver;
+
+const WEBHOOKS_KEY: &str = "webhooks";
+/// Deliveries kept in the log, the oldest being dropped first
+const LOG_CAPACITY: usize = 100;
+/// Attempts of a delivery, retried after a delay doubling each time
+const MAX_ATTEMPTS: usize = 4;
+const RETRY_DELAY: Duration = Duration::from_secs(1);
+const ATTEMPT_TIMEOUT: Duration = Duration::from_secs(10);
+
+const EVENT_HEADER: &str = "x-mocks-event";
+const DELIVERY_HEADER: &str = "x-mocks-delivery";
+const SIGNATURE_HEADER: &str = "x-mocks-signature";
+
+/// A target notified of the changes made to the data
+#[derive(Debug, Clone, PartialEq)]
+struct Webhook {
+    id: u64,
+    url: Uri,
+    /// Resource whose changes are sent, all resources if `None`
+    resource: Option<String>,
+    /// Operations sent, all operations if empty
+    operations: Vec<Operation>,
+    /// Key signing the payloads with HMAC-SHA256
+    secret: Option<String>,
+}
+
+/// Webhooks registered in the config or at runtime, and the log of their deliveries
+///
+/// Declared under `_mocks.webhooks` in the storage file:
+///
+/// ```json
+/// {"_mocks": {"webhooks": [{"url": "http://localhost:4000/hooks", "resource": "orders", "operations": ["insert"], "secret": "s3cret"}]}}
+/// ```
+#[derive(Debug)]
+pub struct Webhooks {
+    client: HttpClient,
+    retry_delay: Duration,
+    registry: Mutex<Registry>,
+}
+
+#[derive(Debug, Default)]
+struct Registry {
+    hooks: Vec<Webhook>,
+    deliveries: VecDeque<Delivery>,
+    last_hook: u64,
+    last_delivery: u64,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Delivery {
+    id: u64,
+    webhook: u64,
+    event: String,
+    url: String,
+    status: Status,
+    attempts: Vec<Attempt>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Status {
+    Pending,
+    Delivered,
+    Failed,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+struct Attempt {
+    at: String,
+    /// Status of the response, or the reason no response came
+    outcome: Result<u16, String>,
+}
+
+impl Webhooks {
+    /// Load the webhooks declared in the config of a storage
+    ///
+    /// # Arguments
+    /// * `storage` - The storage whose config declares the webhooks
+    pub fn load(storage: &Storage) -> Result<Webhooks, MocksError> {
+        let webhooks = Webhooks {
+            client: http_client()?,
+            retry_delay: RETRY_DELAY,
+            registry: Mutex::new(Registry::default()),
+        };
+        match storage.config.get(WEBHOOKS_KEY) {
+            None => {}
+            Some(Value::Array(declared)) => {
+                for value in declared {
+                    // Declared webhooks fail the startup rather than a request
+                    webhooks.register(value).map_err(|e| match e {
+                        MocksError::BadRequest(message) => MocksError::InvalidArgs(message),
+                        e => e,
+                    })?;
+                }
+            }
+            Some(_) => {
+                return Err(MocksError::InvalidArgs(format!(
+                    "{WEBHOOKS_KEY} must be an array of webhooks."
+                )))
+            }
+        }
+        Ok(webhooks)
+    }
+
+    /// Register a webhook, returning it with its ID
+    ///
+    /// # Returns
+    /// * `Err(MocksError::BadRequest)` when the webhook is invalid
+    pub fn register(&self, value: &Value) -> Result<Value, MocksError> {
+        let mut registry = self.registry()?;
+        let hook = Webhook::parse(registry.last_hook + 1, value)?;
+        registry.last_hook = hook.id;
+        let value = hook.to_value();
+        registry.hooks.push(hook);
+        Ok(value)
+    }
+
+    /// Remove a webhook
+    pub fn unregister(&self, id: &str) -> Result<(), MocksError> {
+        let mut registry = self.registry()?;
+        let index = registry
+            .hooks
+            .iter()
+            .position(|hook| id.parse() == Ok(hook.id))
+            .ok_or(MocksError::ObjectNotFound)?;
+        registry.hooks.remove(index);
+        Ok(())
+    }
+
+    /// Registered webhooks as a JSON array
+    pub fn to_value(&self) -> Result<Value, MocksError> {
+        let registry = self.registry()?;
+        Ok(Value::Array(
+            registry.hooks.iter().map(Webhook::to_value).collect(),
+        ))
+    }
+
+    /// Log of the latest deliveries as a JSON array, the oldest first
+    pub fn deliveries(&self) -> Result<Value, MocksError> {
+        let registry = self.registry()?;
+        Ok(Value::Array(
+            registry.deliveries.iter().map(Delivery::to_value).collect(),
+        ))
+    }
+
+    /// Deliver the changes to the matching webhooks until the storage is dropped
+    pub async fn dispatch(self: Arc<Self>, mut changes: Receiver<Change>) {
+        loop {
+            let change = match changes.recv().await {
+                Ok(change) => change,
+                Err(RecvError::Lagged(_)) => continue,
+                Err(RecvError::Closed) => return,
+            };
+            let Ok(deliveries) = self.schedule(&change) else {
+                continue;
+            };
+            for (hook, payload) in deliveries {
+                tokio::spawn(self.clone().deliver(hook, payload));
+            }
+        }
+    }
+
+    /// Log a pending delivery of a change to each matching webhook
+    fn schedule(&self, change: &Change) -> Result<Vec<(Webhook, Payload)>, MocksError> {
+        let mut registry = self.registry()?;
+        let event = format!("{}.{}", change.resource, change.operation.as_str());
+        let hooks: Vec<Webhook> = registry
+            .hooks
+            .iter()
+            .filter(|hook| hook.matches(change))
+            .cloned()
+            .collect();
+
+        let mut deliveries = Vec::new();
+        for hook in hooks {
+            registry.last_delivery += 1;
+            let id = registry.last_delivery;
+            let body = json!({
+                "id": id,
+                "event": event,
+                "timestamp": Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+                "data": change.to_value(),
+            });
+            registry.deliveries.push_back(Delivery {
+                id,
+                webhook: hook.id,
+                event: event.clone(),
+                url: hook.url.to_string(),
+                status: Status::Pending,
+                attempts: Vec::new(),
+            });
+            if registry.deliveries.len() > LOG_CAPACITY {
+                registry.deliveries.pop_front();
+            }
+            let payload = Payload {
+                id,
+                event: event.clone(),
+                body: Bytes::from(body.to_string()),
+            };
+            deliveries.push((hook, payload));
+        }
+        Ok(deliveries)
+    }
+
+    /// POST a payload to a webhook, retrying until it answers with a success status
+    async fn deliver(self: Arc<Self>, hook: Webhook, payload: Payload) {
+        let mut delay = self.retry_delay;
+        for attempt in 1..=MAX_ATTEMPTS {
+            let outcome = self.attempt(&hook, &payload).await;
+            let delivered = matches!(outcome, Ok(status) if (200..300).contains(&status));
+            let status = match (delivered, attempt) {
+                (true, _) => Status::Delivered,
+                (false, MAX_ATTEMPTS) => Status::Failed,
+                (false, _) => Status::Pending,
+            };
+            let attempt = Attempt {
+                at: Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true),
+                outcome,
+            };
+            if let Ok(mut registry) = self.registry() {
+                // Deliveries dropped from the log are still carried out
+                if let Some(delivery) = registry.deliveries.iter_mut().find(|d| d.id == payload.id)
+                {
+                    delivery.status = status;
+                    delivery.attempts.push(attempt);
+                }
+            }
+            if status != Status::Pending {
+                return;
+            }
+            tokio::time::sleep(delay).await;
+            delay *= 2;
+        }
+    }
+
+    async fn attempt(&self, hook: &Webhook, payload: &Payload) -> Result<u16, String> {
+        let mut request = Request::builder()
+            .method(Method::POST)
+            .uri(hook.url.clone())
+            .header(CONTENT_TYPE, "application/json")
+            .header(USER_AGENT, concat!("mocks/", env!("CARGO_PKG_VERSION")))
+            .header(EVENT_HEADER, &payload.event)
+            .header(DELIVERY_HEADER, payload.id);
+        if let Some(secret) = &hook.secret {
+            request = request.header(SIGNATURE_HEADER, sign(secret, &payload.body));
+        }
+        let request = request
+            .body(Full::new(payload.body.clone()))
+            .map_err(|e| e.to_string())?;
+
+        match tokio::time::timeout(ATTEMPT_TIMEOUT, self.client.request(request)).await {
+            Ok(Ok(response)) => Ok(response.status().as_u16()),
+            Ok(Err(e)) => Err(error_chain(&e)),
+            Err(_) => Err(format!(
+                "No response within {} seconds",
+                ATTEMPT_TIMEOUT.as_secs()
+            )),
+        }
+    }
+
+    fn registry(&self) -> Result<MutexGuard<'_, Registry>, MocksError> {
+        self.registry
+            .lock()
+            .map_err(|e| MocksError::Exception(e.to_string()))
+    }
+}
+
+/// Body of a delivery, the same across its attempts
+#[derive(Debug)]
+struct Payload {
+    id: u64,
+    event: String,
+    body: Bytes,
+}
+
+impl Webhook {
+    fn parse(id: u64, value: &Value) -> Result<Webhook, MocksError> {
+        let invalid = |message: &str| MocksError::BadRequest(format!("Invalid webhook: {message}"));
+        let object = value
+            .as_object()
+            .ok_or_else(|| invalid("must be an object"))?;
+        let string = |name: &str| match object.get(name) {
+            None => Ok(None),
+            Some(Value::String(value)) => Ok(Some(value.to_string())),
+            Some(_) => Err(invalid(&format!("{name} must be a string"))),
+        };
+
+        let url = string("url")?.ok_or_else(|| invalid("url is required"))?;
+        let uri: Uri = url.parse().map_err(|e| invalid(&format!("{url}: {e}")))?;
+        match uri.scheme_str() {
+            Some("http") | Some("https") if uri.authority().is_some() => {}
+            _ => {
+                return Err(invalid(&format!(
+                    "{url} must be an http:// or https:// URL"
+                )))
+            }
+        }
+        let operations = match object.get("operations") {
+            None => Vec::new(),
+            Some(Value::Array(operations)) => operations
+                .iter()
+                .map(|operation| {
+                    let operation = operation
+                        .as_str()
+                        .ok_or_else(|| invalid("operations must be strings"))?;
+                    operation.parse().map_err(|_| {
+                        invalid(&format!(
+                            "unknown operation {operation}, use insert, replace, update or delete"
+                        ))
+                    })
+                })
+                .collect::<Result<Vec<Operation>, MocksError>>()?,
+            Some(_) => return Err(invalid("operations must be an array")),
+        };
+
+        Ok(Webhook {
+            id,
+            url: uri,
+            resource: string("resource")?,
+            operations,
+            secret: string("secret")?,
+        })
+    }
+
+    fn matches(&self, change: &Change) -> bool {
+        self.resource
+            .as_ref()
+            .map_or(true, |resource| *resource == change.resource)
+            && (self.operations.is_empty() || self.operations.contains(&change.operation))
+    }
+
+    /// The webhook as a JSON object, without its secret
+    fn to_value(&self) -> Value {
+        let operations: Vec<&str> = self.operations.iter().map(Operation::as_str).collect();
+        json!({
+            "id": self.id,
+            "url": self.url.to_string(),
+            "resource": self.resource,
+            "operations": operations,
+            "signed": self.secret.is_some(),
+        })
+    }
+}
+
+impl Delivery {
+    fn to_value(&self) -> Value {
+        let status = match self.status {
+            Status::Pending => "pending",
+            Status::Delivered => "delivered",
+            Status::Failed => "failed",
+        };
+        let attempts: Vec<Value> = self
+            .attempts
+            .iter()
+            .map(|attempt| match &attempt.outcome {
+                Ok(status) => json!({"at": attempt.at, "status": status}),
+                Err(error) => json!({"at": attempt.at, "error": error}),
+            })
+            .collect();
+        json!({
+            "id": self.id,
+            "webhook": self.webhook,
+            "event": self.event,
+            "url": self.url,
+            "status": status,
+            "attempts": attempts,
+        })
+    }
+}
+
+/// Signature of a payload, as `sha256=` and the hex HMAC-SHA256 of the body
+fn sign(secret: &str, body: &[u8]) -> String {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let tag = hmac::sign(&key, body);
+    let hex: String = tag.as_ref().iter().map(|b| format!("{b:02x}")).collect();
+    format!("sha256={hex}")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use axum::http::{HeaderMap, StatusCode};
+    use axum::routing::post;
+    use axum::Router;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+    use tokio::net::TcpListener;
+    use tokio::sync::broadcast;
+
+    fn load(config: Value) -> Result<Webhooks, MocksError> {
+        let data = json!({"orders": [], "_mocks": config});
+        let storage = Storage::from_value(&data);
+        Webhooks::load(&storage)
+    }
+
+    fn change(resource: &str, operation: Operation) -> Change {
+        Change {
+            operation,
+            resource: resource.to_string(),
+            id: json!(1),
+            value: json!({"id": 1, "total": 10}),
+        }
+    }
+
+    /// Serve a receiver failing its first request, returning its URL and the received requests
+    async fn flaky_receiver() -> (String, Arc<Mutex<Vec<(HeaderMap, Bytes)>>>) {
+        let received = Arc::new(Mutex::new(Vec::new()));
+        let calls = Arc::new(AtomicUsize::new(0));
+        let log = received.clone();
+        let app = Router::new().route(
+            "/hooks",
+            post(move |headers: HeaderMap, body: Bytes| async move {
+                log.lock().unwrap().push((headers, body));
+                match calls.fetch_add(1, Ordering::SeqCst) {
+                    0 => StatusCode::SERVICE_UNAVAILABLE,
+                    _ => StatusCode::NO_CONTENT,
+                }
+            }),
+        );
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, app).await.unwrap() });
+        (format!("http://{addr}/hooks"), received)
+    }
+
+    /// Wait for the first delivery of the log to be done
+    async fn settled(webhooks: &Webhooks) -> Value {
+        for _ in 0..200 {
+            let delivery = webhooks.deliveries().unwrap()[0].clone();
+            if delivery["status"] == "delivered" || delivery["status"] == "failed" {
+                return delivery;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("delivery not settled");
+    }
+
+    #[test]
+    fn test_load() {
+        let webhooks = load(json!({"webhooks": [
+            {"url": "http://localhost:4000/hooks", "resource": "orders", "operations": ["insert"], "secret": "s3cret"},
+            {"url": "https://example.com/all"}
+        ]}))
+        .unwrap();
+        assert_eq!(
+            webhooks.to_value().unwrap(),
+            json!([
+                {"id": 1, "url": "http://localhost:4000/hooks", "resource": "orders", "operations": ["insert"], "signed": true},
+                {"id": 2, "url": "https://example.com/all", "resource": null, "operations": [], "signed": false}
+            ])
+        );
+        assert_eq!(load(json!({})).unwrap().to_value().unwrap(), json!([]));
+
+        assert!(load(json!({"webhooks": {"url": "http://localhost"}})).is_err());
+        assert_eq!(
+            load(json!({"webhooks": [{"resource": "orders"}]})).err(),
+            Some(MocksError::InvalidArgs(
+                "Invalid webhook: url is required".to_string()
+            ))
+        );
+        assert!(load(json!({"webhooks": [{"url": "localhost:4000"}]})).is_err());
+        assert!(
+            load(json!({"webhooks": [{"url": "http://a", "operations": ["create"]}]})).is_err()
+        );
+        assert!(load(json!({"webhooks": [{"url": "http://a", "secret": 1}]})).is_err());
+    }
+
+    #[test]
+    fn test_register_and_unregister() {
+        let webhooks = load(json!({})).unwrap();
+        let hook = webhooks
+            .register(&json!({"url": "http://localhost:4000"}))
+            .unwrap();
+        assert_eq!(hook["id"], 1);
+        webhooks
+            .register(&json!({"url": "http://localhost:4001"}))
+            .unwrap();
+
+        webhooks.unregister("1").unwrap();
+        assert_eq!(webhooks.unregister("1"), Err(MocksError::ObjectNotFound));
+        assert_eq!(webhooks.unregister("x"), Err(MocksError::ObjectNotFound));
+        // IDs are not reused after a removal
+        let hook = webhooks
+            .register(&json!({"url": "http://localhost:4002"}))
+            .unwrap();
+        assert_eq!(hook["id"], 3);
+    }
+
+    #[test]
+    fn test_matches() {
+        let hook = Webhook::parse(
+            1,
+            &json!({"url": "http://a", "resource": "orders", "operations": ["insert", "delete"]}),
+        )
+        .unwrap();
+        assert!(hook.matches(&change("orders", Operation::Insert)));
+        assert!(hook.matches(&change("orders", Operation::Delete)));
+        assert!(!hook.matches(&change("orders", Operation::Update)));
+        assert!(!hook.matches(&change("users", Operation::Insert)));
+
+        let hook = Webhook::parse(1, &json!({"url": "http://a"})).unwrap();
+        assert!(hook.matches(&change("users", Operation::Replace)));
+    }
+
+    #[test]
+    fn test_sign() {
+        // HMAC-SHA256 test case 2 of RFC 4231
+        assert_eq!(
+            sign("Jefe", b"what do ya want for nothing?"),
+            "sha256=5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dispatch() {
+        let (url, received) = flaky_receiver().await;
+        let mut webhooks = load(json!({"webhooks": [
+            {"url": url, "resource": "orders", "operations": ["insert"], "secret": "s3cret"}
+        ]}))
+        .unwrap();
+        webhooks.retry_delay = Duration::from_millis(1);
+        let webhooks = Arc::new(webhooks);
+
+        let (sender, receiver) = broadcast::channel(16);
+        tokio::spawn(webhooks.clone().dispatch(receiver));
+        sender.send(change("orders", Operation::Update)).unwrap();
+        sender.send(change("orders", Operation::Insert)).unwrap();
+
+        let delivery = settled(&webhooks).await;
+        assert_eq!(delivery["id"], 1);
+        assert_eq!(delivery["event"], "orders.insert");
+        assert_eq!(delivery["status"], "delivered");
+        assert_eq!(delivery["attempts"][0]["status"], 503);
+        assert_eq!(delivery["attempts"][1]["status"], 204);
+        assert_eq!(webhooks.deliveries().unwrap().as_array().unwrap().len(), 1);
+
+        let received = received.lock().unwrap();
+        assert_eq!(received.len(), 2);
+        let (headers, body) = &received[1];
+        assert_eq!(headers[EVENT_HEADER], "orders.insert");
+        assert_eq!(headers[DELIVERY_HEADER], "1");
+        assert_eq!(headers[SIGNATURE_HEADER], sign("s3cret", body).as_str());
+        // Retries send the same payload
+        assert_eq!(received[0].1, *body);
+        let body: Value = serde_json::from_slice(body).unwrap();
+        assert_eq!(body["event"], "orders.insert");
+        assert_eq!(body["data"], change("orders", Operation::Insert).to_value());
+    }
+
+    #[tokio::test]
+    async fn test_dispatch_failed() {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        drop(listener);
+
+        let mut webhooks = load(json!({"webhooks": [{"url": format!("http://{addr}")}]})).unwrap();
+        webhooks.retry_delay = Duration::from_millis(1);
+        let webhooks = Arc::new(webhooks);
+
+        let (sender, receiver) = broadcast::channel(16);
+        tokio::spawn(webhooks.clone().dispatch(receiver));
+        sender.send(change("users", Operation::Delete)).unwrap();
+
+        let delivery = settled(&webhooks).await;
+        assert_eq!(delivery["status"], "failed");
+        let attempts = delivery["attempts"].as_array().unwrap();
+        assert_eq!(attempts.len(), MAX_ATTEMPTS);
+        assert!(attempts[0]["error"].is_string());
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error::MocksError;
+use crate::storage::change::{Change, Operation};
 use crate::storage::operation::insert::insert;
 use crate::storage::operation::remove::remove;
 use crate::storage::operation::replace::replace;
@@ -8,27 +9,43 @@ use crate::storage::operation::select_one::select_one;
 use crate::storage::operation::select_with_filter::select_with_filter;
 use crate::storage::operation::update::update;
 use crate::storage::operation::update_one::update_one;
+use crate::storage::owner::{Owner, Ownership};
 use crate::storage::reader::Reader;
+use crate::storage::stamp::Stamps;
 use crate::storage::writer::Writer;
-use serde_json::Value;
+use serde_json::{Map, Value};
 use std::collections::HashMap;
 use std::fs;
 use std::io::{self, Write};
 use std::path::Path;
+use tokio::sync::broadcast;
 
+pub mod change;
 mod operation;
+pub mod owner;
 mod reader;
+pub mod recorder;
+mod stamp;
 mod writer;
 
 pub type StorageData = Value;
 pub type Input = Value;
 
+/// Reserved key in the storage file holding mocks configuration
+pub const CONFIG_KEY: &str = "_mocks";
+
+/// Changes kept for subscribers reading slower than changes are made
+const CHANGES_CAPACITY: usize = 256;
+
 /// Storage module
 #[derive(Clone)]
 pub struct Storage {
     pub file: String,
     pub data: StorageData,
     pub overwrite: bool,
+    /// Contents of the reserved `_mocks` key, kept apart from resource data
+    pub config: Value,
+    changes: broadcast::Sender<Change>,
 }
 
 impl Storage {
@@ -38,12 +55,30 @@ impl Storage {
     /// - `path` - The file path for storage
     /// - `overwrite` - Whether to overwrite the file on changes
     pub fn new(path: &str, overwrite: bool) -> Result<Storage, MocksError> {
-        let data = Reader::new(path).read()?;
-        Ok(Storage {
+        let mut data = Reader::new(path).read()?;
+        let config = data
+            .as_object_mut()
+            .and_then(|obj| obj.remove(CONFIG_KEY))
+            .unwrap_or_else(|| Value::Object(Map::new()));
+        let storage = Storage {
             file: path.to_string(),
             data,
             overwrite,
-        })
+            config,
+            changes: broadcast::channel(CHANGES_CAPACITY).0,
+        };
+        for resource_key in storage.resources() {
+            Ownership::from_config(&storage.config, &resource_key)?;
+        }
+        Ok(storage)
+    }
+
+    /// Load a storage from `data` through a temporary file
+    #[cfg(test)]
+    pub fn from_value(data: &Value) -> Storage {
+        let file = tempfile::NamedTempFile::new().unwrap();
+        fs::write(file.path(), data.to_string()).unwrap();
+        Storage::new(file.path().to_str().unwrap(), false).unwrap()
     }
 
     /// Initialize a new storage file
@@ -65,6 +100,41 @@ impl Storage {
         file_path: &str,
         empty: bool,
         force_overwrite: bool,
+    ) -> Result<(), MocksError> {
+        let data = if empty {
+            serde_json::json!({
+                "posts": [],
+                "profile": {}
+            })
+        } else {
+            serde_json::json!({
+                "posts": [
+                    {
+                        "id": 1,
+                        "title": "Hello World",
+                        "content": "This is a sample post"
+                    }
+                ],
+                "profile": {
+                    "id": 1,
+                    "name": "Sample User"
+                }
+            })
+        };
+
+        Self::init_file_with_data(file_path, &data, force_overwrite)
+    }
+
+    /// Initialize a new storage file with the given data
+    ///
+    /// # Arguments
+    /// - `file_path` - The path where the storage file will be created
+    /// - `data` - The data written to the storage file
+    /// - `force_overwrite` - Whether to overwrite existing files without prompting
+    pub fn init_file_with_data(
+        file_path: &str,
+        data: &Value,
+        force_overwrite: bool,
     ) -> Result<(), MocksError> {
         let path = Path::new(file_path);
 
@@ -92,29 +162,8 @@ impl Storage {
             }
         }
 
-        let data = if empty {
-            serde_json::json!({
-                "posts": [],
-                "profile": {}
-            })
-        } else {
-            serde_json::json!({
-                "posts": [
-                    {
-                        "id": 1,
-                        "title": "Hello World",
-                        "content": "This is a sample post"
-                    }
-                ],
-                "profile": {
-                    "id": 1,
-                    "name": "Sample User"
-                }
-            })
-        };
-
         let writer = Writer::new(file_path);
-        writer.write(&data)?;
+        writer.write(data)?;
 
         Ok(())
     }
@@ -133,10 +182,33 @@ impl Storage {
         resources
     }
 
+    /// Owner of the items a client can access in a resource
+    ///
+    /// # Arguments
+    /// * `resource_key` - The resource accessed
+    /// * `claims` - Claims of the authenticated client, if any
+    ///
+    /// # Returns
+    /// * `None` when the resource is not owned or the client is not authenticated
+    pub fn owner(&self, resource_key: &str, claims: Option<&Value>) -> Option<Owner> {
+        let claims = claims?;
+        // The ownership config is checked when the storage is loaded
+        Ownership::from_config(&self.config, resource_key)
+            .ok()
+            .flatten()
+            .map(|o| o.owner(claims))
+    }
+
+    /// Subscribe to the changes made to the data from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<Change> {
+        self.changes.subscribe()
+    }
+
     /// **GET**
     /// Retrieve all items for a given resource
-    pub fn get_all(&self, resource_key: &str) -> Result<Value, MocksError> {
-        self.fetch(|data| select_all(data, resource_key))
+    pub fn get_all(&self, resource_key: &str, owner: Option<&Owner>) -> Result<Value, MocksError> {
+        let value = self.fetch(|data| select_all(data, resource_key))?;
+        Ok(scope(value, owner))
     }
 
     /// **GET**
@@ -145,20 +217,41 @@ impl Storage {
         &self,
         resource_key: &str,
         filters: &HashMap<String, String>,
+        owner: Option<&Owner>,
     ) -> Result<Value, MocksError> {
-        self.fetch(|data| select_with_filter(data, resource_key, filters))
+        let value = self.fetch(|data| select_with_filter(data, resource_key, filters))?;
+        Ok(scope(value, owner))
     }
 
     /// **GET**
     /// Retrieve a specific item from a resource
-    pub fn get_one(&self, resource_key: &str, item_key: &str) -> Result<Value, MocksError> {
-        self.fetch(|data| select_one(data, resource_key, item_key))
+    pub fn get_one(
+        &self,
+        resource_key: &str,
+        item_key: &str,
+        owner: Option<&Owner>,
+    ) -> Result<Value, MocksError> {
+        let value = self.fetch(|data| select_one(data, resource_key, item_key))?;
+        match owner {
+            Some(owner) => owner.check(value),
+            None => Ok(value),
+        }
     }
 
     /// **POST**
     /// Insert a new item into a resource
-    pub fn insert(&mut self, resource_key: &str, input: &Value) -> Result<Value, MocksError> {
-        self.operate(|data| insert(data, resource_key, input))
+    pub fn insert(
+        &mut self,
+        resource_key: &str,
+        input: &Value,
+        owner: Option<&Owner>,
+    ) -> Result<Value, MocksError> {
+        let records = select_all(&self.data, resource_key).ok();
+        let input = owned_input(input, None, records.as_ref(), owner)?;
+        let input = self.stamps(resource_key).on_insert(&input);
+        self.operate(Operation::Insert, resource_key, None, |data| {
+            insert(data, resource_key, &input)
+        })
     }
 
     /// **PUT**
@@ -168,14 +261,28 @@ impl Storage {
         resource_key: &str,
         item_key: &str,
         input: &Value,
+        owner: Option<&Owner>,
     ) -> Result<Value, MocksError> {
-        self.operate(|data| replace(data, resource_key, item_key, input))
+        let existing = select_one(&self.data, resource_key, item_key).ok();
+        let input = owned_input(input, existing.as_ref(), None, owner)?;
+        let input = self
+            .stamps(resource_key)
+            .on_replace(&input, existing.as_ref());
+        self.operate(Operation::Replace, resource_key, Some(item_key), |data| {
+            replace(data, resource_key, item_key, &input)
+        })
     }
 
     /// **PUT**
     /// Replace the first item in a resource
     pub fn replace_one(&mut self, resource_key: &str, input: &Value) -> Result<Value, MocksError> {
-        self.operate(|data| replace_one(data, resource_key, input))
+        let existing = select_all(&self.data, resource_key).ok();
+        let input = self
+            .stamps(resource_key)
+            .on_replace(input, existing.as_ref());
+        self.operate(Operation::Replace, resource_key, None, |data| {
+            replace_one(data, resource_key, &input)
+        })
     }
 
     /// **PATCH**
@@ -185,20 +292,60 @@ impl Storage {
         resource_key: &str,
         item_key: &str,
         input: &Value,
+        owner: Option<&Owner>,
     ) -> Result<Value, MocksError> {
-        self.operate(|data| update(data, resource_key, item_key, input))
+        let existing = select_one(&self.data, resource_key, item_key).ok();
+        let input = owned_input(input, existing.as_ref(), None, owner)?;
+        let input = self
+            .stamps(resource_key)
+            .on_update(&input, existing.as_ref());
+        self.operate(Operation::Update, resource_key, Some(item_key), |data| {
+            update(data, resource_key, item_key, &input)
+        })
     }
 
     /// **PATCH**
     /// Update parts of the first item in a resource
     pub fn update_one(&mut self, resource_key: &str, input: &Value) -> Result<Value, MocksError> {
-        self.operate(|data| update_one(data, resource_key, input))
+        let existing = select_all(&self.data, resource_key).ok();
+        let input = self
+            .stamps(resource_key)
+            .on_update(input, existing.as_ref());
+        self.operate(Operation::Update, resource_key, None, |data| {
+            update_one(data, resource_key, &input)
+        })
+    }
+
+    /// Replace the whole data of a resource, creating the resource if needed
+    pub fn set_resource(&mut self, resource_key: &str, value: Value) -> Result<Value, MocksError> {
+        self.operate(Operation::Replace, resource_key, None, |data| {
+            let obj = data.as_object_mut().ok_or_else(|| {
+                MocksError::Exception("Storage data is not an object.".to_string())
+            })?;
+            obj.insert(resource_key.to_string(), value.clone());
+            Ok(value)
+        })
     }
 
     /// **DELETE**
     /// Delete an item from a resource
-    pub fn delete(&mut self, resource_key: &str, item_key: &str) -> Result<Value, MocksError> {
-        self.operate(|data| remove(data, resource_key, item_key))
+    pub fn delete(
+        &mut self,
+        resource_key: &str,
+        item_key: &str,
+        owner: Option<&Owner>,
+    ) -> Result<Value, MocksError> {
+        if let Some(owner) = owner {
+            owner.check(select_one(&self.data, resource_key, item_key)?)?;
+        }
+        self.operate(Operation::Delete, resource_key, Some(item_key), |data| {
+            remove(data, resource_key, item_key)
+        })
+    }
+
+    /// Server-managed fields configured for a resource
+    fn stamps(&self, resource_key: &str) -> Stamps {
+        Stamps::from_config(&self.config, resource_key)
     }
 
     /// Fetches data from the storage using the provided operation
@@ -216,26 +363,80 @@ impl Storage {
     /// Perform an operation on the storage data and write changes if successful
     ///
     /// This method abstracts the common pattern of performing an operation,
-    /// writing the changes, and returning the result.
-    fn operate<F>(&mut self, operation: F) -> Result<Value, MocksError>
+    /// writing the changes, publishing them to subscribers, and returning the result.
+    /// `item_key` is the item changed in an array resource, `None` for inserts and object resources.
+    fn operate<F>(
+        &mut self,
+        kind: Operation,
+        resource_key: &str,
+        item_key: Option<&str>,
+        operation: F,
+    ) -> Result<Value, MocksError>
     where
         F: FnOnce(&mut StorageData) -> Result<Value, MocksError>,
     {
         let result = operation(&mut self.data)?;
         self.write()?;
+
+        let id = match (kind, item_key) {
+            (Operation::Insert, _) | (_, Some(_)) => result
+                .get("id")
+                .cloned()
+                .or_else(|| item_key.map(|key| Value::String(key.to_string())))
+                .unwrap_or(Value::Null),
+            _ => Value::Null,
+        };
+        // Sending fails only when nobody is subscribed
+        let _ = self.changes.send(Change {
+            operation: kind,
+            resource: resource_key.to_string(),
+            id,
+            value: result.clone(),
+        });
         Ok(result)
     }
 
     /// Write changes to the storage file if overwrite is enabled
     fn write(&mut self) -> Result<(), MocksError> {
         if self.overwrite {
+            let mut data = self.data.clone();
+            if let (Some(obj), Some(config)) = (data.as_object_mut(), self.config.as_object()) {
+                if !config.is_empty() {
+                    obj.insert(CONFIG_KEY.to_string(), self.config.clone());
+                }
+            }
+
             let writer = Writer::new(&self.file);
-            writer.write(&self.data)?;
+            writer.write(&data)?;
         }
         Ok(())
     }
 }
 
+/// Keep the items of a resource belonging to an owner
+fn scope(value: Value, owner: Option<&Owner>) -> Value {
+    match owner {
+        Some(owner) => owner.retain(value),
+        None => value,
+    }
+}
+
+/// Input written by an owner, who must own the existing item
+fn owned_input(
+    input: &Input,
+    existing: Option<&Value>,
+    records: Option<&Value>,
+    owner: Option<&Owner>,
+) -> Result<Input, MocksError> {
+    let Some(owner) = owner else {
+        return Ok(input.clone());
+    };
+    if existing.is_some_and(|e| !owner.owns(e)) {
+        return Err(MocksError::ObjectNotFound);
+    }
+    owner.assign(input, existing, records)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -346,6 +547,20 @@ mod tests {
         assert!(!content.contains("Hello World"));
     }
 
+    #[test]
+    fn test_init_file_with_data() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.json");
+        let file_path_str = file_path.to_str().unwrap();
+
+        let data = serde_json::json!({"users": [{"id": 1, "name": "Jane"}]});
+        let result = Storage::init_file_with_data(file_path_str, &data, true);
+        assert!(result.is_ok());
+
+        let storage = Storage::new(file_path_str, false).unwrap();
+        assert_eq!(storage.data, data);
+    }
+
     #[test]
     fn test_resources_with_objects_and_arrays() {
         let temp_dir = TempDir::new().unwrap();
@@ -506,4 +721,204 @@ mod tests {
         let storage_result = Storage::new(file_path_str, false);
         assert!(storage_result.is_err());
     }
+
+    #[test]
+    fn test_storage_new_separates_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.json");
+        let file_path_str = file_path.to_str().unwrap();
+
+        let test_data = serde_json::json!({
+            "posts": [{"id": 1}],
+            "_mocks": {"schemas": {"posts": {"type": "object"}}}
+        });
+
+        fs::write(&file_path, test_data.to_string()).unwrap();
+        let storage = Storage::new(file_path_str, false).unwrap();
+
+        assert_eq!(storage.resources(), vec!["posts".to_string()]);
+        assert!(storage.data.get(CONFIG_KEY).is_none());
+        assert_eq!(
+            storage.config,
+            serde_json::json!({"schemas": {"posts": {"type": "object"}}})
+        );
+    }
+
+    #[test]
+    fn test_storage_write_preserves_config() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.json");
+        let file_path_str = file_path.to_str().unwrap();
+
+        let test_data = serde_json::json!({
+            "posts": [],
+            "_mocks": {"schemas": {}}
+        });
+
+        fs::write(&file_path, test_data.to_string()).unwrap();
+        let mut storage = Storage::new(file_path_str, true).unwrap();
+        storage
+            .insert("posts", &serde_json::json!({"id": 1}), None)
+            .unwrap();
+
+        let written: Value =
+            serde_json::from_str(&fs::read_to_string(&file_path).unwrap()).unwrap();
+        assert_eq!(
+            written,
+            serde_json::json!({"posts": [{"id": 1}], "_mocks": {"schemas": {}}})
+        );
+    }
+
+    #[test]
+    fn test_set_resource() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.json");
+        let file_path_str = file_path.to_str().unwrap();
+
+        fs::write(&file_path, r#"{"posts": [{"id": 1}]}"#).unwrap();
+        let mut storage = Storage::new(file_path_str, true).unwrap();
+        storage
+            .set_resource("users", serde_json::json!([{"id": 1}, {"id": 2}]))
+            .unwrap();
+        storage
+            .set_resource("posts", serde_json::json!([]))
+            .unwrap();
+
+        let written: Value =
+            serde_json::from_str(&fs::read_to_string(&file_path).unwrap()).unwrap();
+        assert_eq!(
+            written,
+            serde_json::json!({"posts": [], "users": [{"id": 1}, {"id": 2}]})
+        );
+    }
+
+    #[test]
+    fn test_storage_stamps_changes() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.json");
+        let file_path_str = file_path.to_str().unwrap();
+
+        let test_data = serde_json::json!({
+            "posts": [],
+            "profile": {"id": 1, "name": "a"},
+            "_mocks": {
+                "resources": {
+                    "posts": {"timestamps": true, "version": true},
+                    "profile": {"version": true}
+                }
+            }
+        });
+        fs::write(&file_path, test_data.to_string()).unwrap();
+        let mut storage = Storage::new(file_path_str, false).unwrap();
+
+        let post = storage
+            .insert("posts", &serde_json::json!({"id": 1, "title": "a"}), None)
+            .unwrap();
+        assert_eq!(post["version"], 1);
+        let created = post["createdAt"].clone();
+
+        let post = storage
+            .update("posts", "1", &serde_json::json!({"title": "b"}), None)
+            .unwrap();
+        assert_eq!(post["version"], 2);
+        assert_eq!(post["createdAt"], created);
+
+        let post = storage
+            .replace(
+                "posts",
+                "1",
+                &serde_json::json!({"id": 1, "title": "c"}),
+                None,
+            )
+            .unwrap();
+        assert_eq!(post["version"], 3);
+        assert_eq!(post["createdAt"], created);
+
+        let profile = storage
+            .update_one("profile", &serde_json::json!({"name": "b"}))
+            .unwrap();
+        assert_eq!(
+            profile,
+            serde_json::json!({"id": 1, "name": "b", "version": 1})
+        );
+        let profile = storage
+            .replace_one("profile", &serde_json::json!({"id": 1, "name": "c"}))
+            .unwrap();
+        assert_eq!(
+            profile,
+            serde_json::json!({"id": 1, "name": "c", "version": 2})
+        );
+    }
+
+    #[test]
+    fn test_storage_scopes_owned_items() {
+        let temp_dir = TempDir::new().unwrap();
+        let file_path = temp_dir.path().join("test.json");
+        let file_path_str = file_path.to_str().unwrap();
+
+        let test_data = serde_json::json!({
+            "todos": [{"id": 1, "user_id": 1}, {"id": 2, "user_id": 2}],
+            "_mocks": {"resources": {"todos": {"owner": "user_id"}}}
+        });
+        fs::write(&file_path, test_data.to_string()).unwrap();
+        let mut storage = Storage::new(file_path_str, false).unwrap();
+
+        assert_eq!(storage.owner("todos", None), None);
+        let alice = storage.owner("todos", Some(&serde_json::json!({"sub": "1"})));
+        let alice = alice.as_ref();
+
+        assert_eq!(
+            storage.get_all("todos", alice).unwrap(),
+            serde_json::json!([{"id": 1, "user_id": 1}])
+        );
+        let filters = HashMap::from([("id.exact".to_string(), "2".to_string())]);
+        assert_eq!(
+            storage
+                .get_all_with_filter("todos", &filters, alice)
+                .unwrap(),
+            serde_json::json!([])
+        );
+        assert!(storage.get_one("todos", "1", alice).is_ok());
+        assert_eq!(
+            storage.get_one("todos", "2", alice),
+            Err(MocksError::ObjectNotFound)
+        );
+
+        let todo = storage
+            .insert("todos", &serde_json::json!({"id": 3, "user_id": 2}), alice)
+            .unwrap();
+        assert_eq!(todo, serde_json::json!({"id": 3, "user_id": 1}));
+        let todo = storage
+            .update("todos", "1", &serde_json::json!({"user_id": 2}), alice)
+            .unwrap();
+        assert_eq!(todo["user_id"], 1);
+
+        for result in [
+            storage.update("todos", "2", &serde_json::json!({"done": true}), alice),
+            storage.replace("todos", "2", &serde_json::json!({"id": 2}), alice),
+            storage.delete("todos", "2", alice),
+        ] {
+            assert_eq!(result, Err(MocksError::ObjectNotFound));
+        }
+        assert!(storage.delete("todos", "1", alice).is_ok());
+        assert_eq!(
+            storage
+                .get_all("todos", None)
+                .unwrap()
+                .as_array()
+                .unwrap()
+                .len(),
+            2
+        );
+
+        let invalid = serde_json::json!({
+            "todos": [],
+            "_mocks": {"resources": {"todos": {"owner": {"claim": "org"}}}}
+        });
+        fs::write(&file_path, invalid.to_string()).unwrap();
+        assert!(matches!(
+            Storage::new(file_path_str, false),
+            Err(MocksError::InvalidArgs(_))
+        ));
+    }
 }
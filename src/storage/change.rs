@@ -0,0 +1,106 @@
+use crate::error::MocksError;
+use serde_json::{json, Value};
+use std::str::FromStr;
+
+/// Kind of change made to the data of a resource
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operation {
+    Insert,
+    Replace,
+    Update,
+    Delete,
+}
+
+impl Operation {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Operation::Insert => "insert",
+            Operation::Replace => "replace",
+            Operation::Update => "update",
+            Operation::Delete => "delete",
+        }
+    }
+}
+
+impl FromStr for Operation {
+    type Err = MocksError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "insert" => Ok(Operation::Insert),
+            "replace" => Ok(Operation::Replace),
+            "update" => Ok(Operation::Update),
+            "delete" => Ok(Operation::Delete),
+            _ => Err(MocksError::InvalidArgs(format!(
+                "Invalid operation: {s}. Use: insert, replace, update, delete."
+            ))),
+        }
+    }
+}
+
+/// A change made to the data of a storage, published to its subscribers
+#[derive(Debug, Clone, PartialEq)]
+pub struct Change {
+    pub operation: Operation,
+    pub resource: String,
+    /// ID of the changed item, `null` for object resources
+    pub id: Value,
+    /// The item after the change, or the removed item for deletes
+    pub value: Value,
+}
+
+impl Change {
+    pub fn to_value(&self) -> Value {
+        json!({
+            "operation": self.operation.as_str(),
+            "resource": self.resource,
+            "id": self.id,
+            "value": self.value,
+        })
+    }
+
+    /// The change without the item, for clients not entitled to read it
+    pub fn to_summary(&self) -> Value {
+        json!({
+            "operation": self.operation.as_str(),
+            "resource": self.resource,
+            "id": self.id,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_operation_from_str() {
+        for operation in [
+            Operation::Insert,
+            Operation::Replace,
+            Operation::Update,
+            Operation::Delete,
+        ] {
+            assert_eq!(operation.as_str().parse::<Operation>(), Ok(operation));
+        }
+        assert!("create".parse::<Operation>().is_err());
+    }
+
+    #[test]
+    fn test_to_value() {
+        let change = Change {
+            operation: Operation::Delete,
+            resource: "posts".to_string(),
+            id: json!(1),
+            value: json!({"id": 1, "title": "a"}),
+        };
+        assert_eq!(
+            change.to_value(),
+            json!({"operation": "delete", "resource": "posts", "id": 1, "value": {"id": 1, "title": "a"}})
+        );
+        assert_eq!(
+            change.to_summary(),
+            json!({"operation": "delete", "resource": "posts", "id": 1})
+        );
+    }
+}
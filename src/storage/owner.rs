@@ -0,0 +1,280 @@
+use crate::error::MocksError;
+use crate::schema::resource_name;
+use crate::storage::Input;
+use serde_json::Value;
+
+/// Claim identifying the owner of records, unless configured
+const DEFAULT_CLAIM: &str = "sub";
+
+/// Field of a resource holding the owner of each record
+///
+/// Enabled per resource in the storage config:
+///
+/// ```json
+/// {"_mocks": {"resources": {"todos": {"owner": "user_id"}}}}
+/// ```
+///
+/// `owner` also accepts `{"field": "user_id", "claim": "uid"}`, to match another claim
+/// of the authenticated client than `sub`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ownership {
+    field: String,
+    claim: String,
+}
+
+impl Ownership {
+    /// Read the ownership of a resource from the storage config
+    ///
+    /// # Arguments
+    /// * `config` - Contents of the reserved `_mocks` key
+    /// * `resource_key` - Resource key, possibly nested (e.g. `api/v1/todos`)
+    ///
+    /// # Returns
+    /// * `Ok(None)` when the resource is not owned
+    /// * `Err(MocksError::InvalidArgs)` when `owner` names no field
+    pub fn from_config(
+        config: &Value,
+        resource_key: &str,
+    ) -> Result<Option<Ownership>, MocksError> {
+        let Some(owner) = config
+            .get("resources")
+            .and_then(|r| r.get(resource_name(resource_key)))
+            .and_then(|r| r.get("owner"))
+        else {
+            return Ok(None);
+        };
+
+        let field = match owner {
+            Value::String(field) => Some(field.as_str()),
+            Value::Object(obj) => obj.get("field").and_then(Value::as_str),
+            _ => None,
+        };
+        let claim = match owner.get("claim") {
+            None => Some(DEFAULT_CLAIM),
+            Some(claim) => claim.as_str(),
+        };
+        match (field, claim) {
+            (Some(field), Some(claim)) if !field.is_empty() && !claim.is_empty() => {
+                Ok(Some(Ownership {
+                    field: field.to_string(),
+                    claim: claim.to_string(),
+                }))
+            }
+            _ => Err(MocksError::InvalidArgs(format!(
+                "Invalid owner of {resource_key}: {owner} must be a field name or {{\"field\": ..., \"claim\": ...}}"
+            ))),
+        }
+    }
+
+    /// Owner of the records a client with these claims can access
+    pub fn owner(&self, claims: &Value) -> Owner {
+        Owner {
+            field: self.field.clone(),
+            id: claims
+                .get(&self.claim)
+                .filter(|id| owner_id(id).is_some())
+                .cloned(),
+        }
+    }
+}
+
+/// Client the records of a request are scoped to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Owner {
+    field: String,
+    /// Owner ID of the client; a client without one owns no record
+    id: Option<Value>,
+}
+
+impl Owner {
+    /// Whether a record belongs to the client
+    pub fn owns(&self, item: &Value) -> bool {
+        let Some(id) = &self.id else {
+            return false;
+        };
+        item.get(&self.field).and_then(owner_id) == owner_id(id)
+    }
+
+    /// Keep the records of a resource belonging to the client
+    pub fn retain(&self, value: Value) -> Value {
+        match value {
+            Value::Array(items) => {
+                Value::Array(items.into_iter().filter(|item| self.owns(item)).collect())
+            }
+            other => other,
+        }
+    }
+
+    /// Check a record belongs to the client, hiding it as not found otherwise
+    pub fn check(&self, item: Value) -> Result<Value, MocksError> {
+        if self.owns(&item) {
+            Ok(item)
+        } else {
+            Err(MocksError::ObjectNotFound)
+        }
+    }
+
+    /// Set the owner of a record written by the client
+    ///
+    /// # Arguments
+    /// * `input` - The record written
+    /// * `existing` - The record replaced or updated, which keeps its owner value
+    /// * `records` - Records of the resource, whose owner values give the type of a new
+    ///   owner, so a `"1"` claim is stored as `1` next to numeric owners
+    ///
+    /// # Returns
+    /// * `Err(MocksError::Forbidden)` when the client has no owner ID
+    pub fn assign(
+        &self,
+        input: &Input,
+        existing: Option<&Value>,
+        records: Option<&Value>,
+    ) -> Result<Input, MocksError> {
+        let Some(id) = &self.id else {
+            return Err(MocksError::Forbidden);
+        };
+        let mut input = input.clone();
+        if let Some(obj) = input.as_object_mut() {
+            let id = match existing.and_then(|e| e.get(&self.field)) {
+                Some(existing) => existing.clone(),
+                None => self.typed_like(id, records),
+            };
+            obj.insert(self.field.clone(), id);
+        }
+        Ok(input)
+    }
+
+    /// An owner ID of the type the owners of existing records have
+    fn typed_like(&self, id: &Value, records: Option<&Value>) -> Value {
+        let sample = records
+            .and_then(Value::as_array)
+            .and_then(|items| items.iter().find_map(|item| item.get(&self.field)));
+        match (sample, id) {
+            (Some(Value::Number(_)), Value::String(s)) => s
+                .parse::<i64>()
+                .map(Value::from)
+                .unwrap_or_else(|_| id.clone()),
+            (Some(Value::String(_)), Value::Number(n)) => Value::String(n.to_string()),
+            _ => id.clone(),
+        }
+    }
+}
+
+/// Comparable form of an owner ID, so a claim `"1"` owns records with `1`
+fn owner_id(value: &Value) -> Option<String> {
+    match value {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn owner(sub: Value) -> Owner {
+        let config = json!({"resources": {"todos": {"owner": "user_id"}}});
+        Ownership::from_config(&config, "api/v1/todos")
+            .unwrap()
+            .unwrap()
+            .owner(&json!({"sub": sub}))
+    }
+
+    #[test]
+    fn test_from_config() {
+        assert_eq!(Ownership::from_config(&json!({}), "todos"), Ok(None));
+
+        let config =
+            json!({"resources": {"todos": {"owner": {"field": "tenant", "claim": "org"}}}});
+        assert_eq!(
+            Ownership::from_config(&config, "todos"),
+            Ok(Some(Ownership {
+                field: "tenant".to_string(),
+                claim: "org".to_string(),
+            }))
+        );
+
+        for owner in [
+            json!(true),
+            json!(""),
+            json!({"claim": "org"}),
+            json!({"field": "tenant", "claim": 1}),
+        ] {
+            let config = json!({"resources": {"todos": {"owner": owner}}});
+            assert!(
+                matches!(
+                    Ownership::from_config(&config, "todos"),
+                    Err(MocksError::InvalidArgs(_))
+                ),
+                "{owner} should be invalid"
+            );
+        }
+    }
+
+    #[test]
+    fn test_owns() {
+        let alice = owner(json!("1"));
+        assert!(alice.owns(&json!({"id": 1, "user_id": 1})));
+        assert!(alice.owns(&json!({"id": 1, "user_id": "1"})));
+        assert!(!alice.owns(&json!({"id": 1, "user_id": 2})));
+        assert!(!alice.owns(&json!({"id": 1})));
+
+        let anonymous = owner(json!(null));
+        assert!(!anonymous.owns(&json!({"id": 1, "user_id": null})));
+    }
+
+    #[test]
+    fn test_retain() {
+        let todos = json!([{"id": 1, "user_id": 1}, {"id": 2, "user_id": 2}]);
+        assert_eq!(
+            owner(json!(2)).retain(todos),
+            json!([{"id": 2, "user_id": 2}])
+        );
+        assert_eq!(owner(json!(2)).retain(json!({"id": 1})), json!({"id": 1}));
+    }
+
+    #[test]
+    fn test_check() {
+        let todo = json!({"id": 1, "user_id": 1});
+        assert_eq!(owner(json!(1)).check(todo.clone()), Ok(todo.clone()));
+        assert_eq!(owner(json!(2)).check(todo), Err(MocksError::ObjectNotFound));
+    }
+
+    #[test]
+    fn test_assign() {
+        let alice = owner(json!("1"));
+        assert_eq!(
+            alice.assign(&json!({"id": 1, "user_id": 2}), None, None),
+            Ok(json!({"id": 1, "user_id": "1"}))
+        );
+        let existing = json!({"id": 1, "user_id": 1});
+        assert_eq!(
+            alice.assign(&json!({"user_id": 2}), Some(&existing), None),
+            Ok(json!({"user_id": 1}))
+        );
+        assert_eq!(
+            owner(json!(null)).assign(&json!({"id": 1}), None, None),
+            Err(MocksError::Forbidden)
+        );
+    }
+
+    #[test]
+    fn test_assign_typed_like_records() {
+        let numeric = json!([{"id": 1}, {"id": 2, "user_id": 2}]);
+        assert_eq!(
+            owner(json!("1")).assign(&json!({"id": 3}), None, Some(&numeric)),
+            Ok(json!({"id": 3, "user_id": 1}))
+        );
+        assert_eq!(
+            owner(json!("alice")).assign(&json!({"id": 3}), None, Some(&numeric)),
+            Ok(json!({"id": 3, "user_id": "alice"}))
+        );
+        let strings = json!([{"id": 1, "user_id": "2"}]);
+        assert_eq!(
+            owner(json!(1)).assign(&json!({"id": 3}), None, Some(&strings)),
+            Ok(json!({"id": 3, "user_id": "1"}))
+        );
+    }
+}
@@ -1,4 +1,5 @@
 use crate::error::MocksError;
+use crate::storage::CONFIG_KEY;
 use serde_json::Value;
 use std::collections::HashSet;
 use std::fs;
@@ -56,7 +57,7 @@ impl Reader {
         // Allow only Object or Array
         if obj
             .iter()
-            .filter(|(k, _)| !k.is_empty())
+            .filter(|(k, _)| !k.is_empty() && k.as_str() != CONFIG_KEY)
             .any(|(_, v)| v.is_object() || v.is_array())
         {
             Ok(value)
@@ -0,0 +1,817 @@
+use crate::error::MocksError;
+use crate::storage::writer::Writer;
+use crate::storage::CONFIG_KEY;
+use serde_json::{json, Map, Value};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const ROUTES_KEY: &str = "routes";
+
+/// Directory next to the storage file holding recorded bodies that are not JSON
+const BODIES_DIR: &str = "recordings";
+
+/// Response headers not worth replaying: set per connection, per response or by other options
+const SKIPPED_HEADERS: [&str; 14] = [
+    "connection",
+    "keep-alive",
+    "proxy-authenticate",
+    "te",
+    "trailer",
+    "transfer-encoding",
+    "upgrade",
+    "content-length",
+    "content-encoding",
+    "date",
+    "server",
+    "via",
+    "alt-svc",
+    "etag",
+];
+
+/// Prefixes of response headers added by the CORS and rate limit options
+const SKIPPED_HEADER_PREFIXES: [&str; 2] = ["access-control-", "x-ratelimit-"];
+
+/// A request forwarded to the upstream and its response
+#[derive(Debug)]
+pub struct Exchange<'a> {
+    pub method: &'a str,
+    pub path: &'a str,
+    pub query: Option<&'a str>,
+    pub status: u16,
+    /// Response headers with lowercase names
+    pub headers: &'a [(String, String)],
+    pub body: &'a [u8],
+}
+
+/// Where an exchange was recorded in the storage file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Recorded {
+    /// Items of a collection resource
+    Items { resource: String, count: usize },
+    /// An item of a collection resource
+    Item { resource: String, id: String },
+    /// An item removed from a collection resource
+    Removed { resource: String, id: String },
+    /// An object resource
+    Object { resource: String },
+    /// A canned route, with the file its body was saved to if not JSON
+    Route {
+        method: String,
+        path: String,
+        body_file: Option<String>,
+    },
+    /// Not recorded, for the given reason
+    Skipped(String),
+}
+
+impl fmt::Display for Recorded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Recorded::Items { resource, count } => {
+                let plural = if *count == 1 { "" } else { "s" };
+                write!(f, "{resource} ({count} item{plural})")
+            }
+            Recorded::Item { resource, id } => write!(f, "{resource} (item {id})"),
+            Recorded::Removed { resource, id } => write!(f, "{resource} (removed item {id})"),
+            Recorded::Object { resource } => write!(f, "{resource} (object)"),
+            Recorded::Route {
+                method,
+                path,
+                body_file,
+            } => {
+                write!(f, "route {method} {path}")?;
+                match body_file {
+                    Some(file) => write!(f, " (body in {file})"),
+                    None => Ok(()),
+                }
+            }
+            Recorded::Skipped(reason) => write!(f, "skipped: {reason}"),
+        }
+    }
+}
+
+/// Folds exchanges with an upstream API into a storage file
+///
+/// JSON responses of collections and their items become resources laid out as `Reader::read`
+/// expects: `GET /users` upserts the listed items into `users` by `id`, `GET /users/1`,
+/// `PUT`, `PATCH` and `POST /users` upsert the returned item, and `DELETE /users/1` removes it.
+/// Other exchanges are saved as canned routes under `_mocks.routes`, with one `responses` entry
+/// per query string.
+/// The storage file is written after every recorded exchange.
+pub struct Recorder {
+    file: String,
+    data: Map<String, Value>,
+}
+
+impl Recorder {
+    /// Create a recorder adding to a storage file, created on the first recorded exchange
+    pub fn new(path: &str) -> Result<Recorder, MocksError> {
+        let data = if Path::new(path).exists() {
+            let text =
+                fs::read_to_string(path).map_err(|e| MocksError::FailedReadFile(e.to_string()))?;
+            match serde_json::from_str(&text) {
+                Ok(Value::Object(data)) => data,
+                Ok(_) => {
+                    return Err(MocksError::FailedReadFile(
+                        "Storage file is invalid JSON format.".to_string(),
+                    ))
+                }
+                Err(e) => return Err(MocksError::FailedReadFile(e.to_string())),
+            }
+        } else {
+            Map::new()
+        };
+        Ok(Recorder {
+            file: path.to_string(),
+            data,
+        })
+    }
+
+    /// Record an exchange and write the storage file
+    pub fn record(&mut self, exchange: &Exchange) -> Result<Recorded, MocksError> {
+        let recorded = self.fold(exchange)?;
+        if !matches!(recorded, Recorded::Skipped(_)) {
+            Writer::new(&self.file).write(&Value::Object(self.data.clone()))?;
+        }
+        Ok(recorded)
+    }
+
+    fn fold(&mut self, exchange: &Exchange) -> Result<Recorded, MocksError> {
+        let key = exchange.path.trim_matches('/');
+        let body = if is_json(exchange) {
+            serde_json::from_slice::<Value>(exchange.body).ok()
+        } else {
+            None
+        };
+
+        if (200..300).contains(&exchange.status) {
+            if let Some(recorded) = self.fold_resource(exchange, key, body.as_ref()) {
+                return Ok(recorded);
+            }
+        }
+        if self.is_resource_path(key) {
+            return Ok(Recorded::Skipped(format!(
+                "{} {} is served by a recorded resource",
+                exchange.method, exchange.path
+            )));
+        }
+        if !is_routable(exchange.path) {
+            return Ok(Recorded::Skipped(format!(
+                "{} cannot be a route path",
+                exchange.path
+            )));
+        }
+        self.fold_route(exchange, body)
+    }
+
+    fn fold_resource(
+        &mut self,
+        exchange: &Exchange,
+        key: &str,
+        body: Option<&Value>,
+    ) -> Option<Recorded> {
+        let (parent, last) = key.rsplit_once('/').unwrap_or(("", key));
+        let is_item = |value: &Value| id_of(value).is_some_and(|id| id == last);
+
+        match (exchange.method, body) {
+            ("GET", Some(body)) => {
+                let body = unwrap_resource(body, last);
+                if let Some(items) = listed_items(body) {
+                    if self.fits(key, Value::is_array) {
+                        let count = items.len();
+                        self.upsert(key, items);
+                        return Some(Recorded::Items {
+                            resource: key.to_string(),
+                            count,
+                        });
+                    }
+                }
+                if exchange.query.is_some() {
+                    return None;
+                }
+                self.fold_item_or_object(key, parent, body, is_item(body))
+            }
+            ("PUT" | "PATCH", Some(body)) => {
+                self.fold_item_or_object(key, parent, body, is_item(body))
+            }
+            ("POST", Some(body)) if body.is_object() && id_of(body).is_some() => {
+                if !self.fits(key, Value::is_array) {
+                    return None;
+                }
+                let id = id_of(body)?;
+                self.upsert(key, vec![body.clone()]);
+                Some(Recorded::Item {
+                    resource: key.to_string(),
+                    id,
+                })
+            }
+            ("DELETE", _) => {
+                let items = self.data.get_mut(parent)?.as_array_mut()?;
+                let position = items.iter().position(is_item)?;
+                items.remove(position);
+                Some(Recorded::Removed {
+                    resource: parent.to_string(),
+                    id: last.to_string(),
+                })
+            }
+            _ => None,
+        }
+    }
+
+    fn fold_item_or_object(
+        &mut self,
+        key: &str,
+        parent: &str,
+        body: &Value,
+        is_item: bool,
+    ) -> Option<Recorded> {
+        if !body.is_object() {
+            return None;
+        }
+        if is_item && !parent.is_empty() && self.fits(parent, Value::is_array) {
+            let id = id_of(body)?;
+            self.upsert(parent, vec![body.clone()]);
+            return Some(Recorded::Item {
+                resource: parent.to_string(),
+                id,
+            });
+        }
+        if id_of(body).is_none() && self.fits(key, Value::is_object) {
+            self.data.insert(key.to_string(), body.clone());
+            return Some(Recorded::Object {
+                resource: key.to_string(),
+            });
+        }
+        None
+    }
+
+    /// Whether a resource can be stored at a key, with `kind` of value if already stored
+    ///
+    /// Resources are served at `/{prefix}/{resource}` by their last segment, which must be unique,
+    /// and must not nest under another resource as its items are served below it.
+    /// Paths recorded as routes stay routes.
+    fn fits(&self, key: &str, kind: fn(&Value) -> bool) -> bool {
+        if let Some(value) = self.data.get(key) {
+            return kind(value);
+        }
+        if key.is_empty()
+            || self.routes().any(|route| {
+                route
+                    .get("path")
+                    .and_then(Value::as_str)
+                    .is_some_and(|path| path.trim_matches('/') == key)
+            })
+            || key
+                .split('/')
+                .any(|segment| !is_literal(segment) || segment.starts_with('_'))
+        {
+            return false;
+        }
+        let last = last_segment(key);
+        !self.resource_keys().any(|other| {
+            last_segment(other) == last
+                || other.starts_with(&format!("{key}/"))
+                || key.starts_with(&format!("{other}/"))
+        })
+    }
+
+    /// Whether a path is served by a recorded resource or one of its items
+    fn is_resource_path(&self, key: &str) -> bool {
+        let parent = key.rsplit_once('/').map_or("", |(parent, _)| parent);
+        (self.data.contains_key(key) && key != CONFIG_KEY)
+            || self.data.get(parent).is_some_and(Value::is_array)
+    }
+
+    fn routes(&self) -> impl Iterator<Item = &Value> {
+        self.data
+            .get(CONFIG_KEY)
+            .and_then(|config| config.get(ROUTES_KEY))
+            .and_then(Value::as_array)
+            .into_iter()
+            .flatten()
+    }
+
+    fn resource_keys(&self) -> impl Iterator<Item = &str> {
+        self.data
+            .keys()
+            .map(String::as_str)
+            .filter(|key| *key != CONFIG_KEY)
+    }
+
+    /// Insert items into a collection, replacing those with the same id
+    fn upsert(&mut self, key: &str, items: Vec<Value>) {
+        let collection = self
+            .data
+            .entry(key.to_string())
+            .or_insert_with(|| Value::Array(vec![]));
+        let Value::Array(collection) = collection else {
+            return;
+        };
+        for item in items {
+            let existing = match id_of(&item) {
+                Some(id) => collection
+                    .iter()
+                    .position(|other| id_of(other).as_deref() == Some(id.as_str())),
+                None => collection.iter().position(|other| *other == item),
+            };
+            match existing {
+                Some(position) => collection[position] = item,
+                None => collection.push(item),
+            }
+        }
+    }
+
+    fn fold_route(
+        &mut self,
+        exchange: &Exchange,
+        body: Option<Value>,
+    ) -> Result<Recorded, MocksError> {
+        let base = Path::new(&self.file)
+            .parent()
+            .unwrap_or(Path::new(""))
+            .to_path_buf();
+        let routes = self
+            .data
+            .entry(CONFIG_KEY.to_string())
+            .or_insert_with(|| json!({}))
+            .as_object_mut()
+            .ok_or_else(|| invalid(&format!("{CONFIG_KEY} must be an object")))?
+            .entry(ROUTES_KEY.to_string())
+            .or_insert_with(|| json!([]))
+            .as_array_mut()
+            .ok_or_else(|| invalid(&format!("{ROUTES_KEY} must be an array")))?;
+
+        let position = routes.iter().position(|route| {
+            route.get("method").and_then(Value::as_str) == Some(exchange.method)
+                && route.get("path").and_then(Value::as_str) == Some(exchange.path)
+        });
+        let (route, created) = match position {
+            Some(position) => (&mut routes[position], false),
+            None => {
+                routes.push(json!({"method": exchange.method, "path": exchange.path}));
+                let last = routes.len() - 1;
+                (&mut routes[last], true)
+            }
+        };
+        let route = route
+            .as_object_mut()
+            .ok_or_else(|| invalid("routes must be objects"))?;
+
+        let mut name = slug(exchange.method, exchange.path);
+        if let Some(query) = exchange.query {
+            let query: Map<String, Value> = form_urlencoded::parse(query.as_bytes())
+                .map(|(name, value)| (name.into_owned(), Value::String(value.into_owned())))
+                .collect();
+            let when = json!({ "query": query });
+            let responses = route
+                .entry("responses")
+                .or_insert_with(|| json!([]))
+                .as_array_mut()
+                .ok_or_else(|| invalid("responses must be an array"))?;
+            let position = match responses.iter().position(|r| r.get("when") == Some(&when)) {
+                Some(position) => position,
+                None => {
+                    responses.push(json!({ "when": when }));
+                    responses.len() - 1
+                }
+            };
+            name = format!("{name}-{}", position + 1);
+            let response = responses[position]
+                .as_object_mut()
+                .ok_or_else(|| invalid("responses must be objects"))?;
+            set_response(response, exchange, body.clone(), &base, &name)?;
+        }
+
+        // The first recorded response also answers requests matching no other one
+        let body_file = if exchange.query.is_none() || created {
+            set_response(route, exchange, body, &base, &name)?
+        } else {
+            None
+        };
+        Ok(Recorded::Route {
+            method: exchange.method.to_string(),
+            path: exchange.path.to_string(),
+            body_file,
+        })
+    }
+}
+
+/// Set the status, headers and body of a canned response, returning the body file if written
+fn set_response(
+    response: &mut Map<String, Value>,
+    exchange: &Exchange,
+    body: Option<Value>,
+    base: &Path,
+    name: &str,
+) -> Result<Option<String>, MocksError> {
+    for field in ["status", "headers", "body", "bodyFile"] {
+        response.remove(field);
+    }
+    response.insert("status".to_string(), json!(exchange.status));
+
+    let headers: Map<String, Value> = exchange
+        .headers
+        .iter()
+        .filter(|(name, _)| {
+            !SKIPPED_HEADERS.contains(&name.as_str())
+                && !SKIPPED_HEADER_PREFIXES
+                    .iter()
+                    .any(|prefix| name.starts_with(prefix))
+        })
+        .map(|(name, value)| (name.clone(), Value::String(value.clone())))
+        .collect();
+    if !headers.is_empty() {
+        response.insert("headers".to_string(), Value::Object(headers));
+    }
+
+    if let Some(body) = body {
+        response.insert("body".to_string(), body);
+        return Ok(None);
+    }
+    if exchange.body.is_empty() {
+        return Ok(None);
+    }
+    let file = format!("{BODIES_DIR}/{name}.{}", extension(exchange));
+    let path: PathBuf = base.join(&file);
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir).map_err(|e| MocksError::FailedWriteFile(e.to_string()))?;
+    }
+    fs::write(&path, exchange.body)
+        .map_err(|e| MocksError::FailedWriteFile(format!("{file}: {e}")))?;
+    response.insert("bodyFile".to_string(), Value::String(file.clone()));
+    Ok(Some(file))
+}
+
+fn content_type<'a>(exchange: &Exchange<'a>) -> Option<&'a str> {
+    exchange
+        .headers
+        .iter()
+        .find(|(name, _)| name == "content-type")
+        .map(|(_, value)| value.as_str())
+}
+
+fn is_json(exchange: &Exchange) -> bool {
+    match content_type(exchange) {
+        Some(content_type) => content_type.contains("json"),
+        None => true,
+    }
+}
+
+/// File extension of a body saved to a file, by its content type
+fn extension(exchange: &Exchange) -> &'static str {
+    let essence = content_type(exchange)
+        .and_then(|value| value.split(';').next())
+        .unwrap_or("")
+        .trim();
+    match essence {
+        "text/html" => "html",
+        "text/plain" => "txt",
+        "text/css" => "css",
+        "text/csv" => "csv",
+        "text/javascript" | "application/javascript" => "js",
+        "text/xml" | "application/xml" => "xml",
+        "image/png" => "png",
+        "image/jpeg" => "jpg",
+        "image/gif" => "gif",
+        "image/svg+xml" => "svg",
+        "application/pdf" => "pdf",
+        _ => "bin",
+    }
+}
+
+/// Value of a resource response wrapped in `{"<resource>": ...}`, as mocks itself responds
+fn unwrap_resource<'a>(body: &'a Value, resource: &str) -> &'a Value {
+    match body.as_object() {
+        Some(obj) if obj.len() == 1 => obj
+            .get(resource)
+            .filter(|value| value.is_array() || value.is_object())
+            .unwrap_or(body),
+        _ => body,
+    }
+}
+
+/// Items of a collection response
+fn listed_items(body: &Value) -> Option<Vec<Value>> {
+    let items = body.as_array()?;
+    items
+        .iter()
+        .all(|item| item.is_object())
+        .then(|| items.clone())
+}
+
+fn id_of(value: &Value) -> Option<String> {
+    match value.get("id")? {
+        Value::String(id) => Some(id.clone()),
+        Value::Number(id) => Some(id.to_string()),
+        _ => None,
+    }
+}
+
+fn last_segment(key: &str) -> &str {
+    key.rsplit('/').next().unwrap_or(key)
+}
+
+/// Whether a path segment can be a literal segment of a route
+fn is_literal(segment: &str) -> bool {
+    !segment.is_empty() && !segment.starts_with([':', '*']) && !segment.contains(['{', '}'])
+}
+
+/// Whether a path is accepted as the path of a canned route
+fn is_routable(path: &str) -> bool {
+    match path.strip_prefix('/') {
+        Some(rest) => !rest.starts_with('_') && rest.split('/').all(is_literal),
+        None => false,
+    }
+}
+
+/// File name of a body recorded for a route (e.g. `get-assets-logo-png`)
+fn slug(method: &str, path: &str) -> String {
+    let path: String = path
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+        .collect();
+    let path = path
+        .split('-')
+        .filter(|part| !part.is_empty())
+        .collect::<Vec<_>>()
+        .join("-");
+    format!("{}-{path}", method.to_lowercase())
+}
+
+fn invalid(message: &str) -> MocksError {
+    MocksError::InvalidArgs(format!("Invalid storage file: {message}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    const JSON: [(&str, &str); 1] = [("content-type", "application/json")];
+
+    fn headers(pairs: &[(&str, &str)]) -> Vec<(String, String)> {
+        pairs
+            .iter()
+            .map(|(name, value)| (name.to_string(), value.to_string()))
+            .collect()
+    }
+
+    fn record(
+        recorder: &mut Recorder,
+        method: &str,
+        uri: &str,
+        status: u16,
+        body: &str,
+    ) -> Recorded {
+        record_with_headers(recorder, method, uri, status, &JSON, body)
+    }
+
+    fn record_with_headers(
+        recorder: &mut Recorder,
+        method: &str,
+        uri: &str,
+        status: u16,
+        pairs: &[(&str, &str)],
+        body: &str,
+    ) -> Recorded {
+        let (path, query) = match uri.split_once('?') {
+            Some((path, query)) => (path, Some(query)),
+            None => (uri, None),
+        };
+        let headers = headers(pairs);
+        recorder
+            .record(&Exchange {
+                method,
+                path,
+                query,
+                status,
+                headers: &headers,
+                body: body.as_bytes(),
+            })
+            .unwrap()
+    }
+
+    fn stored(dir: &TempDir) -> Value {
+        let text = fs::read_to_string(dir.path().join("storage.json")).unwrap();
+        serde_json::from_str(&text).unwrap()
+    }
+
+    fn recorder(dir: &TempDir) -> Recorder {
+        Recorder::new(dir.path().join("storage.json").to_str().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_record_collections_and_items() {
+        let dir = TempDir::new().unwrap();
+        let mut recorder = recorder(&dir);
+
+        let recorded = record(
+            &mut recorder,
+            "GET",
+            "/users",
+            200,
+            r#"[{"id":1,"name":"Ann"},{"id":2,"name":"Bob"}]"#,
+        );
+        assert_eq!(
+            recorded,
+            Recorded::Items {
+                resource: "users".to_string(),
+                count: 2
+            }
+        );
+        let recorded = record(
+            &mut recorder,
+            "GET",
+            "/users/2",
+            200,
+            r#"{"id":2,"name":"Bobby"}"#,
+        );
+        assert_eq!(
+            recorded,
+            Recorded::Item {
+                resource: "users".to_string(),
+                id: "2".to_string()
+            }
+        );
+        record(
+            &mut recorder,
+            "POST",
+            "/users",
+            201,
+            r#"{"id":3,"name":"Cid"}"#,
+        );
+        record(&mut recorder, "DELETE", "/users/1", 204, "");
+        // Items of a page are merged into the collection
+        record(
+            &mut recorder,
+            "GET",
+            "/api/v1/posts?page=2",
+            200,
+            r#"{"posts":[{"id":"a","title":"Hello"}]}"#,
+        );
+        record(
+            &mut recorder,
+            "GET",
+            "/settings",
+            200,
+            r#"{"settings":{"theme":"dark"}}"#,
+        );
+        record(
+            &mut recorder,
+            "PATCH",
+            "/profile",
+            200,
+            r#"{"name":"Ann","theme":"dark"}"#,
+        );
+
+        assert_eq!(
+            stored(&dir),
+            json!({
+                "users": [{"id":2,"name":"Bobby"},{"id":3,"name":"Cid"}],
+                "api/v1/posts": [{"id":"a","title":"Hello"}],
+                "profile": {"name":"Ann","theme":"dark"},
+                "settings": {"theme":"dark"}
+            })
+        );
+    }
+
+    #[test]
+    fn test_record_routes() {
+        let dir = TempDir::new().unwrap();
+        let mut recorder = recorder(&dir);
+        record(&mut recorder, "GET", "/users", 200, r#"[{"id":1}]"#);
+
+        let recorded = record_with_headers(
+            &mut recorder,
+            "POST",
+            "/login",
+            200,
+            &[
+                ("content-type", "application/json"),
+                ("x-token", "abc"),
+                ("date", "Mon, 19 Oct 2026 00:00:00 GMT"),
+                ("access-control-allow-origin", "*"),
+            ],
+            r#"{"token":"abc"}"#,
+        );
+        assert_eq!(recorded.to_string(), "route POST /login");
+        record(&mut recorder, "GET", "/search?q=ann", 200, r#"{"hits":1}"#);
+        record(&mut recorder, "GET", "/search", 200, r#"{"hits":0}"#);
+        record(&mut recorder, "GET", "/search?q=bob", 200, r#"{"hits":2}"#);
+        let recorded = record_with_headers(
+            &mut recorder,
+            "GET",
+            "/health",
+            200,
+            &[("content-type", "text/plain")],
+            "ok",
+        );
+        assert_eq!(
+            recorded.to_string(),
+            "route GET /health (body in recordings/get-health.txt)"
+        );
+
+        // Failures of resource endpoints are replayed by the resources themselves
+        let recorded = record(&mut recorder, "GET", "/users/9", 404, r#"{"error":"x"}"#);
+        assert!(matches!(recorded, Recorded::Skipped(_)));
+        let recorded = record(&mut recorder, "GET", "/_internal", 500, "");
+        assert!(matches!(recorded, Recorded::Skipped(_)));
+
+        let json = json!({"content-type": "application/json"});
+        assert_eq!(
+            stored(&dir)[CONFIG_KEY][ROUTES_KEY],
+            json!([
+                {"method": "POST", "path": "/login", "status": 200,
+                 "headers": {"content-type": "application/json", "x-token": "abc"},
+                 "body": {"token": "abc"}},
+                {"method": "GET", "path": "/search", "status": 200, "headers": json,
+                 "body": {"hits": 0},
+                 "responses": [
+                    {"when": {"query": {"q": "ann"}}, "status": 200, "headers": json, "body": {"hits": 1}},
+                    {"when": {"query": {"q": "bob"}}, "status": 200, "headers": json, "body": {"hits": 2}}
+                 ]},
+                {"method": "GET", "path": "/health", "status": 200,
+                 "headers": {"content-type": "text/plain"},
+                 "bodyFile": "recordings/get-health.txt"}
+            ])
+        );
+        assert_eq!(
+            fs::read_to_string(dir.path().join("recordings/get-health.txt")).unwrap(),
+            "ok"
+        );
+    }
+
+    #[test]
+    fn test_record_keeps_resources_readable() {
+        let dir = TempDir::new().unwrap();
+        let mut recorder = recorder(&dir);
+        record(&mut recorder, "GET", "/users", 200, r#"[{"id":1}]"#);
+
+        // The last segment would be served by the users resource already
+        let recorded = record(&mut recorder, "GET", "/admin/users", 200, r#"[{"id":7}]"#);
+        assert_eq!(recorded.to_string(), "route GET /admin/users");
+        // Nested below the items of users
+        let recorded = record(
+            &mut recorder,
+            "GET",
+            "/users/1/settings",
+            200,
+            r#"{"theme":"dark"}"#,
+        );
+        assert_eq!(recorded.to_string(), "route GET /users/1/settings");
+        // An item without an id matching the path is not an item
+        let recorded = record(&mut recorder, "GET", "/users/me", 200, r#"{"id":1}"#);
+        assert!(matches!(recorded, Recorded::Skipped(_)));
+        assert_eq!(stored(&dir)["users"], json!([{"id": 1}]));
+    }
+
+    #[test]
+    fn test_record_adds_to_existing_file() {
+        let dir = TempDir::new().unwrap();
+        let file = dir.path().join("storage.json");
+        fs::write(&file, r#"{"users":[{"id":1}],"_mocks":{"delay":"10ms"}}"#).unwrap();
+
+        let mut recorder = recorder(&dir);
+        record(&mut recorder, "GET", "/users/2", 200, r#"{"id":2}"#);
+        record(&mut recorder, "GET", "/version", 200, r#""1.0""#);
+        assert_eq!(
+            stored(&dir),
+            json!({
+                "users": [{"id":1},{"id":2}],
+                "_mocks": {
+                    "delay": "10ms",
+                    "routes": [{"method": "GET", "path": "/version", "status": 200,
+                                "headers": {"content-type": "application/json"}, "body": "1.0"}]
+                }
+            })
+        );
+
+        fs::write(&file, "[]").unwrap();
+        assert!(Recorder::new(file.to_str().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_recorded_display() {
+        let recorded = Recorded::Items {
+            resource: "users".to_string(),
+            count: 1,
+        };
+        assert_eq!(recorded.to_string(), "users (1 item)");
+        let recorded = Recorded::Removed {
+            resource: "users".to_string(),
+            id: "1".to_string(),
+        };
+        assert_eq!(recorded.to_string(), "users (removed item 1)");
+        let recorded = Recorded::Object {
+            resource: "profile".to_string(),
+        };
+        assert_eq!(recorded.to_string(), "profile (object)");
+    }
+
+    #[test]
+    fn test_slug() {
+        assert_eq!(slug("GET", "/assets/logo.png"), "get-assets-logo-png");
+        assert_eq!(slug("POST", "/api/v1/login"), "post-api-v1-login");
+    }
+}
@@ -0,0 +1,189 @@
+use crate::schema::resource_name;
+use crate::storage::Input;
+use chrono::{SecondsFormat, Utc};
+use serde_json::Value;
+
+const CREATED_FIELD: &str = "createdAt";
+const UPDATED_FIELD: &str = "updatedAt";
+const VERSION_FIELD: &str = "version";
+
+/// Server-managed fields of a resource, set on every change
+///
+/// Enabled per resource in the storage config:
+///
+/// ```json
+/// {"_mocks": {"resources": {"posts": {"timestamps": true, "version": true}}}}
+/// ```
+///
+/// `timestamps` also accepts `{"created": "created_at", "updated": "updated_at"}`
+/// and `version` a field name, to rename the fields.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Stamps {
+    created: Option<String>,
+    updated: Option<String>,
+    version: Option<String>,
+}
+
+impl Stamps {
+    /// Read the options of a resource from the storage config
+    ///
+    /// # Arguments
+    /// * `config` - Contents of the reserved `_mocks` key
+    /// * `resource_key` - Resource key, possibly nested (e.g. `api/v1/posts`)
+    pub fn from_config(config: &Value, resource_key: &str) -> Stamps {
+        let Some(options) = config
+            .get("resources")
+            .and_then(|r| r.get(resource_name(resource_key)))
+        else {
+            return Stamps::default();
+        };
+
+        let (created, updated) = match options.get("timestamps") {
+            Some(Value::Bool(true)) => (
+                Some(CREATED_FIELD.to_string()),
+                Some(UPDATED_FIELD.to_string()),
+            ),
+            Some(Value::Object(fields)) => {
+                let field =
+                    |name: &str| fields.get(name).and_then(Value::as_str).map(str::to_string);
+                (field("created"), field("updated"))
+            }
+            _ => (None, None),
+        };
+        let version = match options.get("version") {
+            Some(Value::Bool(true)) => Some(VERSION_FIELD.to_string()),
+            Some(Value::String(field)) => Some(field.to_string()),
+            _ => None,
+        };
+
+        Stamps {
+            created,
+            updated,
+            version,
+        }
+    }
+
+    /// Stamp the input of a new item
+    pub fn on_insert(&self, input: &Input) -> Input {
+        self.stamp(input, None, true)
+    }
+
+    /// Stamp the input replacing an existing item, keeping its creation timestamp
+    pub fn on_replace(&self, input: &Input, existing: Option<&Value>) -> Input {
+        self.stamp(input, existing, true)
+    }
+
+    /// Stamp the input updating parts of an existing item
+    pub fn on_update(&self, input: &Input, existing: Option<&Value>) -> Input {
+        self.stamp(input, existing, false)
+    }
+
+    fn stamp(&self, input: &Input, existing: Option<&Value>, whole: bool) -> Input {
+        let mut input = input.clone();
+        let Some(obj) = input.as_object_mut() else {
+            return input;
+        };
+        let now = Value::String(now());
+
+        if let Some(field) = &self.created {
+            // A partial update keeps the existing value, or its absence
+            match existing.and_then(|e| e.get(field)) {
+                _ if !whole => {
+                    obj.remove(field);
+                }
+                Some(created) => {
+                    obj.insert(field.to_string(), created.clone());
+                }
+                None => {
+                    obj.insert(field.to_string(), now.clone());
+                }
+            }
+        }
+        if let Some(field) = &self.updated {
+            obj.insert(field.to_string(), now);
+        }
+        if let Some(field) = &self.version {
+            let version = existing
+                .and_then(|e| e.get(field))
+                .and_then(Value::as_i64)
+                .map_or(1, |v| v + 1);
+            obj.insert(field.to_string(), Value::from(version));
+        }
+        input
+    }
+}
+
+fn now() -> String {
+    Utc::now().to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::schema::format;
+    use serde_json::json;
+
+    fn stamps() -> Stamps {
+        let config = json!({"resources": {"posts": {"timestamps": true, "version": true}}});
+        Stamps::from_config(&config, "api/v1/posts")
+    }
+
+    #[test]
+    fn test_from_config() {
+        assert_ne!(stamps(), Stamps::default());
+        assert_eq!(Stamps::from_config(&json!({}), "posts"), Stamps::default());
+
+        let config = json!({
+            "resources": {
+                "posts": {"timestamps": {"created": "created_at"}, "version": "rev"}
+            }
+        });
+        assert_eq!(
+            Stamps::from_config(&config, "posts"),
+            Stamps {
+                created: Some("created_at".to_string()),
+                updated: None,
+                version: Some("rev".to_string()),
+            }
+        );
+    }
+
+    #[test]
+    fn test_on_insert() {
+        let post = stamps().on_insert(&json!({"id": 1, "version": 9}));
+        assert!(format::matches(
+            "date-time",
+            post["createdAt"].as_str().unwrap()
+        ));
+        assert_eq!(post["createdAt"], post["updatedAt"]);
+        assert_eq!(post["version"], 1);
+    }
+
+    #[test]
+    fn test_on_replace() {
+        let existing = json!({"id": 1, "createdAt": "2024-01-01T00:00:00.000Z", "version": 2});
+        let post = stamps().on_replace(&json!({"id": 1, "title": "a"}), Some(&existing));
+        assert_eq!(post["createdAt"], "2024-01-01T00:00:00.000Z");
+        assert_ne!(post["updatedAt"], "2024-01-01T00:00:00.000Z");
+        assert_eq!(post["version"], 3);
+    }
+
+    #[test]
+    fn test_on_update() {
+        let existing = json!({"id": 1, "createdAt": "2024-01-01T00:00:00.000Z", "version": 2});
+        let input = json!({"title": "a", "createdAt": "2000-01-01T00:00:00.000Z"});
+        let post = stamps().on_update(&input, Some(&existing));
+        assert!(post.get("createdAt").is_none());
+        assert!(post["updatedAt"].is_string());
+        assert_eq!(post["version"], 3);
+
+        let existing = json!({"id": 1, "title": "b"});
+        let post = stamps().on_update(&json!({"title": "a"}), Some(&existing));
+        assert!(post.get("createdAt").is_none());
+        assert!(post["updatedAt"].is_string());
+
+        let post = stamps().on_update(&json!({"title": "a"}), None);
+        assert_eq!(post["version"], 1);
+        assert!(post.get("createdAt").is_none());
+    }
+}